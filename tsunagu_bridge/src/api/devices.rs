@@ -0,0 +1,76 @@
+use tokio::sync::broadcast::error::RecvError;
+use tsunagu_common::models::DeviceInfo;
+use tsunagu_common::trust::{TrustLevel, TrustStore};
+
+use super::service;
+use crate::frb_generated::StreamSink;
+
+/// A device on the network
+#[derive(Debug, Clone, PartialEq)]
+pub struct Device {
+    pub id: String,
+    pub name: String,
+    pub model: String,
+    pub os: String,
+    /// Every address the device was found at
+    pub addresses: Vec<String>,
+    pub port: u16,
+    /// Paired before and presenting the same identity
+    pub trusted: bool,
+}
+
+/// Stream the discovered devices, a new list is sent whenever it changes
+///
+/// The stream ends when the service stops.
+pub async fn watch_devices(sink: StreamSink<Vec<Device>>) -> anyhow::Result<()> {
+    let service = service::current().await?;
+    let mut events = service.discovery.lock().await.subscribe();
+    let mut shutdown = service.shutdown.clone();
+    tokio::spawn(async move {
+        let mut last = None;
+        loop {
+            let found = service.discovery.lock().await.discover_devices().await;
+            if let Ok(mut found) = found {
+                // mDNS also resolves our own announcement
+                found.retain(|info| info.id() != service.local_device.id());
+                found.sort_by(|a, b| a.id().cmp(b.id()));
+                let devices: Vec<Device> = {
+                    let trust_store = service.trust_store.read().await;
+                    found
+                        .iter()
+                        .map(|info| device(info, &trust_store))
+                        .collect()
+                };
+                if last.as_ref() != Some(&devices) {
+                    last = Some(devices.clone());
+                    if sink.add(devices).is_err() {
+                        // The app closed the stream
+                        break;
+                    }
+                }
+            }
+
+            // Any change, or missed changes, mean a fresh list
+            let event = tokio::select! {
+                event = events.recv() => event,
+                _ = shutdown.changed() => break,
+            };
+            if let Err(RecvError::Closed) = event {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+pub(crate) fn device(info: &DeviceInfo, trust_store: &TrustStore) -> Device {
+    Device {
+        id: info.id().to_string(),
+        name: info.name().to_string(),
+        model: info.model().to_string(),
+        os: info.os().to_string(),
+        addresses: info.addresses().iter().map(|ip| ip.to_string()).collect(),
+        port: info.port(),
+        trusted: matches!(trust_store.check(info), Ok(TrustLevel::Trusted)),
+    }
+}
@@ -0,0 +1,114 @@
+use tsunagu_common::history::{Direction, HistoryEntry, HistoryFilter, HistoryStore};
+use tsunagu_common::models::TransferStatus;
+
+/// Which transfers to load, empty fields match everything
+pub struct HistoryQuery {
+    /// Peer name, id or ip
+    pub peer: Option<String>,
+    /// `completed`, `failed` or `cancelled`
+    pub status: Option<String>,
+    /// Finished at or after, in seconds since the Unix epoch
+    pub since: Option<u64>,
+    /// Finished before, in seconds since the Unix epoch
+    pub until: Option<u64>,
+    pub limit: Option<u32>,
+}
+
+pub struct HistoryFile {
+    /// Path relative to the transfer, `/` separated
+    pub path: String,
+    pub size: u64,
+    /// Hex encoded SHA-256, if the file was hashed
+    pub hash: Option<String>,
+    /// Where the file was read from or saved to on this device
+    pub local_path: Option<String>,
+}
+
+/// A finished transfer for the history screen
+pub struct HistoryItem {
+    pub transfer_id: String,
+    pub sent: bool,
+    pub peer_id: String,
+    pub peer_name: String,
+    pub files: Vec<HistoryFile>,
+    pub total_size: u64,
+    /// Seconds since the Unix epoch
+    pub started_at: u64,
+    /// Seconds since the Unix epoch
+    pub finished_at: u64,
+    pub duration_ms: u64,
+    /// `completed`, `failed` or `cancelled`
+    pub status: String,
+    /// Why the transfer failed
+    pub error: Option<String>,
+}
+
+/// Finished transfers matching `query`, newest first
+pub fn load_history(query: HistoryQuery) -> anyhow::Result<Vec<HistoryItem>> {
+    let filter = HistoryFilter {
+        peer: query.peer,
+        status: query.status.as_deref().map(str::parse).transpose()?,
+        direction: None,
+        since: query.since,
+        until: query.until,
+        limit: query.limit.map(|limit| limit as usize),
+    };
+    let entries = HistoryStore::open_default()?.query(&filter)?;
+    Ok(entries.iter().map(history_item).collect())
+}
+
+/// Name of a final status as shown to the app, with the reason of a failure
+pub(crate) fn status_name(status: &TransferStatus) -> (&'static str, Option<String>) {
+    match status {
+        TransferStatus::Completed => ("completed", None),
+        TransferStatus::Cancelled => ("cancelled", None),
+        TransferStatus::Failed(reason) => ("failed", Some(reason.clone())),
+        // Not final, never recorded or reported as finished
+        TransferStatus::Pending | TransferStatus::InProgress(_) => ("failed", None),
+    }
+}
+
+fn history_item(entry: &HistoryEntry) -> HistoryItem {
+    let transfer = entry.transfer();
+    let (status, error) = status_name(transfer.status());
+    HistoryItem {
+        transfer_id: transfer.id().to_string(),
+        sent: entry.direction() == Direction::Sent,
+        peer_id: entry.peer().id().to_string(),
+        peer_name: entry.peer().name().to_string(),
+        files: transfer
+            .files()
+            .iter()
+            .enumerate()
+            .map(|(index, file)| HistoryFile {
+                path: file.relative_path().to_string(),
+                size: file.size(),
+                hash: file.hash().map(str::to_string),
+                local_path: entry
+                    .path(index)
+                    .map(|path| path.to_string_lossy().into_owned()),
+            })
+            .collect(),
+        total_size: transfer.total_size(),
+        started_at: entry.started_at(),
+        finished_at: entry.finished_at(),
+        duration_ms: entry.duration().as_millis() as u64,
+        status: status.to_string(),
+        error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_name() {
+        assert_eq!(status_name(&TransferStatus::Completed), ("completed", None));
+        assert_eq!(status_name(&TransferStatus::Cancelled), ("cancelled", None));
+        assert_eq!(
+            status_name(&TransferStatus::Failed("Disk full".into())),
+            ("failed", Some("Disk full".to_string()))
+        );
+    }
+}
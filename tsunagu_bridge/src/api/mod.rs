@@ -1 +1,8 @@
+pub mod devices;
+pub mod history;
+pub mod offers;
+pub mod service;
+pub mod settings;
 pub mod simple;
+pub mod transfers;
+pub mod visibility;
@@ -0,0 +1,175 @@
+use tokio::sync::broadcast::error::RecvError;
+use tsunagu_common::models::TransferInfo;
+use tsunagu_common::transfer::OfferDecision;
+use tsunagu_common::trust::TrustStore;
+
+use super::devices::{device, Device};
+use super::service;
+use crate::frb_generated::StreamSink;
+
+/// A file of an offer or transfer
+#[derive(Debug, Clone)]
+pub struct TransferFile {
+    /// Path relative to the transfer, `/` separated
+    pub path: String,
+    pub size: u64,
+}
+
+/// Files another device wants to send, waiting for [`answer_offer`]
+#[derive(Debug, Clone)]
+pub struct IncomingOffer {
+    pub transfer_id: String,
+    pub sender: Device,
+    pub files: Vec<TransferFile>,
+    pub total_size: u64,
+}
+
+impl IncomingOffer {
+    pub(crate) fn new(transfer: &TransferInfo, trust_store: &TrustStore) -> Self {
+        Self {
+            transfer_id: transfer.id().to_string(),
+            sender: device(transfer.sender(), trust_store),
+            files: transfer_files(transfer),
+            total_size: transfer.total_size(),
+        }
+    }
+}
+
+/// The user's answer to an [`IncomingOffer`]
+pub enum OfferAnswer {
+    Accept,
+    /// Accept only the files at these indexes
+    AcceptFiles {
+        indexes: Vec<u32>,
+    },
+    Reject {
+        reason: String,
+    },
+}
+
+/// Stream offers from devices that are not accepted automatically
+///
+/// Offers that are not answered within two minutes are rejected. The stream
+/// ends when the service stops.
+pub async fn watch_offers(sink: StreamSink<IncomingOffer>) -> anyhow::Result<()> {
+    let service = service::current().await?;
+    let mut offers = service.offers.subscribe();
+    let mut shutdown = service.shutdown.clone();
+    tokio::spawn(async move {
+        loop {
+            let offer = tokio::select! {
+                offer = offers.recv() => offer,
+                _ = shutdown.changed() => break,
+            };
+            match offer {
+                Ok(offer) => {
+                    if sink.add(offer).is_err() {
+                        break;
+                    }
+                }
+                // Skipped offers can still be listed with `pending_offers`
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Offers still waiting for an answer, e.g. after the app was reopened
+pub async fn pending_offers() -> anyhow::Result<Vec<IncomingOffer>> {
+    let service = service::current().await?;
+    let transfers: Vec<TransferInfo> = {
+        let mut pending = service.pending.lock().unwrap();
+        pending.retain(|_, offer| !offer.is_expired());
+        pending
+            .values()
+            .map(|offer| offer.transfer().clone())
+            .collect()
+    };
+    let trust_store = service.trust_store.read().await;
+    Ok(transfers
+        .iter()
+        .map(|transfer| IncomingOffer::new(transfer, &trust_store))
+        .collect())
+}
+
+/// Answer the offer of transfer `transfer_id`
+pub async fn answer_offer(transfer_id: String, answer: OfferAnswer) -> anyhow::Result<()> {
+    let service = service::current().await?;
+    let offer = service
+        .pending
+        .lock()
+        .unwrap()
+        .remove(&transfer_id)
+        .filter(|offer| !offer.is_expired())
+        .ok_or_else(|| anyhow::anyhow!("No pending offer {}", transfer_id))?;
+    offer.respond(match answer {
+        OfferAnswer::Accept => OfferDecision::Accept,
+        OfferAnswer::AcceptFiles { indexes } => {
+            OfferDecision::AcceptPartial(indexes.into_iter().map(|i| i as usize).collect())
+        }
+        OfferAnswer::Reject { reason } => OfferDecision::Reject(reason),
+    });
+    Ok(())
+}
+
+pub(crate) fn transfer_files(transfer: &TransferInfo) -> Vec<TransferFile> {
+    transfer
+        .files()
+        .iter()
+        .map(|file| TransferFile {
+            path: file.relative_path().to_string(),
+            size: file.size(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use tsunagu_common::identity::DeviceIdentity;
+    use tsunagu_common::models::{DeviceInfo, FileInfo};
+
+    fn device(name: &str, identity: &DeviceIdentity) -> DeviceInfo {
+        DeviceInfo::new(
+            name.to_string(),
+            "Test Model".to_string(),
+            [Ipv4Addr::LOCALHOST.into()],
+            8000,
+            "TestOS".to_string(),
+            "1.0.0".to_string(),
+        )
+        .with_public_key(identity.public_key())
+    }
+
+    #[test]
+    fn test_incoming_offer() {
+        let sender = device("Laptop", &DeviceIdentity::generate().unwrap());
+        let receiver = device("Phone", &DeviceIdentity::generate().unwrap());
+        let files = vec![
+            FileInfo::new("a.txt".into(), 5, "text/plain".into(), 0),
+            FileInfo::new("b.bin".into(), 7, "application/octet-stream".into(), 0),
+        ];
+        let transfer = TransferInfo::new(sender.clone(), receiver, files);
+
+        let mut trust_store = TrustStore::in_memory();
+        let offer = IncomingOffer::new(&transfer, &trust_store);
+        assert_eq!(offer.transfer_id, transfer.id());
+        assert_eq!(offer.sender.id, sender.id());
+        assert_eq!(offer.sender.name, "Laptop");
+        assert_eq!(offer.sender.addresses, vec!["127.0.0.1".to_string()]);
+        assert!(!offer.sender.trusted);
+        let files: Vec<_> = offer
+            .files
+            .iter()
+            .map(|f| (f.path.as_str(), f.size))
+            .collect();
+        assert_eq!(files, vec![("a.txt", 5), ("b.bin", 7)]);
+        assert_eq!(offer.total_size, 12);
+
+        trust_store.trust(&sender).unwrap();
+        assert!(IncomingOffer::new(&transfer, &trust_store).sender.trusted);
+    }
+}
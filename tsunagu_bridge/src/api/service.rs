@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use tokio::sync::{broadcast, mpsc, watch, Mutex, RwLock};
+use tsunagu_common::config::load_config;
+use tsunagu_common::device::DeviceManager;
+use tsunagu_common::discovery::{self, Discovery};
+use tsunagu_common::history::HistoryStore;
+use tsunagu_common::models::DeviceInfo;
+use tsunagu_common::paths;
+use tsunagu_common::transfer::{
+    AcceptAll, AcceptTrusted, AcceptancePolicy, ChannelPolicy, OfferRules, PendingOffer,
+    RulePolicy, TcpFileTransfer, TransferManager, TransferServer,
+};
+use tsunagu_common::trust::TrustStore;
+
+use super::devices::{device, Device};
+use super::offers::IncomingOffer;
+
+/// How long an incoming offer waits for an answer from the app
+const OFFER_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Offers announced but not yet delivered to a stream
+const OFFER_CAPACITY: usize = 16;
+
+static SERVICE: Mutex<Option<Running>> = Mutex::const_new(None);
+
+struct Running {
+    service: Service,
+    server: TransferServer,
+    /// Dropped on stop, which ends every stream
+    shutdown: watch::Sender<bool>,
+}
+
+/// The parts of the running service the API functions work with
+#[derive(Clone)]
+pub(crate) struct Service {
+    pub(crate) local_device: DeviceInfo,
+    pub(crate) discovery: Arc<Mutex<Box<dyn Discovery>>>,
+    pub(crate) transfers: TransferManager,
+    pub(crate) trust_store: Arc<RwLock<TrustStore>>,
+    /// Offers waiting for an answer, by transfer id
+    pub(crate) pending: Arc<std::sync::Mutex<HashMap<String, PendingOffer>>>,
+    pub(crate) offers: broadcast::Sender<IncomingOffer>,
+    pub(crate) shutdown: watch::Receiver<bool>,
+}
+
+/// Start receiving, advertising and discovering devices
+///
+/// The device stays discoverable until the service stops. Uses the saved settings, changes to them apply on the next start.
+/// Returns this device as peers see it.
+pub async fn start_service() -> anyhow::Result<Device> {
+    let mut running = SERVICE.lock().await;
+    if running.is_some() {
+        bail!("Service is already running");
+    }
+
+    let config = load_config()?;
+    let device_manager = DeviceManager::new().await?;
+    let mut local_device = device_manager.get_current_device_info().await;
+    local_device.set_name(config.device_name.clone());
+    device_manager
+        .update_device_info(local_device.clone())
+        .await?;
+
+    let trust_store = Arc::new(RwLock::new(TrustStore::load_default()?));
+    let transfer = TcpFileTransfer::new(local_device, config.save_directory.clone())
+        .with_identity(device_manager.identity())
+        .with_trust_store(trust_store.clone())
+        .with_state_dir(paths::data_dir()?.join("transfers"))
+        .with_cancelled_partials(config.cancelled_partials);
+    let mut transfers = TransferManager::new(transfer, config.limits.max_concurrent_transfers)
+        .with_history_store(HistoryStore::open_default()?);
+
+    let (channel, offers_rx) = ChannelPolicy::new(OFFER_TIMEOUT);
+    let untrusted: Arc<dyn AcceptancePolicy> = if config.allow_auto_receive {
+        Arc::new(AcceptAll)
+    } else {
+        Arc::new(channel)
+    };
+    let policy = RulePolicy::new(
+        OfferRules::from_config(&config),
+        trust_store.clone(),
+        Arc::new(AcceptTrusted::new(trust_store.clone(), untrusted)),
+    );
+    let mut server = transfers.server(Arc::new(policy));
+    server.start(config.transfer_port, &device_manager).await?;
+
+    // The server port is only known now, advertise the updated device
+    let local_device = device_manager.get_current_device_info().await;
+    transfers.set_local_device(local_device.clone());
+    let mut discovery: Box<dyn Discovery> =
+        Box::new(discovery::from_config(&config, local_device.clone())?);
+    let started = async {
+        discovery.start().await?;
+        // Visible while the app is open, see `api::visibility` to change it
+        discovery.make_discoverable_while_running().await
+    }
+    .await;
+    if let Err(e) = started {
+        server.stop().await.ok();
+        return Err(e.into());
+    }
+
+    let (shutdown, shutdown_rx) = watch::channel(false);
+    let service = Service {
+        local_device: local_device.clone(),
+        discovery: Arc::new(Mutex::new(discovery)),
+        transfers,
+        trust_store,
+        pending: Arc::new(std::sync::Mutex::new(HashMap::new())),
+        offers: broadcast::channel(OFFER_CAPACITY).0,
+        shutdown: shutdown_rx,
+    };
+    tokio::spawn(collect_offers(service.clone(), offers_rx));
+
+    let this_device = device(&local_device, &*service.trust_store.read().await);
+    *running = Some(Running {
+        service,
+        server,
+        shutdown,
+    });
+    Ok(this_device)
+}
+
+/// Stop the service, transfers in flight are left to finish
+pub async fn stop_service() -> anyhow::Result<()> {
+    let Some(mut running) = SERVICE.lock().await.take() else {
+        return Ok(());
+    };
+    running.shutdown.send_replace(true);
+    running.server.stop().await?;
+    running.service.discovery.lock().await.stop().await?;
+    Ok(())
+}
+
+pub async fn is_service_running() -> bool {
+    SERVICE.lock().await.is_some()
+}
+
+/// The running service, or an error telling the app to start it first
+pub(crate) async fn current() -> anyhow::Result<Service> {
+    SERVICE
+        .lock()
+        .await
+        .as_ref()
+        .map(|running| running.service.clone())
+        .ok_or_else(|| anyhow!("Service is not running"))
+}
+
+/// Keep offers from the acceptance policy until the app answers them
+async fn collect_offers(service: Service, mut offers: mpsc::Receiver<PendingOffer>) {
+    while let Some(offer) = offers.recv().await {
+        let incoming = IncomingOffer::new(offer.transfer(), &*service.trust_store.read().await);
+        {
+            let mut pending = service.pending.lock().unwrap();
+            pending.retain(|_, offer| !offer.is_expired());
+            pending.insert(incoming.transfer_id.clone(), offer);
+        }
+        // Nobody listening is fine, the app can still list pending offers
+        let _ = service.offers.send(incoming);
+    }
+}
@@ -0,0 +1,53 @@
+use std::path::PathBuf;
+use tsunagu_common::config::{load_config, save_config, DiscoveryBackend};
+
+/// The user editable part of the shared config
+pub struct Settings {
+    pub device_name: String,
+    pub save_directory: String,
+    pub allow_auto_receive: bool,
+    /// Any of `mdns`, `multicast` where mDNS is filtered, and `subnet_scan`
+    /// where all multicast is, the devices they find are merged
+    pub discovery_backends: Vec<String>,
+    /// UDP port of the multicast discovery
+    pub discovery_port: u16,
+    pub transfer_port: u16,
+    pub max_concurrent_transfers: u32,
+}
+
+pub async fn load_settings() -> anyhow::Result<Settings> {
+    let config = load_config()?;
+    Ok(Settings {
+        device_name: config.device_name,
+        save_directory: config.save_directory.to_string_lossy().into_owned(),
+        allow_auto_receive: config.allow_auto_receive,
+        discovery_backends: config
+            .discovery_backends
+            .iter()
+            .map(DiscoveryBackend::to_string)
+            .collect(),
+        discovery_port: config.discovery_port,
+        transfer_port: config.transfer_port,
+        max_concurrent_transfers: config.limits.max_concurrent_transfers as u32,
+    })
+}
+
+/// Save `settings`, keeping trusted devices and everything else in the config
+///
+/// A running service picks them up when it is started again.
+pub async fn save_settings(settings: Settings) -> anyhow::Result<()> {
+    let mut config = load_config()?;
+    config.device_name = settings.device_name;
+    config.save_directory = PathBuf::from(settings.save_directory);
+    config.allow_auto_receive = settings.allow_auto_receive;
+    config.discovery_backends = settings
+        .discovery_backends
+        .iter()
+        .map(|backend| backend.parse())
+        .collect::<Result<_, _>>()?;
+    config.discovery_port = settings.discovery_port;
+    config.transfer_port = settings.transfer_port;
+    config.limits.max_concurrent_transfers = settings.max_concurrent_transfers as usize;
+    save_config(&config)?;
+    Ok(())
+}
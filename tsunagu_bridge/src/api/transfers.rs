@@ -0,0 +1,146 @@
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use tokio::sync::broadcast::error::RecvError;
+use tsunagu_common::transfer::{collect_files, CollectOptions, TransferEvent};
+
+use super::devices::{device, Device};
+use super::history::status_name;
+use super::offers::{transfer_files, TransferFile};
+use super::service::{self, Service};
+use crate::frb_generated::StreamSink;
+
+/// Something that happened to a transfer in either direction
+pub enum TransferUpdate {
+    /// Data is about to flow
+    Started {
+        transfer_id: String,
+        sent: bool,
+        peer: Device,
+        files: Vec<TransferFile>,
+        total_size: u64,
+    },
+    Progress {
+        transfer_id: String,
+        file_index: u32,
+        bytes_transferred: u64,
+        total_bytes: u64,
+        bytes_per_second: f64,
+        /// Estimated seconds left, unknown until data flows
+        eta_seconds: Option<u64>,
+    },
+    FileCompleted {
+        transfer_id: String,
+        index: u32,
+    },
+    /// The transfer reached its final status
+    Finished {
+        transfer_id: String,
+        /// `completed`, `failed` or `cancelled`
+        status: String,
+        error: Option<String>,
+    },
+}
+
+/// Send files and directories to the discovered device `device_id`
+///
+/// Returns the transfer id, progress arrives through [`watch_transfers`].
+pub async fn send_files(device_id: String, paths: Vec<String>) -> anyhow::Result<String> {
+    let service = service::current().await?;
+    let receiver = service
+        .discovery
+        .lock()
+        .await
+        .discover_devices()
+        .await?
+        .into_iter()
+        .find(|device| device.id() == device_id)
+        .ok_or_else(|| anyhow!("Unknown device {}", device_id))?;
+
+    let paths: Vec<PathBuf> = paths.into_iter().map(PathBuf::from).collect();
+    let files = collect_files(&paths, &CollectOptions::default())?;
+    if files.is_empty() {
+        return Err(anyhow!("Nothing to send"));
+    }
+    // The transfer keeps running once the handle is dropped
+    let handle = service.transfers.send(files, receiver).await?;
+    Ok(handle.info().id().to_string())
+}
+
+/// Cancel a queued or running transfer, the peer is told as well
+pub async fn cancel_transfer(transfer_id: String) -> anyhow::Result<()> {
+    service::current()
+        .await?
+        .transfers
+        .cancel(&transfer_id)
+        .await?;
+    Ok(())
+}
+
+/// Stream updates of all transfers, the stream ends when the service stops
+pub async fn watch_transfers(sink: StreamSink<TransferUpdate>) -> anyhow::Result<()> {
+    let service = service::current().await?;
+    let mut events = service.transfers.subscribe();
+    let mut shutdown = service.shutdown.clone();
+    tokio::spawn(async move {
+        loop {
+            let event = tokio::select! {
+                event = events.recv() => event,
+                _ = shutdown.changed() => break,
+            };
+            let event = match event {
+                Ok(event) => event,
+                // Progress is sent again soon, a missed update does no harm
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => break,
+            };
+            if sink.add(update(&service, event).await).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+async fn update(service: &Service, event: TransferEvent) -> TransferUpdate {
+    match event {
+        TransferEvent::Started(transfer) => {
+            let sent = transfer.sender().id() == service.local_device.id();
+            let peer = if sent {
+                transfer.receiver()
+            } else {
+                transfer.sender()
+            };
+            TransferUpdate::Started {
+                transfer_id: transfer.id().to_string(),
+                sent,
+                peer: device(peer, &*service.trust_store.read().await),
+                files: transfer_files(&transfer),
+                total_size: transfer.total_size(),
+            }
+        }
+        TransferEvent::Progress(progress) => TransferUpdate::Progress {
+            transfer_id: progress.transfer_id,
+            file_index: progress.file_index as u32,
+            bytes_transferred: progress.bytes_transferred,
+            total_bytes: progress.total_bytes,
+            bytes_per_second: progress.bytes_per_second,
+            eta_seconds: progress.eta.map(|eta| eta.as_secs()),
+        },
+        TransferEvent::FileCompleted { transfer_id, index } => TransferUpdate::FileCompleted {
+            transfer_id,
+            index: index as u32,
+        },
+        TransferEvent::Finished {
+            transfer_id,
+            status,
+        } => {
+            let (status, error) = status_name(&status);
+            TransferUpdate::Finished {
+                transfer_id,
+                status: status.to_string(),
+                error,
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+use tsunagu_common::discovery;
+
+use super::service;
+
+/// Whether other devices can find this one
+pub enum Visibility {
+    Hidden,
+    /// Hidden again after `seconds`, for countdowns
+    For {
+        seconds: u64,
+    },
+    /// Visible for as long as the service runs
+    WhileOpen,
+}
+
+/// Make this device discoverable for `seconds`, replacing the current window
+pub async fn make_discoverable(seconds: u64) -> anyhow::Result<()> {
+    service::current()
+        .await?
+        .discovery
+        .lock()
+        .await
+        .make_discoverable(Duration::from_secs(seconds))
+        .await?;
+    Ok(())
+}
+
+/// Keep this device discoverable until the service stops
+pub async fn make_discoverable_while_open() -> anyhow::Result<()> {
+    service::current()
+        .await?
+        .discovery
+        .lock()
+        .await
+        .make_discoverable_while_running()
+        .await?;
+    Ok(())
+}
+
+/// Hide this device right away, receiving from known peers still works
+pub async fn make_undiscoverable() -> anyhow::Result<()> {
+    service::current()
+        .await?
+        .discovery
+        .lock()
+        .await
+        .make_undiscoverable()
+        .await?;
+    Ok(())
+}
+
+pub async fn visibility() -> anyhow::Result<Visibility> {
+    let visibility = service::current()
+        .await?
+        .discovery
+        .lock()
+        .await
+        .visibility();
+    Ok(match visibility {
+        discovery::Visibility::Hidden => Visibility::Hidden,
+        discovery::Visibility::For(remaining) => Visibility::For {
+            // Round up, so a countdown does not show 0 while still visible
+            seconds: remaining.as_millis().div_ceil(1000) as u64,
+        },
+        discovery::Visibility::WhileRunning => Visibility::WhileOpen,
+    })
+}
@@ -1,10 +1,43 @@
+use std::io::{IsTerminal, Write};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
 
+use crate::error::CliError;
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
 use clap::{Parser, Subcommand};
-use anyhow::Result;
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, mpsc, RwLock};
 use tracing::info;
-use tsunagu_common::{device::DeviceManager, discovery::{Discovery, MdnsDiscovery}, models::DeviceInfo, transfer::TcpFileTransfer};
-use crate::config::CliConfig;
+use tsunagu_common::{
+    config::{Config, CONFIG_FILE},
+    device::DeviceManager,
+    discovery::{
+        self, CompositeDiscovery, Discovery, DiscoveryEvent, ManualDiscovery, Reachability,
+    },
+    history::{Direction, HistoryEntry, HistoryFilter, HistoryStore, StatusFilter, HISTORY_FILE},
+    identity::{DeviceIdentity, IDENTITY_FILE},
+    models::{DeviceInfo, TransferInfo, TransferStatus},
+    network,
+    pairing::{Pairing, PairingPrompt},
+    paths,
+    peers::{AddressBook, ManualPeer},
+    share_code::ShareCode,
+    transfer::{
+        collect_files, AcceptAll, AcceptTrusted, AcceptancePolicy, CollectOptions, OfferDecision,
+        OfferRules, RejectAll, RulePolicy, SymlinkPolicy, TcpFileTransfer, TransferEvent,
+        TransferHandle, TransferManager, TransferProgress, TransferServer,
+    },
+    trust::TrustStore,
+    TsunaguError,
+};
+
+/// How long `send` waits for the receiver to show up in discovery
+const RESOLVE_TIMEOUT: Duration = Duration::from_secs(5);
+const PROGRESS_BAR_WIDTH: usize = 30;
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -16,153 +49,1294 @@ pub struct Cli {
 #[derive(Subcommand)]
 pub enum Commands {
     /// Start the Tsunagu service
-    Start,
+    Start {
+        /// Stop advertising the device after this many seconds instead of
+        /// staying visible while the service runs
+        #[arg(long, value_name = "SECONDS")]
+        discoverable_for: Option<u64>,
+    },
     Discover {
         #[arg(short, long, default_value = "5")]
         timeout: u64,
     },
-    /// Send a file
+    /// Send files and directories
     Send {
-        #[arg(short, long)]
-        file: String,
-        #[arg(short, long)]
-        receiver: String,
+        /// File or directory to send, may be repeated
+        #[arg(short, long, required = true)]
+        file: Vec<String>,
+        /// Send the targets of symbolic links inside directories
+        #[arg(long)]
+        follow_symlinks: bool,
+        /// Leave out files and directories matching this glob, may be repeated
+        #[arg(long)]
+        ignore: Vec<String>,
+        /// Receiver name, id or ip:port
+        #[arg(short, long, required_unless_present = "code")]
+        receiver: Option<String>,
+        /// Code shown by `receive --code`, for when discovery cannot find the receiver,
+        /// connects over IPv4 only
+        #[arg(long, conflicts_with = "receiver")]
+        code: Option<String>,
     },
     /// Receive a file
     Receive {
-        #[arg(short, long)]
-        sender: String,
+        /// Sender name, id or ip
+        #[arg(short, long, required_unless_present = "code")]
+        sender: Option<String>,
+        /// Show a one-time code the sender types into `send --code` instead, codes
+        /// hold IPv4 addresses only
+        #[arg(long, conflicts_with = "sender")]
+        code: bool,
+    },
+    /// Continue an interrupted transfer, or list them without an id
+    Resume {
+        /// Transfer id or a prefix of it
+        id: Option<String>,
+    },
+    /// Pair with a device so its transfers are accepted automatically
+    Pair {
+        /// Device name, id or ip:port
+        device: String,
+    },
+    /// List paired devices
+    Trusted,
+    /// Forget a paired device
+    Unpair {
+        /// Device name or id
+        device: String,
+    },
+    /// Manage devices added by address, for those discovery cannot find
+    Peers {
+        #[command(subcommand)]
+        command: PeersCommand,
+    },
+    /// Show finished transfers, newest first
+    History {
+        /// Only transfers with this peer, by name, id or ip
+        #[arg(long)]
+        peer: Option<String>,
+        /// Only transfers that ended this way: completed, failed or cancelled
+        #[arg(long)]
+        status: Option<String>,
+        /// Only transfers finished on or after this day (YYYY-MM-DD, UTC)
+        #[arg(long)]
+        since: Option<String>,
+        /// Only transfers finished on or before this day (YYYY-MM-DD, UTC)
+        #[arg(long)]
+        until: Option<String>,
+        /// Show at most this many transfers
+        #[arg(short, long, default_value = "20")]
+        limit: usize,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum PeersCommand {
+    /// Add a device by address, e.g. one on another subnet or over a VPN
+    Add {
+        /// host:port of its transfer server, IPv6 addresses in brackets
+        address: String,
+        /// Device id the peer has to present
+        #[arg(long)]
+        fingerprint: Option<String>,
+    },
+    /// Remove a device added by address
+    Remove {
+        /// host:port the device was added with
+        address: String,
     },
+    /// List the devices added by address and whether they can be reached
+    List,
 }
 
 pub struct CliApp {
-    config: CliConfig,
+    config: Config,
+    config_path: PathBuf,
     device_manager: DeviceManager,
-    discovery: MdnsDiscovery,
-    transfer: TcpFileTransfer,
+    discovery: CompositeDiscovery,
+    transfers: TransferManager,
+    history: HistoryStore,
+    trust_store: Arc<RwLock<TrustStore>>,
 }
 
 impl CliApp {
     pub async fn new() -> Result<Self> {
-        let config = CliConfig::load()?;
-        let device_manager = DeviceManager::new().await?;
-        let local_device = device_manager.get_current_device_info().await;
+        Self::open(&paths::config_dir()?, &paths::data_dir()?).await
+    }
+
+    /// Create the app with its settings in `config_dir` and its state in `data_dir`
+    pub async fn open(config_dir: &Path, data_dir: &Path) -> Result<Self> {
+        Self::open_with_discovery(config_dir, data_dir, discovery::from_config).await
+    }
+
+    /// Like [`open`](Self::open), with the discovery built by `make_discovery`
+    async fn open_with_discovery(
+        config_dir: &Path,
+        data_dir: &Path,
+        make_discovery: impl FnOnce(&Config, DeviceInfo) -> tsunagu_common::Result<CompositeDiscovery>,
+    ) -> Result<Self> {
+        let config_path = config_dir.join(CONFIG_FILE);
+        let config = crate::config::load(&config_path)?;
+        let identity = DeviceIdentity::load_or_create(&data_dir.join(IDENTITY_FILE))?;
+        let device_manager = DeviceManager::with_identity(identity).await?;
+        let mut local_device = device_manager.get_current_device_info().await;
+        local_device.set_name(config.device_name.clone());
+        device_manager
+            .update_device_info(local_device.clone())
+            .await?;
 
         info!("local device {:?}", local_device);
 
-        let discovery = MdnsDiscovery::new(local_device.clone())?;
-        let transfer = TcpFileTransfer::new(
-            device_manager.get_current_device_info().await,
-            config.download_dir.clone(),
-        );
+        let discovery = make_discovery(&config, local_device.clone())?;
+        let trust_store = Arc::new(RwLock::new(TrustStore::load(&config_path)?));
+        let transfer = TcpFileTransfer::new(local_device, config.save_directory.clone())
+            .with_identity(device_manager.identity())
+            .with_trust_store(trust_store.clone())
+            .with_state_dir(data_dir.join("transfers"))
+            .with_cancelled_partials(config.cancelled_partials);
+        let history = HistoryStore::new(data_dir.join(HISTORY_FILE));
+        let transfers = TransferManager::new(transfer, config.limits.max_concurrent_transfers)
+            .with_history_store(history.clone());
 
         Ok(Self {
             config,
+            config_path,
             device_manager,
             discovery,
-            transfer,
+            transfers,
+            history,
+            trust_store,
         })
     }
 
     pub async fn run(&mut self, cli: Cli) -> Result<()> {
         match cli.command {
-            Some(Commands::Start) => self.start_service().await?,
+            Some(Commands::Start { discoverable_for }) => {
+                self.start_service(discoverable_for.map(Duration::from_secs))
+                    .await?
+            }
             Some(Commands::Discover { timeout }) => self.discover_devices(timeout).await?,
-            Some(Commands::Send { file, receiver }) => self.send_file(&file, &receiver).await?,
-            Some(Commands::Receive { sender }) => self.receive_file(&sender).await?,
-            None => self.start_service().await?,
+            Some(Commands::Send {
+                file,
+                receiver,
+                code,
+                follow_symlinks,
+                ignore,
+            }) => {
+                let options = CollectOptions {
+                    symlinks: if follow_symlinks {
+                        SymlinkPolicy::Follow
+                    } else {
+                        SymlinkPolicy::Skip
+                    },
+                    ignore,
+                };
+                match (receiver, code) {
+                    (_, Some(code)) => self.send_files_by_code(&file, &options, &code).await?,
+                    (Some(receiver), None) => self.send_files(&file, &options, &receiver).await?,
+                    (None, None) => bail!("A receiver or a code is required"),
+                }
+            }
+            Some(Commands::Receive { sender, code }) => match sender {
+                Some(sender) if !code => self.receive_file(&sender).await?,
+                _ => self.receive_by_code().await?,
+            },
+            Some(Commands::Resume { id }) => self.resume(id.as_deref()).await?,
+            Some(Commands::Pair { device }) => self.pair(&device).await?,
+            Some(Commands::Trusted) => self.list_trusted().await,
+            Some(Commands::Unpair { device }) => self.unpair(&device).await?,
+            Some(Commands::Peers { command }) => self.manage_peers(command).await?,
+            Some(Commands::History {
+                peer,
+                status,
+                since,
+                until,
+                limit,
+            }) => {
+                let filter = HistoryFilter {
+                    peer,
+                    status: status
+                        .as_deref()
+                        .map(str::parse::<StatusFilter>)
+                        .transpose()?,
+                    direction: None,
+                    since: since.as_deref().map(parse_date).transpose()?,
+                    // The whole `until` day is included
+                    until: until
+                        .as_deref()
+                        .map(|day| parse_date(day).map(|start| start + SECONDS_PER_DAY))
+                        .transpose()?,
+                    limit: Some(limit),
+                };
+                self.show_history(&filter)?
+            }
+            None => self.start_service(None).await?,
         }
 
         Ok(())
     }
 
-    async fn start_service(&mut self) -> Result<()> {
+    async fn start_service(&mut self, discoverable_for: Option<Duration>) -> Result<()> {
         info!("Starting Tsunagu service...");
-        self.discovery.start().await?;
-
-        self.discovery.make_discoverable(Duration::from_secs(3600)).await?; // Make discoverable for 1 hour
-        let local_device = self.device_manager.get_current_device_info().await;
-        info!("Tsunagu service started on port {}. Press Ctrl+C to stop.", local_device.port());
+        let untrusted: Arc<dyn AcceptancePolicy> = if self.config.allow_auto_receive {
+            Arc::new(AcceptAll)
+        } else if std::io::stdin().is_terminal() {
+            Arc::new(StdinOfferPrompt::default())
+        } else {
+            Arc::new(RejectAll)
+        };
+        let policy = AcceptTrusted::new(self.trust_store.clone(), untrusted);
+        let mut server = self
+            .start_server(self.with_rules(Arc::new(policy)), discoverable_for)
+            .await?;
+        info!(
+            "Tsunagu service started on port {}, saving files to {}. Press Ctrl+C to stop.",
+            self.device_manager.get_current_device_info().await.port(),
+            self.config.save_directory.display()
+        );
 
         tokio::signal::ctrl_c().await?;
         info!("Stopping Tsunagu service...");
+        server.stop().await?;
         self.discovery.stop().await?;
         Ok(())
     }
 
+    /// Apply the offer rules from the config before `policy` is asked
+    fn with_rules(&self, policy: Arc<dyn AcceptancePolicy>) -> Arc<dyn AcceptancePolicy> {
+        Arc::new(RulePolicy::new(
+            OfferRules::from_config(&self.config),
+            self.trust_store.clone(),
+            policy,
+        ))
+    }
+
+    /// Start the transfer server and advertise the device with its real port
+    ///
+    /// The device stays discoverable for `discoverable_for`, or while the
+    /// server runs if that is `None`.
+    async fn start_server(
+        &mut self,
+        policy: Arc<dyn AcceptancePolicy>,
+        discoverable_for: Option<Duration>,
+    ) -> Result<TransferServer> {
+        let pairing = Arc::new(self.pairing().await);
+        let mut server = self.transfers.server(policy).with_pairing(pairing);
+        server
+            .start(self.config.transfer_port, &self.device_manager)
+            .await?;
+
+        // The server port is only known now, advertise the updated device
+        let local_device = self.device_manager.get_current_device_info().await;
+        self.transfers.set_local_device(local_device.clone());
+        self.discovery.set_local_device(local_device);
+
+        self.discovery.start().await?;
+        match discoverable_for {
+            Some(duration) => self.discovery.make_discoverable(duration).await?,
+            None => self.discovery.make_discoverable_while_running().await?,
+        }
+        Ok(server)
+    }
+
+    /// Print devices as they appear, change and go away until `timeout` seconds passed
     async fn discover_devices(&mut self, timeout: u64) -> Result<()> {
         info!("Discovering devices for {} seconds...", timeout);
+        let mut events = self.discovery.subscribe();
         self.discovery.start().await?;
-        self.discovery.make_discoverable(Duration::from_secs(timeout)).await?;
-        
-        tokio::time::sleep(Duration::from_secs(timeout)).await;
-        
-        let devices = self.discovery.discover_devices().await?;
-        info!("Discovered devices:");
-        for device in devices {
-            info!("- {} ({})", device.name(), device.ip());
-        }
-        
+        self.discovery
+            .make_discoverable(Duration::from_secs(timeout))
+            .await?;
+
+        let deadline = tokio::time::sleep(Duration::from_secs(timeout));
+        tokio::pin!(deadline);
+        loop {
+            let event = tokio::select! {
+                _ = &mut deadline => break,
+                event = events.recv() => event,
+            };
+            match event {
+                Ok(DiscoveryEvent::DeviceAdded(device)) => {
+                    info!("+ {} ({})", device.name(), device.addresses_text())
+                }
+                Ok(DiscoveryEvent::DeviceUpdated(device)) => {
+                    info!("~ {} ({})", device.name(), device.addresses_text())
+                }
+                Ok(DiscoveryEvent::DeviceLost(device)) => {
+                    info!("- {} ({})", device.name(), device.addresses_text())
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+
+        let devices = self.discovery.discover_with_sources().await?;
+        info!("{} device(s) found", devices.len());
+        for found in &devices {
+            let sources: Vec<&str> = found
+                .sightings
+                .iter()
+                .map(|sighting| sighting.source.as_str())
+                .collect();
+            info!(
+                "  {} ({}) via {}",
+                found.device.name(),
+                found.device.addresses_text(),
+                sources.join(", ")
+            );
+        }
         self.discovery.stop().await?;
         Ok(())
     }
 
-    async fn send_file(&self, file: &str, receiver: &str) -> Result<()> {
-        info!("Sending file {} to {}", file, receiver);
-        // Implement file sending logic here
+    async fn send_files(
+        &mut self,
+        paths: &[String],
+        options: &CollectOptions,
+        receiver: &str,
+    ) -> Result<()> {
+        let sources: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+        let files = collect_files(&sources, options)
+            .with_context(|| format!("Cannot send {}", paths.join(", ")))?;
+        if files.is_empty() {
+            bail!("Nothing to send in {}", paths.join(", "));
+        }
+        let receiver = self.resolve_receiver(receiver).await?;
+        info!(
+            "Sending {} file(s) to {} ({}, port {})",
+            files.len(),
+            receiver.name(),
+            receiver.addresses_text(),
+            receiver.port()
+        );
+
+        let handle = self.transfers.send(files, receiver).await?;
+        self.follow_transfer(handle).await
+    }
+
+    /// Send to the receiver that showed `code`, without discovery
+    async fn send_files_by_code(
+        &mut self,
+        paths: &[String],
+        options: &CollectOptions,
+        code: &str,
+    ) -> Result<()> {
+        let code = ShareCode::parse(code)?;
+        let sources: Vec<PathBuf> = paths.iter().map(PathBuf::from).collect();
+        let files = collect_files(&sources, options)
+            .with_context(|| format!("Cannot send {}", paths.join(", ")))?;
+        if files.is_empty() {
+            bail!("Nothing to send in {}", paths.join(", "));
+        }
+        let receiver = code.receiver();
+        info!(
+            "Sending {} file(s) by code to {} (port {})",
+            files.len(),
+            receiver.addresses_text(),
+            receiver.port()
+        );
+
+        let handle = self.transfers.send_with_code(files, code).await?;
+        self.follow_transfer(handle).await
+    }
+
+    /// List interrupted transfers, or continue the one matching `id`
+    async fn resume(&mut self, id: Option<&str>) -> Result<()> {
+        let interrupted = self.transfers.load_interrupted().await?;
+        let Some(id) = id else {
+            if interrupted.is_empty() {
+                info!("No interrupted transfers");
+            }
+            for transfer in &interrupted {
+                info!(
+                    "- {}: {} file(s) to {}",
+                    transfer.id(),
+                    transfer.files().len(),
+                    transfer.receiver().name()
+                );
+            }
+            return Ok(());
+        };
+
+        let transfer_info = interrupted
+            .into_iter()
+            .find(|t| t.id().starts_with(id))
+            .ok_or_else(|| anyhow!("No interrupted transfer {}", id))?;
+        info!(
+            "Resuming transfer {} to {}",
+            transfer_info.id(),
+            transfer_info.receiver().name()
+        );
+        let handle = self.transfers.resume(transfer_info.id()).await?;
+        self.follow_transfer(handle).await
+    }
+
+    /// Draw the progress of a send until it ends
+    async fn follow_transfer(&mut self, handle: TransferHandle) -> Result<()> {
+        let transfer_info = handle.info().clone();
+        let mut events = self.transfers.subscribe();
+        let task = handle.wait();
+        tokio::pin!(task);
+
+        let result = loop {
+            tokio::select! {
+                result = &mut task => break result,
+                event = events.recv() => {
+                    if let Ok(TransferEvent::Progress(progress)) = event {
+                        if progress.transfer_id == transfer_info.id() {
+                            print_progress(&progress);
+                        }
+                    }
+                }
+                _ = tokio::signal::ctrl_c() => {
+                    self.transfers.cancel(transfer_info.id()).await?;
+                    let _ = task.await;
+                    return Err(CliError::Interrupted.into());
+                }
+            }
+        };
+
+        // Show the final progress the task reported before it returned
+        let mut last = None;
+        while let Ok(event) = events.try_recv() {
+            if let TransferEvent::Progress(progress) = event {
+                if progress.transfer_id == transfer_info.id() {
+                    last = Some(progress);
+                }
+            }
+        }
+        if let Some(progress) = last {
+            print_progress(&progress);
+            println!();
+        }
+
+        match result {
+            Ok(()) => {
+                info!("Transfer {} completed", transfer_info.id());
+                Ok(())
+            }
+            Err(TsunaguError::Rejected(reason)) => Err(CliError::Refused(reason).into()),
+            Err(TsunaguError::Cancelled(by)) => Err(CliError::Cancelled(by).into()),
+            Err(e) => Err(anyhow::Error::from(e).context(format!(
+                "Transfer interrupted, continue it with `tsunagu resume {}`",
+                transfer_info.id()
+            ))),
+        }
+    }
+
+    async fn pairing(&self) -> Pairing {
+        Pairing::new(
+            self.device_manager.get_current_device_info().await,
+            self.device_manager.identity(),
+            self.trust_store.clone(),
+            Arc::new(StdinPrompt),
+        )
+    }
+
+    async fn pair(&mut self, device: &str) -> Result<()> {
+        let device = self.resolve_receiver(device).await?;
+        let peer = match self.pairing().await.pair(&device).await {
+            Ok(peer) => peer,
+            Err(TsunaguError::Rejected(reason)) => return Err(CliError::Refused(reason).into()),
+            Err(e) => return Err(e.into()),
+        };
+        info!("Paired with {} ({})", peer.name(), peer.id());
+        Ok(())
+    }
+
+    async fn list_trusted(&self) {
+        let trust_store = self.trust_store.read().await;
+        if trust_store.devices().is_empty() {
+            info!("No paired devices");
+        }
+        for device in trust_store.devices() {
+            info!("- {} ({})", device.name(), device.id());
+        }
+    }
+
+    async fn unpair(&mut self, device: &str) -> Result<()> {
+        let mut trust_store = self.trust_store.write().await;
+        if !trust_store.remove(device) {
+            return Err(CliError::UnknownReceiver(device.to_string()).into());
+        }
+        trust_store.save()?;
+        info!("Forgot {}", device);
+        Ok(())
+    }
+
+    async fn manage_peers(&self, command: PeersCommand) -> Result<()> {
+        let mut address_book = AddressBook::load(&self.config_path)?;
+        match command {
+            PeersCommand::Add {
+                address,
+                fingerprint,
+            } => {
+                let peer = ManualPeer::new(&address, fingerprint.as_deref())?;
+                address_book.add(peer);
+                address_book.save()?;
+                info!("Added {}", address);
+            }
+            PeersCommand::Remove { address } => {
+                if !address_book.remove(&address) {
+                    bail!("No peer at {}", address);
+                }
+                address_book.save()?;
+                info!("Removed {}", address);
+            }
+            PeersCommand::List => {
+                if address_book.peers().is_empty() {
+                    info!("No peers added by address");
+                    return Ok(());
+                }
+                let local_device = self.device_manager.get_current_device_info().await;
+                let discovery = ManualDiscovery::new(&local_device, address_book.peers().to_vec());
+                for status in discovery.probe().await {
+                    let address = status.peer.address();
+                    match status.reachability {
+                        Reachability::Reachable(device) => {
+                            info!("- {}: {} ({})", address, device.name(), device.id())
+                        }
+                        Reachability::Unreachable(reason) => {
+                            info!("- {}: unreachable ({})", address, reason)
+                        }
+                        Reachability::FingerprintMismatch { found } => info!(
+                            "- {}: answered as {}, expected {}",
+                            address,
+                            found,
+                            status.peer.fingerprint().unwrap_or_default()
+                        ),
+                        Reachability::Unknown => info!("- {}", address),
+                    }
+                }
+            }
+        }
         Ok(())
     }
 
-    async fn receive_file(&self, sender: &str) -> Result<()> {
-        info!("Receiving file from {}", sender);
-        // Implement file receiving logic here
+    fn show_history(&self, filter: &HistoryFilter) -> Result<()> {
+        let entries = self.history.query(filter)?;
+        if entries.is_empty() {
+            info!("No transfers in the history");
+        }
+        for entry in &entries {
+            info!("{}", format_history_entry(entry));
+            for (index, file) in entry.transfer().files().iter().enumerate() {
+                let path = entry
+                    .path(index)
+                    .map_or_else(|| "-".to_string(), |path| path.display().to_string());
+                info!(
+                    "    {} ({}) sha256:{} {}",
+                    file.relative_path(),
+                    format_bytes(file.size()),
+                    file.hash().unwrap_or("-"),
+                    path
+                );
+            }
+        }
         Ok(())
     }
+
+    /// Find the receiver by `ip:port`, or by name or id among discovered devices
+    async fn resolve_receiver(&mut self, receiver: &str) -> Result<DeviceInfo> {
+        if let Ok(addr) = receiver.parse::<SocketAddr>() {
+            return Ok(DeviceInfo::new(
+                receiver.to_string(),
+                "Unknown".to_string(),
+                [addr.ip()],
+                addr.port(),
+                "Unknown".to_string(),
+                "Unknown".to_string(),
+            ));
+        }
+
+        info!("Looking for {}...", receiver);
+        let mut events = self.discovery.subscribe();
+        self.discovery.start().await?;
+        let known = self.discovery.discover_devices().await?;
+        let found = match known.into_iter().find(|d| d.matches(receiver)) {
+            Some(device) => Some(device),
+            None => tokio::time::timeout(RESOLVE_TIMEOUT, async {
+                loop {
+                    match events.recv().await {
+                        Ok(DiscoveryEvent::DeviceAdded(device))
+                        | Ok(DiscoveryEvent::DeviceUpdated(device))
+                            if device.matches(receiver) =>
+                        {
+                            return Some(device)
+                        }
+                        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                        Err(broadcast::error::RecvError::Closed) => return None,
+                    }
+                }
+            })
+            .await
+            .ok()
+            .flatten(),
+        };
+        self.discovery.stop().await?;
+
+        found.ok_or_else(|| CliError::UnknownReceiver(receiver.to_string()).into())
+    }
+
+    async fn receive_file(&mut self, sender: &str) -> Result<()> {
+        let (accepted_tx, mut accepted_rx) = mpsc::unbounded_channel();
+        let policy = ExpectSender {
+            sender: Some(sender.to_string()),
+            accepted: accepted_tx,
+        };
+        // Subscribe first so no event of the accepted transfer is missed
+        let mut events = self.transfers.subscribe();
+        let mut server = self
+            .start_server(self.with_rules(Arc::new(policy)), None)
+            .await?;
+        info!(
+            "Waiting for files from {} on port {}. Press Ctrl+C to stop.",
+            sender,
+            self.device_manager.get_current_device_info().await.port()
+        );
+
+        let result = self.wait_for_transfer(&mut accepted_rx, &mut events).await;
+
+        server.stop().await?;
+        self.discovery.stop().await?;
+        result
+    }
+
+    /// Show a one-time code and receive from the sender that types it in
+    ///
+    /// Listens on a port of its own, the transfer server is not started. Only
+    /// IPv4 addresses fit in a code, so unlike the server it listens on IPv4
+    /// only.
+    async fn receive_by_code(&mut self) -> Result<()> {
+        let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, 0)).await?;
+        let port = listener.local_addr()?.port();
+        let code = ShareCode::generate(&network::local_addresses()?, port)?;
+
+        let (accepted_tx, mut accepted_rx) = mpsc::unbounded_channel();
+        // The code authenticates the sender, whoever it is
+        let policy = ExpectSender {
+            sender: None,
+            accepted: accepted_tx,
+        };
+        let mut events = self.transfers.subscribe();
+        let transfer = self.transfers.transfer().clone();
+        let receive_code = code.clone();
+        let task = tokio::spawn(async move {
+            transfer
+                .receive_with_code(listener, &receive_code, &policy)
+                .await
+        });
+        info!("Code: {}", code);
+        info!(
+            "Waiting for `tsunagu send --code` on port {}, the code works once. Press Ctrl+C to stop.",
+            port
+        );
+
+        let result = self.wait_for_transfer(&mut accepted_rx, &mut events).await;
+        // A closed channel means the receive ended, possibly before any offer
+        if result.is_err() && !accepted_rx.is_closed() {
+            task.abort();
+        } else {
+            task.await??;
+        }
+        result
+    }
+
+    /// Wait for the first accepted offer and follow it to completion
+    async fn wait_for_transfer(
+        &mut self,
+        accepted: &mut mpsc::UnboundedReceiver<TransferInfo>,
+        events: &mut broadcast::Receiver<TransferEvent>,
+    ) -> Result<()> {
+        let transfer_info = tokio::select! {
+            transfer_info = accepted.recv() => {
+                transfer_info.ok_or_else(|| anyhow!("Transfer server stopped"))?
+            }
+            _ = tokio::signal::ctrl_c() => return Err(CliError::Interrupted.into()),
+        };
+        info!(
+            "Receiving {} file(s) from {}",
+            transfer_info.files().len(),
+            transfer_info.sender().name()
+        );
+
+        loop {
+            let event = tokio::select! {
+                event = events.recv() => event,
+                _ = tokio::signal::ctrl_c() => {
+                    self.transfers.cancel(transfer_info.id()).await?;
+                    return Err(CliError::Interrupted.into());
+                }
+            };
+            let event = match event {
+                Ok(event) if event.transfer_id() == transfer_info.id() => event,
+                // Progress is only informative, skipping some of it is fine
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => bail!("Transfer events closed"),
+            };
+
+            match event {
+                TransferEvent::Progress(progress) => print_progress(&progress),
+                TransferEvent::Finished { status, .. } => {
+                    println!();
+                    return match status {
+                        TransferStatus::Completed => {
+                            info!("Files saved to {}", self.config.save_directory.display());
+                            Ok(())
+                        }
+                        TransferStatus::Failed(reason) => bail!("Transfer failed: {}", reason),
+                        TransferStatus::Cancelled => {
+                            Err(CliError::Cancelled("by the sender".into()).into())
+                        }
+                        other => bail!("Transfer ended as {:?}", other),
+                    };
+                }
+                TransferEvent::Started(_) | TransferEvent::FileCompleted { .. } => {}
+            }
+        }
+    }
+}
+
+/// Only accepts offers from the expected sender and reports accepted transfers
+struct ExpectSender {
+    /// Any sender when `None`
+    sender: Option<String>,
+    accepted: mpsc::UnboundedSender<TransferInfo>,
+}
+
+#[async_trait]
+impl AcceptancePolicy for ExpectSender {
+    async fn decide(&self, transfer: &TransferInfo) -> OfferDecision {
+        let unexpected = self
+            .sender
+            .as_deref()
+            .is_some_and(|sender| !transfer.sender().matches(sender));
+        if unexpected {
+            return OfferDecision::Reject(format!(
+                "Not expecting files from {}",
+                transfer.sender().name()
+            ));
+        }
+
+        if self.accepted.send(transfer.clone()).is_err() {
+            return OfferDecision::Reject("Receiver is no longer waiting".into());
+        }
+        OfferDecision::Accept
+    }
+}
+
+/// Asks on the terminal whether the verification codes match
+struct StdinPrompt;
+
+#[async_trait]
+impl PairingPrompt for StdinPrompt {
+    async fn confirm(&self, peer: &DeviceInfo, code: &str) -> bool {
+        println!("Pairing with {} ({})", peer.name(), peer.id());
+        println!("Verification code: {}", code);
+        print!("Does the other device show the same code? [y/N] ");
+        std::io::stdout().flush().ok();
+
+        let answer = tokio::task::spawn_blocking(|| {
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line).map(|_| line)
+        })
+        .await;
+        matches!(answer, Ok(Ok(line)) if line.trim().eq_ignore_ascii_case("y"))
+    }
+}
+
+/// Asks on the terminal whether to accept an offer
+#[derive(Default)]
+struct StdinOfferPrompt {
+    /// Offers arriving together are asked one after another
+    lock: tokio::sync::Mutex<()>,
+}
+
+#[async_trait]
+impl AcceptancePolicy for StdinOfferPrompt {
+    async fn decide(&self, transfer: &TransferInfo) -> OfferDecision {
+        let _guard = self.lock.lock().await;
+        println!(
+            "{} ({}) wants to send {} file(s), {}:",
+            transfer.sender().name(),
+            transfer.sender().id(),
+            transfer.files().len(),
+            format_bytes(transfer.total_size())
+        );
+        for (index, file) in transfer.files().iter().enumerate() {
+            println!(
+                "  {:>3}. {} ({})",
+                index + 1,
+                file.relative_path(),
+                format_bytes(file.size())
+            );
+        }
+        print!("Accept? [y/N or file numbers, e.g. 1,3] ");
+        std::io::stdout().flush().ok();
+
+        let answer = tokio::task::spawn_blocking(|| {
+            let mut line = String::new();
+            std::io::stdin().read_line(&mut line).map(|_| line)
+        })
+        .await;
+        match answer {
+            Ok(Ok(line)) => parse_offer_answer(&line, transfer.files().len()),
+            _ => OfferDecision::Reject("No answer".into()),
+        }
+    }
+}
+
+/// Turn the answer to [`StdinOfferPrompt`] into a decision
+fn parse_offer_answer(answer: &str, files: usize) -> OfferDecision {
+    let answer = answer.trim();
+    if answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes") {
+        return OfferDecision::Accept;
+    }
+    let selected: Option<Vec<usize>> = answer
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            part.parse::<usize>()
+                .ok()
+                .filter(|n| (1..=files).contains(n))
+                .map(|n| n - 1)
+        })
+        .collect();
+    match selected {
+        Some(indices) if !indices.is_empty() => OfferDecision::AcceptPartial(indices),
+        _ => OfferDecision::Reject("Declined by the user".into()),
+    }
+}
+
+fn print_progress(progress: &TransferProgress) {
+    print!("\r{}", format_progress(progress));
+    std::io::stdout().flush().ok();
+}
+
+/// Progress bar line like `[=====>    ]  50.0%  1.2 MiB/s  ETA 0:05`
+fn format_progress(progress: &TransferProgress) -> String {
+    let percent = progress.percent();
+    let filled = (percent as usize * PROGRESS_BAR_WIDTH / 100).min(PROGRESS_BAR_WIDTH);
+    let bar = if filled == PROGRESS_BAR_WIDTH {
+        "=".repeat(PROGRESS_BAR_WIDTH)
+    } else {
+        format!(
+            "{}>{}",
+            "=".repeat(filled),
+            " ".repeat(PROGRESS_BAR_WIDTH - filled - 1)
+        )
+    };
+    let eta = match progress.eta {
+        Some(eta) => {
+            let secs = eta.as_secs();
+            format!("{}:{:02}", secs / 60, secs % 60)
+        }
+        None => "--:--".to_string(),
+    };
+    format!(
+        "[{}] {:5.1}%  {}/s  ETA {}",
+        bar,
+        percent,
+        format_bytes(progress.bytes_per_second as u64),
+        eta
+    )
+}
+
+/// Summary line like `2024-05-01 12:30 sent 2 file(s), 1.5 KiB to laptop: completed in 0:05`
+fn format_history_entry(entry: &HistoryEntry) -> String {
+    let (verb, preposition) = match entry.direction() {
+        Direction::Sent => ("sent", "to"),
+        Direction::Received => ("received", "from"),
+    };
+    let status = match entry.status() {
+        TransferStatus::Completed => "completed".to_string(),
+        TransferStatus::Cancelled => "cancelled".to_string(),
+        TransferStatus::Failed(reason) => format!("failed ({})", reason),
+        other => format!("{:?}", other),
+    };
+    let secs = entry.duration().as_secs();
+    format!(
+        "{} {} {} file(s), {} {} {}: {} in {}:{:02}",
+        format_timestamp(entry.finished_at()),
+        verb,
+        entry.transfer().files().len(),
+        format_bytes(entry.transfer().total_size()),
+        preposition,
+        entry.peer().name(),
+        status,
+        secs / 60,
+        secs % 60
+    )
+}
+
+/// Start of the UTC day `YYYY-MM-DD` in seconds since the Unix epoch
+fn parse_date(date: &str) -> Result<u64> {
+    let invalid = || anyhow!("Invalid date {:?}, expected YYYY-MM-DD", date);
+    let mut parts = date.trim().splitn(3, '-');
+    let mut next = || -> Result<i64> {
+        parts
+            .next()
+            .and_then(|part| part.parse().ok())
+            .ok_or_else(invalid)
+    };
+    let (year, month, day) = (next()?, next()?, next()?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return Err(invalid());
+    }
+    let days = days_from_civil(year, month, day);
+    // Catches days past the end of the month, like February 30
+    if civil_from_days(days) != (year, month, day) || days < 0 {
+        return Err(invalid());
+    }
+    Ok(days as u64 * SECONDS_PER_DAY)
+}
+
+/// `YYYY-MM-DD HH:MM` in UTC
+fn format_timestamp(secs: u64) -> String {
+    let (year, month, day) = civil_from_days((secs / SECONDS_PER_DAY) as i64);
+    let time = secs % SECONDS_PER_DAY;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60
+    )
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Inverse of [`days_from_civil`]
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::net::Ipv4Addr;
+
+    /// An app keeping its settings and state in a fresh temporary directory
+    ///
+    /// Its discovery has no sources, so tests stay off the host network.
+    async fn test_app() -> (CliApp, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let app = CliApp::open_with_discovery(
+            &dir.path().join("config"),
+            &dir.path().join("data"),
+            |_, _| Ok(CompositeDiscovery::new()),
+        )
+        .await
+        .unwrap();
+        (app, dir)
+    }
 
     #[tokio::test]
     async fn test_cli_app_creation() {
-        let app = CliApp::new().await;
-        assert!(app.is_ok());
+        let (_app, dir) = test_app().await;
+        // The identity is kept in the given data dir
+        assert!(dir.path().join("data").join(IDENTITY_FILE).exists());
     }
 
     #[tokio::test]
     async fn test_start_service() {
-        let mut app = CliApp::new().await.unwrap();
+        let (mut app, _dir) = test_app().await;
         let cli = Cli {
-            command: Some(Commands::Start),
+            command: Some(Commands::Start {
+                discoverable_for: None,
+            }),
         };
-        let result = app.run(cli).await;
-        assert!(result.is_ok());
+        // The service runs until Ctrl+C, so it must still be running here
+        let result = tokio::time::timeout(Duration::from_secs(2), app.run(cli)).await;
+        assert!(result.is_err());
+
+        let local_device = app.device_manager.get_current_device_info().await;
+        assert_ne!(local_device.port(), 0);
     }
 
     #[tokio::test]
-    async fn test_send_file() {
-        let mut app = CliApp::new().await.unwrap();
+    async fn test_send_missing_file() {
+        let (mut app, _dir) = test_app().await;
         let cli = Cli {
             command: Some(Commands::Send {
-                file: "test.txt".to_string(),
-                receiver: "ReceiverDevice".to_string(),
+                file: vec!["test.txt".to_string()],
+                receiver: Some("ReceiverDevice".to_string()),
+                code: None,
+                follow_symlinks: false,
+                ignore: Vec::new(),
             }),
         };
         let result = app.run(cli).await;
-        assert!(result.is_ok());
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_send_unknown_receiver() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("test.txt");
+        std::fs::write(&path, b"hello").unwrap();
+
+        let (mut app, _dir) = test_app().await;
+        let cli = Cli {
+            command: Some(Commands::Send {
+                file: vec![path.to_string_lossy().into_owned()],
+                receiver: Some("NoSuchReceiverDevice".to_string()),
+                code: None,
+                follow_symlinks: false,
+                ignore: Vec::new(),
+            }),
+        };
+        let err = app.run(cli).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CliError>(),
+            Some(CliError::UnknownReceiver(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_send_file_to_address() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let target_dir = tempfile::tempdir().unwrap();
+        let path = source_dir.path().join("test.txt");
+        std::fs::write(&path, b"hello").unwrap();
+
+        let device_manager = DeviceManager::with_identity(DeviceIdentity::generate().unwrap())
+            .await
+            .unwrap();
+        let receiver =
+            TcpFileTransfer::from_device_manager(&device_manager, target_dir.path().to_path_buf())
+                .await;
+        let mut server = TransferServer::new(receiver, Arc::new(AcceptAll));
+        let port = server.start(0, &device_manager).await.unwrap().port();
+
+        let (mut app, _dir) = test_app().await;
+        let cli = Cli {
+            command: Some(Commands::Send {
+                file: vec![path.to_string_lossy().into_owned()],
+                receiver: Some(format!("127.0.0.1:{}", port)),
+                code: None,
+                follow_symlinks: false,
+                ignore: Vec::new(),
+            }),
+        };
+        app.run(cli).await.unwrap();
+        assert_eq!(
+            std::fs::read(target_dir.path().join("test.txt")).unwrap(),
+            b"hello"
+        );
+
+        server.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_send_file_by_code() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let target_dir = tempfile::tempdir().unwrap();
+        let path = source_dir.path().join("test.txt");
+        std::fs::write(&path, b"hello").unwrap();
+
+        let device_manager = DeviceManager::with_identity(DeviceIdentity::generate().unwrap())
+            .await
+            .unwrap();
+        let receiver =
+            TcpFileTransfer::from_device_manager(&device_manager, target_dir.path().to_path_buf())
+                .await;
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let code = ShareCode::generate(&[Ipv4Addr::LOCALHOST.into()], port).unwrap();
+        let typed = code.to_string();
+        let receiving = tokio::spawn(async move {
+            receiver
+                .receive_with_code(listener, &code, &AcceptAll)
+                .await
+        });
+
+        let (mut app, _dir) = test_app().await;
+        let cli = Cli {
+            command: Some(Commands::Send {
+                file: vec![path.to_string_lossy().into_owned()],
+                receiver: None,
+                code: Some(typed),
+                follow_symlinks: false,
+                ignore: Vec::new(),
+            }),
+        };
+        app.run(cli).await.unwrap();
+        receiving.await.unwrap().unwrap();
+        assert_eq!(
+            std::fs::read(target_dir.path().join("test.txt")).unwrap(),
+            b"hello"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_file_refused() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let path = source_dir.path().join("test.txt");
+        std::fs::write(&path, b"hello").unwrap();
+
+        let device_manager = DeviceManager::with_identity(DeviceIdentity::generate().unwrap())
+            .await
+            .unwrap();
+        let receiver = TcpFileTransfer::from_device_manager(
+            &device_manager,
+            source_dir.path().join("received"),
+        )
+        .await;
+        let mut server = TransferServer::new(receiver, Arc::new(RejectAll));
+        let port = server.start(0, &device_manager).await.unwrap().port();
+
+        let (mut app, _dir) = test_app().await;
+        let cli = Cli {
+            command: Some(Commands::Send {
+                file: vec![path.to_string_lossy().into_owned()],
+                receiver: Some(format!("127.0.0.1:{}", port)),
+                code: None,
+                follow_symlinks: false,
+                ignore: Vec::new(),
+            }),
+        };
+        let err = app.run(cli).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<CliError>(),
+            Some(CliError::Refused(_))
+        ));
+
+        server.stop().await.unwrap();
     }
 
     #[tokio::test]
     async fn test_receive_file() {
-        let mut app = CliApp::new().await.unwrap();
+        let (mut app, _dir) = test_app().await;
         let cli = Cli {
             command: Some(Commands::Receive {
-                sender: "SenderDevice".to_string(),
+                sender: Some("SenderDevice".to_string()),
+                code: false,
             }),
         };
-        let result = app.run(cli).await;
-        assert!(result.is_ok());
+        // Nothing is sent, so the receiver must still be waiting
+        let result = tokio::time::timeout(Duration::from_secs(2), app.run(cli)).await;
+        assert!(result.is_err());
     }
-}
 
+    #[test]
+    fn test_format_progress() {
+        let progress = TransferProgress {
+            transfer_id: "id".to_string(),
+            file_index: 0,
+            file_bytes: 50,
+            file_size: 100,
+            bytes_transferred: 50,
+            total_bytes: 100,
+            bytes_per_second: 1536.0,
+            eta: Some(Duration::from_secs(65)),
+        };
+        let line = format_progress(&progress);
+        assert!(line.starts_with(&format!("[{}>", "=".repeat(15))));
+        assert!(line.ends_with(" 50.0%  1.5 KiB/s  ETA 1:05"));
+
+        let done = TransferProgress {
+            bytes_transferred: 100,
+            eta: None,
+            ..progress
+        };
+        assert!(format_progress(&done).contains(&"=".repeat(PROGRESS_BAR_WIDTH)));
+        assert!(format_progress(&done).ends_with("ETA --:--"));
+        assert_eq!(format_bytes(512), "512 B");
+    }
+
+    #[tokio::test]
+    async fn test_expect_sender_policy() {
+        let (accepted, mut accepted_rx) = mpsc::unbounded_channel();
+        let policy = ExpectSender {
+            sender: Some("Expected".to_string()),
+            accepted,
+        };
+        let device = |name: &str| {
+            DeviceInfo::new(
+                name.to_string(),
+                "Test Model".to_string(),
+                [Ipv4Addr::LOCALHOST.into()],
+                8000,
+                "TestOS".to_string(),
+                "1.0.0".to_string(),
+            )
+        };
+
+        let other = TransferInfo::new(device("Other"), device("Me"), vec![]);
+        assert!(matches!(
+            policy.decide(&other).await,
+            OfferDecision::Reject(_)
+        ));
+        assert!(accepted_rx.try_recv().is_err());
+
+        let expected = TransferInfo::new(device("Expected"), device("Me"), vec![]);
+        assert_eq!(policy.decide(&expected).await, OfferDecision::Accept);
+        assert_eq!(accepted_rx.try_recv().unwrap().id(), expected.id());
+    }
+
+    #[test]
+    fn test_dates() {
+        assert_eq!(parse_date("1970-01-01").unwrap(), 0);
+        assert_eq!(parse_date("2024-02-29").unwrap(), 1_709_164_800);
+        assert!(parse_date("2023-02-29").is_err());
+        assert!(parse_date("2024-13-01").is_err());
+        assert!(parse_date("yesterday").is_err());
+        assert_eq!(format_timestamp(1_709_164_800 + 3_723), "2024-02-29 01:02");
+    }
+
+    #[test]
+    fn test_parse_offer_answer() {
+        assert_eq!(parse_offer_answer("y\n", 3), OfferDecision::Accept);
+        assert_eq!(
+            parse_offer_answer("1, 3\n", 3),
+            OfferDecision::AcceptPartial(vec![0, 2])
+        );
+        assert!(matches!(
+            parse_offer_answer("\n", 3),
+            OfferDecision::Reject(_)
+        ));
+        assert!(matches!(
+            parse_offer_answer("4\n", 3),
+            OfferDecision::Reject(_)
+        ));
+    }
+}
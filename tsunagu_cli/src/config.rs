@@ -1,98 +1,100 @@
-use serde::Deserialize;
-use std::path::PathBuf;
-use anyhow::{Result, Context};
-use config::ConfigError;
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+use tsunagu_common::config::Config;
 
-#[derive(Debug, Deserialize)]
-pub struct CliConfig {
-    #[serde(default = "default_device_name")]
-    pub device_name: String,
-    #[serde(default = "default_discovery_port")]
-    pub discovery_port: u16,
-    #[serde(default = "default_transfer_port")]
-    pub transfer_port: u16,
-    #[serde(default = "default_download_dir")]
-    pub download_dir: PathBuf,
+/// Load the shared config, `TSUNAGU_*` environment variables take precedence
+pub fn load(path: &Path) -> Result<Config> {
+    let mut config = Config::load(path).context("Failed to load configuration")?;
+    apply_env(&mut config, |key| std::env::var(key).ok())?;
+    Ok(config)
 }
 
-fn default_device_name() -> String {
-    gethostname::gethostname().to_string_lossy().into_owned()
-}
-
-fn default_discovery_port() -> u16 {
-    5353
-}
-
-fn default_transfer_port() -> u16 {
-    5354
-}
-
-fn default_download_dir() -> PathBuf {
-    PathBuf::from("./downloads")
-}
-
-impl Default for CliConfig {
-    fn default() -> Self {
-        Self {
-            device_name: default_device_name(),
-            discovery_port: default_discovery_port(),
-            transfer_port: default_transfer_port(),
-            download_dir: default_download_dir(),
-        }
+fn apply_env(config: &mut Config, var: impl Fn(&str) -> Option<String>) -> Result<()> {
+    if let Some(name) = var("TSUNAGU_DEVICE_NAME") {
+        config.device_name = name;
     }
-}
-
-impl CliConfig {
-    pub fn load() -> Result<Self> {
-        let config = config::Config::builder()
-            .add_source(config::File::with_name("config").required(false))
-            .add_source(config::Environment::with_prefix("TSUNAGU"))
-            .build()
-            .context("Failed to build configuration")?;
-
-        config.try_deserialize().map_err(|e| {
-            match e {
-                ConfigError::Message(msg) if msg.contains("missing field") => {
-                    anyhow::anyhow!("Configuration error: Some fields are missing. Using default values where possible. Details: {}", msg)
-                },
-                _ => anyhow::anyhow!("Failed to parse configuration: {}", e),
-            }
-        })
+    // Older versions read the save directory from TSUNAGU_DOWNLOAD_DIR
+    let save_directory = var("TSUNAGU_SAVE_DIRECTORY").or_else(|| {
+        let dir = var("TSUNAGU_DOWNLOAD_DIR")?;
+        warn!("TSUNAGU_DOWNLOAD_DIR is deprecated, use TSUNAGU_SAVE_DIRECTORY instead");
+        Some(dir)
+    });
+    if let Some(dir) = save_directory {
+        config.save_directory = PathBuf::from(dir);
+    }
+    if let Some(backends) = var("TSUNAGU_DISCOVERY_BACKENDS") {
+        config.discovery_backends = backends
+            .split(',')
+            .map(|backend| backend.trim().parse())
+            .collect::<std::result::Result<_, _>>()?;
     }
+    if let Some(port) = var("TSUNAGU_DISCOVERY_PORT") {
+        config.discovery_port = port.parse().context("Invalid TSUNAGU_DISCOVERY_PORT")?;
+    }
+    if let Some(port) = var("TSUNAGU_TRANSFER_PORT") {
+        config.transfer_port = port.parse().context("Invalid TSUNAGU_TRANSFER_PORT")?;
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::env;
-
-    #[test]
-    fn test_default_config() {
-        let config = CliConfig::default();
-        assert!(!config.device_name.is_empty());
-        assert_eq!(config.discovery_port, 5353);
-        assert_eq!(config.transfer_port, 5354);
-        assert!(!config.download_dir.as_os_str().is_empty());
-    }
+    use std::collections::HashMap;
+    use tsunagu_common::config::DiscoveryBackend;
 
     #[test]
-    fn test_load_config_from_env() {
-        env::set_var("TSUNAGU_DEVICE_NAME", "TestDevice");
-        env::set_var("TSUNAGU_DISCOVERY_PORT", "8000");
-        env::set_var("TSUNAGU_TRANSFER_PORT", "8001");
-        env::set_var("TSUNAGU_DOWNLOAD_DIR", "/tmp/downloads");
+    fn test_apply_env() {
+        let env: HashMap<_, _> = [
+            ("TSUNAGU_DEVICE_NAME", "TestDevice"),
+            ("TSUNAGU_DISCOVERY_BACKENDS", "multicast, subnet_scan"),
+            ("TSUNAGU_DISCOVERY_PORT", "8000"),
+            ("TSUNAGU_TRANSFER_PORT", "8001"),
+            ("TSUNAGU_SAVE_DIRECTORY", "/tmp/downloads"),
+        ]
+        .into_iter()
+        .collect();
 
-        let config = CliConfig::load().unwrap();
+        let mut config = Config::default();
+        apply_env(&mut config, |key| env.get(key).map(|v| v.to_string())).unwrap();
 
         assert_eq!(config.device_name, "TestDevice");
+        assert_eq!(
+            config.discovery_backends,
+            vec![DiscoveryBackend::Multicast, DiscoveryBackend::SubnetScan]
+        );
         assert_eq!(config.discovery_port, 8000);
         assert_eq!(config.transfer_port, 8001);
-        assert_eq!(config.download_dir, PathBuf::from("/tmp/downloads"));
+        assert_eq!(config.save_directory, PathBuf::from("/tmp/downloads"));
+    }
 
-        // Clean up environment variables
-        env::remove_var("TSUNAGU_DEVICE_NAME");
-        env::remove_var("TSUNAGU_DISCOVERY_PORT");
-        env::remove_var("TSUNAGU_TRANSFER_PORT");
-        env::remove_var("TSUNAGU_DOWNLOAD_DIR");
+    #[test]
+    fn test_apply_env_deprecated_download_dir() {
+        let mut config = Config::default();
+        apply_env(&mut config, |key| {
+            (key == "TSUNAGU_DOWNLOAD_DIR").then(|| "/tmp/old".to_string())
+        })
+        .unwrap();
+        assert_eq!(config.save_directory, PathBuf::from("/tmp/old"));
+
+        // The new name wins when both are set
+        let env: HashMap<_, _> = [
+            ("TSUNAGU_DOWNLOAD_DIR", "/tmp/old"),
+            ("TSUNAGU_SAVE_DIRECTORY", "/tmp/new"),
+        ]
+        .into_iter()
+        .collect();
+        apply_env(&mut config, |key| env.get(key).map(|v| v.to_string())).unwrap();
+        assert_eq!(config.save_directory, PathBuf::from("/tmp/new"));
+    }
+
+    #[test]
+    fn test_apply_env_invalid_port() {
+        let mut config = Config::default();
+        let result = apply_env(&mut config, |key| {
+            (key == "TSUNAGU_TRANSFER_PORT").then(|| "not a port".to_string())
+        });
+        assert!(result.is_err());
     }
 }
@@ -0,0 +1,25 @@
+use thiserror::Error;
+
+/// Failures of a CLI command that map to a specific exit code
+#[derive(Error, Debug)]
+pub enum CliError {
+    #[error("Unknown receiver: {0}")]
+    UnknownReceiver(String),
+    #[error("Transfer refused: {0}")]
+    Refused(String),
+    #[error("Transfer interrupted")]
+    Interrupted,
+    #[error("Transfer cancelled {0}")]
+    Cancelled(String),
+}
+
+impl CliError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::UnknownReceiver(_) => 2,
+            CliError::Refused(_) => 3,
+            CliError::Interrupted => 130,
+            CliError::Cancelled(_) => 4,
+        }
+    }
+}
@@ -1,19 +1,26 @@
-mod config;
 mod cli;
+mod config;
+mod error;
 
-use anyhow::Result;
-use cli::{Cli, CliApp};
 use clap::Parser;
-use tracing_subscriber;
+use cli::{Cli, CliApp};
+use error::CliError;
+use tracing::error;
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() {
     // Initialize logging
     tracing_subscriber::fmt::init();
 
     let cli = Cli::parse();
-    let mut app = CliApp::new().await?;
-    app.run(cli).await?;
+    let result = match CliApp::new().await {
+        Ok(mut app) => app.run(cli).await,
+        Err(e) => Err(e),
+    };
 
-    Ok(())
+    if let Err(e) = result {
+        error!("{:#}", e);
+        let code = e.downcast_ref::<CliError>().map_or(1, CliError::exit_code);
+        std::process::exit(code);
+    }
 }
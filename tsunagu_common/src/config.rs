@@ -1,18 +1,334 @@
-use std::path::PathBuf;
+use crate::error::TsunaguError;
+use crate::peers::ManualPeer;
+use crate::trust::TrustedDevice;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::info;
+
+/// Schema version written to the config file
+pub const CONFIG_VERSION: u32 = 3;
+
+/// File in the config dir holding the settings
+pub const CONFIG_FILE: &str = "config.toml";
+
+/// Default port of the multicast discovery, away from mDNS on 5353
+const DEFAULT_DISCOVERY_PORT: u16 = 5356;
 
 /// 应用配置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Config {
+    pub version: u32,
     pub device_name: String,
     pub save_directory: PathBuf,
+    /// Accept offers from devices that are not trusted without asking, off
+    /// by default so unknown senders are asked about
     pub allow_auto_receive: bool,
+    /// How devices find each other, the devices found by all are merged
+    pub discovery_backends: Vec<DiscoveryBackend>,
+    /// UDP port of the multicast discovery, mDNS always uses 5353
+    pub discovery_port: u16,
+    /// Preferred transfer port, another one is used if it is taken
+    pub transfer_port: u16,
+    pub limits: Limits,
+    pub receive: ReceiveRules,
+    /// What happens to partially received files when a transfer is cancelled
+    pub cancelled_partials: CancelledPartials,
+    pub trusted_devices: Vec<TrustedDevice>,
+    /// Devices added by address, e.g. on another subnet or over a VPN
+    pub manual_peers: Vec<ManualPeer>,
+}
+
+/// Protocol used to find devices and be found
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiscoveryBackend {
+    /// Bonjour, understood by other mDNS tools
+    #[default]
+    Mdns,
+    /// Tsunagu's own announcements to a multicast group on `discovery_port`,
+    /// for networks that filter mDNS
+    Multicast,
+    /// Probing every host of the local networks on `transfer_port`, for
+    /// networks without any multicast
+    SubnetScan,
+}
+
+impl fmt::Display for DiscoveryBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Mdns => "mdns",
+            Self::Multicast => "multicast",
+            Self::SubnetScan => "subnet_scan",
+        })
+    }
+}
+
+impl FromStr for DiscoveryBackend {
+    type Err = TsunaguError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "mdns" => Ok(Self::Mdns),
+            "multicast" => Ok(Self::Multicast),
+            "subnet_scan" => Ok(Self::SubnetScan),
+            _ => Err(TsunaguError::Config(format!(
+                "Unknown discovery backend {}, expected mdns, multicast or subnet_scan",
+                s
+            ))),
+        }
+    }
+}
+
+/// Resource limits for transfers
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Limits {
+    pub max_concurrent_transfers: usize,
+    /// Largest offer accepted, bigger ones are rejected, in bytes
+    pub max_offer_size: Option<u64>,
+}
+
+/// Rules applied to incoming offers before anyone is asked
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReceiveRules {
+    /// File extensions that are never accepted, e.g. `["exe", "bat"]`
+    pub blocked_extensions: Vec<String>,
+    /// Reject offers from devices that are not paired
+    pub reject_unknown_devices: bool,
+}
+
+/// Handling of partial files left by a cancelled transfer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CancelledPartials {
+    #[default]
+    Delete,
+    /// Move them to a `.tsunagu-quarantine` directory in the save directory
+    Quarantine,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            version: CONFIG_VERSION,
+            device_name: gethostname::gethostname().to_string_lossy().into_owned(),
+            save_directory: dirs::download_dir()
+                .map(|dir| dir.join("Tsunagu"))
+                .unwrap_or_else(|| PathBuf::from("downloads")),
+            allow_auto_receive: false,
+            discovery_backends: vec![DiscoveryBackend::default()],
+            discovery_port: DEFAULT_DISCOVERY_PORT,
+            transfer_port: 5354,
+            limits: Limits::default(),
+            receive: ReceiveRules::default(),
+            cancelled_partials: CancelledPartials::default(),
+            trusted_devices: Vec::new(),
+            manual_peers: Vec::new(),
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_concurrent_transfers: 3,
+            max_offer_size: None,
+        }
+    }
+}
+
+impl Config {
+    /// Location of the config file in the platform config directory
+    pub fn default_path() -> Result<PathBuf> {
+        Ok(crate::paths::config_dir()?.join(CONFIG_FILE))
+    }
+
+    /// Load the config at `path`, an absent file gives the defaults
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e.into()),
+        };
+        let table: toml::Table = toml::from_str(&text).map_err(|e| invalid(path, e))?;
+        migrate(table)?.try_into().map_err(|e| invalid(path, e))
+    }
+
+    /// Atomically write the config to `path`
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let text = toml::to_string_pretty(self)
+            .map_err(|e| TsunaguError::Config(format!("Failed to serialize config: {}", e)))?;
+        crate::paths::write_atomic(path, text.as_bytes())
+    }
 }
 
 /// 加载配置
-pub fn load_config() -> Config {
-    unimplemented!()
+pub fn load_config() -> Result<Config> {
+    Config::load(&Config::default_path()?)
 }
 
 /// 保存配置
-pub fn save_config(config: &Config) {
-    unimplemented!()
+pub fn save_config(config: &Config) -> Result<()> {
+    config.save(&Config::default_path()?)
+}
+
+/// Bring a config written by an older version up to [`CONFIG_VERSION`]
+fn migrate(mut table: toml::Table) -> Result<toml::Table> {
+    let version = match table.get("version") {
+        None => 0,
+        Some(value) => value
+            .as_integer()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| TsunaguError::Config(format!("Invalid config version {}", value)))?,
+    };
+    if version > CONFIG_VERSION {
+        return Err(TsunaguError::Config(format!(
+            "Config version {} is newer than supported version {}",
+            version, CONFIG_VERSION
+        )));
+    }
+
+    if version < 1 {
+        // Unversioned configs come from the CLI, which called the save directory `download_dir`
+        if let Some(dir) = table.remove("download_dir") {
+            table.entry("save_directory").or_insert(dir);
+        }
+    }
+
+    if version < 2 {
+        // The port was the unused mDNS one, the multicast discovery needs its own
+        if table
+            .get("discovery_port")
+            .and_then(|port| port.as_integer())
+            == Some(5353)
+        {
+            table.insert(
+                "discovery_port".into(),
+                i64::from(DEFAULT_DISCOVERY_PORT).into(),
+            );
+        }
+    }
+
+    if version < 3 {
+        // A single backend became a list of backends used together
+        if let Some(backend) = table.remove("discovery_backend") {
+            table.insert("discovery_backends".into(), vec![backend].into());
+        }
+    }
+
+    if version != CONFIG_VERSION {
+        info!(
+            "Migrated config from version {} to {}",
+            version, CONFIG_VERSION
+        );
+        table.insert("version".into(), i64::from(CONFIG_VERSION).into());
+    }
+    Ok(table)
+}
+
+fn invalid(path: &Path, e: impl std::fmt::Display) -> TsunaguError {
+    TsunaguError::Config(format!("Invalid config {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_missing_file_gives_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config::load(&dir.path().join(CONFIG_FILE)).unwrap();
+        assert_eq!(config, Config::default());
+        assert!(!config.allow_auto_receive);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join(CONFIG_FILE);
+
+        let config = Config {
+            device_name: "Laptop".to_string(),
+            allow_auto_receive: true,
+            limits: Limits {
+                max_offer_size: Some(1024),
+                ..Limits::default()
+            },
+            ..Config::default()
+        };
+        config.save(&path).unwrap();
+
+        assert_eq!(Config::load(&path).unwrap(), config);
+        // No temp files are left behind
+        assert_eq!(
+            std::fs::read_dir(path.parent().unwrap()).unwrap().count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_migrate_unversioned_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(
+            &path,
+            "device_name = \"Old\"\ndownload_dir = \"/tmp/old\"\ntransfer_port = 6000\n",
+        )
+        .unwrap();
+
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.device_name, "Old");
+        assert_eq!(config.save_directory, PathBuf::from("/tmp/old"));
+        assert_eq!(config.transfer_port, 6000);
+        assert_eq!(config.discovery_port, DEFAULT_DISCOVERY_PORT);
+    }
+
+    #[test]
+    fn test_migrate_mdns_discovery_port() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(&path, "version = 1\ndiscovery_port = 5353\n").unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.discovery_port, DEFAULT_DISCOVERY_PORT);
+        assert_eq!(config.discovery_backends, vec![DiscoveryBackend::Mdns]);
+
+        // A port picked on purpose is kept
+        std::fs::write(&path, "version = 1\ndiscovery_port = 6000\n").unwrap();
+        assert_eq!(Config::load(&path).unwrap().discovery_port, 6000);
+        assert!("bonjour".parse::<DiscoveryBackend>().is_err());
+    }
+
+    #[test]
+    fn test_migrate_single_discovery_backend() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(&path, "version = 2\ndiscovery_backend = \"multicast\"\n").unwrap();
+        let config = Config::load(&path).unwrap();
+        assert_eq!(config.discovery_backends, vec![DiscoveryBackend::Multicast]);
+
+        for backend in [
+            DiscoveryBackend::Mdns,
+            DiscoveryBackend::Multicast,
+            DiscoveryBackend::SubnetScan,
+        ] {
+            assert_eq!(
+                backend.to_string().parse::<DiscoveryBackend>().unwrap(),
+                backend
+            );
+        }
+    }
+
+    #[test]
+    fn test_rejects_newer_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE);
+        std::fs::write(&path, format!("version = {}\n", CONFIG_VERSION + 1)).unwrap();
+        assert!(matches!(Config::load(&path), Err(TsunaguError::Config(_))));
+    }
 }
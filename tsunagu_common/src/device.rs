@@ -1,20 +1,27 @@
-use local_ip_address::local_ip;
+use gethostname::gethostname;
+use std::env;
 use std::net::IpAddr;
-use tokio::sync::RwLock;
 use std::sync::Arc;
-use std::env;
-use gethostname::gethostname;
+use tokio::sync::RwLock;
 
+use crate::error::TsunaguError;
+use crate::identity::DeviceIdentity;
 use crate::models::DeviceInfo;
+use crate::network;
 use crate::Result;
-use crate::error::TsunaguError;
 
 pub struct DeviceManager {
     device_info: Arc<RwLock<DeviceInfo>>,
+    identity: Arc<DeviceIdentity>,
 }
 
 impl DeviceManager {
+    /// Create the manager with the identity stored in the data directory
     pub async fn new() -> Result<Self> {
+        Self::with_identity(DeviceIdentity::load_or_create_default()?).await
+    }
+
+    pub async fn with_identity(identity: DeviceIdentity) -> Result<Self> {
         let os = env::consts::OS.to_string();
         let version = env::consts::ARCH.to_string();
         let hostname = Self::get_hostname()?;
@@ -22,17 +29,24 @@ impl DeviceManager {
         let device_info = DeviceInfo::new(
             hostname,
             std::env::consts::OS.to_string(),
-            Self::get_local_ip()?.to_string(),
+            Self::get_local_addresses()?,
             0, // Port will be set later when starting the server
             os,
             version,
-        );
+        )
+        .with_public_key(identity.public_key());
 
         Ok(Self {
             device_info: Arc::new(RwLock::new(device_info)),
+            identity: Arc::new(identity),
         })
     }
 
+    /// The identity of this device
+    pub fn identity(&self) -> Arc<DeviceIdentity> {
+        Arc::clone(&self.identity)
+    }
+
     /// Get the current device's information
     pub async fn get_current_device_info(&self) -> DeviceInfo {
         self.device_info.read().await.clone()
@@ -52,17 +66,24 @@ impl DeviceManager {
         Ok(())
     }
 
-    /// Get the local IP address
-    fn get_local_ip() -> Result<IpAddr> {
-        local_ip().map_err(|e| TsunaguError::Network(format!("Failed to get local IP: {}", e)))
+    /// Get the addresses of every usable interface
+    fn get_local_addresses() -> Result<Vec<IpAddr>> {
+        let addresses = network::local_addresses()?;
+        if addresses.is_empty() {
+            return Err(TsunaguError::Network(
+                "Failed to get local IP: no interfaces".into(),
+            ));
+        }
+        Ok(addresses)
     }
 
     /// Get the hostname
     fn get_hostname() -> Result<String> {
-        gethostname().to_str()
+        gethostname()
+            .to_str()
             .ok_or_else(|| TsunaguError::Device("Failed to get hostname".into()))
             .map(|s| s.to_string())
-    } 
+    }
 }
 
 #[cfg(test)]
@@ -71,12 +92,12 @@ mod tests {
 
     #[tokio::test]
     async fn test_device_manager_creation() -> Result<()> {
-        let manager = DeviceManager::new().await?;
+        let manager = DeviceManager::with_identity(DeviceIdentity::generate()?).await?;
         let info = manager.get_current_device_info().await;
 
         assert!(!info.name().is_empty());
         assert!(!info.model().is_empty());
-        assert!(!info.ip().is_empty());
+        assert!(!info.addresses().is_empty());
         assert_eq!(info.port(), 0);
 
         Ok(())
@@ -84,15 +105,15 @@ mod tests {
 
     #[tokio::test]
     async fn test_device_info_update() -> Result<()> {
-        let manager = DeviceManager::new().await?;
+        let manager = DeviceManager::with_identity(DeviceIdentity::generate()?).await?;
 
         // Test hostname
         let hostname = DeviceManager::get_hostname()?;
         assert!(!hostname.is_empty());
 
-        // Test local IP
-        let local_ip = DeviceManager::get_local_ip()?;
-        assert!(local_ip.is_ipv4() || local_ip.is_ipv6());
+        // Test local addresses
+        let addresses = DeviceManager::get_local_addresses()?;
+        assert!(!addresses.is_empty());
 
         // Test updating port
         manager.update_port(8000).await.unwrap();
@@ -104,7 +125,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_port_update() -> Result<()> {
-        let manager = DeviceManager::new().await?;
+        let manager = DeviceManager::with_identity(DeviceIdentity::generate()?).await?;
 
         manager.update_port(9000).await?;
         let port_updated_info = manager.get_current_device_info().await;
@@ -114,6 +135,23 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_stable_device_id() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("identity.pk8");
+
+        let first = DeviceManager::with_identity(DeviceIdentity::load_or_create(&path)?).await?;
+        let second = DeviceManager::with_identity(DeviceIdentity::load_or_create(&path)?).await?;
+
+        let first_info = first.get_current_device_info().await;
+        let second_info = second.get_current_device_info().await;
+        assert_eq!(first_info.id(), second_info.id());
+        assert_eq!(first_info.id(), first.identity().fingerprint());
+        assert!(first_info.has_verified_id());
+
+        Ok(())
+    }
+
     #[test]
     fn test_get_hostname() {
         let hostname = DeviceManager::get_hostname().unwrap();
@@ -121,8 +159,8 @@ mod tests {
     }
 
     #[test]
-    fn test_get_local_ip() {
-        let ip = DeviceManager::get_local_ip().unwrap();
-        assert!(ip.is_ipv4() || ip.is_ipv6());
+    fn test_get_local_addresses() {
+        let addresses = DeviceManager::get_local_addresses().unwrap();
+        assert!(addresses.iter().all(|ip| !ip.is_unspecified()));
     }
 }
@@ -0,0 +1,559 @@
+use super::{Discovery, DiscoveryEvent, Visibility};
+use crate::models::DeviceInfo;
+use crate::Result;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Events buffered per subscriber before it starts lagging
+const EVENT_CAPACITY: usize = 64;
+
+/// A source that saw a device, and when it last did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sighting {
+    /// Name the source was added under, e.g. `mdns`
+    pub source: String,
+    /// Seconds since the Unix epoch
+    pub last_seen: u64,
+}
+
+/// A device merged from everything its sources reported
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiscoveredDevice {
+    pub device: DeviceInfo,
+    /// Most recent first
+    pub sightings: Vec<Sighting>,
+}
+
+/// Discovery through several sources at once, e.g. mDNS and a subnet scan
+///
+/// Devices are merged by id: one seen by several sources is reported once,
+/// with the addresses of all of them, and only lost when the last source
+/// loses it. Visibility changes go to every source.
+pub struct CompositeDiscovery {
+    sources: Vec<Source>,
+    table: Arc<Mutex<SightingTable>>,
+    events: broadcast::Sender<DiscoveryEvent>,
+    /// Pass the events of each source on, running between `start` and `stop`
+    forwarders: Vec<JoinHandle<()>>,
+}
+
+struct Source {
+    name: String,
+    discovery: Box<dyn Discovery>,
+}
+
+impl CompositeDiscovery {
+    pub fn new() -> Self {
+        Self {
+            sources: Vec::new(),
+            table: Arc::new(Mutex::new(SightingTable::default())),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            forwarders: Vec::new(),
+        }
+    }
+
+    /// Also find devices through `discovery`, reported as `name`
+    pub fn with_source(mut self, name: impl Into<String>, discovery: Box<dyn Discovery>) -> Self {
+        self.sources.push(Source {
+            name: name.into(),
+            discovery,
+        });
+        self
+    }
+
+    /// Names of the sources, in the order they were added
+    pub fn source_names(&self) -> Vec<&str> {
+        self.sources
+            .iter()
+            .map(|source| source.name.as_str())
+            .collect()
+    }
+
+    /// The devices found, with the sources that saw them
+    ///
+    /// Asks every source, like [`Discovery::discover_devices`].
+    pub async fn discover_with_sources(&self) -> Result<Vec<DiscoveredDevice>> {
+        self.refresh().await?;
+        Ok(self.table.lock().unwrap().discovered())
+    }
+
+    /// Ask every source for its devices and bring the table up to date
+    ///
+    /// A failing source is skipped, unless every source fails.
+    async fn refresh(&self) -> Result<()> {
+        let results = futures::future::join_all(
+            self.sources
+                .iter()
+                .map(|source| source.discovery.discover_devices()),
+        )
+        .await;
+
+        let mut last_error = None;
+        let mut answered = self.sources.is_empty();
+        for (source, result) in self.sources.iter().zip(results) {
+            match result {
+                Ok(devices) => {
+                    answered = true;
+                    let events = self
+                        .table
+                        .lock()
+                        .unwrap()
+                        .sync(&source.name, devices, unix_now());
+                    for event in events {
+                        self.emit(event);
+                    }
+                }
+                Err(e) => {
+                    warn!("Discovery through {} failed: {}", source.name, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        match last_error {
+            Some(e) if !answered => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn spawn_forwarder(
+        &self,
+        name: String,
+        mut events: broadcast::Receiver<DiscoveryEvent>,
+    ) -> JoinHandle<()> {
+        let table = Arc::clone(&self.table);
+        let sender = self.events.clone();
+        tokio::spawn(async move {
+            loop {
+                let event = match events.recv().await {
+                    Ok(event) => event,
+                    // The next `discover_devices` catches up
+                    Err(RecvError::Lagged(skipped)) => {
+                        debug!("Missed {} events from {}", skipped, name);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                let merged = {
+                    let mut table = table.lock().unwrap();
+                    match event {
+                        DiscoveryEvent::DeviceAdded(device)
+                        | DiscoveryEvent::DeviceUpdated(device) => {
+                            table.see(&name, device, unix_now())
+                        }
+                        DiscoveryEvent::DeviceLost(device) => table.lose(&name, device.id()),
+                    }
+                };
+                if let Some(merged) = merged {
+                    // Nobody listening is fine
+                    let _ = sender.send(merged);
+                }
+            }
+        })
+    }
+
+    fn emit(&self, event: DiscoveryEvent) {
+        // Nobody listening is fine
+        let _ = self.events.send(event);
+    }
+}
+
+impl Default for CompositeDiscovery {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Discovery for CompositeDiscovery {
+    async fn start(&mut self) -> Result<()> {
+        let forwarders: Vec<JoinHandle<()>> = self
+            .sources
+            .iter()
+            .map(|source| self.spawn_forwarder(source.name.clone(), source.discovery.subscribe()))
+            .collect();
+        for previous in std::mem::replace(&mut self.forwarders, forwarders) {
+            previous.abort();
+        }
+        for source in &mut self.sources {
+            source.discovery.start().await?;
+        }
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        for source in &mut self.sources {
+            source.discovery.stop().await?;
+        }
+        for forwarder in self.forwarders.drain(..) {
+            forwarder.abort();
+        }
+        Ok(())
+    }
+
+    async fn discover_devices(&self) -> Result<Vec<DeviceInfo>> {
+        Ok(self
+            .discover_with_sources()
+            .await?
+            .into_iter()
+            .map(|discovered| discovered.device)
+            .collect())
+    }
+
+    async fn make_discoverable(&mut self, duration: Duration) -> Result<()> {
+        for source in &mut self.sources {
+            source.discovery.make_discoverable(duration).await?;
+        }
+        Ok(())
+    }
+
+    async fn make_discoverable_while_running(&mut self) -> Result<()> {
+        for source in &mut self.sources {
+            source.discovery.make_discoverable_while_running().await?;
+        }
+        Ok(())
+    }
+
+    async fn make_undiscoverable(&mut self) -> Result<()> {
+        for source in &mut self.sources {
+            source.discovery.make_undiscoverable().await?;
+        }
+        Ok(())
+    }
+
+    /// The widest visibility of any source
+    fn visibility(&self) -> Visibility {
+        self.sources
+            .iter()
+            .map(|source| source.discovery.visibility())
+            .fold(Visibility::Hidden, |widest, visibility| {
+                match (widest, visibility) {
+                    (Visibility::WhileRunning, _) | (_, Visibility::Hidden) => widest,
+                    (Visibility::For(a), Visibility::For(b)) => Visibility::For(a.max(b)),
+                    (_, visibility) => visibility,
+                }
+            })
+    }
+
+    fn set_local_device(&mut self, device: DeviceInfo) {
+        for source in &mut self.sources {
+            source.discovery.set_local_device(device.clone());
+        }
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<DiscoveryEvent> {
+        self.events.subscribe()
+    }
+}
+
+/// What each source reported of each device
+#[derive(Default)]
+struct SightingTable {
+    /// Device id to source name to the device as that source saw it
+    devices: HashMap<String, HashMap<String, (DeviceInfo, u64)>>,
+}
+
+impl SightingTable {
+    /// Record that `source` saw `device`, returning the change to report
+    fn see(&mut self, source: &str, device: DeviceInfo, now: u64) -> Option<DiscoveryEvent> {
+        let id = device.id().to_string();
+        let before = self.merged(&id);
+        let last_seen = device.last_seen().unwrap_or(now);
+        self.devices
+            .entry(id.clone())
+            .or_default()
+            .insert(source.to_string(), (device, last_seen));
+        let after = self.merged(&id)?;
+        match before {
+            None => Some(DiscoveryEvent::DeviceAdded(after)),
+            Some(before) if !same_device(&before, &after) => {
+                Some(DiscoveryEvent::DeviceUpdated(after))
+            }
+            Some(_) => None,
+        }
+    }
+
+    /// Record that `source` lost device `id`, returning the change to report
+    fn lose(&mut self, source: &str, id: &str) -> Option<DiscoveryEvent> {
+        let before = self.merged(id)?;
+        let sources = self.devices.get_mut(id)?;
+        sources.remove(source)?;
+        if sources.is_empty() {
+            self.devices.remove(id);
+            return Some(DiscoveryEvent::DeviceLost(before));
+        }
+        let after = self.merged(id)?;
+        (!same_device(&before, &after)).then_some(DiscoveryEvent::DeviceUpdated(after))
+    }
+
+    /// Replace everything `source` saw with `devices`, returning the changes
+    fn sync(&mut self, source: &str, devices: Vec<DeviceInfo>, now: u64) -> Vec<DiscoveryEvent> {
+        let gone: Vec<String> = self
+            .devices
+            .iter()
+            .filter(|(id, sources)| {
+                sources.contains_key(source) && !devices.iter().any(|device| device.id() == *id)
+            })
+            .map(|(id, _)| id.clone())
+            .collect();
+        let mut events: Vec<DiscoveryEvent> =
+            gone.iter().filter_map(|id| self.lose(source, id)).collect();
+        events.extend(
+            devices
+                .into_iter()
+                .filter_map(|device| self.see(source, device, now)),
+        );
+        events
+    }
+
+    /// Device `id` as all its sources together know it
+    ///
+    /// The most recent sighting wins, the addresses of all are kept.
+    fn merged(&self, id: &str) -> Option<DeviceInfo> {
+        let sources = self.devices.get(id)?;
+        let (latest, last_seen) = sources.values().max_by_key(|(_, last_seen)| *last_seen)?;
+        let mut device = latest.clone();
+        device.set_addresses(
+            sources
+                .values()
+                .flat_map(|(device, _)| device.addresses().iter().copied())
+                .collect::<Vec<_>>(),
+        );
+        device.set_last_seen(Some(*last_seen));
+        Some(device)
+    }
+
+    fn discovered(&self) -> Vec<DiscoveredDevice> {
+        let mut discovered: Vec<DiscoveredDevice> = self
+            .devices
+            .iter()
+            .filter_map(|(id, sources)| {
+                let mut sightings: Vec<Sighting> = sources
+                    .iter()
+                    .map(|(source, (_, last_seen))| Sighting {
+                        source: source.clone(),
+                        last_seen: *last_seen,
+                    })
+                    .collect();
+                sightings
+                    .sort_by(|a, b| b.last_seen.cmp(&a.last_seen).then(a.source.cmp(&b.source)));
+                Some(DiscoveredDevice {
+                    device: self.merged(id)?,
+                    sightings,
+                })
+            })
+            .collect();
+        discovered.sort_by(|a, b| a.device.id().cmp(b.device.id()));
+        discovered
+    }
+}
+
+/// Whether `a` and `b` are the same apart from when they were seen
+fn same_device(a: &DeviceInfo, b: &DeviceInfo) -> bool {
+    let mut a = a.clone();
+    a.set_last_seen(None);
+    let mut b = b.clone();
+    b.set_last_seen(None);
+    a == b
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn device(name: &str, ip: Ipv4Addr) -> DeviceInfo {
+        DeviceInfo::new(
+            name.to_string(),
+            "TestModel".to_string(),
+            [ip.into()],
+            8000,
+            "TestOS".to_string(),
+            "1.0".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_merge_sources() {
+        let mut table = SightingTable::default();
+        let lan = Ipv4Addr::new(192, 168, 1, 20);
+        let vpn = Ipv4Addr::new(10, 8, 0, 2);
+        let peer = device("Peer", lan);
+
+        assert!(matches!(
+            table.see("mdns", peer.clone(), 100),
+            Some(DiscoveryEvent::DeviceAdded(_))
+        ));
+        // The same device from another source is not a new one
+        let mut over_vpn = peer.clone();
+        over_vpn.set_addresses([vpn.into()]);
+        let updated = table.see("subnet_scan", over_vpn, 110).unwrap();
+        assert!(matches!(&updated, DiscoveryEvent::DeviceUpdated(d)
+            if d.addresses().contains(&lan.into()) && d.addresses().contains(&vpn.into())));
+        assert_eq!(updated.device().last_seen(), Some(110));
+
+        let discovered = table.discovered();
+        assert_eq!(discovered.len(), 1);
+        let sources: Vec<&str> = discovered[0]
+            .sightings
+            .iter()
+            .map(|sighting| sighting.source.as_str())
+            .collect();
+        assert_eq!(sources, ["subnet_scan", "mdns"]);
+
+        // Lost by one source, the other still sees it
+        assert!(matches!(
+            table.lose("subnet_scan", peer.id()),
+            Some(DiscoveryEvent::DeviceUpdated(d)) if !d.addresses().contains(&vpn.into())
+        ));
+        assert!(matches!(
+            table.lose("mdns", peer.id()),
+            Some(DiscoveryEvent::DeviceLost(_))
+        ));
+        assert!(table.discovered().is_empty());
+        assert_eq!(table.lose("mdns", peer.id()), None);
+    }
+
+    #[test]
+    fn test_sync_drops_devices_a_source_no_longer_has() {
+        let mut table = SightingTable::default();
+        let kept = device("Kept", Ipv4Addr::new(192, 168, 1, 20));
+        let gone = device("Gone", Ipv4Addr::new(192, 168, 1, 21));
+
+        let events = table.sync("subnet_scan", vec![kept.clone(), gone.clone()], 100);
+        assert_eq!(events.len(), 2);
+        // Nothing changed, nothing to report
+        assert!(table
+            .sync("subnet_scan", vec![kept.clone(), gone.clone()], 100)
+            .is_empty());
+
+        let events = table.sync("subnet_scan", vec![kept.clone()], 130);
+        assert!(matches!(&events[..], [DiscoveryEvent::DeviceLost(d)] if d.id() == gone.id()));
+        assert_eq!(table.discovered()[0].sightings[0].last_seen, 130);
+    }
+
+    /// A source whose devices and events the test controls
+    struct FakeSource {
+        devices: Arc<Mutex<Vec<DeviceInfo>>>,
+        events: broadcast::Sender<DiscoveryEvent>,
+        visibility: Visibility,
+    }
+
+    impl FakeSource {
+        fn new() -> Self {
+            Self {
+                devices: Arc::new(Mutex::new(Vec::new())),
+                events: broadcast::channel(EVENT_CAPACITY).0,
+                visibility: Visibility::Hidden,
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Discovery for FakeSource {
+        async fn start(&mut self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn stop(&mut self) -> Result<()> {
+            self.visibility = Visibility::Hidden;
+            Ok(())
+        }
+
+        async fn discover_devices(&self) -> Result<Vec<DeviceInfo>> {
+            Ok(self.devices.lock().unwrap().clone())
+        }
+
+        async fn make_discoverable(&mut self, duration: Duration) -> Result<()> {
+            self.visibility = Visibility::For(duration);
+            Ok(())
+        }
+
+        async fn make_discoverable_while_running(&mut self) -> Result<()> {
+            self.visibility = Visibility::WhileRunning;
+            Ok(())
+        }
+
+        async fn make_undiscoverable(&mut self) -> Result<()> {
+            self.visibility = Visibility::Hidden;
+            Ok(())
+        }
+
+        fn visibility(&self) -> Visibility {
+            self.visibility
+        }
+
+        fn set_local_device(&mut self, _device: DeviceInfo) {}
+
+        fn subscribe(&self) -> broadcast::Receiver<DiscoveryEvent> {
+            self.events.subscribe()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_composite_discovery() {
+        let mdns = FakeSource::new();
+        let mdns_events = mdns.events.clone();
+        let mdns_devices = Arc::clone(&mdns.devices);
+        let scan = FakeSource::new();
+        let scan_devices = Arc::clone(&scan.devices);
+        let mut composite = CompositeDiscovery::new()
+            .with_source("mdns", Box::new(mdns))
+            .with_source("subnet_scan", Box::new(scan));
+        assert_eq!(composite.source_names(), ["mdns", "subnet_scan"]);
+        let mut events = composite.subscribe();
+        composite.start().await.unwrap();
+
+        // Events of a source are passed on merged
+        let peer = device("Peer", Ipv4Addr::new(192, 168, 1, 20));
+        mdns_devices.lock().unwrap().push(peer.clone());
+        mdns_events
+            .send(DiscoveryEvent::DeviceAdded(peer.clone()))
+            .unwrap();
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            DiscoveryEvent::DeviceAdded(d) if d.id() == peer.id()
+        ));
+
+        // Found by the scan too, still one device
+        scan_devices.lock().unwrap().push(peer.clone());
+        let discovered = composite.discover_with_sources().await.unwrap();
+        assert_eq!(discovered.len(), 1);
+        assert_eq!(discovered[0].sightings.len(), 2);
+
+        mdns_events
+            .send(DiscoveryEvent::DeviceLost(peer.clone()))
+            .unwrap();
+        mdns_devices.lock().unwrap().clear();
+        scan_devices.lock().unwrap().clear();
+        let devices = composite.discover_devices().await.unwrap();
+        assert!(devices.is_empty());
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            DiscoveryEvent::DeviceLost(d) if d.id() == peer.id()
+        ));
+
+        composite
+            .make_discoverable(Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(
+            composite.visibility(),
+            Visibility::For(Duration::from_secs(60))
+        );
+        composite.stop().await.unwrap();
+        assert_eq!(composite.visibility(), Visibility::Hidden);
+    }
+}
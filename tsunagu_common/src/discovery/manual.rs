@@ -0,0 +1,319 @@
+use super::probe;
+use super::registry::DeviceRegistry;
+use super::{Discovery, DiscoveryEvent, Visibility, DEFAULT_DEVICE_TTL};
+use crate::error::TsunaguError;
+use crate::models::DeviceInfo;
+use crate::network;
+use crate::peers::ManualPeer;
+use crate::Result;
+use async_trait::async_trait;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
+
+/// Manual peers may be behind a VPN or a router, give them longer than a scan
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Results younger than this are returned without probing again
+const REPROBE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Whether a manually added peer answered its last probe
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reachability {
+    /// Not probed yet
+    Unknown,
+    Reachable(DeviceInfo),
+    /// Nothing answered, with the reason
+    Unreachable(String),
+    /// A device answered, but not the one with the expected fingerprint
+    FingerprintMismatch {
+        found: String,
+    },
+}
+
+/// A manually added peer and how its last probe went
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerStatus {
+    pub peer: ManualPeer,
+    pub reachability: Reachability,
+}
+
+/// Discovery of the peers in the address book, by probing each of them
+///
+/// For devices discovery cannot find, e.g. on another VLAN or over a VPN. A
+/// peer that stops answering stays listed with the time it last answered
+/// until the device TTL has passed. Cannot make this device discoverable.
+pub struct ManualDiscovery {
+    local_id: String,
+    registry: DeviceRegistry,
+    statuses: Mutex<Vec<PeerStatus>>,
+    device_ttl: Duration,
+    /// When the last probe finished, held while one runs
+    last_probe: tokio::sync::Mutex<Option<Instant>>,
+    /// Expiry of the devices found, running between `start` and `stop`
+    expiry: Option<JoinHandle<()>>,
+}
+
+impl ManualDiscovery {
+    pub fn new(local_device: &DeviceInfo, peers: Vec<ManualPeer>) -> Self {
+        Self {
+            local_id: local_device.id().to_string(),
+            registry: DeviceRegistry::new(),
+            statuses: Mutex::new(
+                peers
+                    .into_iter()
+                    .map(|peer| PeerStatus {
+                        peer,
+                        reachability: Reachability::Unknown,
+                    })
+                    .collect(),
+            ),
+            device_ttl: DEFAULT_DEVICE_TTL,
+            last_probe: tokio::sync::Mutex::new(None),
+            expiry: None,
+        }
+    }
+
+    /// Drop peers that did not answer for `ttl` and no longer accept connections
+    pub fn with_device_ttl(mut self, ttl: Duration) -> Self {
+        self.device_ttl = ttl;
+        self
+    }
+
+    /// How the last probe of each peer went
+    pub fn statuses(&self) -> Vec<PeerStatus> {
+        self.statuses.lock().unwrap().clone()
+    }
+
+    /// Probe every peer now
+    pub async fn probe(&self) -> Vec<PeerStatus> {
+        let mut last_probe = self.last_probe.lock().await;
+        self.probe_locked(&mut last_probe).await
+    }
+
+    /// Probe while holding the `last_probe` lock, so probes do not overlap
+    async fn probe_locked(&self, last_probe: &mut Option<Instant>) -> Vec<PeerStatus> {
+        let peers: Vec<ManualPeer> = self
+            .statuses
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|status| status.peer.clone())
+            .collect();
+        let mut probes = JoinSet::new();
+        for (index, peer) in peers.into_iter().enumerate() {
+            probes.spawn(async move {
+                let reachability = reach(&peer).await;
+                (index, peer, reachability)
+            });
+        }
+
+        while let Some(finished) = probes.join_next().await {
+            let Ok((index, peer, reachability)) = finished else {
+                continue;
+            };
+            match &reachability {
+                Reachability::Reachable(device) if device.id() != self.local_id => {
+                    self.registry.seen(device.clone(), peer.address()).await
+                }
+                Reachability::FingerprintMismatch { found } => {
+                    warn!(
+                        "{} answered as {}, not the expected {}",
+                        peer.address(),
+                        found,
+                        peer.fingerprint().unwrap_or_default()
+                    );
+                    self.registry.remove_key(peer.address()).await;
+                }
+                Reachability::Unreachable(reason) => {
+                    debug!("{} is unreachable: {}", peer.address(), reason)
+                }
+                Reachability::Reachable(_) | Reachability::Unknown => {}
+            }
+            self.statuses.lock().unwrap()[index].reachability = reachability;
+        }
+        *last_probe = Some(Instant::now());
+        self.statuses()
+    }
+}
+
+#[async_trait]
+impl Discovery for ManualDiscovery {
+    async fn start(&mut self) -> Result<()> {
+        info!(
+            "Starting discovery of {} manual peer(s)",
+            self.statuses.lock().unwrap().len()
+        );
+        if let Some(previous) = self
+            .expiry
+            .replace(self.registry.spawn_expiry(self.device_ttl))
+        {
+            previous.abort();
+        }
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        info!("Stopping discovery of manual peers");
+        if let Some(expiry) = self.expiry.take() {
+            expiry.abort();
+        }
+        Ok(())
+    }
+
+    /// Probes first unless the last probe is recent
+    async fn discover_devices(&self) -> Result<Vec<DeviceInfo>> {
+        let mut last_probe = self.last_probe.lock().await;
+        if last_probe.is_none_or(|last| last.elapsed() >= REPROBE_INTERVAL) {
+            self.probe_locked(&mut last_probe).await;
+        }
+        drop(last_probe);
+        Ok(self.registry.devices().await)
+    }
+
+    // Probes reach the transfer server, there is nothing to advertise
+
+    async fn make_discoverable(&mut self, _duration: Duration) -> Result<()> {
+        Ok(())
+    }
+
+    async fn make_discoverable_while_running(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn make_undiscoverable(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn visibility(&self) -> Visibility {
+        Visibility::Hidden
+    }
+
+    fn set_local_device(&mut self, device: DeviceInfo) {
+        self.local_id = device.id().to_string();
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<DiscoveryEvent> {
+        self.registry.subscribe()
+    }
+}
+
+/// Resolve `peer` and ask whichever of its addresses answers who it is
+async fn reach(peer: &ManualPeer) -> Reachability {
+    let device = match identify(peer.address()).await {
+        Ok(device) => device,
+        Err(e) => return Reachability::Unreachable(e.to_string()),
+    };
+    match peer.fingerprint() {
+        Some(expected) if device.id() != expected || !device.has_verified_id() => {
+            Reachability::FingerprintMismatch {
+                found: device.id().to_string(),
+            }
+        }
+        _ => Reachability::Reachable(device),
+    }
+}
+
+async fn identify(address: &str) -> Result<DeviceInfo> {
+    let resolved = tokio::time::timeout(CONNECT_TIMEOUT, tokio::net::lookup_host(address))
+        .await
+        .map_err(|_| TsunaguError::Network(format!("Resolving {} timed out", address)))??;
+    let mut last_error = None;
+    for addr in resolved.filter(|addr| network::is_usable(addr.ip())) {
+        match probe::identify(addr, CONNECT_TIMEOUT).await {
+            Ok(device) => return Ok(device),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error
+        .unwrap_or_else(|| TsunaguError::Network(format!("No usable address for {}", address))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encryption::SessionRole;
+    use crate::identity::DeviceIdentity;
+    use crate::transfer::protocol::Connection;
+    use std::net::Ipv4Addr;
+    use tokio::net::TcpListener;
+
+    fn device(name: &str, identity: &DeviceIdentity) -> DeviceInfo {
+        DeviceInfo::new(
+            name.to_string(),
+            "TestModel".to_string(),
+            [Ipv4Addr::new(10, 8, 0, 2).into()],
+            8000,
+            "TestOS".to_string(),
+            "1.0".to_string(),
+        )
+        .with_public_key(identity.public_key())
+    }
+
+    /// Answer connections on `listener` the way the transfer server does
+    fn serve(listener: TcpListener, device: DeviceInfo) {
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let device = device.clone();
+                tokio::spawn(async move {
+                    let _ = Connection::new(stream)
+                        .handshake(&device, None, SessionRole::Responder)
+                        .await;
+                });
+            }
+        });
+    }
+
+    #[tokio::test]
+    async fn test_probe() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let identity = DeviceIdentity::generate().unwrap();
+        let peer = device("Peer", &identity);
+        serve(listener, peer.clone());
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let dead_port = closed.local_addr().unwrap().port();
+        drop(closed);
+
+        let other = DeviceIdentity::generate().unwrap().fingerprint();
+        let peers = vec![
+            ManualPeer::new(
+                &format!("127.0.0.1:{}", port),
+                Some(&identity.fingerprint()),
+            )
+            .unwrap(),
+            ManualPeer::new(&format!("127.0.0.1:{}", dead_port), None).unwrap(),
+            ManualPeer::new(&format!("localhost:{}", port), Some(&other)).unwrap(),
+        ];
+        let local = device("Local", &DeviceIdentity::generate().unwrap());
+        let discovery = ManualDiscovery::new(&local, peers);
+        assert!(discovery
+            .statuses()
+            .iter()
+            .all(|status| status.reachability == Reachability::Unknown));
+
+        let statuses = discovery.probe().await;
+        assert!(matches!(
+            &statuses[0].reachability,
+            Reachability::Reachable(d) if d.id() == peer.id()
+        ));
+        assert!(matches!(
+            statuses[1].reachability,
+            Reachability::Unreachable(_)
+        ));
+        assert_eq!(
+            statuses[2].reachability,
+            Reachability::FingerprintMismatch {
+                found: peer.id().to_string()
+            }
+        );
+
+        let devices = discovery.discover_devices().await.unwrap();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].id(), peer.id());
+        assert!(devices[0].addresses().contains(&Ipv4Addr::LOCALHOST.into()));
+    }
+}
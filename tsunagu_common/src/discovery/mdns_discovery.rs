@@ -1,23 +1,47 @@
-use super::Discovery;
+use super::registry::DeviceRegistry;
+use super::{Discovery, DiscoveryEvent, Visibility};
 use crate::error::TsunaguError;
 use crate::models::DeviceInfo;
+use crate::network;
 use crate::Result;
 use async_trait::async_trait;
-use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use mdns_sd::{DaemonEvent, ServiceDaemon, ServiceEvent, ServiceInfo};
 use std::collections::HashMap;
-use std::net::{IpAddr, Ipv4Addr};
+use std::net::IpAddr;
 use std::str;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use tokio::sync::RwLock;
-use tracing::{debug, info};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
 
 const SERVICE_TYPE: &str = "_tsunagu._tcp.local.";
 
+/// How long a device may stay silent before it is checked on and dropped
+///
+/// Matches the TTL of the mDNS address records.
+pub const DEFAULT_DEVICE_TTL: Duration = Duration::from_secs(120);
+
 pub struct MdnsDiscovery {
     mdns: ServiceDaemon,
-    discovered_devices: Arc<RwLock<HashMap<String, DeviceInfo>>>,
+    registry: DeviceRegistry,
+    advertising: Arc<Mutex<Advertising>>,
+    device_ttl: Duration,
+    /// Expiry and network watch, running between `start` and `stop`
+    tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+/// What this device advertises, shared by all clones
+struct Advertising {
     local_device: DeviceInfo,
+    /// Full name of the registered service, `None` while hidden
+    fullname: Option<String>,
+    /// When the device is hidden again, `None` to stay visible until stopped
+    until: Option<Instant>,
+    /// Hides the device once `until` has passed
+    timer: Option<JoinHandle<()>>,
 }
 
 impl MdnsDiscovery {
@@ -25,43 +49,139 @@ impl MdnsDiscovery {
         let mdns = ServiceDaemon::new().map_err(TsunaguError::Mdns)?;
         Ok(Self {
             mdns,
-            discovered_devices: Arc::new(RwLock::new(HashMap::new())),
-            local_device,
+            registry: DeviceRegistry::new(),
+            advertising: Arc::new(Mutex::new(Advertising {
+                local_device,
+                fullname: None,
+                until: None,
+                timer: None,
+            })),
+            device_ttl: DEFAULT_DEVICE_TTL,
+            tasks: Arc::new(Mutex::new(Vec::new())),
         })
     }
 
-    fn create_service_info(&self) -> ServiceInfo {
+    /// Drop devices that were silent for `ttl` and no longer accept connections
+    ///
+    /// Covers peers that vanished without announcing it, e.g. when their
+    /// network went away.
+    pub fn with_device_ttl(mut self, ttl: Duration) -> Self {
+        self.device_ttl = ttl;
+        self
+    }
+
+    fn create_service_info(local_device: &DeviceInfo) -> ServiceInfo {
         let mut properties = HashMap::new();
-        properties.insert("model".to_string(), self.local_device.model().to_string());
-        properties.insert("os".to_string(), self.local_device.os().to_string());
-        properties.insert(
-            "version".to_string(),
-            self.local_device.version().to_string(),
-        );
+        properties.insert("model".to_string(), local_device.model().to_string());
+        properties.insert("os".to_string(), local_device.os().to_string());
+        properties.insert("version".to_string(), local_device.version().to_string());
+        properties.insert("id".to_string(), local_device.id().to_string());
+        if let Some(public_key) = local_device.public_key() {
+            properties.insert("pk".to_string(), STANDARD.encode(public_key));
+        }
 
-        let instance_name = self.local_device.name();
+        let instance_name = local_device.name();
         let hostname = format!("{}.", instance_name);
+        let addresses: Vec<IpAddr> = local_device.addresses().iter().copied().collect();
 
         ServiceInfo::new(
             SERVICE_TYPE,
-            &instance_name,
+            instance_name,
             &hostname,
-            self.local_device.ip(),
-            self.local_device.port(),
+            &addresses[..],
+            local_device.port(),
             Some(properties),
         )
         .expect("Failed to create ServiceInfo")
     }
 
+    /// Register the service, hiding it again at `until` if given
+    fn advertise(&self, until: Option<Instant>) -> Result<()> {
+        let mut advertising = self.advertising.lock().unwrap();
+        let service_info = Self::create_service_info(&advertising.local_device);
+        let fullname = service_info.get_fullname().to_string();
+        self.mdns
+            .register(service_info)
+            .map_err(TsunaguError::Mdns)?;
+
+        if let Some(timer) = advertising.timer.take() {
+            timer.abort();
+        }
+        advertising.fullname = Some(fullname);
+        advertising.until = until;
+        if let Some(until) = until {
+            let discovery = self.clone();
+            advertising.timer = Some(tokio::spawn(async move {
+                tokio::time::sleep_until(until).await;
+                let mut advertising = discovery.advertising.lock().unwrap();
+                // A new window may have been opened while this one ended
+                if advertising.until == Some(until) {
+                    advertising.timer = None;
+                    info!("Discoverable window ended");
+                    discovery.hide(&mut advertising);
+                }
+            }));
+        }
+        Ok(())
+    }
+
+    /// Unregister the service, peers are told it went away
+    fn hide(&self, advertising: &mut Advertising) {
+        advertising.until = None;
+        let Some(fullname) = advertising.fullname.take() else {
+            return;
+        };
+        match self.mdns.unregister(&fullname) {
+            Ok(_) => info!("Service unregistered: {}", fullname),
+            Err(e) => warn!("Failed to unregister service {}: {}", fullname, e),
+        }
+    }
+
+    /// Announce the service again whenever the host's addresses change
+    ///
+    /// Peers on a network that just came up would not hear about the device
+    /// otherwise, and the advertised address may have changed.
+    fn watch_network(&self) -> Result<JoinHandle<()>> {
+        let events = self.mdns.monitor().map_err(TsunaguError::Mdns)?;
+        let discovery = self.clone();
+        Ok(tokio::spawn(async move {
+            while let Ok(event) = events.recv_async().await {
+                if let DaemonEvent::IpAdd(ip) | DaemonEvent::IpDel(ip) = event {
+                    debug!("Network changed at {}", ip);
+                    if let Err(e) = discovery.readvertise() {
+                        warn!("Failed to announce the device again: {}", e);
+                    }
+                }
+            }
+        }))
+    }
+
+    fn readvertise(&self) -> Result<()> {
+        let mut advertising = self.advertising.lock().unwrap();
+        if advertising.fullname.is_none() {
+            return Ok(());
+        }
+        if let Ok(addresses) = network::local_addresses() {
+            advertising.local_device.set_addresses(addresses);
+        }
+        info!(
+            "Announcing the device again at {}",
+            advertising.local_device.addresses_text()
+        );
+        self.mdns
+            .register(Self::create_service_info(&advertising.local_device))
+            .map_err(TsunaguError::Mdns)
+    }
+
     async fn handle_event(&self, event: ServiceEvent) {
         match event {
             ServiceEvent::ServiceResolved(info) => {
-                let ip = info
+                let addresses: Vec<IpAddr> = info
                     .get_addresses()
                     .iter()
-                    .next()
-                    .map(|&addr| addr)
-                    .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+                    .copied()
+                    .filter(|ip| network::is_usable(*ip))
+                    .collect();
                 let model = info.get_property_val_str("model").unwrap_or_default();
                 let os = info.get_property_val_str("os").unwrap_or_default();
                 let version = info.get_property_val_str("version").unwrap_or_default();
@@ -71,37 +191,52 @@ impl MdnsDiscovery {
                 let device_info = DeviceInfo::new(
                     hostname.to_string(),
                     model.to_string(),
-                    ip.to_string(),
+                    addresses,
                     info.get_port(),
                     os.to_string(),
                     version.to_string(),
                 );
-
-                let mut devices = self.discovered_devices.write().await;
-                if let Some(existing_device) = devices.get(&device_info.id().to_string()) {
-                    if existing_device != &device_info {
-                        info!("Updating existing device: {}", info.get_fullname());
-                        devices.insert(device_info.id().to_string(), device_info);
-                    } else {
-                        debug!("Device already exists and is up-to-date: {}", info.get_fullname());
-                    }
-                } else {
-                    info!("New device discovered: {}", info.get_fullname());
-                    devices.insert(device_info.id().to_string(), device_info);
-                }
+                let Some(device_info) = Self::with_advertised_identity(device_info, &info) else {
+                    return;
+                };
+                self.registry.seen(device_info, info.get_fullname()).await;
             }
-            ServiceEvent::ServiceRemoved(name, _type) => {
-                debug!("Service removed: {} ({})", name, _type);
-                self.discovered_devices
-                    .write()
-                    .await
-                    .retain(|_, v| v.name() != name);
-                info!("Device removed: {}", name);
+            // Devices are tracked by their full service name, the hostname
+            // they are displayed with may be shared or change
+            ServiceEvent::ServiceRemoved(service_type, fullname) => {
+                debug!("Service removed: {} ({})", fullname, service_type);
+                self.registry.remove_key(&fullname).await;
             }
             _ => {}
         }
     }
 
+    /// Apply the identity from the TXT record, so the id is stable across resolves
+    ///
+    /// Peers without an identity key are keyed by their service name. Returns
+    /// `None` if the advertised id does not match the advertised key.
+    fn with_advertised_identity(device_info: DeviceInfo, info: &ServiceInfo) -> Option<DeviceInfo> {
+        let advertised_id = info.get_property_val_str("id");
+        let public_key = info
+            .get_property_val_str("pk")
+            .and_then(|pk| STANDARD.decode(pk).ok());
+
+        match public_key {
+            Some(public_key) => {
+                let device_info = device_info.with_public_key(&public_key);
+                if advertised_id.is_some_and(|id| id != device_info.id()) {
+                    warn!(
+                        "Ignoring {}: advertised id does not match its identity key",
+                        info.get_fullname()
+                    );
+                    return None;
+                }
+                Some(device_info)
+            }
+            None => Some(device_info.with_id(info.get_fullname().to_string())),
+        }
+    }
+
     pub async fn manual_discover(&self) -> Result<()> {
         info!("Manually triggering device discovery");
         self.mdns.browse(SERVICE_TYPE).map_err(TsunaguError::Mdns)?;
@@ -116,6 +251,14 @@ impl Discovery for MdnsDiscovery {
         let browse_handle = self.mdns.browse(SERVICE_TYPE).map_err(TsunaguError::Mdns)?;
         let discovery = self.clone();
 
+        let tasks = vec![
+            self.registry.spawn_expiry(self.device_ttl),
+            self.watch_network()?,
+        ];
+        for previous in std::mem::replace(&mut *self.tasks.lock().unwrap(), tasks) {
+            previous.abort();
+        }
+
         tokio::spawn(async move {
             info!("mDNS discovery loop started");
             while let Ok(event) = browse_handle.recv_async().await {
@@ -129,52 +272,68 @@ impl Discovery for MdnsDiscovery {
 
     async fn stop(&mut self) -> Result<()> {
         info!("Stopping mDNS discovery");
+        self.make_undiscoverable().await?;
+        for task in self.tasks.lock().unwrap().drain(..) {
+            task.abort();
+        }
         self.mdns.shutdown().map_err(TsunaguError::Mdns)?;
 
         Ok(())
     }
 
     async fn discover_devices(&self) -> Result<Vec<DeviceInfo>> {
-        let devices = self
-            .discovered_devices
-            .read()
-            .await
-            .values()
-            .cloned()
-            .collect();
+        let devices = self.registry.devices().await;
         info!("Discovered devices: {:?}", devices);
         Ok(devices)
     }
 
     async fn make_discoverable(&mut self, duration: Duration) -> Result<()> {
         info!("Making device discoverable for {:?}", duration);
-        let service_info = self.create_service_info();
-        self.mdns
-            .register(service_info)
-            .map_err(TsunaguError::Mdns)?;
+        self.advertise(Some(Instant::now() + duration))
+    }
 
-        // let mdns = self.mdns.clone();
-        // let service_fullname = self.local_device.name().to_string();
-        //
-        // tokio::spawn(async move {
-        //     tokio::time::sleep(duration).await;
-        //     if let Err(e) = mdns.unregister(&service_fullname) {
-        //         error!("Failed to unregister service: {}", e);
-        //     } else {
-        //         info!("Service unregistered: {}", service_fullname);
-        //     }
-        // });
+    async fn make_discoverable_while_running(&mut self) -> Result<()> {
+        info!("Making device discoverable until discovery stops");
+        self.advertise(None)
+    }
 
+    async fn make_undiscoverable(&mut self) -> Result<()> {
+        let mut advertising = self.advertising.lock().unwrap();
+        if let Some(timer) = advertising.timer.take() {
+            timer.abort();
+        }
+        self.hide(&mut advertising);
         Ok(())
     }
+
+    fn visibility(&self) -> Visibility {
+        let advertising = self.advertising.lock().unwrap();
+        match (&advertising.fullname, advertising.until) {
+            (None, _) => Visibility::Hidden,
+            (Some(_), None) => Visibility::WhileRunning,
+            (Some(_), Some(until)) => {
+                Visibility::For(until.saturating_duration_since(Instant::now()))
+            }
+        }
+    }
+
+    fn set_local_device(&mut self, device: DeviceInfo) {
+        self.advertising.lock().unwrap().local_device = device;
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<DiscoveryEvent> {
+        self.registry.subscribe()
+    }
 }
 
 impl Clone for MdnsDiscovery {
     fn clone(&self) -> Self {
         Self {
             mdns: self.mdns.clone(),
-            discovered_devices: Arc::clone(&self.discovered_devices),
-            local_device: self.local_device.clone(),
+            registry: self.registry.clone(),
+            advertising: Arc::clone(&self.advertising),
+            device_ttl: self.device_ttl,
+            tasks: Arc::clone(&self.tasks),
         }
     }
 }
@@ -185,33 +344,124 @@ mod tests {
     use std::net::Ipv4Addr;
 
     #[tokio::test]
-    async fn test_mdns_discovery() {
+    async fn test_resolved_services_are_reported() {
+        let device = |name: &str, port: u16| {
+            DeviceInfo::new(
+                name.to_string(),
+                "TestModel".to_string(),
+                [Ipv4Addr::LOCALHOST.into()],
+                port,
+                "TestOS".to_string(),
+                "1.0".to_string(),
+            )
+        };
+        let discovery = MdnsDiscovery::new(device("Local", 8000)).unwrap();
+        let mut events = discovery.subscribe();
+
+        let peer = device("Peer", 8001);
+        let resolved = |peer: &DeviceInfo| {
+            ServiceEvent::ServiceResolved(MdnsDiscovery::create_service_info(peer))
+        };
+        discovery.handle_event(resolved(&peer)).await;
+        let added = match events.try_recv().unwrap() {
+            DiscoveryEvent::DeviceAdded(device) => device,
+            other => panic!("unexpected event {:?}", other),
+        };
+        assert_eq!(added.port(), 8001);
+
+        // Resolving the same service again changes nothing
+        discovery.handle_event(resolved(&peer)).await;
+        assert!(events.try_recv().is_err());
+
+        let mut moved = peer.clone();
+        moved.set_port(8002);
+        discovery.handle_event(resolved(&moved)).await;
+        let updated = events.try_recv().unwrap();
+        assert!(matches!(&updated, DiscoveryEvent::DeviceUpdated(d) if d.port() == 8002));
+        assert_eq!(updated.device().id(), added.id());
+        assert_eq!(discovery.discover_devices().await.unwrap().len(), 1);
+
+        let fullname = MdnsDiscovery::create_service_info(&peer)
+            .get_fullname()
+            .to_string();
+        discovery
+            .handle_event(ServiceEvent::ServiceRemoved(
+                SERVICE_TYPE.to_string(),
+                fullname,
+            ))
+            .await;
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            DiscoveryEvent::DeviceLost(_)
+        ));
+        assert!(discovery.discover_devices().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_discoverable_window() {
         let local_device = DeviceInfo::new(
-            "TestDevice.local.".to_string(),
+            "WindowDevice".to_string(),
             "TestModel".to_string(),
-            Ipv4Addr::LOCALHOST.to_string(),
+            [Ipv4Addr::LOCALHOST.into()],
             8000,
             "TestOS".to_string(),
             "1.0".to_string(),
         );
-
         let mut discovery = MdnsDiscovery::new(local_device).unwrap();
+        assert_eq!(discovery.visibility(), Visibility::Hidden);
 
-        discovery.start().await.unwrap();
         discovery
-            .make_discoverable(Duration::from_secs(5))
+            .make_discoverable(Duration::from_millis(300))
             .await
             .unwrap();
+        let remaining = discovery.visibility().remaining().unwrap();
+        assert!(remaining > Duration::ZERO && remaining <= Duration::from_millis(300));
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert_eq!(discovery.visibility(), Visibility::Hidden);
+
+        // A new window replaces the old one instead of being cut short by it
+        discovery
+            .make_discoverable(Duration::from_millis(300))
+            .await
+            .unwrap();
+        discovery.make_discoverable_while_running().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert_eq!(discovery.visibility(), Visibility::WhileRunning);
+
+        discovery.make_undiscoverable().await.unwrap();
+        assert_eq!(discovery.visibility(), Visibility::Hidden);
+        discovery.mdns.shutdown().unwrap();
+    }
 
-        // Manually trigger discovery
-        discovery.manual_discover().await.unwrap();
+    #[test]
+    fn test_advertised_identity_roundtrip() {
+        let identity = crate::identity::DeviceIdentity::generate().unwrap();
+        let device = |name: &str| {
+            DeviceInfo::new(
+                name.to_string(),
+                "TestModel".to_string(),
+                [Ipv4Addr::LOCALHOST.into()],
+                8000,
+                "TestOS".to_string(),
+                "1.0".to_string(),
+            )
+        };
+        let local_device = device("IdentityDevice").with_public_key(identity.public_key());
 
-        // Wait for a moment to allow discovery
-        tokio::time::sleep(Duration::from_secs(2)).await;
+        let service_info = MdnsDiscovery::create_service_info(&local_device);
 
-        let devices = discovery.discover_devices().await.unwrap();
-        println!("Discovered devices: {:?}", devices);
+        // Every resolve builds a fresh DeviceInfo, the id must still match
+        let resolved =
+            MdnsDiscovery::with_advertised_identity(device("IdentityDevice."), &service_info)
+                .unwrap();
+        assert_eq!(resolved.id(), local_device.id());
+        assert_eq!(resolved.public_key(), local_device.public_key());
 
-        discovery.stop().await.unwrap();
+        let service_info = MdnsDiscovery::create_service_info(&device("PlainDevice"));
+        let first =
+            MdnsDiscovery::with_advertised_identity(device("PlainDevice."), &service_info).unwrap();
+        let second =
+            MdnsDiscovery::with_advertised_identity(device("PlainDevice."), &service_info).unwrap();
+        assert_eq!(first.id(), second.id());
     }
 }
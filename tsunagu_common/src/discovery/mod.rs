@@ -1,12 +1,66 @@
-use async_trait::async_trait;
+use crate::config::{Config, DiscoveryBackend};
+use crate::error::TsunaguError;
 use crate::models::DeviceInfo;
 use crate::Result;
+use async_trait::async_trait;
+use std::time::Duration;
+use tokio::sync::broadcast;
 
+mod composite;
+mod manual;
 mod mdns_discovery;
-pub use mdns_discovery::MdnsDiscovery;
+mod multicast_discovery;
+mod probe;
+mod registry;
+mod subnet_scan;
+pub use composite::{CompositeDiscovery, DiscoveredDevice, Sighting};
+pub use manual::{ManualDiscovery, PeerStatus, Reachability};
+pub use mdns_discovery::{MdnsDiscovery, DEFAULT_DEVICE_TTL};
+pub use multicast_discovery::{MulticastDiscovery, MULTICAST_GROUP};
+pub use subnet_scan::SubnetScanDiscovery;
+
+/// Change to the set of discovered devices, see [`Discovery::subscribe`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiscoveryEvent {
+    DeviceAdded(DeviceInfo),
+    /// A known device changed, e.g. its address or port
+    DeviceUpdated(DeviceInfo),
+    /// The device went away, carrying its last known state
+    DeviceLost(DeviceInfo),
+}
+
+impl DiscoveryEvent {
+    pub fn device(&self) -> &DeviceInfo {
+        match self {
+            DiscoveryEvent::DeviceAdded(device)
+            | DiscoveryEvent::DeviceUpdated(device)
+            | DiscoveryEvent::DeviceLost(device) => device,
+        }
+    }
+}
+
+/// Whether and how long other devices can find this one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    Hidden,
+    /// Hidden again once the remaining time has passed
+    For(Duration),
+    /// Visible until discovery stops, e.g. while the app is open
+    WhileRunning,
+}
+
+impl Visibility {
+    /// Time left in a timed window, for countdowns
+    pub fn remaining(&self) -> Option<Duration> {
+        match self {
+            Visibility::For(remaining) => Some(*remaining),
+            Visibility::Hidden | Visibility::WhileRunning => None,
+        }
+    }
+}
 
 #[async_trait]
-pub trait Discovery {
+pub trait Discovery: Send + Sync {
     /// Start the discovery service
     async fn start(&mut self) -> Result<()>;
 
@@ -16,8 +70,59 @@ pub trait Discovery {
     /// Discover nearby devices
     async fn discover_devices(&self) -> Result<Vec<DeviceInfo>>;
 
-    /// Make the current device discoverable
-    async fn make_discoverable(&mut self, duration: std::time::Duration) -> Result<()>;
-}
+    /// Make the current device discoverable for `duration`
+    ///
+    /// Replaces any earlier window, the device is hidden again afterwards.
+    async fn make_discoverable(&mut self, duration: Duration) -> Result<()>;
+
+    /// Keep the current device discoverable until discovery stops
+    async fn make_discoverable_while_running(&mut self) -> Result<()>;
+
+    /// Stop advertising the current device right away
+    async fn make_undiscoverable(&mut self) -> Result<()>;
 
+    /// Whether the current device is advertised and for how long
+    fn visibility(&self) -> Visibility;
 
+    /// Update the advertised device, e.g. once the transfer server port is known
+    ///
+    /// Takes effect the next time the device is announced.
+    fn set_local_device(&mut self, device: DeviceInfo);
+
+    /// Subscribe to devices appearing, changing and going away
+    ///
+    /// Only changes after subscribing are reported, combine with
+    /// [`Discovery::discover_devices`] for the devices already known.
+    fn subscribe(&self) -> broadcast::Receiver<DiscoveryEvent>;
+}
+
+/// The discovery backends selected in `config`, advertising `local_device`
+///
+/// The peers of the address book are probed too, whatever the backends.
+pub fn from_config(config: &Config, local_device: DeviceInfo) -> Result<CompositeDiscovery> {
+    if config.discovery_backends.is_empty() && config.manual_peers.is_empty() {
+        return Err(TsunaguError::Config(
+            "No discovery backend configured".into(),
+        ));
+    }
+    let mut composite = CompositeDiscovery::new();
+    for backend in &config.discovery_backends {
+        let source: Box<dyn Discovery> = match backend {
+            DiscoveryBackend::Mdns => Box::new(MdnsDiscovery::new(local_device.clone())?),
+            DiscoveryBackend::Multicast => Box::new(MulticastDiscovery::new(
+                local_device.clone(),
+                config.discovery_port,
+            )?),
+            DiscoveryBackend::SubnetScan => Box::new(SubnetScanDiscovery::new(
+                local_device.clone(),
+                config.transfer_port,
+            )),
+        };
+        composite = composite.with_source(backend.to_string(), source);
+    }
+    if !config.manual_peers.is_empty() {
+        let manual = ManualDiscovery::new(&local_device, config.manual_peers.clone());
+        composite = composite.with_source("manual", Box::new(manual));
+    }
+    Ok(composite)
+}
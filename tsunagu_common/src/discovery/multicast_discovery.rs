@@ -0,0 +1,469 @@
+use super::registry::DeviceRegistry;
+use super::{Discovery, DiscoveryEvent, Visibility, DEFAULT_DEVICE_TTL};
+use crate::models::DeviceInfo;
+use crate::network;
+use crate::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
+
+/// Group the announcements go to, administratively scoped (RFC 2365)
+pub const MULTICAST_GROUP: Ipv4Addr = Ipv4Addr::new(239, 255, 84, 83);
+
+/// Version of the datagrams, others are ignored
+const PACKET_VERSION: u16 = 1;
+
+/// How often a discoverable device announces itself
+const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(30);
+
+const MAX_PACKET_SIZE: usize = 8 * 1024;
+
+/// What the devices tell each other
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Packet {
+    /// A discoverable device, sent on its own and in answer to a query
+    Announce { device: DeviceInfo },
+    /// Ask discoverable devices to announce themselves
+    Query,
+    /// The device is no longer discoverable
+    Goodbye { id: String },
+}
+
+#[derive(Serialize, Deserialize)]
+struct Datagram {
+    version: u16,
+    #[serde(flatten)]
+    packet: Packet,
+}
+
+/// Discovery through Tsunagu's own announcements to a UDP multicast group
+///
+/// Works where mDNS is filtered, but only finds other Tsunagu devices using
+/// the same port.
+pub struct MulticastDiscovery {
+    socket: Arc<UdpSocket>,
+    port: u16,
+    registry: DeviceRegistry,
+    advertising: Arc<Mutex<Advertising>>,
+    /// Sending changes the outgoing interface of the shared socket
+    sending: Arc<tokio::sync::Mutex<()>>,
+    device_ttl: Duration,
+    /// Receiver, announcer and expiry, running between `start` and `stop`
+    tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
+}
+
+/// What this device advertises, shared by all clones
+struct Advertising {
+    local_device: DeviceInfo,
+    visible: bool,
+    /// When the device is hidden again, `None` to stay visible until stopped
+    until: Option<Instant>,
+    /// Hides the device once `until` has passed
+    timer: Option<JoinHandle<()>>,
+}
+
+impl MulticastDiscovery {
+    /// Listen on `port`, other instances on this host may share it
+    ///
+    /// Must be called within a Tokio runtime.
+    pub fn new(local_device: DeviceInfo, port: u16) -> Result<Self> {
+        Ok(Self {
+            socket: Arc::new(bind(port)?),
+            port,
+            registry: DeviceRegistry::new(),
+            advertising: Arc::new(Mutex::new(Advertising {
+                local_device,
+                visible: false,
+                until: None,
+                timer: None,
+            })),
+            sending: Arc::new(tokio::sync::Mutex::new(())),
+            device_ttl: DEFAULT_DEVICE_TTL,
+            tasks: Arc::new(Mutex::new(Vec::new())),
+        })
+    }
+
+    /// Drop devices that were silent for `ttl` and no longer accept connections
+    pub fn with_device_ttl(mut self, ttl: Duration) -> Self {
+        self.device_ttl = ttl;
+        self
+    }
+
+    /// Ask discoverable devices to announce themselves
+    pub async fn query(&self) -> Result<()> {
+        self.send_to_group(&Packet::Query).await
+    }
+
+    /// Join the group on every IPv4 interface, again after the network changed
+    fn join_groups(&self) {
+        let interfaces = ipv4_interfaces();
+        for interface in interfaces.iter().chain([&Ipv4Addr::UNSPECIFIED]) {
+            // Interfaces joined before fail with "address in use"
+            if let Err(e) = self.socket.join_multicast_v4(MULTICAST_GROUP, *interface) {
+                debug!("Not joining {} on {}: {}", MULTICAST_GROUP, interface, e);
+            }
+        }
+    }
+
+    /// Send `packet` to the group on every IPv4 interface
+    async fn send_to_group(&self, packet: &Packet) -> Result<()> {
+        let bytes = encode(packet)?;
+        let group = SocketAddr::from((MULTICAST_GROUP, self.port));
+        let _sending = self.sending.lock().await;
+        let mut sent = false;
+        for interface in ipv4_interfaces() {
+            if let Err(e) = SockRef::from(&*self.socket).set_multicast_if_v4(&interface) {
+                debug!("Cannot send on {}: {}", interface, e);
+                continue;
+            }
+            match self.socket.send_to(&bytes, group).await {
+                Ok(_) => sent = true,
+                Err(e) => debug!("Sending on {} failed: {}", interface, e),
+            }
+        }
+        if !sent {
+            // Let the system pick the interface
+            SockRef::from(&*self.socket).set_multicast_if_v4(&Ipv4Addr::UNSPECIFIED)?;
+            self.socket.send_to(&bytes, group).await?;
+        }
+        Ok(())
+    }
+
+    async fn send_to(&self, packet: &Packet, to: SocketAddr) -> Result<()> {
+        self.socket.send_to(&encode(packet)?, to).await?;
+        Ok(())
+    }
+
+    /// Handle a datagram from `from`, returning the answer to send back
+    async fn handle_packet(&self, packet: Packet, from: SocketAddr) -> Option<Packet> {
+        let local_id = self
+            .advertising
+            .lock()
+            .unwrap()
+            .local_device
+            .id()
+            .to_string();
+        match packet {
+            Packet::Announce { mut device } => {
+                // Our own announcements come back through the loopback
+                if device.id() == local_id {
+                    return None;
+                }
+                if device.public_key().is_some() && !device.has_verified_id() {
+                    warn!("Ignoring {}: id does not match its identity key", from);
+                    return None;
+                }
+                // The sender is reachable at the address it sent from, whatever it claims
+                let addresses: Vec<IpAddr> = device
+                    .addresses()
+                    .iter()
+                    .copied()
+                    .chain([from.ip()])
+                    .filter(|ip| network::is_usable(*ip))
+                    .collect();
+                device.set_addresses(addresses);
+                let key = device.id().to_string();
+                self.registry.seen(device, &key).await;
+                None
+            }
+            Packet::Query => self.announcement(),
+            Packet::Goodbye { id } => {
+                if id != local_id {
+                    self.registry.remove_key(&id).await;
+                }
+                None
+            }
+        }
+    }
+
+    /// The announcement of this device, if it is discoverable
+    fn announcement(&self) -> Option<Packet> {
+        let advertising = self.advertising.lock().unwrap();
+        advertising.visible.then(|| Packet::Announce {
+            device: advertising.local_device.clone(),
+        })
+    }
+
+    fn spawn_receiver(&self) -> JoinHandle<()> {
+        let discovery = self.clone();
+        tokio::spawn(async move {
+            let mut buf = vec![0; MAX_PACKET_SIZE];
+            loop {
+                let (len, from) = match discovery.socket.recv_from(&mut buf).await {
+                    Ok(received) => received,
+                    Err(e) => {
+                        // E.g. an ICMP error for an earlier answer on Windows
+                        debug!("Receiving failed: {}", e);
+                        continue;
+                    }
+                };
+                let Some(packet) = decode(&buf[..len]) else {
+                    debug!("Ignoring datagram from {}", from);
+                    continue;
+                };
+                if let Some(answer) = discovery.handle_packet(packet, from).await {
+                    if let Err(e) = discovery.send_to(&answer, from).await {
+                        debug!("Answering {} failed: {}", from, e);
+                    }
+                }
+            }
+        })
+    }
+
+    fn spawn_announcer(&self) -> JoinHandle<()> {
+        let discovery = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(ANNOUNCE_INTERVAL);
+            loop {
+                interval.tick().await;
+                discovery.join_groups();
+                if let Some(announcement) = discovery.announcement() {
+                    if let Err(e) = discovery.send_to_group(&announcement).await {
+                        warn!("Failed to announce the device: {}", e);
+                    }
+                }
+            }
+        })
+    }
+
+    /// Announce the device, hiding it again at `until` if given
+    async fn advertise(&self, until: Option<Instant>) -> Result<()> {
+        let announcement = {
+            let mut advertising = self.advertising.lock().unwrap();
+            if let Some(timer) = advertising.timer.take() {
+                timer.abort();
+            }
+            advertising.visible = true;
+            advertising.until = until;
+            if let Some(until) = until {
+                let discovery = self.clone();
+                advertising.timer = Some(tokio::spawn(async move {
+                    tokio::time::sleep_until(until).await;
+                    // A new window may have been opened while this one ended
+                    if discovery.advertising.lock().unwrap().until != Some(until) {
+                        return;
+                    }
+                    info!("Discoverable window ended");
+                    if let Err(e) = discovery.hide(false).await {
+                        warn!("Failed to say goodbye: {}", e);
+                    }
+                }));
+            }
+            Packet::Announce {
+                device: advertising.local_device.clone(),
+            }
+        };
+        self.send_to_group(&announcement).await
+    }
+
+    /// Stop announcing and tell the others, `abort_timer` unless called by it
+    async fn hide(&self, abort_timer: bool) -> Result<()> {
+        let goodbye = {
+            let mut advertising = self.advertising.lock().unwrap();
+            match advertising.timer.take() {
+                Some(timer) if abort_timer => timer.abort(),
+                _ => {}
+            }
+            let was_visible = std::mem::replace(&mut advertising.visible, false);
+            advertising.until = None;
+            was_visible.then(|| Packet::Goodbye {
+                id: advertising.local_device.id().to_string(),
+            })
+        };
+        match goodbye {
+            Some(goodbye) => self.send_to_group(&goodbye).await,
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl Discovery for MulticastDiscovery {
+    async fn start(&mut self) -> Result<()> {
+        info!("Starting multicast discovery on port {}", self.port);
+        self.join_groups();
+        let tasks = vec![
+            self.spawn_receiver(),
+            self.spawn_announcer(),
+            self.registry.spawn_expiry(self.device_ttl),
+        ];
+        for previous in std::mem::replace(&mut *self.tasks.lock().unwrap(), tasks) {
+            previous.abort();
+        }
+        self.query().await
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        info!("Stopping multicast discovery");
+        self.make_undiscoverable().await?;
+        for task in self.tasks.lock().unwrap().drain(..) {
+            task.abort();
+        }
+        Ok(())
+    }
+
+    async fn discover_devices(&self) -> Result<Vec<DeviceInfo>> {
+        Ok(self.registry.devices().await)
+    }
+
+    async fn make_discoverable(&mut self, duration: Duration) -> Result<()> {
+        info!("Making device discoverable for {:?}", duration);
+        self.advertise(Some(Instant::now() + duration)).await
+    }
+
+    async fn make_discoverable_while_running(&mut self) -> Result<()> {
+        info!("Making device discoverable until discovery stops");
+        self.advertise(None).await
+    }
+
+    async fn make_undiscoverable(&mut self) -> Result<()> {
+        self.hide(true).await
+    }
+
+    fn visibility(&self) -> Visibility {
+        let advertising = self.advertising.lock().unwrap();
+        match (advertising.visible, advertising.until) {
+            (false, _) => Visibility::Hidden,
+            (true, None) => Visibility::WhileRunning,
+            (true, Some(until)) => Visibility::For(until.saturating_duration_since(Instant::now())),
+        }
+    }
+
+    fn set_local_device(&mut self, device: DeviceInfo) {
+        self.advertising.lock().unwrap().local_device = device;
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<DiscoveryEvent> {
+        self.registry.subscribe()
+    }
+}
+
+impl Clone for MulticastDiscovery {
+    fn clone(&self) -> Self {
+        Self {
+            socket: Arc::clone(&self.socket),
+            port: self.port,
+            registry: self.registry.clone(),
+            advertising: Arc::clone(&self.advertising),
+            sending: Arc::clone(&self.sending),
+            device_ttl: self.device_ttl,
+            tasks: Arc::clone(&self.tasks),
+        }
+    }
+}
+
+fn bind(port: u16) -> Result<UdpSocket> {
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+    // Other Tsunagu instances on this host listen on the same port
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&SocketAddr::from((Ipv4Addr::UNSPECIFIED, port)).into())?;
+    socket.set_multicast_loop_v4(true)?;
+    Ok(UdpSocket::from_std(socket.into())?)
+}
+
+fn ipv4_interfaces() -> Vec<Ipv4Addr> {
+    network::local_addresses()
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|ip| match ip {
+            IpAddr::V4(ip) => Some(ip),
+            IpAddr::V6(_) => None,
+        })
+        .collect()
+}
+
+fn encode(packet: &Packet) -> Result<Vec<u8>> {
+    Ok(serde_json::to_vec(&Datagram {
+        version: PACKET_VERSION,
+        packet: packet.clone(),
+    })?)
+}
+
+/// The packet in `bytes`, `None` for other versions and foreign traffic
+fn decode(bytes: &[u8]) -> Option<Packet> {
+    serde_json::from_slice::<Datagram>(bytes)
+        .ok()
+        .filter(|datagram| datagram.version == PACKET_VERSION)
+        .map(|datagram| datagram.packet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn device(name: &str) -> DeviceInfo {
+        DeviceInfo::new(
+            name.to_string(),
+            "TestModel".to_string(),
+            [Ipv4Addr::new(10, 0, 0, 7).into()],
+            8000,
+            "TestOS".to_string(),
+            "1.0".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_packet_roundtrip() {
+        let packet = Packet::Announce {
+            device: device("Peer"),
+        };
+        assert_eq!(decode(&encode(&packet).unwrap()), Some(packet));
+        assert_eq!(decode(b"not a packet"), None);
+
+        let future = br#"{"version":2,"type":"query"}"#;
+        assert_eq!(decode(future), None);
+    }
+
+    #[tokio::test]
+    async fn test_handle_packet() {
+        let discovery = MulticastDiscovery::new(device("Local"), 0).unwrap();
+        let mut events = discovery.subscribe();
+        let from = SocketAddr::from((Ipv4Addr::new(192, 168, 1, 20), 5356));
+
+        // Hidden devices do not answer queries
+        assert_eq!(discovery.handle_packet(Packet::Query, from).await, None);
+        discovery.advertising.lock().unwrap().visible = true;
+        assert!(matches!(
+            discovery.handle_packet(Packet::Query, from).await,
+            Some(Packet::Announce { .. })
+        ));
+
+        let peer = device("Peer");
+        let announce = Packet::Announce {
+            device: peer.clone(),
+        };
+        discovery.handle_packet(announce, from).await;
+        let added = events.try_recv().unwrap();
+        assert!(matches!(&added, DiscoveryEvent::DeviceAdded(d) if d.id() == peer.id()));
+        assert!(added.device().addresses().contains(&from.ip()));
+        assert!(added
+            .device()
+            .addresses()
+            .contains(&Ipv4Addr::new(10, 0, 0, 7).into()));
+
+        // Our own announcement is not a peer
+        let own = discovery.announcement().unwrap();
+        discovery.handle_packet(own, from).await;
+        assert!(events.try_recv().is_err());
+
+        let goodbye = Packet::Goodbye {
+            id: peer.id().to_string(),
+        };
+        discovery.handle_packet(goodbye, from).await;
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            DiscoveryEvent::DeviceLost(_)
+        ));
+        assert!(discovery.discover_devices().await.unwrap().is_empty());
+    }
+}
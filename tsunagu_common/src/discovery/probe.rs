@@ -0,0 +1,43 @@
+use crate::error::TsunaguError;
+use crate::models::DeviceInfo;
+use crate::network;
+use crate::transfer::protocol::Connection;
+use crate::Result;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+/// How long a host that accepted the connection may take to say who it is
+const IDENTIFY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The device whose transfer server listens on `addr`
+///
+/// The server opens every connection with a `Hello` naming its device,
+/// which is all this reads. The device is not authenticated.
+pub(crate) async fn identify(addr: SocketAddr, connect_timeout: Duration) -> Result<DeviceInfo> {
+    let stream = tokio::time::timeout(connect_timeout, TcpStream::connect(addr))
+        .await
+        .map_err(|_| TsunaguError::Network(format!("Connection to {} timed out", addr)))??;
+    let mut device = tokio::time::timeout(IDENTIFY_TIMEOUT, Connection::new(stream).identify())
+        .await
+        .map_err(|_| {
+            TsunaguError::Network(format!("{} did not identify itself in time", addr))
+        })??;
+    if device.public_key().is_some() && !device.has_verified_id() {
+        return Err(TsunaguError::Network(format!(
+            "{}: id does not match its identity key",
+            addr
+        )));
+    }
+
+    // The device is reachable where it answered, whatever it claims
+    let addresses: Vec<IpAddr> = device
+        .addresses()
+        .iter()
+        .copied()
+        .chain([addr.ip()])
+        .filter(|ip| network::is_usable(*ip))
+        .collect();
+    device.set_addresses(addresses);
+    Ok(device)
+}
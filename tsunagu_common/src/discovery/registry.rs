@@ -0,0 +1,264 @@
+use super::DiscoveryEvent;
+use crate::models::DeviceInfo;
+use crate::network;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, RwLock};
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::{debug, info};
+
+/// Events buffered per subscriber before it starts lagging
+const EVENT_CAPACITY: usize = 64;
+
+/// How long a liveness probe may take to connect
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Devices found by a discovery source, keyed by their stable id
+///
+/// Reports every change as a [`DiscoveryEvent`] and ages out devices that
+/// were not heard from for a while and no longer accept connections.
+#[derive(Clone)]
+pub(crate) struct DeviceRegistry {
+    devices: Arc<RwLock<HashMap<String, Tracked>>>,
+    events: broadcast::Sender<DiscoveryEvent>,
+}
+
+struct Tracked {
+    /// Without `last_seen`, so announcements can be compared
+    device: DeviceInfo,
+    /// Name the source knows the device by, e.g. its mDNS service name
+    key: String,
+    /// Seconds since the Unix epoch
+    last_seen: u64,
+}
+
+impl Tracked {
+    fn snapshot(&self) -> DeviceInfo {
+        let mut device = self.device.clone();
+        device.set_last_seen(Some(self.last_seen));
+        device
+    }
+}
+
+impl DeviceRegistry {
+    pub(crate) fn new() -> Self {
+        Self {
+            devices: Arc::new(RwLock::new(HashMap::new())),
+            events: broadcast::channel(EVENT_CAPACITY).0,
+        }
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<DiscoveryEvent> {
+        self.events.subscribe()
+    }
+
+    pub(crate) async fn devices(&self) -> Vec<DeviceInfo> {
+        self.devices
+            .read()
+            .await
+            .values()
+            .map(Tracked::snapshot)
+            .collect()
+    }
+
+    /// Record that the source announced `device` under `key`
+    pub(crate) async fn seen(&self, mut device: DeviceInfo, key: &str) {
+        device.set_last_seen(None);
+        let now = unix_now();
+        let mut devices = self.devices.write().await;
+        let event = match devices.get_mut(device.id()) {
+            Some(tracked) => {
+                tracked.last_seen = now;
+                tracked.key = key.to_string();
+                if tracked.device == device {
+                    debug!("Device already exists and is up-to-date: {}", key);
+                    return;
+                }
+                info!("Updating existing device: {}", key);
+                tracked.device = device;
+                DiscoveryEvent::DeviceUpdated(tracked.snapshot())
+            }
+            None => {
+                info!("New device discovered: {}", key);
+                let tracked = Tracked {
+                    device,
+                    key: key.to_string(),
+                    last_seen: now,
+                };
+                let event = DiscoveryEvent::DeviceAdded(tracked.snapshot());
+                devices.insert(tracked.device.id().to_string(), tracked);
+                event
+            }
+        };
+        self.emit(event);
+    }
+
+    /// Drop the devices the source announced under `key`
+    pub(crate) async fn remove_key(&self, key: &str) {
+        let mut devices = self.devices.write().await;
+        let lost: Vec<String> = devices
+            .iter()
+            .filter(|(_, tracked)| tracked.key == key)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in lost {
+            if let Some(tracked) = devices.remove(&id) {
+                info!("Device removed: {}", key);
+                self.emit(DiscoveryEvent::DeviceLost(tracked.snapshot()));
+            }
+        }
+    }
+
+    /// Check on the devices in the background until the handle is aborted
+    ///
+    /// Devices not heard from for half of `ttl` are probed, those that do not
+    /// answer are dropped once `ttl` has passed.
+    pub(crate) fn spawn_expiry(&self, ttl: Duration) -> JoinHandle<()> {
+        let registry = self.clone();
+        let period = (ttl / 4).max(Duration::from_secs(1));
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(period);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                interval.tick().await;
+                registry.check_liveness(ttl, unix_now()).await;
+            }
+        })
+    }
+
+    /// Probe quiet devices and drop those gone for longer than `ttl` at `now`
+    async fn check_liveness(&self, ttl: Duration, now: u64) {
+        let ttl = ttl.as_secs();
+        let quiet: Vec<DeviceInfo> = self
+            .devices
+            .read()
+            .await
+            .values()
+            .filter(|tracked| now.saturating_sub(tracked.last_seen) >= ttl / 2)
+            .map(|tracked| tracked.device.clone())
+            .collect();
+        if quiet.is_empty() {
+            return;
+        }
+
+        let mut probes = JoinSet::new();
+        for device in quiet {
+            probes.spawn(async move {
+                let alive = probe(&device).await;
+                (device, alive)
+            });
+        }
+        while let Some(Ok((device, alive))) = probes.join_next().await {
+            let mut devices = self.devices.write().await;
+            let Some(tracked) = devices.get_mut(device.id()) else {
+                continue;
+            };
+            if alive {
+                tracked.last_seen = tracked.last_seen.max(now);
+            } else if now.saturating_sub(tracked.last_seen) >= ttl {
+                info!("Device {} is gone, not seen for {}s", device.name(), ttl);
+                if let Some(tracked) = devices.remove(device.id()) {
+                    self.emit(DiscoveryEvent::DeviceLost(tracked.snapshot()));
+                }
+            }
+        }
+    }
+
+    fn emit(&self, event: DiscoveryEvent) {
+        // Nobody listening is fine
+        let _ = self.events.send(event);
+    }
+}
+
+/// Whether the device still accepts connections on its advertised port
+async fn probe(device: &DeviceInfo) -> bool {
+    network::connect(device, PROBE_TIMEOUT).await.is_ok()
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use tokio::net::TcpListener;
+
+    fn device(name: &str, port: u16) -> DeviceInfo {
+        DeviceInfo::new(
+            name.to_string(),
+            "TestModel".to_string(),
+            [Ipv4Addr::LOCALHOST.into()],
+            port,
+            "TestOS".to_string(),
+            "1.0".to_string(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_seen_and_removed() {
+        let registry = DeviceRegistry::new();
+        let mut events = registry.subscribe();
+
+        let peer = device("Peer", 8000);
+        registry
+            .seen(peer.clone(), "Peer._tsunagu._tcp.local.")
+            .await;
+        let added = events.try_recv().unwrap();
+        assert!(matches!(added, DiscoveryEvent::DeviceAdded(_)));
+        assert!(added.device().last_seen().is_some());
+
+        registry
+            .seen(peer.clone(), "Peer._tsunagu._tcp.local.")
+            .await;
+        assert!(events.try_recv().is_err());
+
+        let mut moved = peer.clone();
+        moved.set_port(8001);
+        registry.seen(moved, "Peer._tsunagu._tcp.local.").await;
+        assert!(matches!(
+            events.try_recv().unwrap(),
+            DiscoveryEvent::DeviceUpdated(d) if d.port() == 8001
+        ));
+
+        registry.remove_key("Other._tsunagu._tcp.local.").await;
+        assert!(events.try_recv().is_err());
+        registry.remove_key("Peer._tsunagu._tcp.local.").await;
+        let lost = events.try_recv().unwrap();
+        assert!(matches!(&lost, DiscoveryEvent::DeviceLost(d) if d.id() == peer.id()));
+        assert!(registry.devices().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_expiry() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let alive = device("Alive", listener.local_addr().unwrap().port());
+        let closed = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let gone = device("Gone", closed.local_addr().unwrap().port());
+        drop(closed);
+
+        let registry = DeviceRegistry::new();
+        registry.seen(alive.clone(), "alive").await;
+        registry.seen(gone.clone(), "gone").await;
+        let mut events = registry.subscribe();
+        let ttl = Duration::from_secs(120);
+
+        // Quiet but within the TTL, nothing is dropped yet
+        registry.check_liveness(ttl, unix_now() + 90).await;
+        assert_eq!(registry.devices().await.len(), 2);
+        assert!(events.try_recv().is_err());
+
+        // The live device answered the probe above, so only the other one ages out
+        registry.check_liveness(ttl, unix_now() + 150).await;
+        let lost = events.try_recv().unwrap();
+        assert!(matches!(&lost, DiscoveryEvent::DeviceLost(d) if d.id() == gone.id()));
+        let remaining = registry.devices().await;
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].id(), alive.id());
+    }
+}
@@ -0,0 +1,241 @@
+use super::probe;
+use super::registry::DeviceRegistry;
+use super::{Discovery, DiscoveryEvent, Visibility, DEFAULT_DEVICE_TTL};
+use crate::models::DeviceInfo;
+use crate::network;
+use crate::Result;
+use async_trait::async_trait;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::Instant;
+use tracing::{debug, info};
+
+/// Networks wider than this prefix are only scanned around this host
+const WIDEST_SCAN: u8 = 22;
+
+/// Hosts probed at the same time
+const MAX_CONCURRENT_PROBES: usize = 128;
+
+/// Most addresses on a network are unused and never answer
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Results younger than this are returned without scanning again
+const RESCAN_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Discovery by probing every host of the local networks on the transfer port
+///
+/// A fallback for networks that block multicast entirely. Only finds devices
+/// listening on `port`, and cannot make this device discoverable: it is found
+/// whenever its transfer server runs.
+pub struct SubnetScanDiscovery {
+    local_device: Arc<Mutex<DeviceInfo>>,
+    port: u16,
+    registry: DeviceRegistry,
+    device_ttl: Duration,
+    /// When the last scan finished, held while one runs
+    last_scan: Arc<tokio::sync::Mutex<Option<Instant>>>,
+    /// Expiry of the devices found, running between `start` and `stop`
+    expiry: Option<JoinHandle<()>>,
+}
+
+impl SubnetScanDiscovery {
+    /// Look for devices with a transfer server on `port`
+    pub fn new(local_device: DeviceInfo, port: u16) -> Self {
+        Self {
+            local_device: Arc::new(Mutex::new(local_device)),
+            port,
+            registry: DeviceRegistry::new(),
+            device_ttl: DEFAULT_DEVICE_TTL,
+            last_scan: Arc::new(tokio::sync::Mutex::new(None)),
+            expiry: None,
+        }
+    }
+
+    /// Drop devices that were not found for `ttl` and no longer accept connections
+    pub fn with_device_ttl(mut self, ttl: Duration) -> Self {
+        self.device_ttl = ttl;
+        self
+    }
+
+    /// Probe every host of the local networks now
+    pub async fn scan(&self) -> Result<()> {
+        let mut last_scan = self.last_scan.lock().await;
+        self.scan_locked(&mut last_scan).await
+    }
+
+    /// Scan while holding the `last_scan` lock, so scans do not overlap
+    async fn scan_locked(&self, last_scan: &mut Option<Instant>) -> Result<()> {
+        let hosts: Vec<IpAddr> = network::local_subnets()?
+            .iter()
+            .flat_map(|subnet| subnet.neighbours(WIDEST_SCAN))
+            .map(IpAddr::from)
+            .collect();
+        info!("Scanning {} hosts on port {}", hosts.len(), self.port);
+        self.scan_hosts(hosts).await;
+        *last_scan = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Probe `hosts`, at most [`MAX_CONCURRENT_PROBES`] at a time
+    async fn scan_hosts(&self, hosts: Vec<IpAddr>) {
+        let local_id = self.local_device.lock().unwrap().id().to_string();
+        let mut hosts = hosts.into_iter();
+        let mut probes = JoinSet::new();
+        loop {
+            while probes.len() < MAX_CONCURRENT_PROBES {
+                let Some(ip) = hosts.next() else { break };
+                let addr = SocketAddr::new(ip, self.port);
+                probes.spawn(async move { (addr, probe::identify(addr, CONNECT_TIMEOUT).await) });
+            }
+            let Some(finished) = probes.join_next().await else {
+                return;
+            };
+            let device = match finished {
+                Ok((_, Ok(device))) => device,
+                Ok((addr, Err(e))) => {
+                    debug!("Nothing found at {}: {}", addr, e);
+                    continue;
+                }
+                Err(_) => continue,
+            };
+            // Our own server answers on every address of this host
+            if device.id() == local_id {
+                continue;
+            }
+            let key = device.id().to_string();
+            self.registry.seen(device, &key).await;
+        }
+    }
+}
+
+#[async_trait]
+impl Discovery for SubnetScanDiscovery {
+    async fn start(&mut self) -> Result<()> {
+        info!("Starting subnet scan discovery on port {}", self.port);
+        if let Some(previous) = self
+            .expiry
+            .replace(self.registry.spawn_expiry(self.device_ttl))
+        {
+            previous.abort();
+        }
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        info!("Stopping subnet scan discovery");
+        if let Some(expiry) = self.expiry.take() {
+            expiry.abort();
+        }
+        Ok(())
+    }
+
+    /// Scans first unless the last scan is recent
+    async fn discover_devices(&self) -> Result<Vec<DeviceInfo>> {
+        let mut last_scan = self.last_scan.lock().await;
+        if last_scan.is_none_or(|last| last.elapsed() >= RESCAN_INTERVAL) {
+            self.scan_locked(&mut last_scan).await?;
+        }
+        drop(last_scan);
+        Ok(self.registry.devices().await)
+    }
+
+    // Scans find the transfer server, there is nothing to advertise
+
+    async fn make_discoverable(&mut self, _duration: Duration) -> Result<()> {
+        Ok(())
+    }
+
+    async fn make_discoverable_while_running(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn make_undiscoverable(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn visibility(&self) -> Visibility {
+        Visibility::Hidden
+    }
+
+    fn set_local_device(&mut self, device: DeviceInfo) {
+        *self.local_device.lock().unwrap() = device;
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<DiscoveryEvent> {
+        self.registry.subscribe()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encryption::SessionRole;
+    use crate::transfer::protocol::Connection;
+    use std::net::Ipv4Addr;
+    use tokio::net::TcpListener;
+
+    fn device(name: &str) -> DeviceInfo {
+        DeviceInfo::new(
+            name.to_string(),
+            "TestModel".to_string(),
+            [Ipv4Addr::new(10, 0, 0, 7).into()],
+            8000,
+            "TestOS".to_string(),
+            "1.0".to_string(),
+        )
+    }
+
+    /// Answer connections on `listener` the way the transfer server does
+    fn serve(listener: TcpListener, device: DeviceInfo) {
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let device = device.clone();
+                tokio::spawn(async move {
+                    let _ = Connection::new(stream)
+                        .handshake(&device, None, SessionRole::Responder)
+                        .await;
+                });
+            }
+        });
+    }
+
+    #[tokio::test]
+    async fn test_scan_hosts() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let peer = device("Peer");
+        serve(listener, peer.clone());
+
+        let discovery = SubnetScanDiscovery::new(device("Local"), port);
+        let mut events = discovery.subscribe();
+        // Nothing listens on the second address
+        let hosts = vec![
+            Ipv4Addr::LOCALHOST.into(),
+            Ipv4Addr::new(127, 0, 0, 2).into(),
+        ];
+        discovery.scan_hosts(hosts).await;
+
+        let added = events.try_recv().unwrap();
+        assert!(matches!(&added, DiscoveryEvent::DeviceAdded(d) if d.id() == peer.id()));
+        assert!(added
+            .device()
+            .addresses()
+            .contains(&Ipv4Addr::LOCALHOST.into()));
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scan_skips_own_device() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let local = device("Local");
+        serve(listener, local.clone());
+
+        let discovery = SubnetScanDiscovery::new(local, port);
+        discovery.scan_hosts(vec![Ipv4Addr::LOCALHOST.into()]).await;
+        assert!(discovery.registry.devices().await.is_empty());
+    }
+}
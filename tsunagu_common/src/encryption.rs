@@ -1,6 +1,22 @@
 use crate::error::TsunaguError;
 use crate::Result;
-use ring::{aead, rand::{self, SecureRandom}};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ring::{
+    aead, agreement, hkdf,
+    rand::{self, SecureRandom},
+};
+
+const NONCE_LEN: usize = aead::NONCE_LEN;
+const COUNTER_LEN: usize = 8;
+
+const INITIATOR_TO_RESPONDER: &[u8] = b"tsunagu v2 initiator to responder";
+const RESPONDER_TO_INITIATOR: &[u8] = b"tsunagu v2 responder to initiator";
+const SESSION_ID: &[u8] = b"tsunagu v2 session id";
+
+const KEY_MASK_SALT: &[u8] = b"tsunagu v2 key mask";
+const INITIATOR_KEY_MASK: &[u8] = b"tsunagu v2 initiator key mask";
+const RESPONDER_KEY_MASK: &[u8] = b"tsunagu v2 responder key mask";
+const PUBLIC_KEY_LEN: usize = 32;
 
 pub struct Encryption {
     key: aead::LessSafeKey,
@@ -12,17 +28,22 @@ impl Encryption {
     pub fn new() -> Self {
         let rng = rand::SystemRandom::new();
         let mut key_bytes = [0u8; 32]; // AES-256 uses 32-byte keys
-        rng.fill(&mut key_bytes).expect("Failed to generate random key");
-        let key =
-            aead::UnboundKey::new(&aead::AES_256_GCM, &key_bytes)
-                .unwrap();
+        rng.fill(&mut key_bytes)
+            .expect("Failed to generate random key");
+        let key = aead::UnboundKey::new(&aead::AES_256_GCM, &key_bytes).unwrap();
         let key = aead::LessSafeKey::new(key);
         Self { key, key_bytes }
     }
 
     /// Encrypt the given data
+    ///
+    /// A random nonce is generated for every call and prepended to the output.
     pub fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
-        let nonce = aead::Nonce::assume_unique_for_key([0u8; 12]);
+        let mut nonce_bytes = [0u8; NONCE_LEN];
+        rand::SystemRandom::new()
+            .fill(&mut nonce_bytes)
+            .map_err(|e| TsunaguError::Encryption(e.to_string()))?;
+        let nonce = aead::Nonce::assume_unique_for_key(nonce_bytes);
         let aad = aead::Aad::empty();
 
         let mut in_out = data.to_vec();
@@ -30,15 +51,22 @@ impl Encryption {
             .seal_in_place_append_tag(nonce, aad, &mut in_out)
             .map_err(|e| TsunaguError::Encryption(e.to_string()))?;
 
-        Ok(in_out)
+        let mut output = nonce_bytes.to_vec();
+        output.append(&mut in_out);
+        Ok(output)
     }
 
     /// Decrypt the given data
     pub fn decrypt(&self, encrypted_data: &[u8]) -> Result<Vec<u8>> {
-        let nonce = aead::Nonce::assume_unique_for_key([0u8; 12]);
+        if encrypted_data.len() < NONCE_LEN {
+            return Err(TsunaguError::Encryption("Ciphertext too short".into()));
+        }
+        let (nonce_bytes, ciphertext) = encrypted_data.split_at(NONCE_LEN);
+        let nonce = aead::Nonce::try_assume_unique_for_key(nonce_bytes)
+            .map_err(|e| TsunaguError::Encryption(e.to_string()))?;
         let aad = aead::Aad::empty();
 
-        let mut in_out = encrypted_data.to_vec();
+        let mut in_out = ciphertext.to_vec();
         let decrypted_data = self
             .key
             .open_in_place(nonce, aad, &mut in_out)
@@ -49,12 +77,13 @@ impl Encryption {
 
     /// Get the base64 encoded key for sharing
     pub fn get_base64_key(&self) -> String {
-        base64::encode(self.key_bytes)
+        STANDARD.encode(self.key_bytes)
     }
 
     /// Create an Encryption instance from a base64 encoded key
     pub fn from_base64_key(base64_key: &str) -> Result<Self> {
-        let key_bytes = base64::decode(base64_key)
+        let key_bytes = STANDARD
+            .decode(base64_key)
             .map_err(|e| TsunaguError::Encryption(format!("Invalid base64 key: {}", e)))?;
 
         let key = aead::UnboundKey::new(&aead::AES_256_GCM, &key_bytes)
@@ -64,10 +93,257 @@ impl Encryption {
         let mut key_array = [0u8; 32];
         key_array.copy_from_slice(&key_bytes);
 
-        Ok(Self { key, key_bytes: key_array })
+        Ok(Self {
+            key,
+            key_bytes: key_array,
+        })
+    }
+}
+
+impl Default for Encryption {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Which end of a session we are, decides the direction of the derived keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionRole {
+    Initiator,
+    Responder,
+}
+
+/// Ephemeral X25519 key pair for a single session
+pub struct KeyExchange {
+    private_key: agreement::EphemeralPrivateKey,
+    public_key: Vec<u8>,
+}
+
+impl KeyExchange {
+    pub fn new() -> Result<Self> {
+        let rng = rand::SystemRandom::new();
+        let private_key = agreement::EphemeralPrivateKey::generate(&agreement::X25519, &rng)
+            .map_err(|e| TsunaguError::Encryption(format!("Failed to generate key: {}", e)))?;
+        let public_key = private_key
+            .compute_public_key()
+            .map_err(|e| TsunaguError::Encryption(format!("Failed to compute public key: {}", e)))?
+            .as_ref()
+            .to_vec();
+
+        Ok(Self {
+            private_key,
+            public_key,
+        })
+    }
+
+    /// Our public key, to be sent to the peer
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    /// Combine with the peer's public key into the session cipher
+    ///
+    /// Each direction gets its own key derived with HKDF-SHA256, salted with
+    /// both public keys so the keys are bound to this exchange.
+    pub fn finish(self, peer_public_key: &[u8], role: SessionRole) -> Result<SessionCipher> {
+        let (initiator_key, responder_key) = match role {
+            SessionRole::Initiator => (self.public_key.as_slice(), peer_public_key),
+            SessionRole::Responder => (peer_public_key, self.public_key.as_slice()),
+        };
+        let mut salt = initiator_key.to_vec();
+        salt.extend_from_slice(responder_key);
+
+        let peer = agreement::UnparsedPublicKey::new(&agreement::X25519, peer_public_key);
+        let prk = agreement::agree_ephemeral(self.private_key, &peer, |shared_secret| {
+            hkdf::Salt::new(hkdf::HKDF_SHA256, &salt).extract(shared_secret)
+        })
+        .map_err(|_| TsunaguError::Encryption("Key agreement failed".into()))?;
+
+        let initiator_to_responder = derive_key(&prk, INITIATOR_TO_RESPONDER)?;
+        let responder_to_initiator = derive_key(&prk, RESPONDER_TO_INITIATOR)?;
+        let (sealing_key, opening_key) = match role {
+            SessionRole::Initiator => (initiator_to_responder, responder_to_initiator),
+            SessionRole::Responder => (responder_to_initiator, initiator_to_responder),
+        };
+
+        let mut session_id = [0u8; 32];
+        prk.expand(&[SESSION_ID], OutputLen(session_id.len()))
+            .and_then(|okm| okm.fill(&mut session_id))
+            .map_err(|_| TsunaguError::Encryption("Failed to derive session id".into()))?;
+
+        Ok(SessionCipher {
+            sealing_key,
+            opening_key,
+            send_counter: 0,
+            recv_counter: 0,
+            session_id,
+        })
+    }
+}
+
+/// Authenticated encryption for one direction pair of a session
+///
+/// Every sealed message is prefixed with its 64-bit counter, which also forms
+/// the nonce. The receiving side only accepts the next expected counter, so
+/// replayed, dropped or reordered messages are rejected.
+pub struct SessionCipher {
+    sealing_key: aead::LessSafeKey,
+    opening_key: aead::LessSafeKey,
+    send_counter: u64,
+    recv_counter: u64,
+    session_id: [u8; 32],
+}
+
+impl SessionCipher {
+    /// Encrypt a message for the peer
+    pub fn seal(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        let counter = self.send_counter;
+        self.send_counter = counter
+            .checked_add(1)
+            .ok_or_else(|| TsunaguError::Encryption("Nonce counter exhausted".into()))?;
+
+        let counter_bytes = counter.to_be_bytes();
+        let mut output = Vec::with_capacity(COUNTER_LEN + data.len() + aead::MAX_TAG_LEN);
+        output.extend_from_slice(&counter_bytes);
+        output.extend_from_slice(data);
+
+        let tag = self
+            .sealing_key
+            .seal_in_place_separate_tag(
+                counter_nonce(counter),
+                aead::Aad::from(counter_bytes),
+                &mut output[COUNTER_LEN..],
+            )
+            .map_err(|e| TsunaguError::Encryption(e.to_string()))?;
+        output.extend_from_slice(tag.as_ref());
+        Ok(output)
+    }
+
+    /// Decrypt a message from the peer
+    pub fn open(&mut self, data: &[u8]) -> Result<Vec<u8>> {
+        if data.len() < COUNTER_LEN + aead::MAX_TAG_LEN {
+            return Err(TsunaguError::Encryption("Ciphertext too short".into()));
+        }
+        let (counter_bytes, ciphertext) = data.split_at(COUNTER_LEN);
+        let counter_bytes: [u8; COUNTER_LEN] = counter_bytes.try_into().unwrap_or_default();
+        let counter = u64::from_be_bytes(counter_bytes);
+        if counter != self.recv_counter {
+            return Err(TsunaguError::Encryption(format!(
+                "Replayed or reordered message: expected {}, got {}",
+                self.recv_counter, counter
+            )));
+        }
+
+        let mut in_out = ciphertext.to_vec();
+        let plaintext_len = self
+            .opening_key
+            .open_in_place(
+                counter_nonce(counter),
+                aead::Aad::from(counter_bytes),
+                &mut in_out,
+            )
+            .map_err(|_| TsunaguError::Encryption("Message authentication failed".into()))?
+            .len();
+        in_out.truncate(plaintext_len);
+
+        self.recv_counter += 1;
+        Ok(in_out)
+    }
+
+    /// Value identifying this session, identical on both ends
+    pub fn session_id(&self) -> &[u8; 32] {
+        &self.session_id
+    }
+}
+
+/// Hides the public keys of a [`KeyExchange`] behind a secret both ends know
+///
+/// A password authenticated exchange in the style of EKE: a peer that does not
+/// know the secret unmasks a different key than the one we sent, so its
+/// session keys differ and the first sealed message fails to open. Any 32
+/// bytes are a usable X25519 key, so an attacker can test a single guess of
+/// the secret per exchange. Keys on the curve's twist are the exception,
+/// which lets an eavesdropper rule out about half of the guesses per exchange;
+/// a secret must therefore only be used for one exchange.
+pub struct KeyMask {
+    initiator: [u8; PUBLIC_KEY_LEN],
+    responder: [u8; PUBLIC_KEY_LEN],
+}
+
+impl KeyMask {
+    pub fn new(secret: &[u8]) -> Result<Self> {
+        let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, KEY_MASK_SALT).extract(secret);
+        let expand = |info: &[u8]| {
+            let mut mask = [0u8; PUBLIC_KEY_LEN];
+            prk.expand(&[info], OutputLen(mask.len()))
+                .and_then(|okm| okm.fill(&mut mask))
+                .map_err(|_| TsunaguError::Encryption("Failed to derive key mask".into()))?;
+            Ok::<_, TsunaguError>(mask)
+        };
+        Ok(Self {
+            initiator: expand(INITIATOR_KEY_MASK)?,
+            responder: expand(RESPONDER_KEY_MASK)?,
+        })
+    }
+
+    /// Mask the public key of the side with `role`
+    ///
+    /// The top bit of an X25519 key is always clear, it is set at random so
+    /// it does not give away the same bit of the mask every time.
+    pub fn mask(&self, public_key: &[u8], role: SessionRole) -> Result<Vec<u8>> {
+        let mut random = [0u8; 1];
+        rand::SystemRandom::new()
+            .fill(&mut random)
+            .map_err(|e| TsunaguError::Encryption(e.to_string()))?;
+        let mut masked = self.apply(public_key, role)?;
+        masked[PUBLIC_KEY_LEN - 1] = (masked[PUBLIC_KEY_LEN - 1] & 0x7f) | (random[0] & 0x80);
+        Ok(masked)
+    }
+
+    /// Recover the public key the side with `role` masked
+    pub fn unmask(&self, masked: &[u8], role: SessionRole) -> Result<Vec<u8>> {
+        let mut public_key = self.apply(masked, role)?;
+        public_key[PUBLIC_KEY_LEN - 1] &= 0x7f;
+        Ok(public_key)
+    }
+
+    fn apply(&self, key: &[u8], role: SessionRole) -> Result<Vec<u8>> {
+        if key.len() != PUBLIC_KEY_LEN {
+            return Err(TsunaguError::Encryption(format!(
+                "Invalid public key length: {}",
+                key.len()
+            )));
+        }
+        let mask = match role {
+            SessionRole::Initiator => &self.initiator,
+            SessionRole::Responder => &self.responder,
+        };
+        Ok(key.iter().zip(mask).map(|(k, m)| k ^ m).collect())
+    }
+}
+
+struct OutputLen(usize);
+
+impl hkdf::KeyType for OutputLen {
+    fn len(&self) -> usize {
+        self.0
     }
 }
 
+fn derive_key(prk: &hkdf::Prk, info: &[u8]) -> Result<aead::LessSafeKey> {
+    let info = [info];
+    let okm = prk
+        .expand(&info, &aead::AES_256_GCM)
+        .map_err(|_| TsunaguError::Encryption("Key derivation failed".into()))?;
+    Ok(aead::LessSafeKey::new(aead::UnboundKey::from(okm)))
+}
+
+fn counter_nonce(counter: u64) -> aead::Nonce {
+    let mut nonce = [0u8; NONCE_LEN];
+    nonce[NONCE_LEN - COUNTER_LEN..].copy_from_slice(&counter.to_be_bytes());
+    aead::Nonce::assume_unique_for_key(nonce)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -84,6 +360,14 @@ mod tests {
         assert_eq!(decrypted, original_data);
     }
 
+    #[test]
+    fn test_unique_nonces() {
+        let encryption = Encryption::new();
+        let first = encryption.encrypt(b"same").unwrap();
+        let second = encryption.encrypt(b"same").unwrap();
+        assert_ne!(first, second);
+    }
+
     #[test]
     fn test_base64_key() {
         let encryption = Encryption::new();
@@ -103,4 +387,89 @@ mod tests {
         let result = Encryption::from_base64_key("invalid_base64");
         assert!(result.is_err());
     }
+
+    fn session_pair() -> (SessionCipher, SessionCipher) {
+        let initiator = KeyExchange::new().unwrap();
+        let responder = KeyExchange::new().unwrap();
+        let initiator_public = initiator.public_key().to_vec();
+        let responder_public = responder.public_key().to_vec();
+
+        (
+            initiator
+                .finish(&responder_public, SessionRole::Initiator)
+                .unwrap(),
+            responder
+                .finish(&initiator_public, SessionRole::Responder)
+                .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_session_roundtrip() {
+        let (mut initiator, mut responder) = session_pair();
+        assert_eq!(initiator.session_id(), responder.session_id());
+
+        for i in 0..3 {
+            let message = format!("message {}", i);
+            let sealed = initiator.seal(message.as_bytes()).unwrap();
+            assert_eq!(responder.open(&sealed).unwrap(), message.as_bytes());
+
+            let sealed = responder.seal(message.as_bytes()).unwrap();
+            assert_eq!(initiator.open(&sealed).unwrap(), message.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_session_directional_keys() {
+        let (mut initiator, _responder) = session_pair();
+        let sealed = initiator.seal(b"hello").unwrap();
+        // Our own messages cannot be reflected back at us
+        assert!(initiator.open(&sealed).is_err());
+    }
+
+    #[test]
+    fn test_session_rejects_replay_and_reordering() {
+        let (mut initiator, mut responder) = session_pair();
+        let first = initiator.seal(b"first").unwrap();
+        let second = initiator.seal(b"second").unwrap();
+
+        assert!(responder.open(&second).is_err());
+        responder.open(&first).unwrap();
+        assert!(responder.open(&first).is_err());
+        responder.open(&second).unwrap();
+    }
+
+    #[test]
+    fn test_key_mask() {
+        let exchange = KeyExchange::new().unwrap();
+        let mask = KeyMask::new(b"secret").unwrap();
+        let masked = mask
+            .mask(exchange.public_key(), SessionRole::Initiator)
+            .unwrap();
+        assert_ne!(masked, exchange.public_key());
+        assert_eq!(
+            mask.unmask(&masked, SessionRole::Initiator).unwrap(),
+            exchange.public_key()
+        );
+        // Each direction and each secret masks differently
+        assert_ne!(
+            mask.unmask(&masked, SessionRole::Responder).unwrap(),
+            exchange.public_key()
+        );
+        let other = KeyMask::new(b"other secret").unwrap();
+        assert_ne!(
+            other.unmask(&masked, SessionRole::Initiator).unwrap(),
+            exchange.public_key()
+        );
+        assert!(mask.unmask(&masked[1..], SessionRole::Initiator).is_err());
+    }
+
+    #[test]
+    fn test_session_rejects_tampering() {
+        let (mut initiator, mut responder) = session_pair();
+        let mut sealed = initiator.seal(b"hello").unwrap();
+        let last = sealed.len() - 1;
+        sealed[last] ^= 1;
+        assert!(responder.open(&sealed).is_err());
+    }
 }
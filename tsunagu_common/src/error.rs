@@ -8,19 +8,38 @@ pub enum TsunaguError {
     Io(#[from] std::io::Error),
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
+    #[error("Config error: {0}")]
+    Config(String),
     #[error("Network error: {0}")]
     Network(String),
     #[error("Encryption error: {0}")]
     Encryption(String),
     #[error("Transfer error: {0}")]
     Transfer(String),
+    #[error("Transfer rejected: {0}")]
+    Rejected(String),
+    #[error("Transfer cancelled: {0}")]
+    Cancelled(String),
+    #[error("Integrity check failed for {file}: expected hash {expected}, got {actual}")]
+    IntegrityMismatch {
+        file: String,
+        expected: String,
+        actual: String,
+    },
     #[error("Device error: {0}")]
     Device(String),
+    #[error("Identity of {name} has changed (paired as {expected}, now {actual}), possible impersonation")]
+    IdentityChanged {
+        name: String,
+        expected: String,
+        actual: String,
+    },
     #[error("Discovery error: {0}")]
     Discovery(String),
+    #[error("Invalid share code: {0}")]
+    InvalidCode(String),
     #[error("mDNS error: {0}")]
     Mdns(#[from] mdns_sd::Error),
     #[error("Address parse error: {0}")]
     AddrParse(#[from] AddrParseError),
 }
-
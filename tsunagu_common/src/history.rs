@@ -0,0 +1,365 @@
+use crate::error::TsunaguError;
+use crate::models::{DeviceInfo, TransferInfo, TransferStatus};
+use crate::paths;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+/// File in the data dir holding the history, one JSON entry per line
+pub const HISTORY_FILE: &str = "history.jsonl";
+
+/// Which way a transfer went, seen from this device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+/// A finished transfer as kept in the history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    transfer: TransferInfo,
+    direction: Direction,
+    /// Seconds since the Unix epoch
+    started_at: u64,
+    /// Seconds since the Unix epoch
+    finished_at: u64,
+    duration_ms: u64,
+    /// Where each file was read from or saved to, `None` if it never arrived
+    paths: Vec<Option<PathBuf>>,
+}
+
+impl HistoryEntry {
+    pub(crate) fn new(
+        transfer: TransferInfo,
+        direction: Direction,
+        started_at: SystemTime,
+        paths: Vec<Option<PathBuf>>,
+    ) -> Self {
+        let finished_at = SystemTime::now();
+        Self {
+            transfer,
+            direction,
+            started_at: unix_seconds(started_at),
+            finished_at: unix_seconds(finished_at),
+            duration_ms: finished_at
+                .duration_since(started_at)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            paths,
+        }
+    }
+
+    /// Final state of the transfer, including the file hashes
+    pub fn transfer(&self) -> &TransferInfo {
+        &self.transfer
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// The other side of the transfer
+    pub fn peer(&self) -> &DeviceInfo {
+        match self.direction {
+            Direction::Sent => self.transfer.receiver(),
+            Direction::Received => self.transfer.sender(),
+        }
+    }
+
+    pub fn status(&self) -> &TransferStatus {
+        self.transfer.status()
+    }
+
+    pub fn started_at(&self) -> u64 {
+        self.started_at
+    }
+
+    pub fn finished_at(&self) -> u64 {
+        self.finished_at
+    }
+
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.duration_ms)
+    }
+
+    /// Local path of the file at `index`, see [`HistoryEntry::paths`]
+    pub fn path(&self, index: usize) -> Option<&Path> {
+        self.paths.get(index)?.as_deref()
+    }
+
+    pub fn paths(&self) -> &[Option<PathBuf>] {
+        &self.paths
+    }
+}
+
+/// Final statuses a history query can filter on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusFilter {
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl StatusFilter {
+    pub fn matches(&self, status: &TransferStatus) -> bool {
+        matches!(
+            (self, status),
+            (StatusFilter::Completed, TransferStatus::Completed)
+                | (StatusFilter::Failed, TransferStatus::Failed(_))
+                | (StatusFilter::Cancelled, TransferStatus::Cancelled)
+        )
+    }
+}
+
+impl FromStr for StatusFilter {
+    type Err = TsunaguError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "completed" => Ok(StatusFilter::Completed),
+            "failed" => Ok(StatusFilter::Failed),
+            "cancelled" | "canceled" => Ok(StatusFilter::Cancelled),
+            other => Err(TsunaguError::Config(format!(
+                "Unknown status {:?}, expected completed, failed or cancelled",
+                other
+            ))),
+        }
+    }
+}
+
+/// Which history entries to return, every field left empty matches all
+#[derive(Debug, Clone, Default)]
+pub struct HistoryFilter {
+    /// Peer name, id or ip, as accepted by [`DeviceInfo::matches`]
+    pub peer: Option<String>,
+    pub status: Option<StatusFilter>,
+    pub direction: Option<Direction>,
+    /// Finished at or after, in seconds since the Unix epoch
+    pub since: Option<u64>,
+    /// Finished before, in seconds since the Unix epoch
+    pub until: Option<u64>,
+    /// Return at most this many of the newest entries
+    pub limit: Option<usize>,
+}
+
+impl HistoryFilter {
+    pub fn matches(&self, entry: &HistoryEntry) -> bool {
+        self.peer
+            .as_deref()
+            .is_none_or(|peer| entry.peer().matches(peer))
+            && self
+                .status
+                .is_none_or(|status| status.matches(entry.status()))
+            && self
+                .direction
+                .is_none_or(|direction| direction == entry.direction)
+            && self.since.is_none_or(|since| entry.finished_at >= since)
+            && self.until.is_none_or(|until| entry.finished_at < until)
+    }
+}
+
+/// Append-only log of finished transfers
+#[derive(Debug, Clone)]
+pub struct HistoryStore {
+    path: PathBuf,
+}
+
+impl HistoryStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// The store in the default data dir
+    pub fn open_default() -> Result<Self> {
+        Ok(Self::new(paths::data_dir()?.join(HISTORY_FILE)))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Add `entry` at the end of the log
+    pub fn append(&self, entry: &HistoryEntry) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&self.path)?;
+
+        let mut line = Vec::new();
+        // Start on a fresh line if the last write was cut short
+        if file.seek(SeekFrom::End(0))? > 0 {
+            let mut last = [0u8];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                line.push(b'\n');
+            }
+        }
+        serde_json::to_writer(&mut line, entry)?;
+        line.push(b'\n');
+        // A single write keeps lines of concurrent writers apart
+        file.write_all(&line)?;
+        Ok(())
+    }
+
+    /// Entries matching `filter`, newest first
+    ///
+    /// Lines that cannot be parsed, e.g. cut short by a crash, are skipped.
+    pub fn query(&self, filter: &HistoryFilter) -> Result<Vec<HistoryEntry>> {
+        let file = match fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut entries = Vec::new();
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str::<HistoryEntry>(&line) {
+                Ok(entry) if filter.matches(&entry) => entries.push(entry),
+                Ok(_) => {}
+                Err(e) => warn!(
+                    "Skipping line {} of {}: {}",
+                    number + 1,
+                    self.path.display(),
+                    e
+                ),
+            }
+        }
+        entries.reverse();
+        if let Some(limit) = filter.limit {
+            entries.truncate(limit);
+        }
+        Ok(entries)
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::FileInfo;
+
+    fn entry(peer: &str, direction: Direction, status: TransferStatus) -> HistoryEntry {
+        let device = |name: &str, ip: &str| {
+            DeviceInfo::new(
+                name.into(),
+                "model".into(),
+                [ip.parse().unwrap()],
+                7878,
+                "linux".into(),
+                "1.0".into(),
+            )
+        };
+        let local = device("local", "10.0.0.1");
+        let peer = device(peer, "10.0.0.2");
+        let (sender, receiver) = match direction {
+            Direction::Sent => (local, peer),
+            Direction::Received => (peer, local),
+        };
+        let file = FileInfo::new("a.txt".into(), 5, "text/plain".into(), 0);
+        let mut transfer = TransferInfo::new(sender, receiver, vec![file]);
+        transfer.set_status(status);
+        HistoryEntry::new(
+            transfer,
+            direction,
+            SystemTime::now() - Duration::from_secs(2),
+            vec![Some(PathBuf::from("/tmp/a.txt"))],
+        )
+    }
+
+    #[test]
+    fn test_append_and_query() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = HistoryStore::new(dir.path().join("nested").join(HISTORY_FILE));
+        assert!(store.query(&HistoryFilter::default()).unwrap().is_empty());
+
+        let first = entry("laptop", Direction::Sent, TransferStatus::Completed);
+        let second = entry("phone", Direction::Received, TransferStatus::Cancelled);
+        let third = entry(
+            "laptop",
+            Direction::Received,
+            TransferStatus::Failed("lost".into()),
+        );
+        for entry in [&first, &second, &third] {
+            store.append(entry).unwrap();
+        }
+        // A torn write must not hide the rest of the history
+        OpenOptions::new()
+            .append(true)
+            .open(store.path())
+            .unwrap()
+            .write_all(b"{\"transfer\":")
+            .unwrap();
+        let fourth = entry("tablet", Direction::Sent, TransferStatus::Completed);
+        store.append(&fourth).unwrap();
+
+        let ids = |filter: HistoryFilter| -> Vec<String> {
+            store
+                .query(&filter)
+                .unwrap()
+                .iter()
+                .map(|e| e.transfer().id().to_string())
+                .collect()
+        };
+        assert_eq!(
+            ids(HistoryFilter::default()),
+            [
+                fourth.transfer().id(),
+                third.transfer().id(),
+                second.transfer().id(),
+                first.transfer().id()
+            ]
+        );
+        let laptop = HistoryFilter {
+            peer: Some("laptop".into()),
+            ..HistoryFilter::default()
+        };
+        assert_eq!(ids(laptop), [third.transfer().id(), first.transfer().id()]);
+        let cancelled = HistoryFilter {
+            status: Some("cancelled".parse().unwrap()),
+            ..HistoryFilter::default()
+        };
+        assert_eq!(ids(cancelled), [second.transfer().id()]);
+        let sent = HistoryFilter {
+            direction: Some(Direction::Sent),
+            ..HistoryFilter::default()
+        };
+        assert_eq!(ids(sent), [fourth.transfer().id(), first.transfer().id()]);
+        let future = HistoryFilter {
+            since: Some(first.finished_at() + 3600),
+            ..HistoryFilter::default()
+        };
+        assert!(ids(future).is_empty());
+        let limited = HistoryFilter {
+            limit: Some(1),
+            ..HistoryFilter::default()
+        };
+        assert_eq!(ids(limited), [fourth.transfer().id()]);
+
+        let loaded = &store.query(&HistoryFilter::default()).unwrap()[3];
+        assert_eq!(loaded.peer().name(), "laptop");
+        assert_eq!(loaded.path(0), Some(Path::new("/tmp/a.txt")));
+        assert!(loaded.duration() >= Duration::from_secs(2));
+        assert!("paused".parse::<StatusFilter>().is_err());
+    }
+}
@@ -0,0 +1,194 @@
+use crate::error::TsunaguError;
+use crate::Result;
+use ring::{
+    digest,
+    rand::SystemRandom,
+    signature::{self, Ed25519KeyPair, KeyPair},
+};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+/// File in the data dir holding the device key
+pub const IDENTITY_FILE: &str = "identity.pk8";
+
+/// Number of public key digest bytes used for the device id
+const FINGERPRINT_LEN: usize = 16;
+
+/// Long-lived Ed25519 key pair identifying this device
+///
+/// The device id is the fingerprint of the public key, so it stays the same
+/// across restarts and is the same on every peer that sees the device.
+pub struct DeviceIdentity {
+    key_pair: Ed25519KeyPair,
+}
+
+impl DeviceIdentity {
+    /// Generate a new identity that is not persisted
+    pub fn generate() -> Result<Self> {
+        let pkcs8 = generate_pkcs8()?;
+        Self::from_pkcs8(&pkcs8)
+    }
+
+    /// Load the identity from the default location, creating it if missing
+    pub fn load_or_create_default() -> Result<Self> {
+        Self::load_or_create(&crate::paths::data_dir()?.join(IDENTITY_FILE))
+    }
+
+    /// Load the identity stored at `path`, creating it if missing
+    pub fn load_or_create(path: &Path) -> Result<Self> {
+        match fs::read(path) {
+            Ok(pkcs8) => Self::from_pkcs8(&pkcs8),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::create(path),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn create(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let pkcs8 = generate_pkcs8()?;
+        let tmp_path = temp_path(path);
+        write_private(&tmp_path, &pkcs8)?;
+
+        // Linking fails if another process created the identity first, in
+        // which case theirs wins
+        let linked = fs::hard_link(&tmp_path, path);
+        fs::remove_file(&tmp_path).ok();
+        match linked {
+            Ok(()) => {
+                let identity = Self::from_pkcs8(&pkcs8)?;
+                info!(
+                    "Created device identity {} at {}",
+                    identity.fingerprint(),
+                    path.display()
+                );
+                Ok(identity)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                Self::from_pkcs8(&fs::read(path)?)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn from_pkcs8(pkcs8: &[u8]) -> Result<Self> {
+        let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8)
+            .map_err(|e| TsunaguError::Encryption(format!("Invalid identity key: {}", e)))?;
+        Ok(Self { key_pair })
+    }
+
+    pub fn public_key(&self) -> &[u8] {
+        self.key_pair.public_key().as_ref()
+    }
+
+    /// Device id derived from the public key
+    pub fn fingerprint(&self) -> String {
+        fingerprint(self.public_key())
+    }
+
+    /// Sign `message` with the identity key
+    pub fn sign(&self, message: &[u8]) -> Vec<u8> {
+        self.key_pair.sign(message).as_ref().to_vec()
+    }
+}
+
+/// Device id for an identity public key: hex of the truncated SHA-256 digest
+pub fn fingerprint(public_key: &[u8]) -> String {
+    digest::digest(&digest::SHA256, public_key).as_ref()[..FINGERPRINT_LEN]
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Whether `text` looks like a [`fingerprint`]
+pub fn is_fingerprint(text: &str) -> bool {
+    text.len() == FINGERPRINT_LEN * 2
+        && text
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+/// Verify a signature made with [`DeviceIdentity::sign`]
+pub fn verify(public_key: &[u8], message: &[u8], signature_bytes: &[u8]) -> Result<()> {
+    signature::UnparsedPublicKey::new(&signature::ED25519, public_key)
+        .verify(message, signature_bytes)
+        .map_err(|_| TsunaguError::Encryption("Invalid identity signature".into()))
+}
+
+fn generate_pkcs8() -> Result<Vec<u8>> {
+    Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+        .map(|doc| doc.as_ref().to_vec())
+        .map_err(|e| TsunaguError::Encryption(format!("Failed to generate identity: {}", e)))
+}
+
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}.tmp", uuid::Uuid::new_v4()));
+    path.with_file_name(name)
+}
+
+/// Write a file only readable by the current user
+fn write_private(path: &Path, data: &[u8]) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identity_is_persistent() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join(IDENTITY_FILE);
+
+        let first = DeviceIdentity::load_or_create(&path).unwrap();
+        let second = DeviceIdentity::load_or_create(&path).unwrap();
+        assert_eq!(first.public_key(), second.public_key());
+        assert_eq!(first.fingerprint(), second.fingerprint());
+        assert_eq!(first.fingerprint().len(), FINGERPRINT_LEN * 2);
+        assert!(is_fingerprint(&first.fingerprint()));
+        assert!(!is_fingerprint(&first.fingerprint().to_uppercase()));
+        assert!(!is_fingerprint("Laptop"));
+    }
+
+    #[test]
+    fn test_distinct_identities() {
+        let first = DeviceIdentity::generate().unwrap();
+        let second = DeviceIdentity::generate().unwrap();
+        assert_ne!(first.fingerprint(), second.fingerprint());
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let identity = DeviceIdentity::generate().unwrap();
+        let signature = identity.sign(b"message");
+
+        verify(identity.public_key(), b"message", &signature).unwrap();
+        assert!(verify(identity.public_key(), b"other message", &signature).is_err());
+        let other = DeviceIdentity::generate().unwrap();
+        assert!(verify(other.public_key(), b"message", &signature).is_err());
+    }
+
+    #[test]
+    fn test_corrupt_identity_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(IDENTITY_FILE);
+        fs::write(&path, b"not a key").unwrap();
+        assert!(DeviceIdentity::load_or_create(&path).is_err());
+    }
+}
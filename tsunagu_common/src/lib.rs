@@ -1,10 +1,18 @@
+pub mod config;
 pub mod device;
 pub mod discovery;
-pub mod transfer;
 pub mod encryption;
 pub mod error;
+pub mod history;
+pub mod identity;
 pub mod models;
-pub mod config;
+pub mod network;
+pub mod pairing;
+pub mod paths;
+pub mod peers;
+pub mod share_code;
+pub mod transfer;
+pub mod trust;
 
 pub use error::TsunaguError;
 pub type Result<T> = std::result::Result<T, TsunaguError>;
@@ -1,21 +1,34 @@
-use serde::{Deserialize, Serialize};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeSet;
+use std::net::{IpAddr, SocketAddr};
+
+use crate::identity;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct DeviceInfo {
     id: String,
     name: String,
     model: String,
-    ip: String,
+    /// Every address the device may be reachable at, e.g. Wi-Fi and Ethernet
+    #[serde(alias = "ip", deserialize_with = "deserialize_addresses")]
+    addresses: BTreeSet<IpAddr>,
     port: u16,
     os: String,
     version: String,
+    /// Base64 encoded identity public key, absent for peers without an identity
+    #[serde(default)]
+    public_key: Option<String>,
+    /// When discovery last heard from the device, seconds since the Unix epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_seen: Option<u64>,
 }
 
 impl DeviceInfo {
     pub fn new(
         name: String,
         model: String,
-        ip: String,
+        addresses: impl IntoIterator<Item = IpAddr>,
         port: u16,
         os: String,
         version: String,
@@ -28,22 +41,37 @@ impl DeviceInfo {
             id: uuid::Uuid::new_v4().to_string(),
             name,
             model,
-            ip,
+            addresses: addresses.into_iter().collect(),
             port,
             os,
             version,
+            public_key: None,
+            last_seen: None,
         }
     }
 
+    /// Attach the device's identity key, the id becomes its fingerprint
+    pub fn with_public_key(mut self, public_key: &[u8]) -> Self {
+        self.id = identity::fingerprint(public_key);
+        self.public_key = Some(STANDARD.encode(public_key));
+        self
+    }
+
+    /// Replace the generated id, for peers without an identity key
+    pub fn with_id(mut self, id: String) -> Self {
+        self.id = id;
+        self
+    }
+
     pub fn new_from_mdns(
         name: String,
         model: String,
-        ip: Option<String>,
+        ip: Option<IpAddr>,
         port: u16,
         os: String,
-        version: String
+        version: String,
     ) -> Option<Self> {
-        ip.map(|ip_str| Self::new(name, model, ip_str, port, os, version))
+        ip.map(|ip| Self::new(name, model, [ip], port, os, version))
     }
 
     pub fn id(&self) -> &str {
@@ -54,12 +82,29 @@ impl DeviceInfo {
         &self.name
     }
 
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+
     pub fn model(&self) -> &str {
         &self.model
     }
 
-    pub fn ip(&self) -> &str {
-        &self.ip
+    pub fn addresses(&self) -> &BTreeSet<IpAddr> {
+        &self.addresses
+    }
+
+    pub fn set_addresses(&mut self, addresses: impl IntoIterator<Item = IpAddr>) {
+        self.addresses = addresses.into_iter().collect();
+    }
+
+    /// The addresses separated by commas, for display
+    pub fn addresses_text(&self) -> String {
+        self.addresses
+            .iter()
+            .map(IpAddr::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
     }
 
     pub fn port(&self) -> u16 {
@@ -70,6 +115,28 @@ impl DeviceInfo {
         self.port = port;
     }
 
+    /// Identity public key, if the device has one
+    pub fn public_key(&self) -> Option<Vec<u8>> {
+        self.public_key
+            .as_deref()
+            .and_then(|key| STANDARD.decode(key).ok())
+    }
+
+    /// Whether the id is the fingerprint of the attached public key
+    pub fn has_verified_id(&self) -> bool {
+        self.public_key()
+            .is_some_and(|key| identity::fingerprint(&key) == self.id)
+    }
+
+    /// When discovery last heard from the device, `None` if it was not discovered
+    pub fn last_seen(&self) -> Option<u64> {
+        self.last_seen
+    }
+
+    pub(crate) fn set_last_seen(&mut self, last_seen: Option<u64>) {
+        self.last_seen = last_seen;
+    }
+
     pub fn os(&self) -> &str {
         &self.os
     }
@@ -77,4 +144,111 @@ impl DeviceInfo {
     pub fn version(&self) -> &str {
         &self.version
     }
+
+    /// Whether `query` refers to this device, by id, name, any of its
+    /// addresses or address and port
+    ///
+    /// Names are compared case-insensitively and without the mDNS
+    /// `.local.` suffix.
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.trim();
+        if query == self.id || normalize_name(query) == normalize_name(&self.name) {
+            return true;
+        }
+        if let Ok(ip) = query.parse::<IpAddr>() {
+            return self.addresses.contains(&ip);
+        }
+        query
+            .parse::<SocketAddr>()
+            .is_ok_and(|addr| addr.port() == self.port && self.addresses.contains(&addr.ip()))
+    }
+}
+
+/// Accept the single `ip` string written by earlier versions as well
+fn deserialize_addresses<'de, D>(deserializer: D) -> Result<BTreeSet<IpAddr>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Addresses {
+        Many(BTreeSet<IpAddr>),
+        One(String),
+    }
+
+    Ok(match Addresses::deserialize(deserializer)? {
+        Addresses::Many(addresses) => addresses,
+        // Unparsable, e.g. empty, leaves the device without an address
+        Addresses::One(ip) => ip.parse().into_iter().collect(),
+    })
+}
+
+fn normalize_name(name: &str) -> String {
+    let name = name.trim_end_matches('.');
+    name.strip_suffix(".local").unwrap_or(name).to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let device = DeviceInfo::new(
+            "MyLaptop.local.".to_string(),
+            "Test Model".to_string(),
+            ["192.168.1.10".parse().unwrap(), "fd00::10".parse().unwrap()],
+            5354,
+            "TestOS".to_string(),
+            "1.0.0".to_string(),
+        );
+
+        assert!(device.matches(device.id()));
+        assert!(device.matches("mylaptop"));
+        assert!(device.matches("MyLaptop.local"));
+        assert!(device.matches("192.168.1.10"));
+        assert!(device.matches("192.168.1.10:5354"));
+        assert!(device.matches("fd00::10"));
+        assert!(device.matches("[fd00::10]:5354"));
+        assert!(!device.matches("192.168.1.10:1234"));
+        assert!(!device.matches("192.168.1.11"));
+        assert!(!device.matches("OtherLaptop"));
+    }
+
+    #[test]
+    fn test_deserialize_single_ip() {
+        let json = r#"{"id":"a","name":"Old","model":"m","ip":"192.168.1.10",
+            "port":5354,"os":"o","version":"v","public_key":null}"#;
+        let device: DeviceInfo = serde_json::from_str(json).unwrap();
+        let expected: IpAddr = "192.168.1.10".parse().unwrap();
+        assert_eq!(
+            device.addresses().iter().collect::<Vec<_>>(),
+            vec![&expected]
+        );
+
+        let text = serde_json::to_string(&device).unwrap();
+        assert_eq!(serde_json::from_str::<DeviceInfo>(&text).unwrap(), device);
+    }
+
+    #[test]
+    fn test_with_public_key() {
+        let identity = crate::identity::DeviceIdentity::generate().unwrap();
+        let device = DeviceInfo::new(
+            "MyLaptop".to_string(),
+            "Test Model".to_string(),
+            ["192.168.1.10".parse().unwrap()],
+            5354,
+            "TestOS".to_string(),
+            "1.0.0".to_string(),
+        );
+        assert!(!device.has_verified_id());
+
+        let device = device.with_public_key(identity.public_key());
+        assert_eq!(device.id(), identity.fingerprint());
+        assert_eq!(device.public_key().unwrap(), identity.public_key());
+        assert!(device.has_verified_id());
+
+        let spoofed = device.with_id("someone-else".to_string());
+        assert!(!spoofed.has_verified_id());
+    }
 }
@@ -1,4 +1,9 @@
 use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::error::TsunaguError;
+use crate::Result;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileInfo {
@@ -6,6 +11,16 @@ pub struct FileInfo {
     size: u64,
     mime_type: String,
     last_modified: u64,
+    /// Path below the receiver's save directory with `/` separators, the
+    /// name when absent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    relative_path: Option<String>,
+    /// Hex encoded SHA-256 of the content, known once the file was sent
+    #[serde(default)]
+    hash: Option<String>,
+    /// Local path of the file on the sending side, never sent over the wire
+    #[serde(skip)]
+    source_path: Option<PathBuf>,
 }
 
 impl FileInfo {
@@ -15,8 +30,93 @@ impl FileInfo {
             size,
             mime_type,
             last_modified,
+            relative_path: None,
+            hash: None,
+            source_path: None,
+        }
+    }
+
+    /// Build a `FileInfo` from a file on disk
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let metadata = std::fs::metadata(path)?;
+        if !metadata.is_file() {
+            return Err(TsunaguError::Transfer(format!(
+                "Not a regular file: {}",
+                path.display()
+            )));
         }
+
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or_else(|| {
+                TsunaguError::Transfer(format!("Invalid file name: {}", path.display()))
+            })?
+            .to_string();
+        let mime_type = mime_guess::from_path(path)
+            .first_or_octet_stream()
+            .to_string();
+        let last_modified = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        Ok(Self {
+            name,
+            size: metadata.len(),
+            mime_type,
+            last_modified,
+            relative_path: None,
+            hash: None,
+            source_path: Some(path.to_path_buf()),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn mime_type(&self) -> &str {
+        &self.mime_type
+    }
+
+    pub fn last_modified(&self) -> u64 {
+        self.last_modified
+    }
+
+    /// Where the receiver puts the file, relative to its save directory
+    pub fn relative_path(&self) -> &str {
+        self.relative_path.as_deref().unwrap_or(&self.name)
+    }
+
+    /// Send the file as part of a directory tree, `path` uses `/` separators
+    pub fn with_relative_path(mut self, path: String) -> Self {
+        self.relative_path = Some(path);
+        self
     }
 
-    // Add getters for fields
+    pub fn hash(&self) -> Option<&str> {
+        self.hash.as_deref()
+    }
+
+    pub(crate) fn set_hash(&mut self, hash: String) {
+        self.hash = Some(hash);
+    }
+
+    pub fn source_path(&self) -> Option<&Path> {
+        self.source_path.as_deref()
+    }
+
+    /// Set where the file is read from when it is sent
+    pub fn with_source_path(mut self, path: PathBuf) -> Self {
+        self.source_path = Some(path);
+        self
+    }
 }
@@ -1,5 +1,5 @@
-use serde::{Deserialize, Serialize};
 use super::{DeviceInfo, FileInfo};
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransferInfo {
@@ -10,12 +10,14 @@ pub struct TransferInfo {
     status: TransferStatus,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum TransferStatus {
     Pending,
     InProgress(f32), // Progress percentage
     Completed,
     Failed(String),
+    /// Stopped on purpose by either side
+    Cancelled,
 }
 
 impl TransferInfo {
@@ -29,5 +31,42 @@ impl TransferInfo {
         }
     }
 
-    // Add getters for fields and methods to update status
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn sender(&self) -> &DeviceInfo {
+        &self.sender
+    }
+
+    pub fn receiver(&self) -> &DeviceInfo {
+        &self.receiver
+    }
+
+    pub fn files(&self) -> &[FileInfo] {
+        &self.files
+    }
+
+    pub(crate) fn set_file_hash(&mut self, index: usize, hash: String) {
+        if let Some(file) = self.files.get_mut(index) {
+            file.set_hash(hash);
+        }
+    }
+
+    pub(crate) fn set_files(&mut self, files: Vec<FileInfo>) {
+        self.files = files;
+    }
+
+    pub fn status(&self) -> &TransferStatus {
+        &self.status
+    }
+
+    pub fn set_status(&mut self, status: TransferStatus) {
+        self.status = status;
+    }
+
+    /// Total size of all files in bytes
+    pub fn total_size(&self) -> u64 {
+        self.files.iter().map(|f| f.size()).sum()
+    }
 }
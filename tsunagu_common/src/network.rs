@@ -0,0 +1,270 @@
+use crate::error::TsunaguError;
+use crate::models::DeviceInfo;
+use crate::Result;
+use if_addrs::IfAddr;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::task::JoinSet;
+use tracing::debug;
+
+/// How long an attempt may go unanswered before the next address is tried
+///
+/// The earlier attempts keep running, the first connection wins (RFC 8305).
+const ATTEMPT_DELAY: Duration = Duration::from_millis(250);
+
+/// An address of this host and the network it is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Subnet {
+    ip: IpAddr,
+    netmask: IpAddr,
+}
+
+impl Subnet {
+    /// Whether `ip` is on this network, and so reachable without a router
+    pub(crate) fn contains(&self, ip: IpAddr) -> bool {
+        match (self.ip, self.netmask, ip) {
+            (IpAddr::V4(own), IpAddr::V4(mask), IpAddr::V4(ip)) => {
+                u32::from(own) & u32::from(mask) == u32::from(ip) & u32::from(mask)
+            }
+            (IpAddr::V6(own), IpAddr::V6(mask), IpAddr::V6(ip)) => {
+                u128::from(own) & u128::from(mask) == u128::from(ip) & u128::from(mask)
+            }
+            _ => false,
+        }
+    }
+
+    /// The other IPv4 hosts on this network, for scanning
+    ///
+    /// Networks wider than a /`widest` are narrowed to the /`widest` around
+    /// this host. IPv6 networks are far too large to walk and give none, as
+    /// do loopback and link-local ones.
+    pub(crate) fn neighbours(&self, widest: u8) -> Vec<Ipv4Addr> {
+        let (IpAddr::V4(own), IpAddr::V4(mask)) = (self.ip, self.netmask) else {
+            return Vec::new();
+        };
+        if own.is_loopback() || own.is_link_local() || !(1..=32).contains(&widest) {
+            return Vec::new();
+        }
+        let mask = u32::from(mask) | (u32::MAX << (32 - widest));
+        let network = u32::from(own) & mask;
+        let broadcast = network | !mask;
+        // A /31 has no network and broadcast address (RFC 3021)
+        let hosts = if broadcast - network < 2 {
+            network..=broadcast
+        } else {
+            network + 1..=broadcast - 1
+        };
+        hosts.map(Ipv4Addr::from).filter(|ip| *ip != own).collect()
+    }
+}
+
+/// Addresses peers can reach this host at, one per usable interface
+///
+/// Falls back to the loopback addresses on a host without any network, so
+/// the device can at least be reached from itself.
+pub fn local_addresses() -> Result<Vec<IpAddr>> {
+    let subnets = local_subnets()?;
+    let usable: Vec<IpAddr> = subnets
+        .iter()
+        .map(|subnet| subnet.ip)
+        .filter(|ip| is_usable(*ip) && !ip.is_loopback())
+        .collect();
+    if !usable.is_empty() {
+        return Ok(usable);
+    }
+    Ok(subnets
+        .iter()
+        .map(|subnet| subnet.ip)
+        .filter(|ip| ip.is_loopback())
+        .collect())
+}
+
+/// Networks this host is on
+pub(crate) fn local_subnets() -> Result<Vec<Subnet>> {
+    let interfaces = if_addrs::get_if_addrs()
+        .map_err(|e| TsunaguError::Network(format!("Failed to list interfaces: {}", e)))?;
+    Ok(interfaces
+        .into_iter()
+        .map(|interface| match interface.addr {
+            IfAddr::V4(addr) => Subnet {
+                ip: addr.ip.into(),
+                netmask: addr.netmask.into(),
+            },
+            IfAddr::V6(addr) => Subnet {
+                ip: addr.ip.into(),
+                netmask: addr.netmask.into(),
+            },
+        })
+        .collect())
+}
+
+/// Whether `ip` can be connected to as it is
+///
+/// IPv6 link-local addresses are left out, they need the interface they
+/// belong to, which a plain address does not carry.
+pub(crate) fn is_usable(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => !ip.is_unspecified() && !ip.is_multicast() && !ip.is_broadcast(),
+        IpAddr::V6(ip) => {
+            !ip.is_unspecified() && !ip.is_multicast() && (ip.segments()[0] & 0xffc0) != 0xfe80
+        }
+    }
+}
+
+/// Order a peer's addresses for connecting
+///
+/// Addresses on one of our subnets come first. Within that and the rest,
+/// IPv6 and IPv4 take turns so a broken family does not hold up the other.
+pub(crate) fn connect_order(
+    addresses: impl IntoIterator<Item = IpAddr>,
+    subnets: &[Subnet],
+) -> Vec<IpAddr> {
+    let (local, remote): (Vec<IpAddr>, Vec<IpAddr>) = addresses
+        .into_iter()
+        .filter(|ip| is_usable(*ip))
+        .partition(|ip| subnets.iter().any(|subnet| subnet.contains(*ip)));
+    let mut ordered = interleave_families(local);
+    ordered.extend(interleave_families(remote));
+    ordered
+}
+
+fn interleave_families(addresses: Vec<IpAddr>) -> Vec<IpAddr> {
+    let (v6, v4): (Vec<IpAddr>, Vec<IpAddr>) = addresses.into_iter().partition(IpAddr::is_ipv6);
+    let mut v6 = v6.into_iter();
+    let mut v4 = v4.into_iter();
+    let mut ordered = Vec::new();
+    loop {
+        match (v6.next(), v4.next()) {
+            (None, None) => return ordered,
+            (a, b) => ordered.extend(a.into_iter().chain(b)),
+        }
+    }
+}
+
+/// Connect to `device` on whichever of its addresses answers first
+pub(crate) async fn connect(device: &DeviceInfo, timeout: Duration) -> Result<TcpStream> {
+    // Without interface information every address is equally good
+    let subnets = local_subnets().unwrap_or_default();
+    let addresses: Vec<SocketAddr> = connect_order(device.addresses().iter().copied(), &subnets)
+        .into_iter()
+        .map(|ip| SocketAddr::new(ip, device.port()))
+        .collect();
+    if addresses.is_empty() {
+        return Err(TsunaguError::Network(format!(
+            "No usable address for {}",
+            device.name()
+        )));
+    }
+
+    tokio::time::timeout(timeout, connect_any(&addresses))
+        .await
+        .map_err(|_| TsunaguError::Network(format!("Connection to {} timed out", device.name())))?
+}
+
+/// Happy eyeballs: try the addresses in order, staggered by [`ATTEMPT_DELAY`]
+async fn connect_any(addresses: &[SocketAddr]) -> Result<TcpStream> {
+    let mut queue = addresses.iter().copied().peekable();
+    let mut attempts = JoinSet::new();
+    let mut last_error = None;
+    while queue.peek().is_some() || !attempts.is_empty() {
+        if let Some(addr) = queue.next() {
+            attempts.spawn(async move { (addr, TcpStream::connect(addr).await) });
+        }
+        let next_attempt = tokio::time::sleep(ATTEMPT_DELAY);
+        tokio::select! {
+            Some(finished) = attempts.join_next() => match finished {
+                // The attempts still running are dropped with the set
+                Ok((addr, Ok(stream))) => {
+                    debug!("Connected to {}", addr);
+                    return Ok(stream);
+                }
+                Ok((addr, Err(e))) => {
+                    debug!("Connecting to {} failed: {}", addr, e);
+                    last_error = Some(e);
+                }
+                Err(e) => last_error = Some(std::io::Error::other(e)),
+            },
+            _ = next_attempt, if queue.peek().is_some() => {}
+        }
+    }
+    Err(last_error
+        .map(Into::into)
+        .unwrap_or_else(|| TsunaguError::Network("No address to connect to".into())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv6Addr;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_connect_order() {
+        let subnet = Subnet {
+            ip: Ipv4Addr::new(192, 168, 1, 5).into(),
+            netmask: Ipv4Addr::new(255, 255, 255, 0).into(),
+        };
+        let vpn: IpAddr = Ipv4Addr::new(10, 8, 0, 2).into();
+        let lan: IpAddr = Ipv4Addr::new(192, 168, 1, 20).into();
+        let global: IpAddr = "2001:db8::20".parse().unwrap();
+        let link_local: IpAddr = "fe80::1".parse().unwrap();
+
+        let ordered = connect_order([vpn, link_local, global, lan], &[subnet]);
+        assert_eq!(ordered, vec![lan, global, vpn]);
+
+        let other_v6: IpAddr = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0x30).into();
+        let ordered = connect_order([vpn, global, other_v6], &[]);
+        assert_eq!(ordered, vec![global, vpn, other_v6]);
+    }
+
+    #[test]
+    fn test_neighbours() {
+        let subnet = Subnet {
+            ip: Ipv4Addr::new(192, 168, 1, 5).into(),
+            netmask: Ipv4Addr::new(255, 255, 255, 0).into(),
+        };
+        let hosts = subnet.neighbours(22);
+        assert_eq!(hosts.len(), 253);
+        assert_eq!(hosts[0], Ipv4Addr::new(192, 168, 1, 1));
+        assert_eq!(hosts[252], Ipv4Addr::new(192, 168, 1, 254));
+        assert!(!hosts.contains(&Ipv4Addr::new(192, 168, 1, 5)));
+
+        // A /16 is narrowed to the /24 around this host
+        let wide = Subnet {
+            ip: Ipv4Addr::new(10, 1, 2, 3).into(),
+            netmask: Ipv4Addr::new(255, 255, 0, 0).into(),
+        };
+        let hosts = wide.neighbours(24);
+        assert_eq!(hosts.first(), Some(&Ipv4Addr::new(10, 1, 2, 1)));
+        assert_eq!(hosts.last(), Some(&Ipv4Addr::new(10, 1, 2, 254)));
+
+        let point_to_point = Subnet {
+            ip: Ipv4Addr::new(10, 0, 0, 0).into(),
+            netmask: Ipv4Addr::new(255, 255, 255, 254).into(),
+        };
+        assert_eq!(
+            point_to_point.neighbours(24),
+            vec![Ipv4Addr::new(10, 0, 0, 1)]
+        );
+
+        let loopback = Subnet {
+            ip: Ipv4Addr::LOCALHOST.into(),
+            netmask: Ipv4Addr::new(255, 0, 0, 0).into(),
+        };
+        assert!(loopback.neighbours(24).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_connect_any_skips_dead_addresses() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        // Nothing listens on this loopback address, the connection is refused
+        let dead = SocketAddr::new(Ipv4Addr::new(127, 0, 0, 2).into(), port);
+        let live = SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port);
+
+        let stream = connect_any(&[dead, live]).await.unwrap();
+        assert_eq!(stream.peer_addr().unwrap(), live);
+        assert!(connect_any(&[dead]).await.is_err());
+    }
+}
@@ -0,0 +1,288 @@
+use crate::encryption::SessionRole;
+use crate::error::TsunaguError;
+use crate::identity::DeviceIdentity;
+use crate::models::DeviceInfo;
+use crate::network;
+use crate::transfer::protocol::{unexpected, Connection, Message};
+use crate::transfer::CONNECT_TIMEOUT;
+use crate::trust::TrustStore;
+use crate::Result;
+use async_trait::async_trait;
+use ring::digest;
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::RwLock;
+use tracing::info;
+
+/// Asks the user to compare the verification code shown on both devices
+#[async_trait]
+pub trait PairingPrompt: Send + Sync {
+    async fn confirm(&self, peer: &DeviceInfo, code: &str) -> bool;
+}
+
+/// Six digit code derived from both identity keys
+///
+/// Both devices compute the same code regardless of who initiated, and a
+/// man in the middle would have to present different keys, changing it.
+pub fn verification_code(local_key: &[u8], peer_key: &[u8]) -> String {
+    let (first, second) = if local_key <= peer_key {
+        (local_key, peer_key)
+    } else {
+        (peer_key, local_key)
+    };
+
+    let mut context = digest::Context::new(&digest::SHA256);
+    context.update(b"tsunagu pairing");
+    context.update(first);
+    context.update(second);
+    let digest = context.finish();
+
+    let mut value = [0u8; 8];
+    value.copy_from_slice(&digest.as_ref()[..8]);
+    let code = u64::from_be_bytes(value) % 1_000_000;
+    format!("{:03} {:03}", code / 1000, code % 1000)
+}
+
+/// Pairs devices and records them in the trust store
+pub struct Pairing {
+    local_device: DeviceInfo,
+    identity: Arc<DeviceIdentity>,
+    trust_store: Arc<RwLock<TrustStore>>,
+    prompt: Arc<dyn PairingPrompt>,
+}
+
+impl Pairing {
+    pub fn new(
+        local_device: DeviceInfo,
+        identity: Arc<DeviceIdentity>,
+        trust_store: Arc<RwLock<TrustStore>>,
+        prompt: Arc<dyn PairingPrompt>,
+    ) -> Self {
+        Self {
+            local_device,
+            identity,
+            trust_store,
+            prompt,
+        }
+    }
+
+    /// Update the device announced in the handshake
+    pub fn set_local_device(&mut self, device: DeviceInfo) {
+        self.local_device = device;
+    }
+
+    /// Pair with `peer`, returning the device as it identified itself
+    pub async fn pair(&self, peer: &DeviceInfo) -> Result<DeviceInfo> {
+        info!("Pairing with {} at {}", peer.name(), peer.addresses_text());
+
+        let mut conn = Connection::new(network::connect(peer, CONNECT_TIMEOUT).await?);
+        let peer = conn
+            .handshake(
+                &self.local_device,
+                Some(&self.identity),
+                SessionRole::Initiator,
+            )
+            .await?;
+        conn.send_message(&Message::PairRequest).await?;
+        self.confirm(&mut conn, &peer).await?;
+        Ok(peer)
+    }
+
+    /// Answer a `PairRequest` received on an already authenticated connection
+    pub(crate) async fn respond<S>(&self, conn: &mut Connection<S>, peer: &DeviceInfo) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        info!("Pairing requested by {}", peer.name());
+        self.confirm(conn, peer).await
+    }
+
+    async fn confirm<S>(&self, conn: &mut Connection<S>, peer: &DeviceInfo) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        let peer_key = match peer.public_key() {
+            Some(key) if peer.has_verified_id() => key,
+            _ => {
+                conn.send_message(&Message::PairConfirm { accepted: false })
+                    .await
+                    .ok();
+                return Err(TsunaguError::Device(format!(
+                    "{} has no verifiable identity",
+                    peer.name()
+                )));
+            }
+        };
+
+        // Never silently re-pair a device whose key changed
+        if let Err(e) = self.trust_store.read().await.check(peer) {
+            conn.send_message(&Message::PairConfirm { accepted: false })
+                .await
+                .ok();
+            return Err(e);
+        }
+
+        let code = verification_code(self.identity.public_key(), &peer_key);
+        let accepted = self.prompt.confirm(peer, &code).await;
+        conn.send_message(&Message::PairConfirm { accepted })
+            .await?;
+
+        let peer_accepted = match conn.recv_message().await? {
+            Message::PairConfirm { accepted } => accepted,
+            other => return Err(unexpected(&other)),
+        };
+        if !accepted {
+            return Err(TsunaguError::Rejected("Pairing declined".into()));
+        }
+        if !peer_accepted {
+            return Err(TsunaguError::Rejected(format!(
+                "Pairing declined by {}",
+                peer.name()
+            )));
+        }
+
+        let mut trust_store = self.trust_store.write().await;
+        trust_store.trust(peer)?;
+        trust_store.save()?;
+        info!("Paired with {} ({})", peer.name(), peer.id());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::trust::TrustLevel;
+    use std::net::Ipv4Addr;
+
+    struct FixedPrompt {
+        answer: bool,
+        codes: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl FixedPrompt {
+        fn new(answer: bool) -> Arc<Self> {
+            Arc::new(Self {
+                answer,
+                codes: std::sync::Mutex::new(Vec::new()),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl PairingPrompt for FixedPrompt {
+        async fn confirm(&self, _peer: &DeviceInfo, code: &str) -> bool {
+            self.codes.lock().unwrap().push(code.to_string());
+            self.answer
+        }
+    }
+
+    fn pairing(name: &str, prompt: Arc<FixedPrompt>) -> Pairing {
+        let identity = Arc::new(DeviceIdentity::generate().unwrap());
+        let device = DeviceInfo::new(
+            name.to_string(),
+            "Test Model".to_string(),
+            [Ipv4Addr::LOCALHOST.into()],
+            8000,
+            "TestOS".to_string(),
+            "1.0.0".to_string(),
+        )
+        .with_public_key(identity.public_key());
+        Pairing::new(
+            device,
+            identity,
+            Arc::new(RwLock::new(TrustStore::in_memory())),
+            prompt,
+        )
+    }
+
+    async fn run_pairing(left: &Pairing, right: &Pairing) -> (Result<()>, Result<()>) {
+        let (a, b) = tokio::io::duplex(4096);
+        let mut left_conn = Connection::new(a);
+        let mut right_conn = Connection::new(b);
+
+        let initiator = async {
+            let peer = left_conn
+                .handshake(
+                    &left.local_device,
+                    Some(&left.identity),
+                    SessionRole::Initiator,
+                )
+                .await?;
+            left_conn.send_message(&Message::PairRequest).await?;
+            left.confirm(&mut left_conn, &peer).await
+        };
+        let responder = async {
+            let peer = right_conn
+                .handshake(
+                    &right.local_device,
+                    Some(&right.identity),
+                    SessionRole::Responder,
+                )
+                .await?;
+            match right_conn.recv_message().await? {
+                Message::PairRequest => right.respond(&mut right_conn, &peer).await,
+                other => Err(unexpected(&other)),
+            }
+        };
+        tokio::join!(initiator, responder)
+    }
+
+    #[test]
+    fn test_verification_code_is_symmetric() {
+        let a = DeviceIdentity::generate().unwrap();
+        let b = DeviceIdentity::generate().unwrap();
+        let code = verification_code(a.public_key(), b.public_key());
+        assert_eq!(code, verification_code(b.public_key(), a.public_key()));
+        assert_eq!(code.len(), 7);
+
+        let c = DeviceIdentity::generate().unwrap();
+        assert_ne!(code, verification_code(a.public_key(), c.public_key()));
+    }
+
+    #[tokio::test]
+    async fn test_pairing_confirmed_on_both_ends() {
+        let left_prompt = FixedPrompt::new(true);
+        let right_prompt = FixedPrompt::new(true);
+        let left = pairing("Left", left_prompt.clone());
+        let right = pairing("Right", right_prompt.clone());
+
+        let (l, r) = run_pairing(&left, &right).await;
+        l.unwrap();
+        r.unwrap();
+
+        assert_eq!(
+            *left_prompt.codes.lock().unwrap(),
+            *right_prompt.codes.lock().unwrap()
+        );
+        assert_eq!(
+            left.trust_store
+                .read()
+                .await
+                .check(&right.local_device)
+                .unwrap(),
+            TrustLevel::Trusted
+        );
+        assert_eq!(
+            right
+                .trust_store
+                .read()
+                .await
+                .check(&left.local_device)
+                .unwrap(),
+            TrustLevel::Trusted
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pairing_declined_by_one_end() {
+        let left = pairing("Left", FixedPrompt::new(true));
+        let right = pairing("Right", FixedPrompt::new(false));
+
+        let (l, r) = run_pairing(&left, &right).await;
+        assert!(matches!(l, Err(TsunaguError::Rejected(_))));
+        assert!(matches!(r, Err(TsunaguError::Rejected(_))));
+        assert!(left.trust_store.read().await.devices().is_empty());
+        assert!(right.trust_store.read().await.devices().is_empty());
+    }
+}
@@ -0,0 +1,52 @@
+use crate::error::TsunaguError;
+use crate::Result;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Directory for persistent application data, e.g. the device identity
+///
+/// Can be overridden with the `TSUNAGU_DATA_DIR` environment variable.
+pub fn data_dir() -> Result<PathBuf> {
+    if let Some(dir) = std::env::var_os("TSUNAGU_DATA_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+    dirs::data_dir()
+        .map(|dir| dir.join("tsunagu"))
+        .ok_or_else(|| TsunaguError::Device("Failed to determine data directory".into()))
+}
+
+/// Directory for user editable settings
+///
+/// Can be overridden with the `TSUNAGU_CONFIG_DIR` environment variable.
+pub fn config_dir() -> Result<PathBuf> {
+    if let Some(dir) = std::env::var_os("TSUNAGU_CONFIG_DIR") {
+        return Ok(PathBuf::from(dir));
+    }
+    dirs::config_dir()
+        .map(|dir| dir.join("tsunagu"))
+        .ok_or_else(|| TsunaguError::Config("Failed to determine config directory".into()))
+}
+
+/// Replace the file at `path` so readers see either the old or the new content
+pub(crate) fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}.tmp", uuid::Uuid::new_v4()));
+    let tmp_path = path.with_file_name(name);
+
+    let written = File::create(&tmp_path).and_then(|mut file| {
+        file.write_all(data)?;
+        file.sync_all()
+    });
+    match written.and_then(|()| fs::rename(&tmp_path, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            fs::remove_file(&tmp_path).ok();
+            Err(e.into())
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use crate::config::Config;
+use crate::error::TsunaguError;
+use crate::identity;
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A peer added by address, for devices discovery cannot find
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ManualPeer {
+    /// `host:port`, the host a name or an IP address
+    address: String,
+    /// Device id the peer has to present, if set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fingerprint: Option<String>,
+    /// Seconds since the Unix epoch
+    added_at: u64,
+}
+
+impl ManualPeer {
+    /// A peer at `address`, `host:port` with IPv6 addresses in brackets
+    pub fn new(address: &str, fingerprint: Option<&str>) -> Result<Self> {
+        let address = address.trim();
+        check_address(address)?;
+        let fingerprint = fingerprint
+            .map(|fingerprint| {
+                let fingerprint = fingerprint.trim().to_ascii_lowercase();
+                if identity::is_fingerprint(&fingerprint) {
+                    Ok(fingerprint)
+                } else {
+                    Err(TsunaguError::Config(format!(
+                        "Invalid fingerprint {}, expected a device id",
+                        fingerprint
+                    )))
+                }
+            })
+            .transpose()?;
+        let added_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        Ok(Self {
+            address: address.to_string(),
+            fingerprint,
+            added_at,
+        })
+    }
+
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    pub fn fingerprint(&self) -> Option<&str> {
+        self.fingerprint.as_deref()
+    }
+
+    pub fn added_at(&self) -> u64 {
+        self.added_at
+    }
+}
+
+/// Peers the user added by address, persisted in the config file
+#[derive(Debug)]
+pub struct AddressBook {
+    path: Option<PathBuf>,
+    peers: Vec<ManualPeer>,
+}
+
+impl AddressBook {
+    /// An address book that only lives in memory
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            peers: Vec::new(),
+        }
+    }
+
+    /// Load the address book from the default config file
+    pub fn load_default() -> Result<Self> {
+        Self::load(&Config::default_path()?)
+    }
+
+    /// Load the peers of the config file at `path`
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            peers: Config::load(path)?.manual_peers,
+        })
+    }
+
+    /// Persist the address book, a no-op for in-memory ones
+    ///
+    /// The rest of the config is re-read first so concurrent edits to other
+    /// settings are kept.
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut config = Config::load(path)?;
+        config.manual_peers = self.peers.clone();
+        config.save(path)
+    }
+
+    pub fn peers(&self) -> &[ManualPeer] {
+        &self.peers
+    }
+
+    /// Add `peer`, replacing an earlier entry for the same address
+    pub fn add(&mut self, peer: ManualPeer) {
+        self.peers
+            .retain(|p| !p.address.eq_ignore_ascii_case(&peer.address));
+        self.peers.push(peer);
+    }
+
+    /// Remove the peer at `address`, returns whether anything was removed
+    pub fn remove(&mut self, address: &str) -> bool {
+        let before = self.peers.len();
+        self.peers
+            .retain(|p| !p.address.eq_ignore_ascii_case(address.trim()));
+        self.peers.len() != before
+    }
+}
+
+/// Check that `address` is a `host:port` that can be connected to
+fn check_address(address: &str) -> Result<()> {
+    let invalid = || {
+        TsunaguError::Config(format!(
+            "Invalid peer address {}, expected host:port",
+            address
+        ))
+    };
+    let (host, port) = address.rsplit_once(':').ok_or_else(invalid)?;
+    let port: u16 = port.parse().map_err(|_| invalid())?;
+    let host = match host.strip_prefix('[').and_then(|h| h.strip_suffix(']')) {
+        Some(v6) => {
+            v6.parse::<Ipv6Addr>().map_err(|_| invalid())?;
+            v6
+        }
+        // An IPv6 address needs brackets to tell it from the port
+        None if host.contains(':') => return Err(invalid()),
+        None => host,
+    };
+    if host.is_empty() || port == 0 || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_unspecified()) {
+        return Err(invalid());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::DeviceIdentity;
+
+    #[test]
+    fn test_peer_address() {
+        for valid in ["nas.lan:5354", "10.8.0.2:5354", "[2001:db8::2]:5354"] {
+            assert_eq!(ManualPeer::new(valid, None).unwrap().address(), valid);
+        }
+        for invalid in [
+            "nas.lan",
+            "nas.lan:0",
+            ":5354",
+            "2001:db8::2:5354",
+            "[nas]:5354",
+            "0.0.0.0:5354",
+        ] {
+            assert!(ManualPeer::new(invalid, None).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_peer_fingerprint() {
+        let id = DeviceIdentity::generate().unwrap().fingerprint();
+        let peer = ManualPeer::new("nas.lan:5354", Some(&id.to_uppercase())).unwrap();
+        assert_eq!(peer.fingerprint(), Some(id.as_str()));
+        assert!(ManualPeer::new("nas.lan:5354", Some("nas")).is_err());
+    }
+
+    #[test]
+    fn test_add_and_remove() {
+        let mut book = AddressBook::in_memory();
+        book.add(ManualPeer::new("nas.lan:5354", None).unwrap());
+        book.add(ManualPeer::new("10.8.0.2:5354", None).unwrap());
+        // Adding the same address again replaces it
+        let id = DeviceIdentity::generate().unwrap().fingerprint();
+        book.add(ManualPeer::new("NAS.lan:5354", Some(&id)).unwrap());
+        assert_eq!(book.peers().len(), 2);
+        assert_eq!(book.peers()[1].fingerprint(), Some(id.as_str()));
+
+        assert!(book.remove("nas.lan:5354"));
+        assert!(!book.remove("nas.lan:5354"));
+        assert_eq!(book.peers().len(), 1);
+    }
+
+    #[test]
+    fn test_persistence() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+
+        let mut book = AddressBook::load(&path).unwrap();
+        book.add(ManualPeer::new("nas.lan:5354", None).unwrap());
+        book.save().unwrap();
+
+        let reloaded = AddressBook::load(&path).unwrap();
+        assert_eq!(reloaded.peers(), book.peers());
+        assert_eq!(Config::load(&path).unwrap().manual_peers, book.peers());
+    }
+}
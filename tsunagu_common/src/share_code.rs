@@ -0,0 +1,266 @@
+use crate::encryption::KeyMask;
+use crate::error::TsunaguError;
+use crate::models::DeviceInfo;
+use crate::Result;
+use ring::digest;
+use ring::rand::{SecureRandom, SystemRandom};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr};
+
+/// Bytes of the one-time secret, a guess has to be right on the first try
+const SECRET_LEN: usize = 2;
+
+/// Addresses a code carries at most, each adds four words
+pub const MAX_ADDRESSES: usize = 2;
+
+/// A code for connecting to a receiver without discovery
+///
+/// The receiver shows the code, the sender types it in. It carries the
+/// receiver's IPv4 addresses and port and a one-time secret, each byte
+/// spelled as a word from [`WORDS`], followed by a checksum word. Both ends
+/// authenticate each other by the whole code through
+/// [`Connection::handshake_with_code`](crate::transfer::protocol::Connection::handshake_with_code).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareCode {
+    secret: [u8; SECRET_LEN],
+    port: u16,
+    addresses: Vec<Ipv4Addr>,
+}
+
+impl ShareCode {
+    /// A fresh code for a receiver listening on `port`
+    ///
+    /// Only IPv4 addresses fit in a code, loopback ones are used only if
+    /// there is nothing else.
+    pub fn generate(addresses: &[IpAddr], port: u16) -> Result<Self> {
+        let ipv4: Vec<Ipv4Addr> = addresses
+            .iter()
+            .filter_map(|ip| match ip {
+                IpAddr::V4(ip) if !ip.is_unspecified() && !ip.is_multicast() => Some(*ip),
+                _ => None,
+            })
+            .collect();
+        let mut usable: Vec<Ipv4Addr> = ipv4
+            .iter()
+            .copied()
+            .filter(|ip| !ip.is_loopback())
+            .collect();
+        if usable.is_empty() {
+            usable = ipv4;
+        }
+        usable.truncate(MAX_ADDRESSES);
+        if usable.is_empty() {
+            return Err(TsunaguError::InvalidCode(
+                "No IPv4 address to put in the code".into(),
+            ));
+        }
+
+        let mut secret = [0u8; SECRET_LEN];
+        SystemRandom::new()
+            .fill(&mut secret)
+            .map_err(|e| TsunaguError::Encryption(e.to_string()))?;
+        Ok(Self {
+            secret,
+            port,
+            addresses: usable,
+        })
+    }
+
+    /// Read a code as typed, words separated by dashes or spaces
+    pub fn parse(code: &str) -> Result<Self> {
+        let bytes = code
+            .split(|c: char| c == '-' || c.is_whitespace())
+            .filter(|word| !word.is_empty())
+            .map(|word| {
+                let word = word.to_ascii_lowercase();
+                WORDS
+                    .iter()
+                    .position(|w| *w == word)
+                    .map(|index| index as u8)
+                    .ok_or_else(|| TsunaguError::InvalidCode(format!("Unknown word {}", word)))
+            })
+            .collect::<Result<Vec<u8>>>()?;
+
+        let header = SECRET_LEN + 2;
+        let address_bytes = bytes.len().saturating_sub(header + 1);
+        if bytes.len() < header + 1 + 4
+            || address_bytes % 4 != 0
+            || address_bytes / 4 > MAX_ADDRESSES
+        {
+            return Err(TsunaguError::InvalidCode(format!(
+                "{} words is not a valid length",
+                bytes.len()
+            )));
+        }
+        let (payload, checksum) = bytes.split_at(bytes.len() - 1);
+        if checksum[0] != checksum_of(payload) {
+            return Err(TsunaguError::InvalidCode(
+                "Checksum does not match, check the words and their order".into(),
+            ));
+        }
+
+        let mut secret = [0u8; SECRET_LEN];
+        secret.copy_from_slice(&payload[..SECRET_LEN]);
+        let port = u16::from_be_bytes([payload[SECRET_LEN], payload[SECRET_LEN + 1]]);
+        if port == 0 {
+            return Err(TsunaguError::InvalidCode("Port 0 is not valid".into()));
+        }
+        let addresses = payload[header..]
+            .chunks_exact(4)
+            .map(|octets| Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3]))
+            .collect();
+        Ok(Self {
+            secret,
+            port,
+            addresses,
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn addresses(&self) -> &[Ipv4Addr] {
+        &self.addresses
+    }
+
+    /// The receiver to connect to, known only by where it listens
+    ///
+    /// Who it is is learned in the handshake.
+    pub fn receiver(&self) -> DeviceInfo {
+        DeviceInfo::new(
+            "Receiver by code".to_string(),
+            "Unknown".to_string(),
+            self.addresses.iter().copied().map(IpAddr::from),
+            self.port,
+            "Unknown".to_string(),
+            "Unknown".to_string(),
+        )
+    }
+
+    /// Mask for the handshake keys, derived from the whole code
+    ///
+    /// Someone who changed the addresses or port of a code gets a different
+    /// mask than the receiver and fails the handshake.
+    pub fn key_mask(&self) -> Result<KeyMask> {
+        KeyMask::new(&self.payload())
+    }
+
+    fn payload(&self) -> Vec<u8> {
+        let mut bytes = self.secret.to_vec();
+        bytes.extend_from_slice(&self.port.to_be_bytes());
+        for ip in &self.addresses {
+            bytes.extend_from_slice(&ip.octets());
+        }
+        bytes
+    }
+}
+
+impl fmt::Display for ShareCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut bytes = self.payload();
+        bytes.push(checksum_of(&bytes));
+        let words: Vec<&str> = bytes.iter().map(|&b| WORDS[b as usize]).collect();
+        write!(f, "{}", words.join("-"))
+    }
+}
+
+/// Catches mistyped and swapped words before the one-time secret is spent
+fn checksum_of(payload: &[u8]) -> u8 {
+    let mut context = digest::Context::new(&digest::SHA256);
+    context.update(b"tsunagu share code");
+    context.update(payload);
+    context.finish().as_ref()[0]
+}
+
+/// One word per byte value, short and easy to tell apart when read aloud
+pub const WORDS: [&str; 256] = [
+    "acid", "acorn", "actor", "alarm", "album", "alley", "amber", "anchor", "apple", "april",
+    "apron", "arena", "armor", "arrow", "atlas", "attic", "bacon", "badge", "bagel", "baker",
+    "bamboo", "basil", "beach", "beard", "berry", "bison", "blade", "blaze", "blend", "bloom",
+    "boots", "brain", "bread", "brick", "bride", "brush", "cabin", "cable", "cactus", "camel",
+    "candy", "canoe", "cargo", "carol", "cedar", "chair", "chalk", "charm", "chess", "chili",
+    "cider", "cloud", "clown", "cobra", "comet", "cookie", "coral", "couch", "crane", "crown",
+    "curry", "daisy", "dance", "delta", "denim", "depot", "diary", "dingo", "disco", "diver",
+    "donut", "dough", "dragon", "dream", "drift", "drum", "eagle", "earth", "easel", "ebony",
+    "elbow", "ember", "fable", "fairy", "falcon", "fence", "ferry", "fiber", "flame", "flask",
+    "fleet", "flint", "flute", "forge", "frost", "fruit", "fudge", "gamma", "garlic", "gauge",
+    "genie", "ghost", "giant", "ginger", "glass", "globe", "glove", "grape", "grass", "gravy",
+    "guava", "hammer", "harbor", "hazel", "heart", "hedge", "honey", "hotel", "igloo", "inlet",
+    "ivory", "jacket", "jelly", "jewel", "joker", "juice", "jumbo", "kayak", "kebab", "kettle",
+    "knife", "koala", "ladder", "lagoon", "lemon", "lever", "lilac", "linen", "llama", "lobby",
+    "lotus", "lyric", "magic", "mango", "maple", "melon", "metal", "mimic", "mocha", "motor",
+    "mouse", "muffin", "music", "nacho", "navy", "nectar", "needle", "nylon", "oasis", "ocean",
+    "olive", "omega", "onion", "opera", "orbit", "otter", "oxygen", "paddle", "panda", "paper",
+    "parade", "pasta", "peach", "pearl", "pebble", "pepper", "piano", "pilot", "pixel", "pizza",
+    "plaza", "pony", "poppy", "quartz", "quest", "quilt", "radar", "radio", "raven", "razor",
+    "relic", "rhino", "ribbon", "river", "robin", "rocket", "rodeo", "ruby", "rugby", "salad",
+    "salmon", "salsa", "scarf", "scout", "shadow", "shark", "shelf", "siren", "sketch", "slogan",
+    "smoke", "snack", "spark", "spice", "squid", "stamp", "steam", "stone", "storm", "sugar",
+    "swamp", "syrup", "table", "talon", "tango", "tapir", "taxi", "tiger", "toast", "token",
+    "topaz", "torch", "tower", "tulip", "tuna", "umbrella", "valve", "vapor", "velvet", "venus",
+    "viola", "violin", "voice", "wafer", "walnut", "waltz", "whale", "wheat", "willow", "window",
+    "wizard", "wombat", "yacht", "yodel", "yogurt", "zebra",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn code() -> ShareCode {
+        ShareCode::generate(
+            &[
+                "::1".parse().unwrap(),
+                Ipv4Addr::LOCALHOST.into(),
+                Ipv4Addr::new(192, 168, 1, 20).into(),
+            ],
+            40123,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_words_are_unique() {
+        let unique: HashSet<&str> = WORDS.iter().copied().collect();
+        assert_eq!(unique.len(), WORDS.len());
+        assert!(WORDS
+            .iter()
+            .all(|w| w.chars().all(|c| c.is_ascii_lowercase())));
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let code = code();
+        // Loopback is left out when there is a real address
+        assert_eq!(code.addresses(), [Ipv4Addr::new(192, 168, 1, 20)]);
+
+        let text = code.to_string();
+        assert_eq!(text.split('-').count(), 9);
+        assert_eq!(ShareCode::parse(&text).unwrap(), code);
+        let typed = text.replace('-', " ").to_uppercase();
+        assert_eq!(ShareCode::parse(&format!(" {} ", typed)).unwrap(), code);
+    }
+
+    #[test]
+    fn test_invalid_codes() {
+        let text = code().to_string();
+        let mut words: Vec<&str> = text.split('-').collect();
+
+        assert!(ShareCode::parse("").is_err());
+        assert!(ShareCode::parse(&text.replacen('-', "-nonsense-", 1)).is_err());
+        assert!(ShareCode::parse(&words[..words.len() - 1].join("-")).is_err());
+
+        // Swapping two words is caught by the checksum
+        let (first, last) = (0, words.len() - 2);
+        if words[first] != words[last] {
+            words.swap(first, last);
+            assert!(ShareCode::parse(&words.join("-")).is_err());
+        }
+    }
+
+    #[test]
+    fn test_generate_needs_ipv4() {
+        assert!(ShareCode::generate(&["2001:db8::2".parse().unwrap()], 40123).is_err());
+    }
+}
@@ -1,108 +0,0 @@
-use crate::models::{DeviceInfo, FileInfo, TransferInfo, TransferStatus};
-use crate::Result;
-use async_trait::async_trait;
-use std::path::PathBuf;
-
-#[async_trait]
-pub trait FileTransfer {
-    /// Initialize a file transfer
-    async fn init_transfer(
-        &mut self,
-        files: Vec<FileInfo>,
-        receiver: DeviceInfo,
-    ) -> Result<TransferInfo>;
-
-    /// Start a file transfer
-    async fn start_transfer(&mut self, transfer_info: &TransferInfo) -> Result<()>;
-
-    /// Pause a file transfer
-    async fn pause_transfer(&mut self, transfer_info: &TransferInfo) -> Result<()>;
-
-    /// Resume a file transfer
-    async fn resume_transfer(&mut self, transfer_info: &TransferInfo) -> Result<()>;
-
-    /// Cancel a file transfer
-    async fn cancel_transfer(&mut self, transfer_info: &TransferInfo) -> Result<()>;
-
-    /// Get the current status of a transfer
-    async fn get_transfer_status(&self, transfer_info: &TransferInfo) -> Result<TransferStatus>;
-}
-
-pub struct TcpFileTransfer {
-    local_device: DeviceInfo,
-    transfer_dir: PathBuf,
-}
-
-impl TcpFileTransfer {
-    pub fn new(local_device: DeviceInfo, transfer_dir: PathBuf) -> Self {
-        Self {
-            local_device,
-            transfer_dir,
-        }
-    }
-
-    // You can add more helper methods here as needed
-}
-
-#[async_trait]
-impl FileTransfer for TcpFileTransfer {
-    // Implement FileTransfer trait methods
-    async fn init_transfer(
-        &mut self,
-        files: Vec<FileInfo>,
-        receiver: DeviceInfo,
-    ) -> Result<TransferInfo> {
-        // TODO: Implement initialization logic
-        unimplemented!()
-    }
-
-    async fn start_transfer(&mut self, transfer_info: &TransferInfo) -> Result<()> {
-        // TODO: Implement start transfer logic
-        unimplemented!()
-    }
-
-    async fn pause_transfer(&mut self, transfer_info: &TransferInfo) -> Result<()> {
-        // TODO: Implement pause transfer logic
-        unimplemented!()
-    }
-
-    async fn resume_transfer(&mut self, transfer_info: &TransferInfo) -> Result<()> {
-        // TODO: Implement resume transfer logic
-        unimplemented!()
-    }
-
-    async fn cancel_transfer(&mut self, transfer_info: &TransferInfo) -> Result<()> {
-        // TODO: Implement cancel transfer logic
-        unimplemented!()
-    }
-
-    async fn get_transfer_status(&self, transfer_info: &TransferInfo) -> Result<TransferStatus> {
-        // TODO: Implement get transfer status logic
-        unimplemented!()
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::models::DeviceInfo;
-
-    #[tokio::test]
-    async fn test_tcp_file_transfer_creation() {
-        let device_info = DeviceInfo::new(
-            "Test Device".to_string(),
-            "Test Model".to_string(),
-            "127.0.0.1".to_string(),
-            8000,
-            "TestOS".to_string(),
-            "1.0.0".to_string(),
-        );
-        let transfer_dir = PathBuf::from("/tmp/transfer");
-        let tcp_transfer = TcpFileTransfer::new(device_info, transfer_dir);
-
-        assert_eq!(tcp_transfer.local_device.name(), "Test Device");
-        assert_eq!(tcp_transfer.transfer_dir, PathBuf::from("/tmp/transfer"));
-    }
-
-    // Add more tests for TcpFileTransfer methods as they are implemented
-}
@@ -0,0 +1,223 @@
+use crate::models::{TransferInfo, TransferStatus};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Minimum time between two progress events of the same transfer
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Weight of the newest sample in the smoothed throughput
+const RATE_SMOOTHING: f64 = 0.3;
+
+/// Something that happened to a transfer, see [`TcpFileTransfer::subscribe`]
+///
+/// [`TcpFileTransfer::subscribe`]: super::TcpFileTransfer::subscribe
+#[derive(Debug, Clone)]
+pub enum TransferEvent {
+    /// Data is about to flow, sent once the offer was accepted
+    Started(Box<TransferInfo>),
+    Progress(TransferProgress),
+    /// A file was transferred completely
+    FileCompleted {
+        transfer_id: String,
+        index: usize,
+    },
+    /// The transfer reached its final status
+    Finished {
+        transfer_id: String,
+        status: TransferStatus,
+    },
+}
+
+impl TransferEvent {
+    pub fn transfer_id(&self) -> &str {
+        match self {
+            TransferEvent::Started(info) => info.id(),
+            TransferEvent::Progress(progress) => &progress.transfer_id,
+            TransferEvent::FileCompleted { transfer_id, .. } => transfer_id,
+            TransferEvent::Finished { transfer_id, .. } => transfer_id,
+        }
+    }
+}
+
+/// Progress of a running transfer
+#[derive(Debug, Clone, PartialEq)]
+pub struct TransferProgress {
+    pub transfer_id: String,
+    /// Index of the current file in the transfer
+    pub file_index: usize,
+    pub file_bytes: u64,
+    pub file_size: u64,
+    pub bytes_transferred: u64,
+    pub total_bytes: u64,
+    /// Smoothed throughput in bytes per second
+    pub bytes_per_second: f64,
+    /// Estimated time until the transfer completes, unknown until data flows
+    pub eta: Option<Duration>,
+}
+
+impl TransferProgress {
+    /// Overall progress in percent
+    pub fn percent(&self) -> f32 {
+        percent(self.bytes_transferred, self.total_bytes)
+    }
+}
+
+/// Turns byte counts into throttled progress reports
+pub(crate) struct ProgressTracker {
+    progress: TransferProgress,
+    sample_at: Instant,
+    sample_bytes: u64,
+    reported_at: Option<Instant>,
+}
+
+impl ProgressTracker {
+    pub(crate) fn new(transfer: &TransferInfo) -> Self {
+        Self {
+            progress: TransferProgress {
+                transfer_id: transfer.id().to_string(),
+                file_index: 0,
+                file_bytes: 0,
+                file_size: transfer.files().first().map_or(0, |f| f.size()),
+                bytes_transferred: 0,
+                total_bytes: transfer.total_size(),
+                bytes_per_second: 0.0,
+                eta: None,
+            },
+            sample_at: Instant::now(),
+            sample_bytes: 0,
+            reported_at: None,
+        }
+    }
+
+    pub(crate) fn start_file(&mut self, index: usize, size: u64) {
+        self.progress.file_index = index;
+        self.progress.file_bytes = 0;
+        self.progress.file_size = size;
+    }
+
+    /// Count `bytes` that are already there, e.g. when resuming, without
+    /// affecting the throughput
+    pub(crate) fn skip(&mut self, bytes: u64) {
+        self.progress.file_bytes += bytes;
+        self.progress.bytes_transferred += bytes;
+        self.sample_bytes += bytes;
+    }
+
+    /// Record `bytes` more, returning a report if one is due
+    pub(crate) fn advance(&mut self, bytes: u64) -> Option<TransferProgress> {
+        self.progress.file_bytes += bytes;
+        self.progress.bytes_transferred += bytes;
+
+        let now = Instant::now();
+        let due = self
+            .reported_at
+            .is_none_or(|at| now.duration_since(at) >= PROGRESS_INTERVAL);
+        let done = self.progress.file_bytes >= self.progress.file_size;
+        if !due && !done {
+            return None;
+        }
+        Some(self.report(now))
+    }
+
+    fn report(&mut self, now: Instant) -> TransferProgress {
+        let elapsed = now.duration_since(self.sample_at).as_secs_f64();
+        if elapsed > 0.0 {
+            let rate = (self.progress.bytes_transferred - self.sample_bytes) as f64 / elapsed;
+            self.progress.bytes_per_second = if self.progress.bytes_per_second == 0.0 {
+                rate
+            } else {
+                RATE_SMOOTHING * rate + (1.0 - RATE_SMOOTHING) * self.progress.bytes_per_second
+            };
+            self.sample_at = now;
+            self.sample_bytes = self.progress.bytes_transferred;
+        }
+
+        let remaining = self
+            .progress
+            .total_bytes
+            .saturating_sub(self.progress.bytes_transferred);
+        self.progress.eta = if remaining == 0 {
+            Some(Duration::ZERO)
+        } else if self.progress.bytes_per_second > 0.0 {
+            Some(Duration::from_secs_f64(
+                remaining as f64 / self.progress.bytes_per_second,
+            ))
+        } else {
+            None
+        };
+
+        self.reported_at = Some(now);
+        self.progress.clone()
+    }
+}
+
+fn percent(done: u64, total: u64) -> f32 {
+    if total == 0 {
+        100.0
+    } else {
+        (done as f64 / total as f64 * 100.0) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{DeviceInfo, FileInfo};
+    use std::net::Ipv4Addr;
+
+    fn transfer(sizes: &[u64]) -> TransferInfo {
+        let dir = tempfile::tempdir().unwrap();
+        let files = sizes
+            .iter()
+            .enumerate()
+            .map(|(i, size)| {
+                let path = dir.path().join(format!("{}.bin", i));
+                std::fs::write(&path, vec![0u8; *size as usize]).unwrap();
+                FileInfo::from_path(&path).unwrap()
+            })
+            .collect();
+        let device = DeviceInfo::new(
+            "Test".to_string(),
+            "Test Model".to_string(),
+            [Ipv4Addr::LOCALHOST.into()],
+            8000,
+            "TestOS".to_string(),
+            "1.0.0".to_string(),
+        );
+        TransferInfo::new(device.clone(), device, files)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_progress_is_throttled() {
+        let mut tracker = ProgressTracker::new(&transfer(&[1000, 1000]));
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        let first = tracker.advance(100).unwrap();
+        assert_eq!(first.bytes_transferred, 100);
+        assert_eq!(first.bytes_per_second, 100.0);
+        assert_eq!(first.eta, Some(Duration::from_secs(19)));
+
+        // Too soon for another report
+        assert!(tracker.advance(100).is_none());
+
+        tokio::time::advance(PROGRESS_INTERVAL).await;
+        let second = tracker.advance(100).unwrap();
+        assert_eq!(second.file_bytes, 300);
+        assert!(second.bytes_per_second > first.bytes_per_second);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_file_end_is_always_reported() {
+        let mut tracker = ProgressTracker::new(&transfer(&[10, 20]));
+        tracker.advance(5).unwrap();
+        let end = tracker.advance(5).unwrap();
+        assert_eq!(end.file_bytes, 10);
+
+        tracker.start_file(1, 20);
+        tracker.advance(20).unwrap();
+        let last = tracker.report(Instant::now());
+        assert_eq!(last.file_index, 1);
+        assert_eq!(last.percent(), 100.0);
+        assert_eq!(last.eta, Some(Duration::ZERO));
+    }
+}
@@ -0,0 +1,98 @@
+use crate::error::TsunaguError;
+use crate::Result;
+use ring::digest;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// SHA-256 of a whole file, computed while it is streamed
+pub(crate) struct FileHasher {
+    context: digest::Context,
+}
+
+impl FileHasher {
+    pub(crate) fn new() -> Self {
+        Self {
+            context: digest::Context::new(&digest::SHA256),
+        }
+    }
+
+    pub(crate) fn update(&mut self, data: &[u8]) {
+        self.context.update(data);
+    }
+
+    /// Feed the next `len` bytes of `reader`, e.g. data that is not sent
+    /// again when resuming
+    pub(crate) async fn update_from<R>(&mut self, reader: &mut R, len: u64) -> Result<()>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let mut buf = vec![0u8; super::CHUNK_SIZE];
+        let mut remaining = len;
+        while remaining > 0 {
+            let want = remaining.min(buf.len() as u64) as usize;
+            let n = reader.read(&mut buf[..want]).await?;
+            if n == 0 {
+                return Err(TsunaguError::Transfer(format!(
+                    "File ended {} bytes early",
+                    remaining
+                )));
+            }
+            self.context.update(&buf[..n]);
+            remaining -= n as u64;
+        }
+        Ok(())
+    }
+
+    /// Hex encoded digest
+    pub(crate) fn finish(self) -> String {
+        hex(self.context.finish().as_ref())
+    }
+}
+
+/// Fail with [`TsunaguError::IntegrityMismatch`] unless the hashes are equal
+pub(crate) fn check(file: &str, expected: &str, actual: &str) -> Result<()> {
+    if expected == actual {
+        return Ok(());
+    }
+    Err(TsunaguError::IntegrityMismatch {
+        file: file.to_string(),
+        expected: expected.to_string(),
+        actual: actual.to_string(),
+    })
+}
+
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_streamed_hash_matches_whole_hash() {
+        let data: Vec<u8> = (0..100_000).map(|i| i as u8).collect();
+
+        let mut whole = FileHasher::new();
+        whole.update(&data);
+
+        // A resumed file hashes the prefix from disk and the rest from the wire
+        let mut resumed = FileHasher::new();
+        resumed.update_from(&mut &data[..], 70_000).await.unwrap();
+        resumed.update(&data[70_000..]);
+
+        let expected = whole.finish();
+        assert_eq!(expected, resumed.finish());
+        assert_eq!(expected.len(), 64);
+        check("data.bin", &expected, &expected).unwrap();
+        assert!(matches!(
+            check("data.bin", &expected, "00"),
+            Err(TsunaguError::IntegrityMismatch { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_short_prefix_fails() {
+        let mut hasher = FileHasher::new();
+        assert!(hasher.update_from(&mut &[0u8; 10][..], 20).await.is_err());
+    }
+}
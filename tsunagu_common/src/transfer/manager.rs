@@ -0,0 +1,326 @@
+use super::{
+    unknown_transfer, AcceptancePolicy, FileTransfer, FinishHook, TcpFileTransfer, TransferEvent,
+    TransferServer,
+};
+use crate::error::TsunaguError;
+use crate::history::{HistoryEntry, HistoryStore};
+use crate::models::{DeviceInfo, FileInfo, TransferInfo, TransferStatus};
+use crate::share_code::ShareCode;
+use crate::Result;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{broadcast, oneshot, Semaphore};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Finished transfers kept in memory, oldest are dropped first
+const HISTORY_LIMIT: usize = 100;
+
+/// Runs many transfers of one [`TcpFileTransfer`] side by side
+///
+/// At most `max_concurrent` transfers move data at a time, counting both
+/// directions when the server comes from [`TransferManager::server`]. Further
+/// sends wait in a queue in the order they were made.
+#[derive(Clone)]
+pub struct TransferManager {
+    transfer: TcpFileTransfer,
+    slots: Arc<Semaphore>,
+    /// Sends waiting for a slot, with the switch to cancel them
+    queued: Arc<Mutex<HashMap<String, oneshot::Sender<()>>>>,
+    history: Arc<Mutex<VecDeque<HistoryEntry>>>,
+}
+
+/// A send handed to the [`TransferManager`]
+pub struct TransferHandle {
+    info: TransferInfo,
+    task: JoinHandle<Result<()>>,
+}
+
+impl TransferHandle {
+    pub fn info(&self) -> &TransferInfo {
+        &self.info
+    }
+
+    /// Wait until the transfer has finished
+    pub async fn wait(self) -> Result<()> {
+        self.task
+            .await
+            .map_err(|e| TsunaguError::Transfer(format!("Transfer task failed: {}", e)))?
+    }
+}
+
+impl TransferManager {
+    pub fn new(transfer: TcpFileTransfer, max_concurrent: usize) -> Self {
+        let history = Arc::new(Mutex::new(VecDeque::new()));
+        let transfer = transfer.with_on_finish(recorder(Arc::clone(&history), None));
+        Self {
+            transfer,
+            slots: Arc::new(Semaphore::new(max_concurrent.max(1))),
+            queued: Arc::new(Mutex::new(HashMap::new())),
+            history,
+        }
+    }
+
+    /// Also append finished transfers to `store`, which outlives the process
+    pub fn with_history_store(mut self, store: HistoryStore) -> Self {
+        let hook = recorder(Arc::clone(&self.history), Some(store));
+        self.transfer = self.transfer.with_on_finish(hook);
+        self
+    }
+
+    /// The underlying transfer handler
+    pub fn transfer(&self) -> &TcpFileTransfer {
+        &self.transfer
+    }
+
+    /// Update the device announced in the handshake of new transfers
+    pub fn set_local_device(&mut self, device: DeviceInfo) {
+        self.transfer.set_local_device(device);
+    }
+
+    /// Subscribe to events of all transfers
+    pub fn subscribe(&self) -> broadcast::Receiver<TransferEvent> {
+        self.transfer.subscribe()
+    }
+
+    /// A server whose incoming transfers count against the same limit
+    pub fn server(&self, policy: Arc<dyn AcceptancePolicy>) -> TransferServer {
+        TransferServer::new(self.transfer.clone(), policy).with_slots(Arc::clone(&self.slots))
+    }
+
+    /// Queue sending `files` to `receiver`
+    pub async fn send(&self, files: Vec<FileInfo>, receiver: DeviceInfo) -> Result<TransferHandle> {
+        let info = self.transfer.clone().init_transfer(files, receiver).await?;
+        Ok(self.spawn(info, false))
+    }
+
+    /// Queue sending `files` to the receiver that gave `code`
+    pub async fn send_with_code(
+        &self,
+        files: Vec<FileInfo>,
+        code: ShareCode,
+    ) -> Result<TransferHandle> {
+        let info = self.transfer.init_code_transfer(files, code).await?;
+        Ok(self.spawn(info, false))
+    }
+
+    /// Queue continuing the interrupted or failed send `transfer_id`
+    pub async fn resume(&self, transfer_id: &str) -> Result<TransferHandle> {
+        if self.queued.lock().unwrap().contains_key(transfer_id) {
+            return Err(TsunaguError::Transfer(format!(
+                "Transfer {} is already queued",
+                transfer_id
+            )));
+        }
+        let info = self
+            .transfer
+            .get_transfer(transfer_id)
+            .await
+            .ok_or_else(|| unknown_transfer(transfer_id))?;
+        Ok(self.spawn(info, true))
+    }
+
+    /// Register the sends interrupted in an earlier run, see [`TransferManager::resume`]
+    pub async fn load_interrupted(&self) -> Result<Vec<TransferInfo>> {
+        self.transfer.load_interrupted().await
+    }
+
+    /// Transfers that are queued or running
+    pub async fn list(&self) -> Vec<TransferInfo> {
+        self.transfer
+            .list_transfers()
+            .await
+            .into_iter()
+            .filter(|info| {
+                matches!(
+                    info.status(),
+                    TransferStatus::Pending | TransferStatus::InProgress(_)
+                )
+            })
+            .collect()
+    }
+
+    /// Current or final state of `transfer_id`
+    pub async fn get(&self, transfer_id: &str) -> Option<TransferInfo> {
+        match self.transfer.get_transfer(transfer_id).await {
+            Some(info) => Some(info),
+            None => self
+                .history
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|entry| entry.transfer().id() == transfer_id)
+                .map(|entry| entry.transfer().clone()),
+        }
+    }
+
+    /// Cancel a queued or running transfer
+    pub async fn cancel(&self, transfer_id: &str) -> Result<()> {
+        let queued = self.queued.lock().unwrap().remove(transfer_id);
+        if let Some(cancel) = queued {
+            // The task may have just taken a slot, then it was not waiting anymore
+            cancel.send(()).ok();
+            return Ok(());
+        }
+        let info = self
+            .transfer
+            .get_transfer(transfer_id)
+            .await
+            .ok_or_else(|| unknown_transfer(transfer_id))?;
+        self.transfer.clone().cancel_transfer(&info).await
+    }
+
+    /// Transfers finished since the manager was created, newest first
+    pub fn history(&self) -> Vec<HistoryEntry> {
+        self.history.lock().unwrap().iter().cloned().collect()
+    }
+
+    fn spawn(&self, info: TransferInfo, resume: bool) -> TransferHandle {
+        let (cancel, cancelled) = oneshot::channel();
+        self.queued
+            .lock()
+            .unwrap()
+            .insert(info.id().to_string(), cancel);
+
+        let manager = self.clone();
+        let task_info = info.clone();
+        let task = tokio::spawn(async move {
+            let id = task_info.id();
+            let permit = tokio::select! {
+                biased;
+                _ = cancelled => None,
+                permit = Arc::clone(&manager.slots).acquire_owned() => permit.ok(),
+            };
+            let queued = manager.queued.lock().unwrap().remove(id).is_some();
+            let Some(_permit) = permit.filter(|_| queued) else {
+                debug!("Transfer {} cancelled while queued", id);
+                return manager.transfer.cancel_pending(id).await;
+            };
+
+            let mut transfer = manager.transfer.clone();
+            if resume {
+                transfer.resume_transfer(&task_info).await
+            } else {
+                transfer.start_transfer(&task_info).await
+            }
+        });
+        TransferHandle { info, task }
+    }
+}
+
+/// Keep finished transfers in `history` and, if given, in `store`
+fn recorder(
+    history: Arc<Mutex<VecDeque<HistoryEntry>>>,
+    store: Option<HistoryStore>,
+) -> FinishHook {
+    Arc::new(move |entry: HistoryEntry| {
+        if let Some(store) = &store {
+            if let Err(e) = store.append(&entry) {
+                warn!(
+                    "Failed to record transfer {} in the history: {}",
+                    entry.transfer().id(),
+                    e
+                );
+            }
+        }
+        let mut history = history.lock().unwrap();
+        history.push_front(entry);
+        history.truncate(HISTORY_LIMIT);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::DeviceManager;
+    use crate::history::{Direction, HistoryFilter, StatusFilter};
+    use crate::identity::DeviceIdentity;
+    use crate::transfer::{ChannelPolicy, OfferDecision};
+    use std::net::Ipv4Addr;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_queue_and_history() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let target_dir = tempfile::tempdir().unwrap();
+        let path = source_dir.path().join("hello.txt");
+        std::fs::write(&path, b"hello").unwrap();
+
+        let receiver_devices = DeviceManager::with_identity(DeviceIdentity::generate().unwrap())
+            .await
+            .unwrap();
+        let receiver = TcpFileTransfer::from_device_manager(
+            &receiver_devices,
+            target_dir.path().to_path_buf(),
+        )
+        .await;
+        let (policy, mut offers) = ChannelPolicy::new(Duration::from_secs(10));
+        let mut server = TransferServer::new(receiver, Arc::new(policy));
+        server.start(0, &receiver_devices).await.unwrap();
+        let mut receiver_device = receiver_devices.get_current_device_info().await;
+        receiver_device.set_addresses([Ipv4Addr::LOCALHOST.into()]);
+
+        let sender_devices = DeviceManager::with_identity(DeviceIdentity::generate().unwrap())
+            .await
+            .unwrap();
+        let sender = TcpFileTransfer::from_device_manager(&sender_devices, PathBuf::new()).await;
+        let store = HistoryStore::new(source_dir.path().join("history.jsonl"));
+        let manager = TransferManager::new(sender, 1).with_history_store(store.clone());
+
+        let file = || vec![FileInfo::from_path(&path).unwrap()];
+        let first = manager.send(file(), receiver_device.clone()).await.unwrap();
+        let offer = offers.recv().await.unwrap();
+        assert_eq!(offer.transfer().id(), first.info().id());
+
+        // The only slot is taken, the second send waits
+        let second = manager.send(file(), receiver_device).await.unwrap();
+        let second_id = second.info().id().to_string();
+        assert_eq!(manager.list().await.len(), 2);
+        manager.cancel(&second_id).await.unwrap();
+        assert!(matches!(
+            second.wait().await,
+            Err(TsunaguError::Cancelled(_))
+        ));
+        assert_eq!(
+            manager.get(&second_id).await.unwrap().status(),
+            &TransferStatus::Cancelled
+        );
+
+        offer.respond(OfferDecision::Accept);
+        let first_id = first.info().id().to_string();
+        first.wait().await.unwrap();
+        assert_eq!(
+            std::fs::read(target_dir.path().join("hello.txt")).unwrap(),
+            b"hello"
+        );
+
+        assert!(manager.list().await.is_empty());
+        let history: Vec<String> = manager
+            .history()
+            .iter()
+            .map(|entry| entry.transfer().id().to_string())
+            .collect();
+        assert_eq!(history, [first_id.clone(), second_id.clone()]);
+
+        let stored = store.query(&HistoryFilter::default()).unwrap();
+        assert_eq!(stored.len(), 2);
+        assert_eq!(stored[0].transfer().id(), first_id);
+        assert_eq!(stored[0].direction(), Direction::Sent);
+        assert_eq!(stored[0].path(0), Some(path.as_path()));
+        assert!(stored[0].transfer().files()[0].hash().is_some());
+        let cancelled = HistoryFilter {
+            status: Some(StatusFilter::Cancelled),
+            ..HistoryFilter::default()
+        };
+        let cancelled = store.query(&cancelled).unwrap();
+        assert_eq!(cancelled[0].transfer().id(), second_id);
+        assert_eq!(
+            manager.get(&first_id).await.unwrap().status(),
+            &TransferStatus::Completed
+        );
+        assert!(manager.cancel(&first_id).await.is_err());
+
+        server.stop().await.unwrap();
+    }
+}
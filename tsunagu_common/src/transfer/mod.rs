@@ -0,0 +1,1980 @@
+use crate::config::CancelledPartials;
+use crate::device::DeviceManager;
+use crate::encryption::SessionRole;
+use crate::error::TsunaguError;
+use crate::history::{Direction, HistoryEntry};
+use crate::identity::DeviceIdentity;
+use crate::models::{DeviceInfo, FileInfo, TransferInfo, TransferStatus};
+use crate::network;
+use crate::share_code::ShareCode;
+use crate::trust::TrustStore;
+use crate::Result;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, watch, RwLock, Semaphore};
+use tracing::{debug, info, warn};
+
+mod events;
+mod integrity;
+mod manager;
+mod policy;
+pub mod protocol;
+mod resume;
+mod server;
+mod tree;
+
+use events::ProgressTracker;
+pub use events::{TransferEvent, TransferProgress};
+use integrity::FileHasher;
+pub use manager::{TransferHandle, TransferManager};
+pub use policy::{
+    AcceptAll, AcceptTrusted, AcceptancePolicy, ChannelPolicy, OfferDecision, OfferRules,
+    PendingOffer, RejectAll, RulePolicy,
+};
+use protocol::{unexpected, Connection, Frame, Message, ResumePoint, CHUNK_SIZE};
+use resume::{ChunkHasher, ResumeManifest};
+pub use server::TransferServer;
+pub use tree::{collect_files, CollectOptions, SymlinkPolicy, IGNORE_FILE};
+
+pub(crate) const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Events buffered per subscriber before it starts lagging
+const EVENT_CAPACITY: usize = 256;
+
+/// Received chunks between two saves of the resume manifest
+const MANIFEST_SAVE_CHUNKS: usize = 64;
+
+#[async_trait]
+pub trait FileTransfer {
+    /// Initialize a file transfer
+    async fn init_transfer(
+        &mut self,
+        files: Vec<FileInfo>,
+        receiver: DeviceInfo,
+    ) -> Result<TransferInfo>;
+
+    /// Start a file transfer
+    async fn start_transfer(&mut self, transfer_info: &TransferInfo) -> Result<()>;
+
+    /// Pause a file transfer
+    async fn pause_transfer(&mut self, transfer_info: &TransferInfo) -> Result<()>;
+
+    /// Resume a file transfer
+    async fn resume_transfer(&mut self, transfer_info: &TransferInfo) -> Result<()>;
+
+    /// Cancel a file transfer, the peer is told to stop as well
+    async fn cancel_transfer(&mut self, transfer_info: &TransferInfo) -> Result<()>;
+
+    /// Get the current status of a transfer
+    async fn get_transfer_status(&self, transfer_info: &TransferInfo) -> Result<TransferStatus>;
+}
+
+/// Control state of a running transfer, set by pause/resume/cancel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TransferControl {
+    Running,
+    Paused,
+    Cancelled,
+}
+
+struct TransferState {
+    info: TransferInfo,
+    control: watch::Sender<TransferControl>,
+    /// A connection for this transfer is currently open
+    running: bool,
+    direction: Direction,
+    /// When the current attempt started
+    started_at: SystemTime,
+    /// Where each file is read from or was saved to
+    paths: Vec<Option<PathBuf>>,
+    /// Code the receiver gave, for sends to a receiver by code
+    code: Option<ShareCode>,
+}
+
+/// File transfer over plain TCP using the framed protocol in [`protocol`]
+#[derive(Clone)]
+pub struct TcpFileTransfer {
+    local_device: DeviceInfo,
+    transfer_dir: PathBuf,
+    transfers: Arc<RwLock<HashMap<String, TransferState>>>,
+    identity: Option<Arc<DeviceIdentity>>,
+    trust_store: Option<Arc<RwLock<TrustStore>>>,
+    events: broadcast::Sender<TransferEvent>,
+    state_dir: Option<PathBuf>,
+    cancelled_partials: CancelledPartials,
+    on_finish: Option<FinishHook>,
+}
+
+/// Called with the final state of every transfer
+pub(crate) type FinishHook = Arc<dyn Fn(HistoryEntry) + Send + Sync>;
+
+impl TcpFileTransfer {
+    pub fn new(local_device: DeviceInfo, transfer_dir: PathBuf) -> Self {
+        Self {
+            local_device,
+            transfer_dir,
+            transfers: Arc::new(RwLock::new(HashMap::new())),
+            identity: None,
+            trust_store: None,
+            events: broadcast::channel(EVENT_CAPACITY).0,
+            state_dir: None,
+            cancelled_partials: CancelledPartials::default(),
+            on_finish: None,
+        }
+    }
+
+    /// Create a transfer handler for the device and identity of `device_manager`
+    pub async fn from_device_manager(
+        device_manager: &DeviceManager,
+        transfer_dir: PathBuf,
+    ) -> Self {
+        Self::new(device_manager.get_current_device_info().await, transfer_dir)
+            .with_identity(device_manager.identity())
+    }
+
+    /// Authenticate as `identity` in the handshake
+    ///
+    /// Required whenever the local device carries an identity key.
+    pub fn with_identity(mut self, identity: Arc<DeviceIdentity>) -> Self {
+        self.identity = Some(identity);
+        self
+    }
+
+    /// Check peers against `trust_store`, refusing peers whose identity changed
+    pub fn with_trust_store(mut self, trust_store: Arc<RwLock<TrustStore>>) -> Self {
+        self.trust_store = Some(trust_store);
+        self
+    }
+
+    /// Persist outgoing transfers in `dir` so they survive a restart
+    pub fn with_state_dir(mut self, dir: PathBuf) -> Self {
+        self.state_dir = Some(dir);
+        self
+    }
+
+    /// Choose what happens to partially received files of cancelled transfers
+    pub fn with_cancelled_partials(mut self, action: CancelledPartials) -> Self {
+        self.cancelled_partials = action;
+        self
+    }
+
+    /// Hand finished transfers to `hook`
+    ///
+    /// Unless they failed, they are forgotten afterwards, the hook's owner
+    /// keeps them from then on.
+    pub(crate) fn with_on_finish(mut self, hook: FinishHook) -> Self {
+        self.on_finish = Some(hook);
+        self
+    }
+
+    /// Register the outgoing transfers that were interrupted in an earlier run
+    ///
+    /// They can be continued with [`FileTransfer::resume_transfer`].
+    pub async fn load_interrupted(&self) -> Result<Vec<TransferInfo>> {
+        let Some(dir) = &self.state_dir else {
+            return Ok(Vec::new());
+        };
+        let transfers = resume::load_outgoing(dir)?;
+        for transfer in &transfers {
+            if !self.transfers.read().await.contains_key(transfer.id()) {
+                self.register(transfer.clone(), Direction::Sent).await?;
+            }
+        }
+        Ok(transfers)
+    }
+
+    pub fn local_device(&self) -> &DeviceInfo {
+        &self.local_device
+    }
+
+    /// Update the device announced in the handshake, e.g. once the server port is known
+    pub fn set_local_device(&mut self, device: DeviceInfo) {
+        self.local_device = device;
+    }
+
+    pub fn transfer_dir(&self) -> &Path {
+        &self.transfer_dir
+    }
+
+    /// Receive a transfer from an already connected peer
+    ///
+    /// The offer is passed to `policy`; accepted files are written into `transfer_dir`.
+    pub async fn receive<S>(&self, stream: S, policy: &dyn AcceptancePolicy) -> Result<TransferInfo>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        let (mut conn, peer) = self.accept(stream).await?;
+        match conn.recv_message().await? {
+            Message::Offer { transfer } => {
+                self.receive_offer(conn, &peer, *transfer, policy, None)
+                    .await
+            }
+            other => Err(unexpected(&other)),
+        }
+    }
+
+    /// Receive a single transfer from the sender that has `code`
+    ///
+    /// Connections on `listener` that hang up or are not Tsunagu before
+    /// exchanging keys are ignored. The first that does uses up the code,
+    /// whether it knew the code or not, so an attacker only gets one guess.
+    /// The sender proved it knows the code, `policy` may accept it without asking.
+    pub async fn receive_with_code(
+        &self,
+        listener: TcpListener,
+        code: &ShareCode,
+        policy: &dyn AcceptancePolicy,
+    ) -> Result<TransferInfo> {
+        let mask = code.key_mask()?;
+        let (mut conn, peer) = loop {
+            let (stream, addr) = listener.accept().await?;
+            let mut conn = Connection::new(stream);
+            match conn
+                .handshake_with_code(
+                    &self.local_device,
+                    self.identity.as_deref(),
+                    SessionRole::Responder,
+                    &mask,
+                )
+                .await
+            {
+                Ok(peer) => break (conn, peer),
+                Err(e) if conn.is_encrypted() || matches!(e, TsunaguError::Encryption(_)) => {
+                    warn!("Code handshake with {} failed, the code is used up", addr);
+                    return Err(e);
+                }
+                Err(e) => debug!("Ignoring connection from {}: {}", addr, e),
+            }
+        };
+        drop(listener);
+        debug!("Code handshake completed with {}", peer.name());
+
+        if let Err(e) = self.check_peer(&peer).await {
+            conn.send_message(&Message::Error {
+                message: e.to_string(),
+            })
+            .await
+            .ok();
+            return Err(e);
+        }
+        match conn.recv_message().await? {
+            Message::Offer { transfer } => {
+                self.receive_offer(conn, &peer, *transfer, policy, None)
+                    .await
+            }
+            other => Err(unexpected(&other)),
+        }
+    }
+
+    /// Prepare sending `files` to the receiver that gave `code`
+    ///
+    /// Codes are used up by the first connection, so these transfers are not
+    /// kept for resuming after a restart.
+    pub async fn init_code_transfer(
+        &self,
+        files: Vec<FileInfo>,
+        code: ShareCode,
+    ) -> Result<TransferInfo> {
+        if files.is_empty() {
+            return Err(TsunaguError::Transfer("No files to transfer".into()));
+        }
+
+        let transfer = TransferInfo::new(self.local_device.clone(), code.receiver(), files);
+        self.register(transfer.clone(), Direction::Sent).await?;
+        if let Some(state) = self.transfers.write().await.get_mut(transfer.id()) {
+            state.code = Some(code);
+        }
+        debug!("Initialized transfer {} by code", transfer.id());
+        Ok(transfer)
+    }
+
+    /// Run the responder side of the handshake and check the peer
+    pub(crate) async fn accept<S>(&self, stream: S) -> Result<(Connection<S>, DeviceInfo)>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        let mut conn = Connection::new(stream);
+        let peer = conn
+            .handshake(
+                &self.local_device,
+                self.identity.as_deref(),
+                SessionRole::Responder,
+            )
+            .await?;
+        debug!("Handshake completed with {}", peer.name());
+
+        if let Err(e) = self.check_peer(&peer).await {
+            conn.send_message(&Message::Error {
+                message: e.to_string(),
+            })
+            .await
+            .ok();
+            return Err(e);
+        }
+        Ok((conn, peer))
+    }
+
+    /// Handle an offer received from the authenticated `peer`
+    ///
+    /// Once the offer is accepted a permit is taken from `slots`, if given, and
+    /// held while data moves. Waiting for the policy does not hold one.
+    pub(crate) async fn receive_offer<S>(
+        &self,
+        mut conn: Connection<S>,
+        peer: &DeviceInfo,
+        transfer: TransferInfo,
+        policy: &dyn AcceptancePolicy,
+        slots: Option<&Semaphore>,
+    ) -> Result<TransferInfo>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        info!(
+            "Incoming offer of {} file(s) from {} ({})",
+            transfer.files().len(),
+            peer.name(),
+            transfer.id()
+        );
+
+        // The policy sees the sender from the offer, which must be the peer
+        // that authenticated in the handshake
+        let sender_matches = transfer.sender().id() == peer.id();
+        let valid_id = resume::is_valid_transfer_id(transfer.id());
+        let previous = if sender_matches && valid_id {
+            ResumeManifest::load(&self.transfer_dir, &transfer)?
+        } else {
+            None
+        };
+        let unsafe_path = transfer
+            .files()
+            .iter()
+            .find_map(|file| tree::safe_relative_path(file.relative_path()).err());
+        let decision = if !sender_matches {
+            OfferDecision::Reject("Sender does not match the connected device".into())
+        } else if !valid_id {
+            OfferDecision::Reject(format!("Invalid transfer id: {}", transfer.id()))
+        } else if let Some(e) = unsafe_path {
+            OfferDecision::Reject(e.to_string())
+        } else if previous.is_some() {
+            // Accepted before, only the connection was lost
+            OfferDecision::Accept
+        } else {
+            policy.decide(&transfer).await
+        };
+        let offered = transfer.files().len();
+        let declined: Vec<usize> = match decision {
+            OfferDecision::Accept => Vec::new(),
+            OfferDecision::AcceptPartial(accepted) if accepted.iter().any(|&i| i < offered) => {
+                (0..offered).filter(|i| !accepted.contains(i)).collect()
+            }
+            decision => {
+                let reason = match decision {
+                    OfferDecision::Reject(reason) => reason,
+                    _ => "No files accepted".to_string(),
+                };
+                info!("Rejected transfer {}: {}", transfer.id(), reason);
+                conn.send_message(&Message::Reject {
+                    transfer_id: transfer.id().to_string(),
+                    reason: reason.clone(),
+                })
+                .await?;
+                return Err(TsunaguError::Rejected(reason));
+            }
+        };
+        // Registered right away, so an offer reusing the id of a running
+        // transfer cannot take it over
+        let mut control = match self.register(transfer.clone(), Direction::Received).await {
+            Ok(control) => control,
+            Err(e) => {
+                info!("Rejected transfer {}: {}", transfer.id(), e);
+                conn.send_message(&Message::Reject {
+                    transfer_id: transfer.id().to_string(),
+                    reason: e.to_string(),
+                })
+                .await?;
+                return Err(TsunaguError::Rejected(e.to_string()));
+            }
+        };
+        let accepted = async {
+            let permit =
+                match slots {
+                    Some(slots) => Some(slots.acquire().await.map_err(|_| {
+                        TsunaguError::Transfer("Transfer manager shut down".into())
+                    })?),
+                    None => None,
+                };
+
+            tokio::fs::create_dir_all(&self.transfer_dir).await?;
+            let (manifest, resume) = match previous {
+                Some(mut manifest) => {
+                    let resume = manifest.verify().await?;
+                    info!(
+                        "Resuming transfer {} with {} file(s) partially received",
+                        transfer.id(),
+                        resume.len()
+                    );
+                    (manifest, resume)
+                }
+                None => {
+                    let mut manifest = ResumeManifest::new(&self.transfer_dir, &transfer)?;
+                    for &index in &declined {
+                        manifest.decline(index);
+                    }
+                    (manifest, Vec::new())
+                }
+            };
+            manifest.save()?;
+            let declined = manifest.declined();
+            if !declined.is_empty() {
+                info!(
+                    "Accepted {} of {} file(s) of transfer {}",
+                    offered - declined.len(),
+                    offered,
+                    transfer.id()
+                );
+            }
+
+            conn.send_message(&Message::Accept {
+                transfer_id: transfer.id().to_string(),
+                resume,
+                declined,
+            })
+            .await?;
+            Ok((permit, manifest))
+        }
+        .await;
+        let (_permit, mut manifest) = match accepted {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                // Failed like any other attempt, so the id can be offered again
+                let result = Err(e);
+                self.finish(transfer.id(), &result).await;
+                return result.map(|()| transfer);
+            }
+        };
+
+        let result = self
+            .receive_files(&mut conn, &transfer, &mut control, &mut manifest)
+            .await;
+        match &result {
+            Ok(()) => manifest.remove(),
+            Err(e) => {
+                // Keep what was received so the sender can resume
+                if let Err(save_error) = manifest.save() {
+                    warn!(
+                        "Failed to save resume state of {}: {}",
+                        transfer.id(),
+                        save_error
+                    );
+                }
+                debug!("Transfer {} interrupted: {}", transfer.id(), e);
+            }
+        }
+        let finished = self.finish(transfer.id(), &result).await;
+        result?;
+
+        // Acknowledge only once the status is final, so the sender never
+        // observes completion before we do
+        if let Err(e) = conn
+            .send_message(&Message::Complete {
+                transfer_id: transfer.id().to_string(),
+            })
+            .await
+        {
+            warn!("Failed to acknowledge transfer {}: {}", transfer.id(), e);
+        }
+
+        Ok(finished.unwrap_or(transfer))
+    }
+
+    /// Subscribe to events of all transfers handled by this instance and its clones
+    pub fn subscribe(&self) -> broadcast::Receiver<TransferEvent> {
+        self.events.subscribe()
+    }
+
+    /// Mark a registered transfer that never started as cancelled, e.g. while queued
+    pub(crate) async fn cancel_pending(&self, transfer_id: &str) -> Result<()> {
+        if let Some(dir) = &self.state_dir {
+            resume::remove_outgoing(dir, transfer_id);
+        }
+        let result = Err(TsunaguError::Cancelled("before it started".into()));
+        self.finish(transfer_id, &result).await;
+        result
+    }
+
+    /// Snapshot of the transfer with `transfer_id`, if known to this instance
+    pub async fn get_transfer(&self, transfer_id: &str) -> Option<TransferInfo> {
+        self.transfers
+            .read()
+            .await
+            .get(transfer_id)
+            .map(|state| state.info.clone())
+    }
+
+    /// Snapshot of every transfer known to this instance
+    pub async fn list_transfers(&self) -> Vec<TransferInfo> {
+        self.transfers
+            .read()
+            .await
+            .values()
+            .map(|state| state.info.clone())
+            .collect()
+    }
+
+    async fn check_peer(&self, peer: &DeviceInfo) -> Result<()> {
+        if let Some(trust_store) = &self.trust_store {
+            trust_store.read().await.check(peer)?;
+        }
+        Ok(())
+    }
+
+    /// Track `info`, refusing an id another transfer still uses
+    ///
+    /// Only a failed transfer may be registered again, by the same sender in
+    /// the same direction, which is how a resumed offer arrives.
+    async fn register(
+        &self,
+        info: TransferInfo,
+        direction: Direction,
+    ) -> Result<watch::Receiver<TransferControl>> {
+        let mut transfers = self.transfers.write().await;
+        if let Some(existing) = transfers.get(info.id()) {
+            let resumable = existing.direction == direction
+                && existing.info.sender().id() == info.sender().id()
+                && matches!(existing.info.status(), TransferStatus::Failed(_));
+            if !resumable {
+                return Err(TsunaguError::Transfer(format!(
+                    "Transfer {} already exists",
+                    info.id()
+                )));
+            }
+        }
+        let (control, receiver) = watch::channel(TransferControl::Running);
+        let paths = match direction {
+            Direction::Sent => info
+                .files()
+                .iter()
+                .map(|file| file.source_path().map(Path::to_path_buf))
+                .collect(),
+            Direction::Received => vec![None; info.files().len()],
+        };
+        transfers.insert(
+            info.id().to_string(),
+            TransferState {
+                info,
+                control,
+                running: false,
+                direction,
+                started_at: SystemTime::now(),
+                paths,
+                code: None,
+            },
+        );
+        Ok(receiver)
+    }
+
+    /// Get rid of the partial files of a cancelled transfer as configured
+    fn drop_partials(&self, manifest: &mut ResumeManifest) {
+        match self.cancelled_partials {
+            CancelledPartials::Delete => manifest.discard(),
+            CancelledPartials::Quarantine => {
+                manifest.quarantine(&self.transfer_dir.join(resume::QUARANTINE_DIR))
+            }
+        }
+    }
+
+    fn emit(&self, event: TransferEvent) {
+        // Nobody listening is fine
+        let _ = self.events.send(event);
+    }
+
+    async fn report_progress(&self, tracker: &mut ProgressTracker, bytes: u64) {
+        if let Some(progress) = tracker.advance(bytes) {
+            self.set_status(
+                &progress.transfer_id,
+                TransferStatus::InProgress(progress.percent()),
+            )
+            .await;
+            self.emit(TransferEvent::Progress(progress));
+        }
+    }
+
+    async fn set_running(&self, transfer_id: &str, running: bool) {
+        if let Some(state) = self.transfers.write().await.get_mut(transfer_id) {
+            state.running = running;
+        }
+    }
+
+    async fn set_file_hash(&self, transfer_id: &str, index: usize, hash: String) {
+        if let Some(state) = self.transfers.write().await.get_mut(transfer_id) {
+            state.info.set_file_hash(index, hash);
+        }
+    }
+
+    async fn set_saved_path(&self, transfer_id: &str, index: usize, path: PathBuf) {
+        if let Some(state) = self.transfers.write().await.get_mut(transfer_id) {
+            if let Some(slot) = state.paths.get_mut(index) {
+                *slot = Some(path);
+            }
+        }
+    }
+
+    async fn set_status(&self, transfer_id: &str, status: TransferStatus) {
+        if let Some(state) = self.transfers.write().await.get_mut(transfer_id) {
+            state.info.set_status(status);
+        }
+    }
+
+    async fn set_control(
+        &self,
+        transfer_info: &TransferInfo,
+        value: TransferControl,
+    ) -> Result<()> {
+        let transfers = self.transfers.read().await;
+        let state = transfers
+            .get(transfer_info.id())
+            .ok_or_else(|| unknown_transfer(transfer_info.id()))?;
+        state.control.send_replace(value);
+        Ok(())
+    }
+
+    /// Record the final status of a transfer, returning its final state
+    pub(crate) async fn finish(
+        &self,
+        transfer_id: &str,
+        result: &Result<()>,
+    ) -> Option<TransferInfo> {
+        let status = match result {
+            Ok(()) => TransferStatus::Completed,
+            Err(TsunaguError::Cancelled(_)) => TransferStatus::Cancelled,
+            Err(e) => TransferStatus::Failed(e.to_string()),
+        };
+        info!("Transfer {} finished: {:?}", transfer_id, status);
+
+        let finished = {
+            let mut transfers = self.transfers.write().await;
+            let finished = transfers.get_mut(transfer_id).map(|state| {
+                state.info.set_status(status.clone());
+                HistoryEntry::new(
+                    state.info.clone(),
+                    state.direction,
+                    state.started_at,
+                    state.paths.clone(),
+                )
+            });
+            // Failed transfers stay registered so they can be resumed
+            if self.on_finish.is_some() && !matches!(status, TransferStatus::Failed(_)) {
+                transfers.remove(transfer_id);
+            }
+            finished
+        };
+        let info = finished.as_ref().map(|entry| entry.transfer().clone());
+        if let (Some(hook), Some(entry)) = (&self.on_finish, finished) {
+            hook(entry);
+        }
+        self.emit(TransferEvent::Finished {
+            transfer_id: transfer_id.to_string(),
+            status,
+        });
+        info
+    }
+
+    async fn send_files<S>(
+        &self,
+        conn: &mut Connection<S>,
+        transfer: &TransferInfo,
+        code: Option<&ShareCode>,
+        control: &mut watch::Receiver<TransferControl>,
+    ) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        let identity = self.identity.as_deref();
+        let peer = match code {
+            Some(code) => {
+                conn.handshake_with_code(
+                    &self.local_device,
+                    identity,
+                    SessionRole::Initiator,
+                    &code.key_mask()?,
+                )
+                .await?
+            }
+            None => {
+                conn.handshake(&self.local_device, identity, SessionRole::Initiator)
+                    .await?
+            }
+        };
+        if transfer.receiver().has_verified_id() && peer.id() != transfer.receiver().id() {
+            return Err(TsunaguError::Device(format!(
+                "Connected to {} ({}) instead of {} ({})",
+                peer.name(),
+                peer.id(),
+                transfer.receiver().name(),
+                transfer.receiver().id()
+            )));
+        }
+        self.check_peer(&peer).await?;
+        conn.send_message(&Message::Offer {
+            transfer: Box::new(transfer.clone()),
+        })
+        .await?;
+
+        let reply = match recv_frame(conn, control).await? {
+            Some(Frame::Message(message)) => message,
+            Some(Frame::Chunk(_)) => return Err(unexpected_chunk()),
+            None => return Err(send_cancel(conn, transfer.id()).await),
+        };
+        let (resume, declined) = match reply {
+            Message::Accept {
+                resume, declined, ..
+            } => (resume, declined),
+            Message::Reject { reason, .. } => return Err(TsunaguError::Rejected(reason)),
+            Message::Error { message } => return Err(TsunaguError::Transfer(message)),
+            other => return Err(unexpected(&other)),
+        };
+        if !declined.is_empty() {
+            info!(
+                "{} declined {} of {} file(s)",
+                peer.name(),
+                declined.len(),
+                transfer.files().len()
+            );
+        }
+
+        self.emit(TransferEvent::Started(Box::new(transfer.clone())));
+        let mut tracker = ProgressTracker::new(transfer);
+        let mut buf = vec![0u8; CHUNK_SIZE];
+
+        for (index, file) in transfer.files().iter().enumerate() {
+            let path = file.source_path().ok_or_else(|| {
+                TsunaguError::Transfer(format!("No source path for file {}", file.name()))
+            })?;
+            tracker.start_file(index, file.size());
+            if declined.contains(&index) {
+                debug!("Skipping {}, declined by the receiver", file.name());
+                tracker.skip(file.size());
+                continue;
+            }
+            let point = resume.iter().find(|point| point.index == index);
+            if point.is_some_and(|point| point.complete) {
+                debug!("Skipping {}, already received", file.name());
+                tracker.skip(file.size());
+                self.emit(TransferEvent::FileCompleted {
+                    transfer_id: transfer.id().to_string(),
+                    index,
+                });
+                continue;
+            }
+
+            let offset = resume_offset(path, point).await?.min(file.size());
+            let mut source = File::open(path).await?;
+            let mut hasher = FileHasher::new();
+            if offset > 0 {
+                debug!("Resuming {} at {} bytes", file.name(), offset);
+                // The hash covers the whole file, including what is not sent again
+                hasher.update_from(&mut source, offset).await?;
+                tracker.skip(offset);
+            }
+
+            conn.send_message(&Message::FileStart { index, offset })
+                .await?;
+            loop {
+                if wait_while_paused(control).await.is_err() {
+                    return Err(send_cancel(conn, transfer.id()).await);
+                }
+                // The receiver only speaks up during the transfer to stop it
+                match conn.try_recv().await? {
+                    None => {}
+                    Some(Frame::Message(Message::Cancel { .. })) => {
+                        return Err(TsunaguError::Cancelled("by the receiver".into()))
+                    }
+                    Some(Frame::Message(Message::Error { message })) => {
+                        return Err(TsunaguError::Transfer(message))
+                    }
+                    Some(Frame::Message(other)) => return Err(unexpected(&other)),
+                    Some(Frame::Chunk(_)) => return Err(unexpected_chunk()),
+                }
+
+                let n = source.read(&mut buf).await?;
+                if n == 0 {
+                    break;
+                }
+                hasher.update(&buf[..n]);
+                conn.send_chunk(&buf[..n]).await?;
+                self.report_progress(&mut tracker, n as u64).await;
+            }
+
+            let hash = hasher.finish();
+            if let Some(expected) = file.hash() {
+                // Already sent once, the file must not have changed since
+                if let Err(e) = integrity::check(file.name(), expected, &hash) {
+                    conn.send_message(&Message::Error {
+                        message: e.to_string(),
+                    })
+                    .await
+                    .ok();
+                    return Err(e);
+                }
+            }
+            self.set_file_hash(transfer.id(), index, hash.clone()).await;
+            conn.send_message(&Message::FileEnd { index, hash }).await?;
+            self.emit(TransferEvent::FileCompleted {
+                transfer_id: transfer.id().to_string(),
+                index,
+            });
+        }
+
+        conn.send_message(&Message::Complete {
+            transfer_id: transfer.id().to_string(),
+        })
+        .await?;
+
+        match recv_frame(conn, control).await? {
+            Some(Frame::Message(Message::Complete { .. })) => Ok(()),
+            Some(Frame::Message(Message::Cancel { .. })) => {
+                Err(TsunaguError::Cancelled("by the receiver".into()))
+            }
+            Some(Frame::Message(Message::Error { message })) => {
+                Err(TsunaguError::Transfer(message))
+            }
+            Some(Frame::Message(other)) => Err(unexpected(&other)),
+            Some(Frame::Chunk(_)) => Err(unexpected_chunk()),
+            None => Err(send_cancel(conn, transfer.id()).await),
+        }
+    }
+
+    async fn receive_files<S>(
+        &self,
+        conn: &mut Connection<S>,
+        transfer: &TransferInfo,
+        control: &mut watch::Receiver<TransferControl>,
+        manifest: &mut ResumeManifest,
+    ) -> Result<()>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        self.emit(TransferEvent::Started(Box::new(transfer.clone())));
+        let mut tracker = ProgressTracker::new(transfer);
+        let mut completed: Vec<bool> = (0..transfer.files().len())
+            .map(|index| manifest.is_complete(index) || manifest.is_declined(index))
+            .collect();
+        for (file, _) in transfer
+            .files()
+            .iter()
+            .zip(&completed)
+            .filter(|(_, done)| **done)
+        {
+            tracker.skip(file.size());
+        }
+
+        loop {
+            let message = match recv_frame(conn, control).await? {
+                Some(Frame::Message(message)) => message,
+                Some(Frame::Chunk(_)) => return Err(unexpected_chunk()),
+                None => {
+                    self.drop_partials(manifest);
+                    return Err(send_cancel(conn, transfer.id()).await);
+                }
+            };
+            match message {
+                Message::FileStart { index, offset } => {
+                    let file = transfer.files().get(index).ok_or_else(|| {
+                        TsunaguError::Transfer(format!("Invalid file index: {}", index))
+                    })?;
+                    manifest.start_file(index, offset)?;
+                    let path = manifest.partial_path(index);
+                    if let Some(parent) = path.parent() {
+                        tokio::fs::create_dir_all(parent).await?;
+                    }
+                    debug!(
+                        "Writing {} to {} from {} bytes",
+                        file.name(),
+                        path.display(),
+                        offset
+                    );
+
+                    let mut target = OpenOptions::new()
+                        .write(true)
+                        .create(true)
+                        .truncate(false)
+                        .open(&path)
+                        .await?;
+                    target.set_len(offset).await?;
+                    target.seek(std::io::SeekFrom::Start(offset)).await?;
+                    tracker.start_file(index, file.size());
+                    tracker.skip(offset);
+                    let mut chunk_hasher = ChunkHasher::new();
+                    let mut file_hasher = FileHasher::new();
+                    if offset > 0 {
+                        file_hasher
+                            .update_from(&mut File::open(&path).await?, offset)
+                            .await?;
+                    }
+                    let mut written = offset;
+                    let sent_hash = loop {
+                        let Some(frame) = recv_frame(conn, control).await? else {
+                            drop(target);
+                            self.drop_partials(manifest);
+                            return Err(send_cancel(conn, transfer.id()).await);
+                        };
+                        match frame {
+                            Frame::Chunk(data) => {
+                                written += data.len() as u64;
+                                if written > file.size() {
+                                    return Err(TsunaguError::Transfer(format!(
+                                        "Received more data than announced for {}",
+                                        file.name()
+                                    )));
+                                }
+                                target.write_all(&data).await?;
+                                file_hasher.update(&data);
+                                for hash in chunk_hasher.update(&data) {
+                                    manifest.push_hash(index, hash);
+                                    if manifest
+                                        .chunk_count(index)
+                                        .is_multiple_of(MANIFEST_SAVE_CHUNKS)
+                                    {
+                                        target.flush().await?;
+                                        manifest.save()?;
+                                    }
+                                }
+                                self.report_progress(&mut tracker, data.len() as u64).await;
+                            }
+                            Frame::Message(Message::FileEnd { index: end, hash })
+                                if end == index =>
+                            {
+                                break hash
+                            }
+                            Frame::Message(Message::Cancel { .. }) => {
+                                drop(target);
+                                self.drop_partials(manifest);
+                                return Err(TsunaguError::Cancelled("by the sender".into()));
+                            }
+                            Frame::Message(Message::Error { message }) => {
+                                return Err(TsunaguError::Transfer(message))
+                            }
+                            Frame::Message(other) => return Err(unexpected(&other)),
+                        }
+                    };
+                    target.flush().await?;
+                    drop(target);
+
+                    if written != file.size() {
+                        return Err(TsunaguError::Transfer(format!(
+                            "Size mismatch for {}: expected {} bytes, received {}",
+                            file.name(),
+                            file.size(),
+                            written
+                        )));
+                    }
+
+                    // Corrupt data never reaches the final path
+                    let hash = file_hasher.finish();
+                    let verified =
+                        integrity::check(file.name(), &sent_hash, &hash).and_then(|()| match file
+                            .hash()
+                        {
+                            Some(offered) => integrity::check(file.name(), offered, &hash),
+                            None => Ok(()),
+                        });
+                    if let Err(e) = verified {
+                        manifest.reset_file(index);
+                        conn.send_message(&Message::Error {
+                            message: e.to_string(),
+                        })
+                        .await
+                        .ok();
+                        return Err(e);
+                    }
+                    self.set_file_hash(transfer.id(), index, hash).await;
+
+                    // Files already in the save directory are never replaced
+                    let final_path =
+                        tree::move_to_free_path(&path, &manifest.final_path(index)).await?;
+                    self.set_saved_path(transfer.id(), index, final_path).await;
+                    manifest.complete_file(index);
+                    manifest.save()?;
+                    completed[index] = true;
+                    self.emit(TransferEvent::FileCompleted {
+                        transfer_id: transfer.id().to_string(),
+                        index,
+                    });
+                }
+                Message::Complete { .. } => {
+                    if completed.iter().any(|done| !done) {
+                        let message = "Transfer completed with missing files".to_string();
+                        conn.send_message(&Message::Error {
+                            message: message.clone(),
+                        })
+                        .await
+                        .ok();
+                        return Err(TsunaguError::Transfer(message));
+                    }
+                    return Ok(());
+                }
+                Message::Cancel { .. } => {
+                    self.drop_partials(manifest);
+                    return Err(TsunaguError::Cancelled("by the sender".into()));
+                }
+                other => return Err(unexpected(&other)),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl FileTransfer for TcpFileTransfer {
+    async fn init_transfer(
+        &mut self,
+        files: Vec<FileInfo>,
+        receiver: DeviceInfo,
+    ) -> Result<TransferInfo> {
+        if files.is_empty() {
+            return Err(TsunaguError::Transfer("No files to transfer".into()));
+        }
+
+        let transfer = TransferInfo::new(self.local_device.clone(), receiver, files);
+        self.register(transfer.clone(), Direction::Sent).await?;
+        debug!("Initialized transfer {}", transfer.id());
+        Ok(transfer)
+    }
+
+    async fn start_transfer(&mut self, transfer_info: &TransferInfo) -> Result<()> {
+        let (mut control, code) = {
+            let mut transfers = self.transfers.write().await;
+            let state = transfers
+                .get_mut(transfer_info.id())
+                .ok_or_else(|| unknown_transfer(transfer_info.id()))?;
+            if state.running {
+                return Err(TsunaguError::Transfer(format!(
+                    "Transfer {} is already running",
+                    transfer_info.id()
+                )));
+            }
+            state.running = true;
+            state.started_at = SystemTime::now();
+            (state.control.subscribe(), state.code.clone())
+        };
+        if let (Some(dir), None) = (&self.state_dir, &code) {
+            if let Err(e) = resume::save_outgoing(dir, transfer_info) {
+                warn!(
+                    "Transfer {} cannot be resumed after a restart: {}",
+                    transfer_info.id(),
+                    e
+                );
+            }
+        }
+
+        info!(
+            "Connecting to {} at {}",
+            transfer_info.receiver().name(),
+            transfer_info.receiver().addresses_text()
+        );
+
+        let result = async {
+            let stream = network::connect(transfer_info.receiver(), CONNECT_TIMEOUT).await?;
+            let mut conn = Connection::new(stream);
+            self.send_files(&mut conn, transfer_info, code.as_ref(), &mut control)
+                .await
+        }
+        .await;
+
+        if let Err(e) = &result {
+            warn!("Transfer {} failed: {}", transfer_info.id(), e);
+        }
+        // Interrupted transfers are kept so they can be resumed later
+        let resumable = matches!(
+            &result,
+            Err(e) if !matches!(e, TsunaguError::Rejected(_) | TsunaguError::Cancelled(_))
+        );
+        if let (Some(dir), false) = (&self.state_dir, resumable) {
+            resume::remove_outgoing(dir, transfer_info.id());
+        }
+        self.set_running(transfer_info.id(), false).await;
+        self.finish(transfer_info.id(), &result).await;
+        result
+    }
+
+    async fn pause_transfer(&mut self, transfer_info: &TransferInfo) -> Result<()> {
+        self.set_control(transfer_info, TransferControl::Paused)
+            .await
+    }
+
+    /// Resume a paused transfer, or reconnect and continue an interrupted one
+    ///
+    /// Continuing an interrupted transfer only sends the data the receiver
+    /// does not have yet and returns once it has completed.
+    async fn resume_transfer(&mut self, transfer_info: &TransferInfo) -> Result<()> {
+        self.set_control(transfer_info, TransferControl::Running)
+            .await?;
+        let running = self
+            .transfers
+            .read()
+            .await
+            .get(transfer_info.id())
+            .is_some_and(|state| state.running);
+        if running {
+            return Ok(());
+        }
+        self.start_transfer(transfer_info).await
+    }
+
+    async fn cancel_transfer(&mut self, transfer_info: &TransferInfo) -> Result<()> {
+        self.set_control(transfer_info, TransferControl::Cancelled)
+            .await
+    }
+
+    async fn get_transfer_status(&self, transfer_info: &TransferInfo) -> Result<TransferStatus> {
+        self.transfers
+            .read()
+            .await
+            .get(transfer_info.id())
+            .map(|state| state.info.status().clone())
+            .ok_or_else(|| unknown_transfer(transfer_info.id()))
+    }
+}
+
+/// Block while the transfer is paused, fail if it has been cancelled
+async fn wait_while_paused(control: &mut watch::Receiver<TransferControl>) -> Result<()> {
+    loop {
+        let state = *control.borrow_and_update();
+        match state {
+            TransferControl::Running => return Ok(()),
+            TransferControl::Cancelled => return Err(TsunaguError::Cancelled("locally".into())),
+            TransferControl::Paused => control
+                .changed()
+                .await
+                .map_err(|_| TsunaguError::Transfer("Transfer control closed".into()))?,
+        }
+    }
+}
+
+/// Wait for the next frame, `None` once the transfer is cancelled locally
+async fn recv_frame<S>(
+    conn: &mut Connection<S>,
+    control: &mut watch::Receiver<TransferControl>,
+) -> Result<Option<Frame>>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    let cancelled = async {
+        // A dropped control sender means nobody can cancel anymore
+        if control
+            .wait_for(|state| *state == TransferControl::Cancelled)
+            .await
+            .is_err()
+        {
+            std::future::pending::<()>().await;
+        }
+    };
+    tokio::select! {
+        frame = conn.recv() => frame.map(Some),
+        () = cancelled => Ok(None),
+    }
+}
+
+/// Tell the peer the transfer was cancelled locally, returning the error to report
+async fn send_cancel<S>(conn: &mut Connection<S>, transfer_id: &str) -> TsunaguError
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    info!("Cancelling transfer {}", transfer_id);
+    conn.send_message(&Message::Cancel {
+        transfer_id: transfer_id.to_string(),
+    })
+    .await
+    .ok();
+    TsunaguError::Cancelled("locally".into())
+}
+
+fn unexpected_chunk() -> TsunaguError {
+    TsunaguError::Network("Unexpected chunk frame, expected a message".into())
+}
+
+/// Where to continue sending `path`, given what the receiver already has
+async fn resume_offset(path: &Path, point: Option<&ResumePoint>) -> Result<u64> {
+    let Some(point) = point else {
+        return Ok(0);
+    };
+    // The file may have changed since, only trust blocks that still match
+    let blocks = resume::verified_blocks(path, &point.chunk_hashes).await?;
+    Ok(blocks as u64 * CHUNK_SIZE as u64)
+}
+
+fn unknown_transfer(id: &str) -> TsunaguError {
+    TsunaguError::Transfer(format!("Unknown transfer: {}", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::DeviceInfo;
+    use std::net::Ipv4Addr;
+    use tokio::net::{TcpListener, TcpStream};
+
+    fn test_device(name: &str, port: u16) -> DeviceInfo {
+        DeviceInfo::new(
+            name.to_string(),
+            "Test Model".to_string(),
+            [Ipv4Addr::LOCALHOST.into()],
+            port,
+            "TestOS".to_string(),
+            "1.0.0".to_string(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_tcp_file_transfer_creation() {
+        let device_info = DeviceInfo::new(
+            "Test Device".to_string(),
+            "Test Model".to_string(),
+            [Ipv4Addr::LOCALHOST.into()],
+            8000,
+            "TestOS".to_string(),
+            "1.0.0".to_string(),
+        );
+        let transfer_dir = PathBuf::from("/tmp/transfer");
+        let tcp_transfer = TcpFileTransfer::new(device_info, transfer_dir);
+
+        assert_eq!(tcp_transfer.local_device.name(), "Test Device");
+        assert_eq!(tcp_transfer.transfer_dir, PathBuf::from("/tmp/transfer"));
+    }
+
+    #[tokio::test]
+    async fn test_send_and_receive() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let target_dir = tempfile::tempdir().unwrap();
+
+        let small = source_dir.path().join("small.txt");
+        let large = source_dir.path().join("large.bin");
+        std::fs::write(&small, b"Hello, Tsunagu!").unwrap();
+        let large_data: Vec<u8> = (0..CHUNK_SIZE * 3 + 17).map(|i| i as u8).collect();
+        std::fs::write(&large, &large_data).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let receiver = TcpFileTransfer::new(
+            test_device("Receiver", port),
+            target_dir.path().to_path_buf(),
+        );
+        let receive_task = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            receiver.receive(stream, &AcceptAll).await
+        });
+
+        let mut sender = TcpFileTransfer::new(test_device("Sender", 0), PathBuf::new());
+        let files = vec![
+            FileInfo::from_path(&small).unwrap(),
+            FileInfo::from_path(&large).unwrap(),
+        ];
+        let transfer = sender
+            .init_transfer(files, test_device("Receiver", port))
+            .await
+            .unwrap();
+        assert_eq!(
+            sender.get_transfer_status(&transfer).await.unwrap(),
+            TransferStatus::Pending
+        );
+
+        let mut events = sender.subscribe();
+        sender.start_transfer(&transfer).await.unwrap();
+        let received = receive_task.await.unwrap().unwrap();
+
+        assert_eq!(received.id(), transfer.id());
+        assert_eq!(received.status(), &TransferStatus::Completed);
+        assert_eq!(
+            sender.get_transfer_status(&transfer).await.unwrap(),
+            TransferStatus::Completed
+        );
+        assert_eq!(
+            std::fs::read(target_dir.path().join("small.txt")).unwrap(),
+            b"Hello, Tsunagu!"
+        );
+        assert_eq!(
+            std::fs::read(target_dir.path().join("large.bin")).unwrap(),
+            large_data
+        );
+        for (sent, received) in sender.list_transfers().await[0]
+            .files()
+            .iter()
+            .zip(received.files())
+        {
+            assert!(sent.hash().is_some());
+            assert_eq!(sent.hash(), received.hash());
+        }
+
+        let mut completed_files = 0;
+        let mut last_progress = None;
+        let finished = loop {
+            match events.try_recv().unwrap() {
+                TransferEvent::Progress(progress) => last_progress = Some(progress),
+                TransferEvent::FileCompleted { .. } => completed_files += 1,
+                TransferEvent::Finished { status, .. } => break status,
+                TransferEvent::Started(info) => assert_eq!(info.id(), transfer.id()),
+            }
+        };
+        assert_eq!(finished, TransferStatus::Completed);
+        assert_eq!(completed_files, 2);
+        let last_progress = last_progress.unwrap();
+        assert_eq!(last_progress.bytes_transferred, transfer.total_size());
+        assert_eq!(last_progress.file_index, 1);
+    }
+
+    #[tokio::test]
+    async fn test_existing_file_is_not_replaced() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let target_dir = tempfile::tempdir().unwrap();
+        let path = source_dir.path().join("notes.txt");
+        std::fs::write(&path, b"theirs").unwrap();
+        std::fs::write(target_dir.path().join("notes.txt"), b"mine").unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let receiver = TcpFileTransfer::new(
+            test_device("Receiver", port),
+            target_dir.path().to_path_buf(),
+        );
+        let receive_task = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            receiver.receive(stream, &AcceptAll).await
+        });
+
+        let mut sender = TcpFileTransfer::new(test_device("Sender", 0), PathBuf::new());
+        let transfer = sender
+            .init_transfer(
+                vec![FileInfo::from_path(&path).unwrap()],
+                test_device("Receiver", port),
+            )
+            .await
+            .unwrap();
+        sender.start_transfer(&transfer).await.unwrap();
+        receive_task.await.unwrap().unwrap();
+
+        assert_eq!(
+            std::fs::read(target_dir.path().join("notes.txt")).unwrap(),
+            b"mine"
+        );
+        assert_eq!(
+            std::fs::read(target_dir.path().join("notes (1).txt")).unwrap(),
+            b"theirs"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_send_by_code() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let target_dir = tempfile::tempdir().unwrap();
+        let path = source_dir.path().join("note.txt");
+        std::fs::write(&path, b"Hello by code").unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let code = ShareCode::generate(&[Ipv4Addr::LOCALHOST.into()], port).unwrap();
+        let receiver = TcpFileTransfer::new(
+            test_device("Receiver", port),
+            target_dir.path().to_path_buf(),
+        );
+        let receive_code = code.clone();
+        let receive_task = tokio::spawn(async move {
+            receiver
+                .receive_with_code(listener, &receive_code, &AcceptAll)
+                .await
+        });
+
+        // A connection that hangs up without a Hello does not use up the code
+        drop(TcpStream::connect(("127.0.0.1", port)).await.unwrap());
+
+        let mut sender = TcpFileTransfer::new(test_device("Sender", 0), PathBuf::new());
+        let typed = ShareCode::parse(&code.to_string()).unwrap();
+        let transfer = sender
+            .init_code_transfer(vec![FileInfo::from_path(&path).unwrap()], typed)
+            .await
+            .unwrap();
+        sender.start_transfer(&transfer).await.unwrap();
+
+        let received = receive_task.await.unwrap().unwrap();
+        assert_eq!(received.status(), &TransferStatus::Completed);
+        assert_eq!(received.sender().name(), "Sender");
+        assert_eq!(
+            std::fs::read(target_dir.path().join("note.txt")).unwrap(),
+            b"Hello by code"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_wrong_code_uses_it_up() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let path = source_dir.path().join("note.txt");
+        std::fs::write(&path, b"Hello by code").unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let addresses = [Ipv4Addr::LOCALHOST.into()];
+        let code = ShareCode::generate(&addresses, port).unwrap();
+        let mut wrong = ShareCode::generate(&addresses, port).unwrap();
+        while wrong == code {
+            wrong = ShareCode::generate(&addresses, port).unwrap();
+        }
+        let receiver = TcpFileTransfer::new(test_device("Receiver", port), PathBuf::new());
+        let receive_task = tokio::spawn(async move {
+            receiver
+                .receive_with_code(listener, &code, &AcceptAll)
+                .await
+        });
+
+        let mut sender = TcpFileTransfer::new(test_device("Sender", 0), PathBuf::new());
+        let transfer = sender
+            .init_code_transfer(vec![FileInfo::from_path(&path).unwrap()], wrong)
+            .await
+            .unwrap();
+        let sent = sender.start_transfer(&transfer).await;
+        assert!(matches!(sent, Err(TsunaguError::Encryption(_))));
+
+        // The receiver gave up and stopped listening
+        let received = receive_task.await.unwrap();
+        assert!(matches!(received, Err(TsunaguError::Encryption(_))));
+        assert!(TcpStream::connect(("127.0.0.1", port)).await.is_err());
+    }
+
+    /// Forwards connections to `target`, cutting the first one after `limit`
+    /// bytes from the sender. Returns the port and the bytes each connection
+    /// carried from the sender.
+    async fn flaky_proxy(target: u16, limit: u64) -> (u16, Arc<RwLock<Vec<u64>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let forwarded = Arc::new(RwLock::new(Vec::new()));
+        let counts = forwarded.clone();
+        tokio::spawn(async move {
+            loop {
+                let (inbound, _) = listener.accept().await.unwrap();
+                let outbound = TcpStream::connect(("127.0.0.1", target)).await.unwrap();
+                let first = counts.read().await.is_empty();
+                let (mut in_read, mut in_write) = inbound.into_split();
+                let (mut out_read, mut out_write) = outbound.into_split();
+                tokio::spawn(async move { tokio::io::copy(&mut out_read, &mut in_write).await });
+
+                let limit = if first { limit } else { u64::MAX };
+                let sent = tokio::io::copy(&mut (&mut in_read).take(limit), &mut out_write)
+                    .await
+                    .unwrap_or_default();
+                counts.write().await.push(sent);
+                // Dropping both halves closes the connection
+            }
+        });
+        (port, forwarded)
+    }
+
+    #[tokio::test]
+    async fn test_resume_after_disconnect() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let target_dir = tempfile::tempdir().unwrap();
+        let state_dir = tempfile::tempdir().unwrap();
+
+        let path = source_dir.path().join("large.bin");
+        let data: Vec<u8> = (0..CHUNK_SIZE * 130 + 99).map(|i| (i * 7) as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let receiver_port = listener.local_addr().unwrap().port();
+        let receiver = TcpFileTransfer::new(
+            test_device("Receiver", receiver_port),
+            target_dir.path().to_path_buf(),
+        );
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let receiver = receiver.clone();
+                tokio::spawn(async move { receiver.receive(stream, &AcceptAll).await });
+            }
+        });
+        let (proxy_port, forwarded) = flaky_proxy(receiver_port, CHUNK_SIZE as u64 * 100).await;
+
+        let sender_device = test_device("Sender", 0);
+        let mut sender = TcpFileTransfer::new(sender_device.clone(), PathBuf::new())
+            .with_state_dir(state_dir.path().to_path_buf());
+        let transfer = sender
+            .init_transfer(
+                vec![FileInfo::from_path(&path).unwrap()],
+                test_device("Receiver", proxy_port),
+            )
+            .await
+            .unwrap();
+        assert!(sender.start_transfer(&transfer).await.is_err());
+        assert!(!target_dir.path().join("large.bin").exists());
+
+        // A restarted sender picks the transfer up from its state directory
+        let mut restarted = TcpFileTransfer::new(sender_device, PathBuf::new())
+            .with_state_dir(state_dir.path().to_path_buf());
+        let interrupted = restarted.load_interrupted().await.unwrap();
+        assert_eq!(interrupted.len(), 1);
+        assert_eq!(interrupted[0].id(), transfer.id());
+
+        // The receiver may still be noticing the dropped connection
+        let mut attempts = 0;
+        while let Err(e) = restarted.resume_transfer(&interrupted[0]).await {
+            attempts += 1;
+            assert!(attempts < 20, "resume failed: {}", e);
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        assert_eq!(
+            std::fs::read(target_dir.path().join("large.bin")).unwrap(),
+            data
+        );
+        // The proxy records a connection once the sender has closed it
+        while forwarded.read().await.len() < attempts + 2 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(*forwarded.read().await.last().unwrap() < data.len() as u64 / 2);
+        assert!(restarted.load_interrupted().await.unwrap().is_empty());
+        // Nothing but the received file is left behind
+        assert_eq!(std::fs::read_dir(target_dir.path()).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_file_is_rejected() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let target_dir = tempfile::tempdir().unwrap();
+        let path = source_dir.path().join("data.txt");
+        std::fs::write(&path, b"original").unwrap();
+
+        let receiver =
+            TcpFileTransfer::new(test_device("Receiver", 0), target_dir.path().to_path_buf());
+        let (a, b) = tokio::io::duplex(64 * 1024);
+        let receive_task = {
+            let receiver = receiver.clone();
+            tokio::spawn(async move { receiver.receive(b, &AcceptAll).await })
+        };
+
+        // A sender whose data does not match the hash it reports
+        let sender = test_device("Sender", 0);
+        let transfer = TransferInfo::new(
+            sender.clone(),
+            test_device("Receiver", 0),
+            vec![FileInfo::from_path(&path).unwrap()],
+        );
+        let mut conn = Connection::new(a);
+        conn.handshake(&sender, None, SessionRole::Initiator)
+            .await
+            .unwrap();
+        conn.send_message(&Message::Offer {
+            transfer: Box::new(transfer.clone()),
+        })
+        .await
+        .unwrap();
+        assert!(matches!(
+            conn.recv_message().await.unwrap(),
+            Message::Accept { .. }
+        ));
+        conn.send_message(&Message::FileStart {
+            index: 0,
+            offset: 0,
+        })
+        .await
+        .unwrap();
+        conn.send_chunk(b"tampered").await.unwrap();
+        let mut hasher = FileHasher::new();
+        hasher.update(b"original");
+        conn.send_message(&Message::FileEnd {
+            index: 0,
+            hash: hasher.finish(),
+        })
+        .await
+        .unwrap();
+
+        assert!(matches!(
+            conn.recv_message().await.unwrap(),
+            Message::Error { .. }
+        ));
+        assert!(matches!(
+            receive_task.await.unwrap(),
+            Err(TsunaguError::IntegrityMismatch { .. })
+        ));
+        assert!(matches!(
+            receiver.get_transfer_status(&transfer).await.unwrap(),
+            TransferStatus::Failed(_)
+        ));
+        assert!(!target_dir.path().join("data.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_send_directory_tree() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let target_dir = tempfile::tempdir().unwrap();
+        let root = source_dir.path().join("photos");
+        std::fs::create_dir_all(root.join("2024/summer")).unwrap();
+        std::fs::write(root.join("index.txt"), b"index").unwrap();
+        std::fs::write(root.join("2024/summer/beach.jpg"), b"sand").unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let receiver = TcpFileTransfer::new(
+            test_device("Receiver", port),
+            target_dir.path().to_path_buf(),
+        );
+        let receive_task = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            receiver.receive(stream, &AcceptAll).await
+        });
+
+        let mut sender = TcpFileTransfer::new(test_device("Sender", 0), PathBuf::new());
+        let files = collect_files(&[root], &CollectOptions::default()).unwrap();
+        let transfer = sender
+            .init_transfer(files, test_device("Receiver", port))
+            .await
+            .unwrap();
+        sender.start_transfer(&transfer).await.unwrap();
+        receive_task.await.unwrap().unwrap();
+
+        let received = target_dir.path().join("photos");
+        assert_eq!(std::fs::read(received.join("index.txt")).unwrap(), b"index");
+        assert_eq!(
+            std::fs::read(received.join("2024").join("summer").join("beach.jpg")).unwrap(),
+            b"sand"
+        );
+    }
+
+    /// Accepts only the files at the given indices
+    struct AcceptOnly(Vec<usize>);
+
+    #[async_trait]
+    impl AcceptancePolicy for AcceptOnly {
+        async fn decide(&self, _transfer: &TransferInfo) -> OfferDecision {
+            OfferDecision::AcceptPartial(self.0.clone())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_partial_accept() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let target_dir = tempfile::tempdir().unwrap();
+        let paths: Vec<PathBuf> = ["a.txt", "b.txt", "c.txt"]
+            .iter()
+            .map(|name| {
+                let path = source_dir.path().join(name);
+                std::fs::write(&path, name.as_bytes()).unwrap();
+                path
+            })
+            .collect();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let receiver = TcpFileTransfer::new(
+            test_device("Receiver", port),
+            target_dir.path().to_path_buf(),
+        );
+        let receive_task = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            receiver.receive(stream, &AcceptOnly(vec![0, 2])).await
+        });
+
+        let mut sender = TcpFileTransfer::new(test_device("Sender", 0), PathBuf::new());
+        let files = paths
+            .iter()
+            .map(|p| FileInfo::from_path(p).unwrap())
+            .collect();
+        let transfer = sender
+            .init_transfer(files, test_device("Receiver", port))
+            .await
+            .unwrap();
+        sender.start_transfer(&transfer).await.unwrap();
+        let received = receive_task.await.unwrap().unwrap();
+
+        assert_eq!(received.status(), &TransferStatus::Completed);
+        assert!(target_dir.path().join("a.txt").exists());
+        assert!(!target_dir.path().join("b.txt").exists());
+        assert!(target_dir.path().join("c.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_receiver_cancels() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let target_dir = tempfile::tempdir().unwrap();
+        let path = source_dir.path().join("data.bin");
+        std::fs::write(&path, vec![7u8; CHUNK_SIZE * 3]).unwrap();
+
+        let mut receiver =
+            TcpFileTransfer::new(test_device("Receiver", 0), target_dir.path().to_path_buf());
+        let mut events = receiver.subscribe();
+        let (a, b) = tokio::io::duplex(64 * 1024);
+        let receive_task = {
+            let receiver = receiver.clone();
+            tokio::spawn(async move { receiver.receive(b, &AcceptAll).await })
+        };
+
+        let sender = test_device("Sender", 0);
+        let transfer = TransferInfo::new(
+            sender.clone(),
+            test_device("Receiver", 0),
+            vec![FileInfo::from_path(&path).unwrap()],
+        );
+        let mut conn = Connection::new(a);
+        conn.handshake(&sender, None, SessionRole::Initiator)
+            .await
+            .unwrap();
+        conn.send_message(&Message::Offer {
+            transfer: Box::new(transfer.clone()),
+        })
+        .await
+        .unwrap();
+        conn.recv_message().await.unwrap();
+        conn.send_message(&Message::FileStart {
+            index: 0,
+            offset: 0,
+        })
+        .await
+        .unwrap();
+        conn.send_chunk(&[7u8; CHUNK_SIZE]).await.unwrap();
+
+        while !matches!(events.recv().await.unwrap(), TransferEvent::Progress(_)) {}
+        receiver.cancel_transfer(&transfer).await.unwrap();
+
+        assert!(matches!(
+            conn.recv_message().await.unwrap(),
+            Message::Cancel { .. }
+        ));
+        assert!(matches!(
+            receive_task.await.unwrap(),
+            Err(TsunaguError::Cancelled(_))
+        ));
+        assert_eq!(
+            receiver.get_transfer_status(&transfer).await.unwrap(),
+            TransferStatus::Cancelled
+        );
+        // Partial data and the resume manifest are gone
+        assert_eq!(std::fs::read_dir(target_dir.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_sender_cancels() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let target_dir = tempfile::tempdir().unwrap();
+        let path = source_dir.path().join("big.bin");
+        std::fs::write(&path, vec![1u8; CHUNK_SIZE * 256]).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let receiver = TcpFileTransfer::new(
+            test_device("Receiver", port),
+            target_dir.path().to_path_buf(),
+        )
+        .with_cancelled_partials(CancelledPartials::Quarantine);
+        let mut receiver_events = receiver.subscribe();
+        let receive_task = {
+            let receiver = receiver.clone();
+            tokio::spawn(async move {
+                let (stream, _) = listener.accept().await.unwrap();
+                receiver.receive(stream, &AcceptAll).await
+            })
+        };
+
+        let mut sender = TcpFileTransfer::new(test_device("Sender", 0), PathBuf::new());
+        let files = vec![FileInfo::from_path(&path).unwrap()];
+        let transfer = sender
+            .init_transfer(files, test_device("Receiver", port))
+            .await
+            .unwrap();
+        let send_task = {
+            let mut sender = sender.clone();
+            let transfer = transfer.clone();
+            tokio::spawn(async move { sender.start_transfer(&transfer).await })
+        };
+
+        while !matches!(
+            receiver_events.recv().await.unwrap(),
+            TransferEvent::Progress(_)
+        ) {}
+        sender.cancel_transfer(&transfer).await.unwrap();
+
+        assert!(matches!(
+            send_task.await.unwrap(),
+            Err(TsunaguError::Cancelled(_))
+        ));
+        assert!(matches!(
+            receive_task.await.unwrap(),
+            Err(TsunaguError::Cancelled(_))
+        ));
+        assert_eq!(
+            sender.get_transfer_status(&transfer).await.unwrap(),
+            TransferStatus::Cancelled
+        );
+        assert_eq!(
+            receiver.get_transfer_status(&transfer).await.unwrap(),
+            TransferStatus::Cancelled
+        );
+        let quarantined = target_dir
+            .path()
+            .join(resume::QUARANTINE_DIR)
+            .join(transfer.id())
+            .join("big.bin");
+        assert!(quarantined.exists());
+        assert!(!target_dir.path().join("big.bin").exists());
+    }
+
+    #[tokio::test]
+    async fn test_unsafe_path_is_rejected() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let target_dir = tempfile::tempdir().unwrap();
+        let path = source_dir.path().join("data.txt");
+        std::fs::write(&path, b"data").unwrap();
+
+        let receiver =
+            TcpFileTransfer::new(test_device("Receiver", 0), target_dir.path().join("inbox"));
+        let (a, b) = tokio::io::duplex(64 * 1024);
+        let receive_task = tokio::spawn(async move { receiver.receive(b, &AcceptAll).await });
+
+        let sender = test_device("Sender", 0);
+        let file = FileInfo::from_path(&path)
+            .unwrap()
+            .with_relative_path("../escape.txt".to_string());
+        let transfer = TransferInfo::new(sender.clone(), test_device("Receiver", 0), vec![file]);
+        let mut conn = Connection::new(a);
+        conn.handshake(&sender, None, SessionRole::Initiator)
+            .await
+            .unwrap();
+        conn.send_message(&Message::Offer {
+            transfer: Box::new(transfer),
+        })
+        .await
+        .unwrap();
+
+        assert!(matches!(
+            conn.recv_message().await.unwrap(),
+            Message::Reject { .. }
+        ));
+        assert!(matches!(
+            receive_task.await.unwrap(),
+            Err(TsunaguError::Rejected(_))
+        ));
+        assert!(!target_dir.path().join("escape.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_transfer_id_is_rejected() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let target_dir = tempfile::tempdir().unwrap();
+        let path = source_dir.path().join("data.txt");
+        std::fs::write(&path, b"data").unwrap();
+
+        let receiver =
+            TcpFileTransfer::new(test_device("Receiver", 0), target_dir.path().to_path_buf());
+        let sender = test_device("Sender", 0);
+        let transfer = TransferInfo::new(
+            sender.clone(),
+            test_device("Receiver", 0),
+            vec![FileInfo::from_path(&path).unwrap()],
+        );
+
+        // The first offer is accepted and then waits for data
+        let (a, b) = tokio::io::duplex(64 * 1024);
+        let first = receiver.clone();
+        let first_task = tokio::spawn(async move { first.receive(b, &AcceptAll).await });
+        let mut first_conn = Connection::new(a);
+        first_conn
+            .handshake(&sender, None, SessionRole::Initiator)
+            .await
+            .unwrap();
+        first_conn
+            .send_message(&Message::Offer {
+                transfer: Box::new(transfer.clone()),
+            })
+            .await
+            .unwrap();
+        assert!(matches!(
+            first_conn.recv_message().await.unwrap(),
+            Message::Accept { .. }
+        ));
+
+        let (a, b) = tokio::io::duplex(64 * 1024);
+        let second = receiver.clone();
+        let second_task = tokio::spawn(async move { second.receive(b, &AcceptAll).await });
+        let mut conn = Connection::new(a);
+        conn.handshake(&sender, None, SessionRole::Initiator)
+            .await
+            .unwrap();
+        conn.send_message(&Message::Offer {
+            transfer: Box::new(transfer.clone()),
+        })
+        .await
+        .unwrap();
+        assert!(matches!(
+            conn.recv_message().await.unwrap(),
+            Message::Reject { .. }
+        ));
+        assert!(matches!(
+            second_task.await.unwrap(),
+            Err(TsunaguError::Rejected(_))
+        ));
+
+        // The first transfer was left alone and can still be cancelled
+        assert_eq!(
+            receiver.get_transfer(transfer.id()).await.unwrap().status(),
+            &TransferStatus::Pending
+        );
+        receiver.clone().cancel_transfer(&transfer).await.unwrap();
+        assert!(matches!(
+            first_task.await.unwrap(),
+            Err(TsunaguError::Cancelled(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_transfer_id_is_rejected() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let target_dir = tempfile::tempdir().unwrap();
+        let path = source_dir.path().join("data.txt");
+        std::fs::write(&path, b"data").unwrap();
+
+        let receiver =
+            TcpFileTransfer::new(test_device("Receiver", 0), target_dir.path().join("inbox"));
+        let (a, b) = tokio::io::duplex(64 * 1024);
+        let receive_task = tokio::spawn(async move { receiver.receive(b, &AcceptAll).await });
+
+        // Joined to the inbox, this id would put the manifest next to it
+        let sender = test_device("Sender", 0);
+        let transfer = TransferInfo::new(
+            sender.clone(),
+            test_device("Receiver", 0),
+            vec![FileInfo::from_path(&path).unwrap()],
+        );
+        let mut offer = serde_json::to_value(&transfer).unwrap();
+        offer["id"] = "/../escape".into();
+        let transfer: TransferInfo = serde_json::from_value(offer).unwrap();
+        let mut conn = Connection::new(a);
+        conn.handshake(&sender, None, SessionRole::Initiator)
+            .await
+            .unwrap();
+        conn.send_message(&Message::Offer {
+            transfer: Box::new(transfer),
+        })
+        .await
+        .unwrap();
+
+        assert!(matches!(
+            conn.recv_message().await.unwrap(),
+            Message::Reject { .. }
+        ));
+        assert!(matches!(
+            receive_task.await.unwrap(),
+            Err(TsunaguError::Rejected(_))
+        ));
+        assert_eq!(std::fs::read_dir(target_dir.path()).unwrap().count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_start_unknown_transfer() {
+        let mut sender = TcpFileTransfer::new(test_device("Sender", 0), PathBuf::new());
+        let transfer =
+            TransferInfo::new(test_device("Sender", 0), test_device("Receiver", 1), vec![]);
+        assert!(sender.start_transfer(&transfer).await.is_err());
+        assert!(sender.get_transfer_status(&transfer).await.is_err());
+    }
+}
@@ -0,0 +1,409 @@
+use crate::config::Config;
+use crate::models::TransferInfo;
+use crate::trust::{TrustLevel, TrustStore};
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, RwLock};
+use tracing::info;
+
+/// Answer to an incoming transfer offer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OfferDecision {
+    Accept,
+    /// Accept only the files at these indices of the offer
+    AcceptPartial(Vec<usize>),
+    Reject(String),
+}
+
+impl OfferDecision {
+    /// Restrict the decision to the files in `allowed`
+    fn restrict(self, allowed: &[usize]) -> Self {
+        let accepted: Vec<usize> = match self {
+            OfferDecision::Accept => allowed.to_vec(),
+            OfferDecision::AcceptPartial(indices) => indices
+                .into_iter()
+                .filter(|index| allowed.contains(index))
+                .collect(),
+            reject @ OfferDecision::Reject(_) => return reject,
+        };
+        if accepted.is_empty() {
+            OfferDecision::Reject("No files accepted".into())
+        } else {
+            OfferDecision::AcceptPartial(accepted)
+        }
+    }
+}
+
+/// Decides whether an incoming transfer offer is accepted
+#[async_trait]
+pub trait AcceptancePolicy: Send + Sync {
+    async fn decide(&self, transfer: &TransferInfo) -> OfferDecision;
+}
+
+/// Accept every offer
+pub struct AcceptAll;
+
+#[async_trait]
+impl AcceptancePolicy for AcceptAll {
+    async fn decide(&self, _transfer: &TransferInfo) -> OfferDecision {
+        OfferDecision::Accept
+    }
+}
+
+/// Reject every offer
+pub struct RejectAll;
+
+#[async_trait]
+impl AcceptancePolicy for RejectAll {
+    async fn decide(&self, _transfer: &TransferInfo) -> OfferDecision {
+        OfferDecision::Reject("Receiver does not accept transfers".into())
+    }
+}
+
+/// Accept offers from trusted devices, defer everything else to `fallback`
+pub struct AcceptTrusted {
+    trust_store: Arc<RwLock<TrustStore>>,
+    fallback: Arc<dyn AcceptancePolicy>,
+}
+
+impl AcceptTrusted {
+    pub fn new(trust_store: Arc<RwLock<TrustStore>>, fallback: Arc<dyn AcceptancePolicy>) -> Self {
+        Self {
+            trust_store,
+            fallback,
+        }
+    }
+}
+
+#[async_trait]
+impl AcceptancePolicy for AcceptTrusted {
+    async fn decide(&self, transfer: &TransferInfo) -> OfferDecision {
+        let trust = self.trust_store.read().await.check(transfer.sender());
+        match trust {
+            Ok(TrustLevel::Trusted) => {
+                info!(
+                    "Auto-accepting transfer from trusted {}",
+                    transfer.sender().name()
+                );
+                OfferDecision::Accept
+            }
+            Ok(TrustLevel::Unknown) => self.fallback.decide(transfer).await,
+            Err(e) => OfferDecision::Reject(e.to_string()),
+        }
+    }
+}
+
+/// Rules that turn offers down before anyone is asked
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OfferRules {
+    /// Largest total size accepted, in bytes
+    pub max_size: Option<u64>,
+    /// File extensions that are never accepted, without the dot
+    pub blocked_extensions: Vec<String>,
+    /// Reject offers from devices that are not trusted
+    pub reject_unknown_devices: bool,
+}
+
+impl OfferRules {
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            max_size: config.limits.max_offer_size,
+            blocked_extensions: config.receive.blocked_extensions.clone(),
+            reject_unknown_devices: config.receive.reject_unknown_devices,
+        }
+    }
+
+    fn is_blocked(&self, name: &str) -> bool {
+        let Some((_, extension)) = name.rsplit_once('.') else {
+            return false;
+        };
+        self.blocked_extensions.iter().any(|blocked| {
+            blocked
+                .trim_start_matches('.')
+                .eq_ignore_ascii_case(extension)
+        })
+    }
+}
+
+/// Apply [`OfferRules`] and pass what is left of the offer to `next`
+///
+/// Files with a blocked extension are left out, the offer is only rejected
+/// when none remain.
+pub struct RulePolicy {
+    rules: OfferRules,
+    trust_store: Arc<RwLock<TrustStore>>,
+    next: Arc<dyn AcceptancePolicy>,
+}
+
+impl RulePolicy {
+    pub fn new(
+        rules: OfferRules,
+        trust_store: Arc<RwLock<TrustStore>>,
+        next: Arc<dyn AcceptancePolicy>,
+    ) -> Self {
+        Self {
+            rules,
+            trust_store,
+            next,
+        }
+    }
+}
+
+#[async_trait]
+impl AcceptancePolicy for RulePolicy {
+    async fn decide(&self, transfer: &TransferInfo) -> OfferDecision {
+        if self.rules.reject_unknown_devices {
+            let trust = self.trust_store.read().await.check(transfer.sender());
+            match trust {
+                Ok(TrustLevel::Trusted) => {}
+                Ok(TrustLevel::Unknown) => {
+                    return OfferDecision::Reject("Only paired devices may send files".into())
+                }
+                Err(e) => return OfferDecision::Reject(e.to_string()),
+            }
+        }
+
+        if let Some(max_size) = self.rules.max_size {
+            if transfer.total_size() > max_size {
+                return OfferDecision::Reject(format!(
+                    "Offer of {} bytes exceeds the limit of {} bytes",
+                    transfer.total_size(),
+                    max_size
+                ));
+            }
+        }
+
+        let allowed: Vec<usize> = transfer
+            .files()
+            .iter()
+            .enumerate()
+            // The file is saved under its relative path, the name is only for show
+            .filter(|(_, file)| {
+                let saved_name = file.relative_path().rsplit('/').next().unwrap_or_default();
+                !self.rules.is_blocked(saved_name)
+            })
+            .map(|(index, _)| index)
+            .collect();
+        if allowed.is_empty() {
+            return OfferDecision::Reject("File type not accepted".into());
+        }
+
+        let decision = self.next.decide(transfer).await;
+        if allowed.len() == transfer.files().len() {
+            decision
+        } else {
+            info!(
+                "Leaving out {} blocked file(s) of transfer {}",
+                transfer.files().len() - allowed.len(),
+                transfer.id()
+            );
+            decision.restrict(&allowed)
+        }
+    }
+}
+
+/// Offer waiting for an answer from the user, see [`ChannelPolicy`]
+#[derive(Debug)]
+pub struct PendingOffer {
+    transfer: TransferInfo,
+    reply: oneshot::Sender<OfferDecision>,
+}
+
+impl PendingOffer {
+    pub fn transfer(&self) -> &TransferInfo {
+        &self.transfer
+    }
+
+    /// The sender stopped waiting for an answer, e.g. after the timeout
+    pub fn is_expired(&self) -> bool {
+        self.reply.is_closed()
+    }
+
+    /// Answer the offer, dropping it without an answer rejects it
+    pub fn respond(self, decision: OfferDecision) {
+        // The sender may have given up waiting
+        self.reply.send(decision).ok();
+    }
+}
+
+/// Hand offers to a UI through a channel and wait for its answer
+///
+/// Offers that are not answered within the timeout are rejected.
+pub struct ChannelPolicy {
+    offers: mpsc::Sender<PendingOffer>,
+    timeout: Duration,
+}
+
+impl ChannelPolicy {
+    pub fn new(timeout: Duration) -> (Self, mpsc::Receiver<PendingOffer>) {
+        let (offers, receiver) = mpsc::channel(16);
+        (Self { offers, timeout }, receiver)
+    }
+}
+
+#[async_trait]
+impl AcceptancePolicy for ChannelPolicy {
+    async fn decide(&self, transfer: &TransferInfo) -> OfferDecision {
+        let (reply, answer) = oneshot::channel();
+        let offer = PendingOffer {
+            transfer: transfer.clone(),
+            reply,
+        };
+        if self.offers.send(offer).await.is_err() {
+            return OfferDecision::Reject("Receiver is not accepting offers".into());
+        }
+        match tokio::time::timeout(self.timeout, answer).await {
+            Ok(Ok(decision)) => decision,
+            Ok(Err(_)) => OfferDecision::Reject("Offer was dismissed".into()),
+            Err(_) => OfferDecision::Reject("Offer was not answered in time".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::DeviceIdentity;
+    use crate::models::{DeviceInfo, FileInfo};
+    use std::net::Ipv4Addr;
+
+    fn device(name: &str) -> DeviceInfo {
+        DeviceInfo::new(
+            name.to_string(),
+            "Test Model".to_string(),
+            [Ipv4Addr::LOCALHOST.into()],
+            8000,
+            "TestOS".to_string(),
+            "1.0.0".to_string(),
+        )
+        .with_public_key(DeviceIdentity::generate().unwrap().public_key())
+    }
+
+    #[tokio::test]
+    async fn test_accept_trusted() {
+        let trusted = device("Trusted");
+        let mut store = TrustStore::in_memory();
+        store.trust(&trusted).unwrap();
+        let policy = AcceptTrusted::new(Arc::new(RwLock::new(store)), Arc::new(RejectAll));
+
+        let from_trusted = TransferInfo::new(trusted, device("Me"), vec![]);
+        assert_eq!(policy.decide(&from_trusted).await, OfferDecision::Accept);
+
+        let from_stranger = TransferInfo::new(device("Stranger"), device("Me"), vec![]);
+        assert!(matches!(
+            policy.decide(&from_stranger).await,
+            OfferDecision::Reject(_)
+        ));
+
+        // Same name as the trusted device, different key
+        let from_impostor = TransferInfo::new(device("Trusted"), device("Me"), vec![]);
+        let policy = AcceptTrusted::new(Arc::clone(&policy.trust_store), Arc::new(AcceptAll));
+        assert!(matches!(
+            policy.decide(&from_impostor).await,
+            OfferDecision::Reject(_)
+        ));
+    }
+
+    fn offer(sender: DeviceInfo, files: &[(&str, u64)]) -> TransferInfo {
+        let files = files
+            .iter()
+            .map(|(name, size)| FileInfo::new(name.to_string(), *size, String::new(), 0))
+            .collect();
+        TransferInfo::new(sender, device("Me"), files)
+    }
+
+    #[tokio::test]
+    async fn test_rule_policy() {
+        let trusted = device("Trusted");
+        let mut store = TrustStore::in_memory();
+        store.trust(&trusted).unwrap();
+        let store = Arc::new(RwLock::new(store));
+        let rules = OfferRules {
+            max_size: Some(100),
+            blocked_extensions: vec!["exe".to_string(), ".bat".to_string()],
+            reject_unknown_devices: true,
+        };
+        let policy = RulePolicy::new(rules, store, Arc::new(AcceptAll));
+
+        let stranger = offer(device("Stranger"), &[("a.txt", 1)]);
+        assert!(matches!(
+            policy.decide(&stranger).await,
+            OfferDecision::Reject(_)
+        ));
+
+        let too_large = offer(trusted.clone(), &[("a.txt", 60), ("b.txt", 60)]);
+        assert!(matches!(
+            policy.decide(&too_large).await,
+            OfferDecision::Reject(_)
+        ));
+
+        let mixed = offer(
+            trusted.clone(),
+            &[("setup.EXE", 1), ("a.txt", 1), ("run.bat", 1), ("b", 1)],
+        );
+        assert_eq!(
+            policy.decide(&mixed).await,
+            OfferDecision::AcceptPartial(vec![1, 3])
+        );
+
+        let blocked = offer(trusted.clone(), &[("setup.exe", 1)]);
+        assert!(matches!(
+            policy.decide(&blocked).await,
+            OfferDecision::Reject(_)
+        ));
+
+        let fine = offer(trusted.clone(), &[("a.txt", 1)]);
+        assert_eq!(policy.decide(&fine).await, OfferDecision::Accept);
+
+        // A harmless name does not hide what the file is saved as
+        let disguised = TransferInfo::new(
+            trusted,
+            device("Me"),
+            vec![
+                FileInfo::new("a.txt".to_string(), 1, String::new(), 0)
+                    .with_relative_path("dir/a.exe".to_string()),
+                FileInfo::new("b.exe".to_string(), 1, String::new(), 0)
+                    .with_relative_path("dir.exe/b.txt".to_string()),
+            ],
+        );
+        assert_eq!(
+            policy.decide(&disguised).await,
+            OfferDecision::AcceptPartial(vec![1])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_channel_policy() {
+        let (policy, mut offers) = ChannelPolicy::new(Duration::from_secs(5));
+        let transfer = offer(device("Sender"), &[("a.txt", 1), ("b.txt", 1)]);
+
+        let ui = tokio::spawn(async move {
+            let pending = offers.recv().await.unwrap();
+            assert_eq!(pending.transfer().files().len(), 2);
+            pending.respond(OfferDecision::AcceptPartial(vec![1]));
+            // Dismissed without an answer
+            drop(offers.recv().await.unwrap());
+        });
+        assert_eq!(
+            policy.decide(&transfer).await,
+            OfferDecision::AcceptPartial(vec![1])
+        );
+        assert!(matches!(
+            policy.decide(&transfer).await,
+            OfferDecision::Reject(_)
+        ));
+        ui.await.unwrap();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_channel_policy_timeout() {
+        let (policy, mut offers) = ChannelPolicy::new(Duration::from_secs(30));
+        let transfer = offer(device("Sender"), &[("a.txt", 1)]);
+        assert!(matches!(
+            policy.decide(&transfer).await,
+            OfferDecision::Reject(_)
+        ));
+        assert!(offers.recv().await.unwrap().is_expired());
+    }
+}
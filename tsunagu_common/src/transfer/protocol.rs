@@ -0,0 +1,598 @@
+use crate::encryption::{KeyExchange, KeyMask, SessionCipher, SessionRole};
+use crate::error::TsunaguError;
+use crate::identity::{self, DeviceIdentity};
+use crate::models::{DeviceInfo, TransferInfo};
+use crate::Result;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Version of the wire protocol, exchanged in the handshake
+pub const PROTOCOL_VERSION: u16 = 6;
+
+/// Size of the file data carried by a single chunk frame
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Upper bound for a single frame, protects against bogus length prefixes
+const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// Frame kind byte plus the counter and tag added by the session cipher
+const FRAME_OVERHEAD: usize = 1 + 8 + 16;
+
+const FRAME_MESSAGE: u8 = 0;
+const FRAME_CHUNK: u8 = 1;
+
+/// Control messages exchanged between sender and receiver
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Message {
+    Hello {
+        version: u16,
+        device: DeviceInfo,
+        public_key: Vec<u8>,
+    },
+    /// Proof that the sender owns the identity key it announced
+    Identity {
+        signature: Option<Vec<u8>>,
+    },
+    Offer {
+        transfer: Box<TransferInfo>,
+    },
+    PairRequest,
+    PairConfirm {
+        accepted: bool,
+    },
+    Accept {
+        transfer_id: String,
+        /// Files the receiver already has data for, when resuming
+        #[serde(default)]
+        resume: Vec<ResumePoint>,
+        /// Indices of offered files the receiver does not want
+        #[serde(default)]
+        declined: Vec<usize>,
+    },
+    Reject {
+        transfer_id: String,
+        reason: String,
+    },
+    FileStart {
+        index: usize,
+        /// Where the data of this file continues, zero unless resuming
+        #[serde(default)]
+        offset: u64,
+    },
+    FileEnd {
+        index: usize,
+        /// SHA-256 of the whole file, hex encoded
+        hash: String,
+    },
+    Complete {
+        transfer_id: String,
+    },
+    Cancel {
+        transfer_id: String,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// Data the receiver kept of a file from an earlier, interrupted attempt
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResumePoint {
+    pub index: usize,
+    /// Hex encoded SHA-256 of every complete [`CHUNK_SIZE`] block received
+    pub chunk_hashes: Vec<String>,
+    /// The file was received completely and needs no data
+    pub complete: bool,
+}
+
+/// A decoded frame
+///
+/// Every frame on the wire is a big-endian `u32` length followed by a one
+/// byte frame kind and the payload. Messages are JSON encoded, chunks carry
+/// raw file data. After the handshake the kind and payload are sealed with
+/// the session cipher.
+#[derive(Debug)]
+pub enum Frame {
+    Message(Message),
+    Chunk(Vec<u8>),
+}
+
+/// A framed connection over any async byte stream
+pub struct Connection<S> {
+    stream: S,
+    cipher: Option<SessionCipher>,
+    /// Bytes read from the stream that do not form a complete frame yet
+    read_buf: Vec<u8>,
+}
+
+impl<S> Connection<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            cipher: None,
+            read_buf: Vec::new(),
+        }
+    }
+
+    /// Give back the stream, dropping any data read but not yet received
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+
+    /// Send a control message
+    pub async fn send_message(&mut self, message: &Message) -> Result<()> {
+        let payload = serde_json::to_vec(message)?;
+        self.write_frame(FRAME_MESSAGE, &payload).await
+    }
+
+    /// Send a chunk of file data
+    pub async fn send_chunk(&mut self, data: &[u8]) -> Result<()> {
+        self.write_frame(FRAME_CHUNK, data).await
+    }
+
+    /// Receive the next frame
+    ///
+    /// Cancel safe: if the future is dropped before it completes, no data is
+    /// lost and the next call continues where it stopped.
+    pub async fn recv(&mut self) -> Result<Frame> {
+        loop {
+            if let Some(body) = self.take_frame()? {
+                return self.decode(body);
+            }
+            self.read_buf.reserve(CHUNK_SIZE + FRAME_OVERHEAD);
+            if self.stream.read_buf(&mut self.read_buf).await? == 0 {
+                return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+            }
+        }
+    }
+
+    /// Receive a frame if one has already arrived, without waiting for one
+    pub async fn try_recv(&mut self) -> Result<Option<Frame>> {
+        tokio::select! {
+            biased;
+            frame = self.recv() => frame.map(Some),
+            () = std::future::ready(()) => Ok(None),
+        }
+    }
+
+    /// Split the next complete frame body off the read buffer
+    fn take_frame(&mut self) -> Result<Option<Vec<u8>>> {
+        let Some(prefix) = self.read_buf.first_chunk::<4>() else {
+            return Ok(None);
+        };
+        let len = u32::from_be_bytes(*prefix) as usize;
+        if len == 0 || len > MAX_FRAME_SIZE + FRAME_OVERHEAD {
+            return Err(TsunaguError::Network(format!(
+                "Invalid frame length: {}",
+                len
+            )));
+        }
+        if self.read_buf.len() < 4 + len {
+            return Ok(None);
+        }
+        let body = self.read_buf[4..4 + len].to_vec();
+        self.read_buf.drain(..4 + len);
+        Ok(Some(body))
+    }
+
+    fn decode(&mut self, mut body: Vec<u8>) -> Result<Frame> {
+        if let Some(cipher) = self.cipher.as_mut() {
+            body = cipher.open(&body)?;
+        }
+
+        let (&kind, payload) = body
+            .split_first()
+            .ok_or_else(|| TsunaguError::Network("Empty frame".into()))?;
+
+        match kind {
+            FRAME_MESSAGE => Ok(Frame::Message(serde_json::from_slice(payload)?)),
+            FRAME_CHUNK => Ok(Frame::Chunk(payload.to_vec())),
+            other => Err(TsunaguError::Network(format!(
+                "Unknown frame kind: {}",
+                other
+            ))),
+        }
+    }
+
+    /// Receive the next frame, which must be a control message
+    pub async fn recv_message(&mut self) -> Result<Message> {
+        match self.recv().await? {
+            Frame::Message(message) => Ok(message),
+            Frame::Chunk(_) => Err(TsunaguError::Network(
+                "Unexpected chunk frame, expected a message".into(),
+            )),
+        }
+    }
+
+    /// Exchange `Hello`s and session keys, returning the peer device
+    ///
+    /// Every frame after the handshake is encrypted and authenticated. If the
+    /// peer announces an identity key it must sign the session with it, so
+    /// the returned device is authenticated whenever it carries a key.
+    pub async fn handshake(
+        &mut self,
+        local_device: &DeviceInfo,
+        local_identity: Option<&DeviceIdentity>,
+        role: SessionRole,
+    ) -> Result<DeviceInfo> {
+        self.handshake_masked(local_device, local_identity, role, None)
+            .await
+    }
+
+    /// Like [`Connection::handshake`], authenticating both ends by a shared secret
+    ///
+    /// The session public keys are masked with `mask`, so the handshake fails
+    /// with an encryption error unless the peer used the same secret. The
+    /// responder stays silent until the initiator's `Hello` arrived. A failure
+    /// that is neither an encryption error nor leaves [`Connection::is_encrypted`]
+    /// set gave the peer nothing to test a guess against.
+    pub async fn handshake_with_code(
+        &mut self,
+        local_device: &DeviceInfo,
+        local_identity: Option<&DeviceIdentity>,
+        role: SessionRole,
+        mask: &KeyMask,
+    ) -> Result<DeviceInfo> {
+        self.handshake_masked(local_device, local_identity, role, Some(mask))
+            .await
+    }
+
+    async fn handshake_masked(
+        &mut self,
+        local_device: &DeviceInfo,
+        local_identity: Option<&DeviceIdentity>,
+        role: SessionRole,
+        mask: Option<&KeyMask>,
+    ) -> Result<DeviceInfo> {
+        let peer_role = match role {
+            SessionRole::Initiator => SessionRole::Responder,
+            SessionRole::Responder => SessionRole::Initiator,
+        };
+        let key_exchange = KeyExchange::new()?;
+        let hello = Message::Hello {
+            version: PROTOCOL_VERSION,
+            device: local_device.clone(),
+            public_key: match mask {
+                Some(mask) => mask.mask(key_exchange.public_key(), role)?,
+                None => key_exchange.public_key().to_vec(),
+            },
+        };
+        let answer_first = mask.is_some() && role == SessionRole::Responder;
+        if !answer_first {
+            self.send_message(&hello).await?;
+        }
+
+        let (peer, peer_key) = match self.recv_message().await? {
+            Message::Hello {
+                version,
+                device,
+                public_key,
+            } if version == PROTOCOL_VERSION => match mask {
+                Some(mask) => (device, mask.unmask(&public_key, peer_role)?),
+                None => (device, public_key),
+            },
+            Message::Hello { version, .. } => {
+                return Err(TsunaguError::Network(format!(
+                    "Unsupported protocol version: {} (expected {})",
+                    version, PROTOCOL_VERSION
+                )))
+            }
+            Message::Error { message } => return Err(TsunaguError::Network(message)),
+            other => return Err(unexpected(&other)),
+        };
+        if answer_first {
+            self.send_message(&hello).await?;
+        }
+        let cipher = key_exchange
+            .finish(&peer_key, role)
+            .map_err(|e| match mask {
+                // A wrong secret can unmask to a key no agreement works with
+                Some(_) => TsunaguError::Encryption("The code does not match".into()),
+                None => e,
+            })?;
+        self.cipher = Some(cipher);
+
+        let signature = local_identity.map(|identity| identity.sign(&self.signed_session(role)));
+        self.send_message(&Message::Identity { signature }).await?;
+
+        // With a mask, only a peer that unmasked our key can seal this
+        let reply = self.recv_message().await.map_err(|e| match e {
+            TsunaguError::Encryption(_) if mask.is_some() => {
+                TsunaguError::Encryption("The code does not match".into())
+            }
+            e => e,
+        })?;
+        match (reply, peer.public_key()) {
+            (Message::Identity { .. }, None) => Ok(peer),
+            (
+                Message::Identity {
+                    signature: Some(signature),
+                },
+                Some(public_key),
+            ) if peer.has_verified_id() => {
+                identity::verify(&public_key, &self.signed_session(peer_role), &signature)
+                    .map_err(|_| {
+                        TsunaguError::Encryption(format!(
+                            "{} failed identity verification",
+                            peer.name()
+                        ))
+                    })?;
+                Ok(peer)
+            }
+            (Message::Identity { .. }, Some(_)) => Err(TsunaguError::Encryption(format!(
+                "{} failed identity verification",
+                peer.name()
+            ))),
+            (other, _) => Err(unexpected(&other)),
+        }
+    }
+
+    /// Read the `Hello` a responder opens the handshake with and hang up
+    ///
+    /// A cheap way to learn which device listens on an address, no keys are
+    /// exchanged and the device is not authenticated.
+    pub async fn identify(mut self) -> Result<DeviceInfo> {
+        match self.recv_message().await? {
+            Message::Hello {
+                version, device, ..
+            } if version == PROTOCOL_VERSION => Ok(device),
+            Message::Hello { version, .. } => Err(TsunaguError::Network(format!(
+                "Unsupported protocol version: {} (expected {})",
+                version, PROTOCOL_VERSION
+            ))),
+            other => Err(unexpected(&other)),
+        }
+    }
+
+    /// What each side signs to bind its identity to this session
+    fn signed_session(&self, role: SessionRole) -> Vec<u8> {
+        let mut message = match role {
+            SessionRole::Initiator => b"tsunagu initiator".to_vec(),
+            SessionRole::Responder => b"tsunagu responder".to_vec(),
+        };
+        if let Some(cipher) = &self.cipher {
+            message.extend_from_slice(cipher.session_id());
+        }
+        message
+    }
+
+    /// Whether the session cipher is active
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    async fn write_frame(&mut self, kind: u8, payload: &[u8]) -> Result<()> {
+        if payload.len() > MAX_FRAME_SIZE {
+            return Err(TsunaguError::Network(format!(
+                "Frame too large: {} bytes",
+                payload.len()
+            )));
+        }
+
+        let mut body = Vec::with_capacity(payload.len() + FRAME_OVERHEAD);
+        body.push(kind);
+        body.extend_from_slice(payload);
+        if let Some(cipher) = self.cipher.as_mut() {
+            body = cipher.seal(&body)?;
+        }
+
+        self.stream.write_u32(body.len() as u32).await?;
+        self.stream.write_all(&body).await?;
+        self.stream.flush().await?;
+        Ok(())
+    }
+}
+
+/// Build the error for a message that is not valid at this point of the protocol
+pub(crate) fn unexpected(message: &Message) -> TsunaguError {
+    TsunaguError::Network(format!("Unexpected message: {:?}", message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn test_device(name: &str) -> DeviceInfo {
+        DeviceInfo::new(
+            name.to_string(),
+            "Test Model".to_string(),
+            [Ipv4Addr::LOCALHOST.into()],
+            8000,
+            "TestOS".to_string(),
+            "1.0.0".to_string(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_frame_roundtrip() {
+        let (a, b) = tokio::io::duplex(1024);
+        let mut sender = Connection::new(a);
+        let mut receiver = Connection::new(b);
+
+        sender
+            .send_message(&Message::Accept {
+                transfer_id: "abc".to_string(),
+                resume: Vec::new(),
+                declined: Vec::new(),
+            })
+            .await
+            .unwrap();
+        sender.send_chunk(b"hello").await.unwrap();
+
+        match receiver.recv().await.unwrap() {
+            Frame::Message(Message::Accept { transfer_id, .. }) => assert_eq!(transfer_id, "abc"),
+            other => panic!("unexpected frame: {:?}", other),
+        }
+        match receiver.recv().await.unwrap() {
+            Frame::Chunk(data) => assert_eq!(data, b"hello"),
+            other => panic!("unexpected frame: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_try_recv_keeps_partial_frames() {
+        let (mut a, b) = tokio::io::duplex(1024);
+        let mut receiver = Connection::new(b);
+        assert!(receiver.try_recv().await.unwrap().is_none());
+
+        // A chunk frame carrying "hello", delivered in two parts
+        let frame = [&6u32.to_be_bytes()[..], &[FRAME_CHUNK], b"hello"].concat();
+        a.write_all(&frame[..7]).await.unwrap();
+        assert!(receiver.try_recv().await.unwrap().is_none());
+        a.write_all(&frame[7..]).await.unwrap();
+        match receiver.try_recv().await.unwrap() {
+            Some(Frame::Chunk(data)) => assert_eq!(data, b"hello"),
+            other => panic!("unexpected frame: {:?}", other),
+        }
+
+        drop(a);
+        assert!(receiver.recv().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_handshake() {
+        let (a, b) = tokio::io::duplex(4096);
+        let mut left = Connection::new(a);
+        let mut right = Connection::new(b);
+
+        let left_device = test_device("Left");
+        let right_device = test_device("Right");
+
+        let (l, r) = tokio::join!(
+            left.handshake(&left_device, None, SessionRole::Initiator),
+            right.handshake(&right_device, None, SessionRole::Responder)
+        );
+        assert_eq!(l.unwrap().name(), "Right");
+        assert_eq!(r.unwrap().name(), "Left");
+        assert!(left.is_encrypted() && right.is_encrypted());
+
+        left.send_chunk(b"secret").await.unwrap();
+        match right.recv().await.unwrap() {
+            Frame::Chunk(data) => assert_eq!(data, b"secret"),
+            other => panic!("unexpected frame: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_frames_encrypted_on_the_wire() {
+        let (a, b) = tokio::io::duplex(4096);
+        let (c, d) = tokio::io::duplex(4096);
+        let mut left = Connection::new(a);
+        let mut right = Connection::new(d);
+
+        // Relay the raw bytes in both directions while recording them
+        let (mut b_read, mut b_write) = tokio::io::split(b);
+        let (mut c_read, mut c_write) = tokio::io::split(c);
+        let upstream = tokio::spawn(async move {
+            let mut seen = Vec::new();
+            let mut buf = [0u8; 1024];
+            loop {
+                let n = b_read.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break seen;
+                }
+                seen.extend_from_slice(&buf[..n]);
+                c_write.write_all(&buf[..n]).await.unwrap();
+            }
+        });
+        tokio::spawn(async move { tokio::io::copy(&mut c_read, &mut b_write).await });
+
+        let left_device = test_device("Left");
+        let right_device = test_device("Right");
+        let (l, r) = tokio::join!(
+            left.handshake(&left_device, None, SessionRole::Initiator),
+            right.handshake(&right_device, None, SessionRole::Responder)
+        );
+        l.unwrap();
+        r.unwrap();
+
+        left.send_chunk(b"top secret payload").await.unwrap();
+        right.recv().await.unwrap();
+        drop(left);
+
+        let seen = upstream.await.unwrap();
+        assert!(!seen
+            .windows(b"top secret payload".len())
+            .any(|w| w == b"top secret payload"));
+    }
+
+    #[tokio::test]
+    async fn test_version_mismatch() {
+        let (a, b) = tokio::io::duplex(4096);
+        let mut left = Connection::new(a);
+        let mut right = Connection::new(b);
+
+        right
+            .send_message(&Message::Hello {
+                version: PROTOCOL_VERSION + 1,
+                device: test_device("Right"),
+                public_key: KeyExchange::new().unwrap().public_key().to_vec(),
+            })
+            .await
+            .unwrap();
+
+        assert!(left
+            .handshake(&test_device("Left"), None, SessionRole::Initiator)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_identify() {
+        let (a, b) = tokio::io::duplex(4096);
+        let mut responder = Connection::new(b);
+        let responding = tokio::spawn(async move {
+            responder
+                .handshake(&test_device("Right"), None, SessionRole::Responder)
+                .await
+        });
+
+        let peer = Connection::new(a).identify().await.unwrap();
+        assert_eq!(peer.name(), "Right");
+        // Hanging up after the Hello fails the responder's handshake
+        assert!(responding.await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_handshake_with_identity() {
+        let (a, b) = tokio::io::duplex(4096);
+        let mut left = Connection::new(a);
+        let mut right = Connection::new(b);
+
+        let left_identity = DeviceIdentity::generate().unwrap();
+        let right_identity = DeviceIdentity::generate().unwrap();
+        let left_device = test_device("Left").with_public_key(left_identity.public_key());
+        let right_device = test_device("Right").with_public_key(right_identity.public_key());
+
+        let (l, r) = tokio::join!(
+            left.handshake(&left_device, Some(&left_identity), SessionRole::Initiator),
+            right.handshake(&right_device, Some(&right_identity), SessionRole::Responder)
+        );
+        assert_eq!(l.unwrap().id(), right_identity.fingerprint());
+        assert_eq!(r.unwrap().id(), left_identity.fingerprint());
+    }
+
+    #[tokio::test]
+    async fn test_handshake_rejects_stolen_identity() {
+        let (a, b) = tokio::io::duplex(4096);
+        let mut left = Connection::new(a);
+        let mut right = Connection::new(b);
+
+        // Announce someone else's key without owning it
+        let victim = DeviceIdentity::generate().unwrap();
+        let impostor = DeviceIdentity::generate().unwrap();
+        let left_device = test_device("Left").with_public_key(victim.public_key());
+        let right_device = test_device("Right");
+
+        let (_, r) = tokio::join!(
+            left.handshake(&left_device, Some(&impostor), SessionRole::Initiator),
+            right.handshake(&right_device, None, SessionRole::Responder)
+        );
+        assert!(r.is_err());
+    }
+}
@@ -0,0 +1,565 @@
+use super::integrity::hex;
+use super::protocol::{ResumePoint, CHUNK_SIZE};
+use super::tree::safe_relative_path;
+use crate::error::TsunaguError;
+use crate::models::TransferInfo;
+use crate::Result;
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path, PathBuf};
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+/// Directory in the save directory that keeps partial files of cancelled transfers
+pub(crate) const QUARANTINE_DIR: &str = ".tsunagu-quarantine";
+
+/// Receiver side progress of a transfer, kept next to the partial files
+///
+/// Only data covered by a chunk hash counts as received. On resume the
+/// partial files are hashed again, so data that never reached the disk
+/// before a crash is simply sent again.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct ResumeManifest {
+    transfer_id: String,
+    sender_id: String,
+    files: Vec<PartialFile>,
+    #[serde(skip)]
+    dir: PathBuf,
+    #[serde(skip)]
+    discarded: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PartialFile {
+    /// Relative path below the transfer directory
+    name: PathBuf,
+    size: u64,
+    chunk_hashes: Vec<String>,
+    complete: bool,
+    /// Left out by the receiver, never written
+    #[serde(default)]
+    declined: bool,
+}
+
+impl ResumeManifest {
+    /// Start tracking a newly accepted transfer written to `dir`
+    pub(crate) fn new(dir: &Path, transfer: &TransferInfo) -> Result<Self> {
+        let files = transfer
+            .files()
+            .iter()
+            .map(|file| {
+                Ok(PartialFile {
+                    name: safe_relative_path(file.relative_path())?,
+                    size: file.size(),
+                    chunk_hashes: Vec::new(),
+                    complete: false,
+                    declined: false,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            transfer_id: transfer.id().to_string(),
+            sender_id: transfer.sender().id().to_string(),
+            files,
+            dir: dir.to_path_buf(),
+            discarded: false,
+        })
+    }
+
+    /// Load the manifest of an earlier attempt of `transfer`, if it matches
+    pub(crate) fn load(dir: &Path, transfer: &TransferInfo) -> Result<Option<Self>> {
+        let data = match std::fs::read(manifest_path(dir, transfer.id())) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let mut manifest: Self = match serde_json::from_slice(&data) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                warn!(
+                    "Ignoring corrupt resume manifest of {}: {}",
+                    transfer.id(),
+                    e
+                );
+                return Ok(None);
+            }
+        };
+        manifest.dir = dir.to_path_buf();
+
+        let same_files = manifest.files.len() == transfer.files().len()
+            && manifest
+                .files
+                .iter()
+                .zip(transfer.files())
+                .all(|(partial, file)| {
+                    partial.size == file.size()
+                        && safe_relative_path(file.relative_path())
+                            .is_ok_and(|name| name == partial.name)
+                });
+        if manifest.sender_id != transfer.sender().id() || !same_files {
+            warn!(
+                "Resume manifest of {} does not match the offer",
+                transfer.id()
+            );
+            return Ok(None);
+        }
+        Ok(Some(manifest))
+    }
+
+    /// Re-check the partial files and report what can be resumed
+    pub(crate) async fn verify(&mut self) -> Result<Vec<ResumePoint>> {
+        let mut points = Vec::new();
+        for index in 0..self.files.len() {
+            if self.files[index].declined {
+                continue;
+            }
+            if self.files[index].complete {
+                if !self.final_path(index).exists() {
+                    self.files[index].complete = false;
+                    self.files[index].chunk_hashes.clear();
+                }
+            } else {
+                let path = self.partial_path(index);
+                let file = &mut self.files[index];
+                let verified = verified_blocks(&path, &file.chunk_hashes).await?;
+                if verified < file.chunk_hashes.len() {
+                    debug!(
+                        "{}: {} of {} chunks verified",
+                        file.name.display(),
+                        verified,
+                        file.chunk_hashes.len()
+                    );
+                }
+                file.chunk_hashes.truncate(verified);
+            }
+
+            let file = &self.files[index];
+            if file.complete || !file.chunk_hashes.is_empty() {
+                points.push(ResumePoint {
+                    index,
+                    chunk_hashes: file.chunk_hashes.clone(),
+                    complete: file.complete,
+                });
+            }
+        }
+        Ok(points)
+    }
+
+    pub(crate) fn is_complete(&self, index: usize) -> bool {
+        self.files[index].complete
+    }
+
+    pub(crate) fn decline(&mut self, index: usize) {
+        self.files[index].declined = true;
+    }
+
+    pub(crate) fn is_declined(&self, index: usize) -> bool {
+        self.files[index].declined
+    }
+
+    /// Indices of the files the receiver left out
+    pub(crate) fn declined(&self) -> Vec<usize> {
+        (0..self.files.len())
+            .filter(|&i| self.files[i].declined)
+            .collect()
+    }
+
+    /// Accept the sender's `offset` for `index`, dropping everything after it
+    pub(crate) fn start_file(&mut self, index: usize, offset: u64) -> Result<()> {
+        let file = &mut self.files[index];
+        let verified = file.chunk_hashes.len() as u64 * CHUNK_SIZE as u64;
+        if file.complete
+            || file.declined
+            || offset > verified.min(file.size)
+            || !offset.is_multiple_of(CHUNK_SIZE as u64)
+        {
+            return Err(TsunaguError::Transfer(format!(
+                "Invalid resume offset {} for {}",
+                offset,
+                file.name.display()
+            )));
+        }
+        file.chunk_hashes
+            .truncate((offset / CHUNK_SIZE as u64) as usize);
+        Ok(())
+    }
+
+    pub(crate) fn push_hash(&mut self, index: usize, hash: String) {
+        self.files[index].chunk_hashes.push(hash);
+    }
+
+    pub(crate) fn chunk_count(&self, index: usize) -> usize {
+        self.files[index].chunk_hashes.len()
+    }
+
+    /// Throw away everything received of `index`
+    pub(crate) fn reset_file(&mut self, index: usize) {
+        self.files[index].chunk_hashes.clear();
+        std::fs::remove_file(self.partial_path(index)).ok();
+    }
+
+    pub(crate) fn complete_file(&mut self, index: usize) {
+        self.files[index].complete = true;
+    }
+
+    /// Hidden file next to the final path that receives the data
+    pub(crate) fn partial_path(&self, index: usize) -> PathBuf {
+        let path = self.final_path(index);
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        path.with_file_name(format!(".{}.{}.part", name, self.transfer_id))
+    }
+
+    pub(crate) fn final_path(&self, index: usize) -> PathBuf {
+        self.dir.join(&self.files[index].name)
+    }
+
+    /// Persist the manifest so the transfer can be resumed later
+    pub(crate) fn save(&self) -> Result<()> {
+        if self.discarded {
+            return Ok(());
+        }
+        crate::paths::write_atomic(
+            &manifest_path(&self.dir, &self.transfer_id),
+            &serde_json::to_vec(self)?,
+        )
+    }
+
+    /// Forget the manifest of a finished transfer
+    pub(crate) fn remove(&mut self) {
+        self.discarded = true;
+        std::fs::remove_file(manifest_path(&self.dir, &self.transfer_id)).ok();
+    }
+
+    /// Move the partial files below `dir`, then forget the manifest
+    ///
+    /// Files keep their relative path in a directory named after the transfer,
+    /// or after a fresh UUID if the peer's id is not one.
+    pub(crate) fn quarantine(&mut self, dir: &Path) {
+        let name = if is_valid_transfer_id(&self.transfer_id) {
+            self.transfer_id.clone()
+        } else {
+            Uuid::new_v4().to_string()
+        };
+        let target_dir = dir.join(name);
+        for index in 0..self.files.len() {
+            let partial = self.partial_path(index);
+            if self.files[index].complete || self.files[index].declined || !partial.exists() {
+                continue;
+            }
+            let name = &self.files[index].name;
+            if !name.components().all(|c| matches!(c, Component::Normal(_))) {
+                warn!("Not quarantining {}: unsafe path", partial.display());
+                continue;
+            }
+            let target = target_dir.join(name);
+            let moved = target
+                .parent()
+                .map_or(Ok(()), std::fs::create_dir_all)
+                .and_then(|()| std::fs::rename(&partial, &target));
+            if let Err(e) = moved {
+                warn!("Failed to quarantine {}: {}", partial.display(), e);
+            }
+        }
+        self.remove();
+    }
+
+    /// Delete the manifest and all partial files, the transfer will not be resumed
+    pub(crate) fn discard(&mut self) {
+        for index in 0..self.files.len() {
+            if !self.files[index].complete && !self.files[index].declined {
+                std::fs::remove_file(self.partial_path(index)).ok();
+            }
+        }
+        self.remove();
+    }
+}
+
+/// Splits a byte stream into [`CHUNK_SIZE`] blocks and hashes each of them
+pub(crate) struct ChunkHasher {
+    context: digest::Context,
+    len: usize,
+}
+
+impl ChunkHasher {
+    pub(crate) fn new() -> Self {
+        Self {
+            context: digest::Context::new(&digest::SHA256),
+            len: 0,
+        }
+    }
+
+    /// Feed `data`, returning the hashes of all blocks it completed
+    pub(crate) fn update(&mut self, mut data: &[u8]) -> Vec<String> {
+        let mut hashes = Vec::new();
+        while !data.is_empty() {
+            let take = (CHUNK_SIZE - self.len).min(data.len());
+            self.context.update(&data[..take]);
+            self.len += take;
+            data = &data[take..];
+
+            if self.len == CHUNK_SIZE {
+                let context =
+                    std::mem::replace(&mut self.context, digest::Context::new(&digest::SHA256));
+                hashes.push(hex(context.finish().as_ref()));
+                self.len = 0;
+            }
+        }
+        hashes
+    }
+}
+
+/// Number of leading blocks of the file at `path` that match `hashes`
+pub(crate) async fn verified_blocks(path: &Path, hashes: &[String]) -> Result<usize> {
+    let mut file = match File::open(path).await {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    for (verified, expected) in hashes.iter().enumerate() {
+        let mut len = 0;
+        while len < CHUNK_SIZE {
+            let n = file.read(&mut buf[len..]).await?;
+            if n == 0 {
+                break;
+            }
+            len += n;
+        }
+        if len < CHUNK_SIZE || hex(digest::digest(&digest::SHA256, &buf).as_ref()) != *expected {
+            return Ok(verified);
+        }
+    }
+    Ok(hashes.len())
+}
+
+/// Outgoing transfer persisted so it can be resumed after a restart
+#[derive(Serialize, Deserialize)]
+struct OutgoingTransfer {
+    transfer: TransferInfo,
+    sources: Vec<Option<PathBuf>>,
+}
+
+pub(crate) fn save_outgoing(dir: &Path, transfer: &TransferInfo) -> Result<()> {
+    let record = OutgoingTransfer {
+        transfer: transfer.clone(),
+        sources: transfer
+            .files()
+            .iter()
+            .map(|f| f.source_path().map(Path::to_path_buf))
+            .collect(),
+    };
+    crate::paths::write_atomic(
+        &dir.join(format!("{}.json", transfer.id())),
+        &serde_json::to_vec(&record)?,
+    )
+}
+
+pub(crate) fn remove_outgoing(dir: &Path, transfer_id: &str) {
+    std::fs::remove_file(dir.join(format!("{}.json", transfer_id))).ok();
+}
+
+pub(crate) fn load_outgoing(dir: &Path) -> Result<Vec<TransferInfo>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut transfers = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("json") {
+            continue;
+        }
+        let record: OutgoingTransfer = match serde_json::from_slice(&std::fs::read(&path)?) {
+            Ok(record) => record,
+            Err(e) => {
+                warn!("Ignoring corrupt transfer record {}: {}", path.display(), e);
+                continue;
+            }
+        };
+
+        let mut transfer = record.transfer;
+        let files = transfer
+            .files()
+            .iter()
+            .zip(record.sources)
+            .map(|(file, source)| match source {
+                Some(source) => file.clone().with_source_path(source),
+                None => file.clone(),
+            })
+            .collect();
+        transfer.set_files(files);
+        transfers.push(transfer);
+    }
+    Ok(transfers)
+}
+
+/// Whether `id` is a transfer id as [`TransferInfo::new`] makes them
+///
+/// Ids name the manifest and partial files, anything else could point
+/// outside the save directory.
+pub(crate) fn is_valid_transfer_id(id: &str) -> bool {
+    Uuid::try_parse(id).is_ok_and(|uuid| uuid.to_string() == id)
+}
+
+fn manifest_path(dir: &Path, transfer_id: &str) -> PathBuf {
+    dir.join(format!(".{}.tsunagu-resume", transfer_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{DeviceInfo, FileInfo};
+    use std::net::Ipv4Addr;
+
+    fn transfer(dir: &Path, data: &[u8]) -> TransferInfo {
+        let path = dir.join("data.bin");
+        std::fs::write(&path, data).unwrap();
+        let device = DeviceInfo::new(
+            "Test".to_string(),
+            "Test Model".to_string(),
+            [Ipv4Addr::LOCALHOST.into()],
+            8000,
+            "TestOS".to_string(),
+            "1.0.0".to_string(),
+        );
+        TransferInfo::new(
+            device.clone(),
+            device,
+            vec![FileInfo::from_path(&path).unwrap()],
+        )
+    }
+
+    #[test]
+    fn test_chunk_hasher_ignores_frame_boundaries() {
+        let data: Vec<u8> = (0..CHUNK_SIZE * 2 + 10).map(|i| i as u8).collect();
+
+        let mut whole = ChunkHasher::new();
+        let expected = whole.update(&data);
+        assert_eq!(expected.len(), 2);
+
+        let mut pieces = ChunkHasher::new();
+        let hashes: Vec<String> = data.chunks(1000).flat_map(|c| pieces.update(c)).collect();
+        assert_eq!(hashes, expected);
+    }
+
+    #[tokio::test]
+    async fn test_verify_truncates_at_corruption() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let target_dir = tempfile::tempdir().unwrap();
+        let data: Vec<u8> = (0..CHUNK_SIZE * 3 + 5).map(|i| i as u8).collect();
+        let transfer = transfer(source_dir.path(), &data);
+
+        let mut manifest = ResumeManifest::new(target_dir.path(), &transfer).unwrap();
+        for hash in ChunkHasher::new().update(&data) {
+            manifest.push_hash(0, hash);
+        }
+        manifest.save().unwrap();
+
+        // Only two blocks made it to disk intact
+        let mut partial = data[..CHUNK_SIZE * 3].to_vec();
+        partial[CHUNK_SIZE * 2] ^= 0xff;
+        std::fs::write(manifest.partial_path(0), &partial).unwrap();
+
+        let mut loaded = ResumeManifest::load(target_dir.path(), &transfer)
+            .unwrap()
+            .unwrap();
+        let points = loaded.verify().await.unwrap();
+        assert_eq!(points.len(), 1);
+        assert_eq!(points[0].chunk_hashes.len(), 2);
+        assert!(!points[0].complete);
+
+        assert!(loaded.start_file(0, CHUNK_SIZE as u64 * 3).is_err());
+        loaded.start_file(0, CHUNK_SIZE as u64).unwrap();
+        assert_eq!(loaded.chunk_count(0), 1);
+
+        loaded.discard();
+        assert!(!manifest.partial_path(0).exists());
+        assert!(ResumeManifest::load(target_dir.path(), &transfer)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_quarantine_keeps_partial_data() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let target_dir = tempfile::tempdir().unwrap();
+        let transfer = transfer(source_dir.path(), b"partial data");
+
+        let mut manifest = ResumeManifest::new(target_dir.path(), &transfer).unwrap();
+        manifest.save().unwrap();
+        std::fs::write(manifest.partial_path(0), b"partial").unwrap();
+
+        let quarantine = target_dir.path().join(".quarantine");
+        manifest.quarantine(&quarantine);
+        assert!(!manifest.partial_path(0).exists());
+        assert_eq!(
+            std::fs::read(quarantine.join(transfer.id()).join("data.bin")).unwrap(),
+            b"partial"
+        );
+        assert!(ResumeManifest::load(target_dir.path(), &transfer)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_quarantine_stays_in_its_directory() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let target_dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let transfer = transfer(source_dir.path(), b"partial data");
+
+        // Offers with such an id are rejected, but the id must not be trusted here either
+        let mut manifest = ResumeManifest::new(target_dir.path(), &transfer).unwrap();
+        manifest.transfer_id = outside.path().to_string_lossy().into_owned();
+        let partial = manifest.partial_path(0);
+        std::fs::create_dir_all(partial.parent().unwrap()).unwrap();
+        std::fs::write(&partial, b"partial").unwrap();
+
+        let quarantine = target_dir.path().join(".quarantine");
+        manifest.quarantine(&quarantine);
+        assert_eq!(std::fs::read_dir(outside.path()).unwrap().count(), 0);
+        let kept: Vec<_> = std::fs::read_dir(&quarantine)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(kept.len(), 1);
+        assert!(is_valid_transfer_id(
+            &kept[0].file_name().unwrap().to_string_lossy()
+        ));
+        assert_eq!(std::fs::read(kept[0].join("data.bin")).unwrap(), b"partial");
+    }
+
+    #[test]
+    fn test_transfer_id_must_be_a_uuid() {
+        let id = Uuid::new_v4().to_string();
+        assert!(is_valid_transfer_id(&id));
+        assert!(!is_valid_transfer_id(&id.to_uppercase()));
+        assert!(!is_valid_transfer_id(&format!("urn:uuid:{}", id)));
+        assert!(!is_valid_transfer_id("/../../../tmp/x"));
+        assert!(!is_valid_transfer_id(""));
+    }
+
+    #[test]
+    fn test_outgoing_roundtrip() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let state_dir = tempfile::tempdir().unwrap();
+        let transfer = transfer(source_dir.path(), b"hello");
+
+        save_outgoing(state_dir.path(), &transfer).unwrap();
+        let loaded = load_outgoing(state_dir.path()).unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].id(), transfer.id());
+        assert_eq!(
+            loaded[0].files()[0].source_path(),
+            transfer.files()[0].source_path()
+        );
+
+        remove_outgoing(state_dir.path(), transfer.id());
+        assert!(load_outgoing(state_dir.path()).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,435 @@
+use super::protocol::{unexpected, Message};
+use super::{AcceptancePolicy, TcpFileTransfer};
+use crate::device::DeviceManager;
+use crate::error::TsunaguError;
+use crate::pairing::Pairing;
+use crate::Result;
+use socket2::{Domain, Protocol, Socket, Type};
+use std::future::Future;
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{watch, Semaphore};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// How long an incoming connection may take to authenticate and say what it wants
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Listens for incoming transfers and hands them to a [`TcpFileTransfer`]
+pub struct TransferServer {
+    transfer: TcpFileTransfer,
+    policy: Arc<dyn AcceptancePolicy>,
+    pairing: Option<Arc<Pairing>>,
+    slots: Option<Arc<Semaphore>>,
+    local_addr: Option<SocketAddr>,
+    shutdown: Option<watch::Sender<bool>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl TransferServer {
+    pub fn new(transfer: TcpFileTransfer, policy: Arc<dyn AcceptancePolicy>) -> Self {
+        Self {
+            transfer,
+            policy,
+            pairing: None,
+            slots: None,
+            local_addr: None,
+            shutdown: None,
+            handle: None,
+        }
+    }
+
+    /// Answer pairing requests with `pairing`, otherwise they are refused
+    pub fn with_pairing(mut self, pairing: Arc<Pairing>) -> Self {
+        self.pairing = Some(pairing);
+        self
+    }
+
+    /// Take a permit from `slots` for every accepted incoming transfer
+    ///
+    /// Accepted offers beyond the limit wait until a running transfer
+    /// finishes. Offers waiting for an answer do not take a permit.
+    pub fn with_slots(mut self, slots: Arc<Semaphore>) -> Self {
+        self.slots = Some(slots);
+        self
+    }
+
+    /// Bind the server and start accepting connections
+    ///
+    /// A `port` of 0 binds an ephemeral port, as does a configured port that
+    /// is already in use. The port actually bound is stored in the device
+    /// manager, so it must be called before the device is advertised.
+    pub async fn start(&mut self, port: u16, device_manager: &DeviceManager) -> Result<SocketAddr> {
+        if self.handle.is_some() {
+            return Err(TsunaguError::Network(
+                "Transfer server already running".into(),
+            ));
+        }
+
+        let listener = match bind(port) {
+            Ok(listener) => listener,
+            Err(e) if port != 0 => {
+                warn!(
+                    "Failed to bind port {} ({}), using an ephemeral port",
+                    port, e
+                );
+                bind(0)?
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let local_addr = listener.local_addr()?;
+
+        device_manager.update_port(local_addr.port()).await?;
+        self.transfer
+            .set_local_device(device_manager.get_current_device_info().await);
+
+        let (shutdown, mut shutdown_rx) = watch::channel(false);
+        let transfer = self.transfer.clone();
+        let policy = Arc::clone(&self.policy);
+        let pairing = self.pairing.clone();
+        let slots = self.slots.clone();
+
+        let handle = tokio::spawn(async move {
+            info!("Transfer server listening on {}", local_addr);
+            loop {
+                tokio::select! {
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, peer)) => {
+                            debug!("Incoming connection from {}", peer);
+                            let transfer = transfer.clone();
+                            let policy = Arc::clone(&policy);
+                            let pairing = pairing.clone();
+                            let slots = slots.clone();
+                            tokio::spawn(async move {
+                                let result = handle_connection(
+                                    stream,
+                                    &transfer,
+                                    policy.as_ref(),
+                                    pairing.as_deref(),
+                                    slots,
+                                )
+                                .await;
+                                if let Err(e) = result {
+                                    warn!("Incoming connection from {} failed: {}", peer, e);
+                                }
+                            });
+                        }
+                        Err(e) => warn!("Failed to accept connection: {}", e),
+                    },
+                    _ = shutdown_rx.changed() => break,
+                }
+            }
+            info!("Transfer server stopped");
+        });
+
+        self.local_addr = Some(local_addr);
+        self.shutdown = Some(shutdown);
+        self.handle = Some(handle);
+        Ok(local_addr)
+    }
+
+    /// Stop accepting connections, transfers in flight are left to finish
+    pub async fn stop(&mut self) -> Result<()> {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send_replace(true);
+        }
+        if let Some(handle) = self.handle.take() {
+            handle.await.map_err(|e| {
+                TsunaguError::Network(format!("Transfer server task failed: {}", e))
+            })?;
+        }
+        self.local_addr = None;
+        Ok(())
+    }
+
+    /// Address the server is bound to, if running
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// The transfer handler, sharing state with the running server
+    pub fn transfer(&self) -> &TcpFileTransfer {
+        &self.transfer
+    }
+}
+
+/// Listen on `port` over IPv6 and IPv4, or IPv4 only where IPv6 is unavailable
+///
+/// Global IPv6 addresses are advertised and dialed first, so a listener on
+/// `0.0.0.0` alone would refuse peers reaching us that way.
+fn bind(port: u16) -> std::io::Result<TcpListener> {
+    match bind_on(SocketAddr::from((Ipv6Addr::UNSPECIFIED, port))) {
+        Ok(listener) => Ok(listener),
+        Err(e) if e.kind() == ErrorKind::AddrInUse => Err(e),
+        Err(e) => {
+            debug!("Cannot listen on IPv6 ({}), using IPv4 only", e);
+            bind_on(SocketAddr::from((Ipv4Addr::UNSPECIFIED, port)))
+        }
+    }
+}
+
+fn bind_on(addr: SocketAddr) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(false)?;
+    }
+    // Same as TcpListener::bind, a restarted server can take its port back
+    #[cfg(not(windows))]
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(1024)?;
+    TcpListener::from_std(socket.into())
+}
+
+/// Dispatch an incoming connection on its first message
+async fn handle_connection(
+    stream: TcpStream,
+    transfer: &TcpFileTransfer,
+    policy: &dyn AcceptancePolicy,
+    pairing: Option<&Pairing>,
+    slots: Option<Arc<Semaphore>>,
+) -> Result<()> {
+    let (mut conn, peer) = match timeout(transfer.accept(stream)).await? {
+        Ok(accepted) => accepted,
+        // Discovery connects to check whether peers are still around, and
+        // subnet scans hang up once they read our Hello
+        Err(TsunaguError::Io(e)) if closed_early(&e) => {
+            debug!("Connection closed before the handshake: {}", e);
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
+    match (timeout(conn.recv_message()).await??, pairing) {
+        (Message::Offer { transfer: offer }, _) => {
+            transfer
+                .receive_offer(conn, &peer, *offer, policy, slots.as_deref())
+                .await?;
+            Ok(())
+        }
+        (Message::PairRequest, Some(pairing)) => pairing.respond(&mut conn, &peer).await,
+        (Message::PairRequest, None) => {
+            conn.send_message(&Message::PairConfirm { accepted: false })
+                .await?;
+            Err(TsunaguError::Rejected("Pairing is not enabled".into()))
+        }
+        (other, _) => Err(unexpected(&other)),
+    }
+}
+
+/// Fail if the peer has not done its part within [`REQUEST_TIMEOUT`]
+async fn timeout<T>(future: impl Future<Output = T>) -> Result<T> {
+    tokio::time::timeout(REQUEST_TIMEOUT, future)
+        .await
+        .map_err(|_| TsunaguError::Network("Peer did not respond in time".into()))
+}
+
+fn closed_early(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        ErrorKind::UnexpectedEof | ErrorKind::BrokenPipe | ErrorKind::ConnectionReset
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::DeviceIdentity;
+    use crate::models::{DeviceInfo, FileInfo, TransferStatus};
+    use crate::transfer::{AcceptAll, ChannelPolicy, FileTransfer, OfferDecision, RejectAll};
+    use std::net::Ipv4Addr;
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use tokio::io::AsyncReadExt;
+
+    async fn start_server(
+        policy: Arc<dyn AcceptancePolicy>,
+        dir: PathBuf,
+    ) -> (TransferServer, DeviceManager) {
+        let device_manager = DeviceManager::with_identity(DeviceIdentity::generate().unwrap())
+            .await
+            .unwrap();
+        let transfer = TcpFileTransfer::from_device_manager(&device_manager, dir).await;
+        let mut server = TransferServer::new(transfer, policy);
+        server.start(0, &device_manager).await.unwrap();
+        (server, device_manager)
+    }
+
+    async fn send(mut receiver: DeviceInfo, path: &std::path::Path) -> Result<()> {
+        let device_manager = DeviceManager::with_identity(DeviceIdentity::generate()?).await?;
+        receiver.set_addresses([Ipv4Addr::LOCALHOST.into()]);
+
+        let mut sender =
+            TcpFileTransfer::from_device_manager(&device_manager, PathBuf::new()).await;
+        let transfer = sender
+            .init_transfer(vec![FileInfo::from_path(path)?], receiver)
+            .await?;
+        sender.start_transfer(&transfer).await
+    }
+
+    #[tokio::test]
+    async fn test_server_updates_port_and_receives() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let target_dir = tempfile::tempdir().unwrap();
+        let path = source_dir.path().join("hello.txt");
+        std::fs::write(&path, b"hello").unwrap();
+
+        let (mut server, device_manager) =
+            start_server(Arc::new(AcceptAll), target_dir.path().to_path_buf()).await;
+        let port = server.local_addr().unwrap().port();
+        assert_ne!(port, 0);
+        assert_eq!(device_manager.get_current_device_info().await.port(), port);
+        assert_eq!(server.transfer().local_device().port(), port);
+
+        send(device_manager.get_current_device_info().await, &path)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read(target_dir.path().join("hello.txt")).unwrap(),
+            b"hello"
+        );
+
+        server.stop().await.unwrap();
+        assert!(server.local_addr().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_server_rejects_by_policy() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let target_dir = tempfile::tempdir().unwrap();
+        let path = source_dir.path().join("hello.txt");
+        std::fs::write(&path, b"hello").unwrap();
+
+        let (mut server, device_manager) =
+            start_server(Arc::new(RejectAll), target_dir.path().to_path_buf()).await;
+
+        let result = send(device_manager.get_current_device_info().await, &path).await;
+        assert!(result.is_err());
+        assert!(!target_dir.path().join("hello.txt").exists());
+
+        server.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_server_falls_back_to_ephemeral_port() {
+        let taken = TcpListener::bind((Ipv4Addr::UNSPECIFIED, 0)).await.unwrap();
+        let taken_port = taken.local_addr().unwrap().port();
+
+        let device_manager = DeviceManager::with_identity(DeviceIdentity::generate().unwrap())
+            .await
+            .unwrap();
+        let transfer = TcpFileTransfer::from_device_manager(&device_manager, PathBuf::new()).await;
+        let mut server = TransferServer::new(transfer, Arc::new(AcceptAll));
+        let addr = server.start(taken_port, &device_manager).await.unwrap();
+        assert_ne!(addr.port(), taken_port);
+
+        tokio::time::timeout(Duration::from_secs(5), server.stop())
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_server_receives_over_ipv6() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let target_dir = tempfile::tempdir().unwrap();
+        let path = source_dir.path().join("hello.txt");
+        std::fs::write(&path, b"hello").unwrap();
+
+        let (mut server, device_manager) =
+            start_server(Arc::new(AcceptAll), target_dir.path().to_path_buf()).await;
+        let mut receiver = device_manager.get_current_device_info().await;
+        receiver.set_addresses([Ipv6Addr::LOCALHOST.into()]);
+
+        let sender_devices = DeviceManager::with_identity(DeviceIdentity::generate().unwrap())
+            .await
+            .unwrap();
+        let mut sender =
+            TcpFileTransfer::from_device_manager(&sender_devices, PathBuf::new()).await;
+        let transfer = sender
+            .init_transfer(vec![FileInfo::from_path(&path).unwrap()], receiver)
+            .await
+            .unwrap();
+        sender.start_transfer(&transfer).await.unwrap();
+        assert_eq!(
+            std::fs::read(target_dir.path().join("hello.txt")).unwrap(),
+            b"hello"
+        );
+
+        server.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pending_offer_holds_no_slot() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let target_dir = tempfile::tempdir().unwrap();
+        let path = source_dir.path().join("hello.txt");
+        std::fs::write(&path, b"hello").unwrap();
+
+        let slots = Arc::new(Semaphore::new(1));
+        let (policy, mut offers) = ChannelPolicy::new(Duration::from_secs(60));
+        let device_manager = DeviceManager::with_identity(DeviceIdentity::generate().unwrap())
+            .await
+            .unwrap();
+        let transfer =
+            TcpFileTransfer::from_device_manager(&device_manager, target_dir.path().to_path_buf())
+                .await;
+        let mut server = TransferServer::new(transfer, Arc::new(policy)).with_slots(slots.clone());
+        server.start(0, &device_manager).await.unwrap();
+
+        let receiver = device_manager.get_current_device_info().await;
+        let sending = tokio::spawn(async move { send(receiver, &path).await });
+        let offer = offers.recv().await.unwrap();
+        // Nobody answered yet, local sends can still take the slot
+        assert_eq!(slots.available_permits(), 1);
+        offer.respond(OfferDecision::Accept);
+        sending.await.unwrap().unwrap();
+        assert_eq!(slots.available_permits(), 1);
+
+        server.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_silent_connection_times_out() {
+        let (mut server, _device_manager) = start_server(Arc::new(AcceptAll), PathBuf::new()).await;
+        let port = server.local_addr().unwrap().port();
+
+        let mut stream = TcpStream::connect((Ipv4Addr::LOCALHOST, port))
+            .await
+            .unwrap();
+        tokio::time::pause();
+        // The server greets, then gives up on a peer that never answers
+        let mut received = Vec::new();
+        tokio::time::timeout(REQUEST_TIMEOUT * 2, stream.read_to_end(&mut received))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!received.is_empty());
+
+        tokio::time::resume();
+        server.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_status_shared_with_server() {
+        let source_dir = tempfile::tempdir().unwrap();
+        let target_dir = tempfile::tempdir().unwrap();
+        let path = source_dir.path().join("hello.txt");
+        std::fs::write(&path, b"hello").unwrap();
+
+        let (mut server, device_manager) =
+            start_server(Arc::new(AcceptAll), target_dir.path().to_path_buf()).await;
+        send(device_manager.get_current_device_info().await, &path)
+            .await
+            .unwrap();
+
+        let transfers = server.transfer().list_transfers().await;
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].status(), &TransferStatus::Completed);
+
+        server.stop().await.unwrap();
+    }
+}
@@ -0,0 +1,421 @@
+use crate::error::TsunaguError;
+use crate::models::FileInfo;
+use crate::Result;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use tracing::{debug, warn};
+
+/// Per-directory file with additional ignore patterns, one per line
+pub const IGNORE_FILE: &str = ".tsunaguignore";
+
+/// Device names Windows refuses as file names, with or without an extension
+const WINDOWS_RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// What to do with symbolic links found while expanding directories
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    /// Leave links out of the transfer
+    #[default]
+    Skip,
+    /// Send what the link points to
+    Follow,
+}
+
+/// How paths given to [`collect_files`] are expanded
+#[derive(Debug, Clone, Default)]
+pub struct CollectOptions {
+    pub symlinks: SymlinkPolicy,
+    /// Glob patterns (`*` and `?`) for files and directories to leave out
+    ///
+    /// Patterns without a `/` match any file or directory name, others match
+    /// the path relative to the directory being sent. A trailing `/` only
+    /// matches directories.
+    pub ignore: Vec<String>,
+}
+
+/// Expand `paths` into the files of a transfer
+///
+/// Files keep their name, directories are walked recursively and their
+/// files get a relative path starting with the directory name.
+pub fn collect_files(paths: &[PathBuf], options: &CollectOptions) -> Result<Vec<FileInfo>> {
+    let mut files = Vec::new();
+    for path in paths {
+        let metadata = fs::metadata(path)?;
+        if metadata.is_dir() {
+            let root = path
+                .canonicalize()?
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .ok_or_else(|| TsunaguError::Transfer(format!("Cannot send {}", path.display())))?;
+            let mut walker = Walker {
+                options,
+                patterns: options.ignore.iter().map(|p| Pattern::new(p)).collect(),
+                visited: HashSet::new(),
+                files: &mut files,
+            };
+            walker.walk(path, &root, "")?;
+        } else {
+            files.push(FileInfo::from_path(path)?);
+        }
+    }
+
+    let mut seen = HashSet::new();
+    for file in &files {
+        if !seen.insert(file.relative_path().to_string()) {
+            return Err(TsunaguError::Transfer(format!(
+                "More than one file would be saved as {}",
+                file.relative_path()
+            )));
+        }
+    }
+    Ok(files)
+}
+
+struct Walker<'a> {
+    options: &'a CollectOptions,
+    patterns: Vec<Pattern>,
+    /// Directories already walked, guards against symlink loops
+    visited: HashSet<PathBuf>,
+    files: &'a mut Vec<FileInfo>,
+}
+
+impl Walker<'_> {
+    /// Walk `dir`, which is sent as `prefix`; `inner` is the path below the root
+    fn walk(&mut self, dir: &Path, prefix: &str, inner: &str) -> Result<()> {
+        if !self.visited.insert(dir.canonicalize()?) {
+            warn!("Skipping {}, already visited", dir.display());
+            return Ok(());
+        }
+
+        let mut patterns = self.patterns.clone();
+        if let Ok(text) = fs::read_to_string(dir.join(IGNORE_FILE)) {
+            patterns.extend(
+                text.lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(|line| Pattern::new(&join(inner, line))),
+            );
+        }
+
+        let mut entries = fs::read_dir(dir)?.collect::<std::io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name == IGNORE_FILE {
+                continue;
+            }
+            let path = entry.path();
+            let relative = join(inner, &name);
+
+            let mut file_type = entry.file_type()?;
+            if file_type.is_symlink() {
+                if self.options.symlinks == SymlinkPolicy::Skip {
+                    debug!("Skipping symlink {}", path.display());
+                    continue;
+                }
+                file_type = fs::metadata(&path)?.file_type();
+            }
+
+            let is_dir = file_type.is_dir();
+            if patterns.iter().any(|p| p.matches(&relative, &name, is_dir)) {
+                debug!("Ignoring {}", path.display());
+                continue;
+            }
+
+            if is_dir {
+                let mut walker = Walker {
+                    options: self.options,
+                    patterns: patterns.clone(),
+                    visited: std::mem::take(&mut self.visited),
+                    files: self.files,
+                };
+                let result = walker.walk(&path, prefix, &relative);
+                self.visited = walker.visited;
+                result?;
+            } else if file_type.is_file() {
+                self.files
+                    .push(FileInfo::from_path(&path)?.with_relative_path(join(prefix, &relative)));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn join(parent: &str, name: &str) -> String {
+    if parent.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", parent, name)
+    }
+}
+
+/// An ignore rule
+#[derive(Debug, Clone)]
+struct Pattern {
+    glob: String,
+    /// Matches the whole relative path instead of a single name
+    anchored: bool,
+    dir_only: bool,
+}
+
+impl Pattern {
+    fn new(pattern: &str) -> Self {
+        let dir_only = pattern.ends_with('/');
+        let glob = pattern.trim_end_matches('/').trim_start_matches('/');
+        Self {
+            anchored: glob.contains('/'),
+            glob: glob.to_string(),
+            dir_only,
+        }
+    }
+
+    fn matches(&self, relative: &str, name: &str, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        let target = if self.anchored { relative } else { name };
+        glob_match(self.glob.as_bytes(), target.as_bytes())
+    }
+}
+
+/// Match `text` against a glob where `*` does not cross `/`
+fn glob_match(pattern: &[u8], text: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some((b'*', rest)) => (0..=text.len())
+            .take_while(|&i| i == 0 || text[i - 1] != b'/')
+            .any(|i| glob_match(rest, &text[i..])),
+        Some((b'?', rest)) => {
+            matches!(text.split_first(), Some((c, tail)) if *c != b'/' && glob_match(rest, tail))
+        }
+        Some((c, rest)) => {
+            matches!(text.split_first(), Some((t, tail)) if t == c && glob_match(rest, tail))
+        }
+    }
+}
+
+/// Turn a relative path sent by a peer into a path below the download dir
+///
+/// Rejects anything that could escape it or that some platform cannot
+/// store: absolute paths, `..`, empty components, backslashes, drive
+/// letters and Windows device names.
+pub(crate) fn safe_relative_path(relative: &str) -> Result<PathBuf> {
+    let invalid = |reason: &str| {
+        TsunaguError::Transfer(format!("Invalid file path {:?}: {}", relative, reason))
+    };
+
+    if relative.is_empty() {
+        return Err(invalid("empty"));
+    }
+    if relative.starts_with('/') {
+        return Err(invalid("absolute path"));
+    }
+
+    let mut path = PathBuf::new();
+    for component in relative.split('/') {
+        match component {
+            "" => return Err(invalid("empty component")),
+            "." | ".." => return Err(invalid("relative component")),
+            _ => {}
+        }
+        if component.contains(['\\', ':', '\0']) {
+            return Err(invalid("reserved character"));
+        }
+        if component.ends_with(['.', ' ']) {
+            return Err(invalid("trailing dot or space"));
+        }
+        let stem = component.split('.').next().unwrap_or_default();
+        if WINDOWS_RESERVED
+            .iter()
+            .any(|reserved| stem.trim_end().eq_ignore_ascii_case(reserved))
+        {
+            return Err(invalid("reserved name"));
+        }
+        path.push(component);
+    }
+
+    // Belt and braces, the checks above should already guarantee this
+    if !path.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(invalid("not a relative path"));
+    }
+    Ok(path)
+}
+
+/// Move `from` to `to`, or to `name (1).ext` and so on if `to` exists
+///
+/// Never replaces a file: the move links the target, which fails if it
+/// appeared meanwhile. Falls back to a rename where links are unsupported.
+pub(crate) async fn move_to_free_path(from: &Path, to: &Path) -> Result<PathBuf> {
+    for attempt in 0.. {
+        let target = numbered_path(to, attempt);
+        match tokio::fs::hard_link(from, &target).await {
+            Ok(()) => {
+                tokio::fs::remove_file(from).await?;
+                return Ok(target);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+            Err(e) if tokio::fs::try_exists(&target).await? => {
+                debug!("{} exists: {}", target.display(), e);
+            }
+            Err(e) => {
+                debug!("Renaming, linking {} failed: {}", target.display(), e);
+                tokio::fs::rename(from, &target).await?;
+                return Ok(target);
+            }
+        }
+    }
+    unreachable!("ran out of file names for {}", to.display())
+}
+
+/// `path` for attempt 0, then `name (1).ext`, `name (2).ext` and so on
+fn numbered_path(path: &Path, attempt: u32) -> PathBuf {
+    if attempt == 0 {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{} ({}).{}", stem, attempt, ext.to_string_lossy()),
+        None => format!("{} ({})", stem, attempt),
+    };
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn relative_paths(files: &[FileInfo]) -> Vec<&str> {
+        files.iter().map(|f| f.relative_path()).collect()
+    }
+
+    #[test]
+    fn test_collect_directory_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("project");
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::create_dir_all(root.join("target/debug")).unwrap();
+        fs::write(root.join("README.md"), b"readme").unwrap();
+        fs::write(root.join("src/main.rs"), b"fn main() {}").unwrap();
+        fs::write(root.join("src/nested/notes.tmp"), b"scratch").unwrap();
+        fs::write(root.join("src/nested/lib.rs"), b"").unwrap();
+        fs::write(root.join("target/debug/app"), b"binary").unwrap();
+        fs::write(root.join(IGNORE_FILE), b"# build output\ntarget/\n").unwrap();
+        let single = dir.path().join("single.txt");
+        fs::write(&single, b"single").unwrap();
+
+        let options = CollectOptions {
+            ignore: vec!["*.tmp".to_string()],
+            ..CollectOptions::default()
+        };
+        let files = collect_files(&[root, single], &options).unwrap();
+        assert_eq!(
+            relative_paths(&files),
+            [
+                "project/README.md",
+                "project/src/main.rs",
+                "project/src/nested/lib.rs",
+                "single.txt"
+            ]
+        );
+        assert_eq!(files[1].name(), "main.rs");
+    }
+
+    #[test]
+    fn test_duplicate_relative_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a");
+        let b = dir.path().join("b");
+        fs::create_dir_all(&a).unwrap();
+        fs::create_dir_all(&b).unwrap();
+        fs::write(a.join("same.txt"), b"a").unwrap();
+        fs::write(b.join("same.txt"), b"b").unwrap();
+
+        let options = CollectOptions::default();
+        let files = [a.join("same.txt"), b.join("same.txt")];
+        assert!(collect_files(&files, &options).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("sub/file.txt"), b"data").unwrap();
+        std::os::unix::fs::symlink(root.join("sub/file.txt"), root.join("link.txt")).unwrap();
+        // A loop back to the root must not be walked forever
+        std::os::unix::fs::symlink(&root, root.join("sub/loop")).unwrap();
+
+        let skipped =
+            collect_files(std::slice::from_ref(&root), &CollectOptions::default()).unwrap();
+        assert_eq!(relative_paths(&skipped), ["root/sub/file.txt"]);
+
+        let options = CollectOptions {
+            symlinks: SymlinkPolicy::Follow,
+            ..CollectOptions::default()
+        };
+        let followed = collect_files(&[root], &options).unwrap();
+        assert_eq!(
+            relative_paths(&followed),
+            ["root/link.txt", "root/sub/file.txt"]
+        );
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(b"*.tmp", b"notes.tmp"));
+        assert!(!glob_match(b"*.tmp", b"notes.txt"));
+        assert!(glob_match(b"src/*.rs", b"src/main.rs"));
+        assert!(!glob_match(b"src/*.rs", b"src/nested/lib.rs"));
+        assert!(glob_match(b"file?.txt", b"file1.txt"));
+    }
+
+    #[test]
+    fn test_safe_relative_path() {
+        assert_eq!(
+            safe_relative_path("dir/sub/a.txt").unwrap(),
+            Path::new("dir").join("sub").join("a.txt")
+        );
+        for bad in [
+            "",
+            "/etc/passwd",
+            "../escape",
+            "dir/../../escape",
+            "dir//a",
+            "./a",
+            "C:/Windows",
+            "dir\\..\\escape",
+            "CON",
+            "dir/nul.txt",
+            "com1 .log",
+            "trailing.",
+        ] {
+            assert!(safe_relative_path(bad).is_err(), "{:?} was accepted", bad);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_move_to_free_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("photo.jpg");
+        std::fs::write(&target, b"mine").unwrap();
+        std::fs::write(dir.path().join("photo (1).jpg"), b"mine too").unwrap();
+
+        let partial = dir.path().join(".photo.jpg.part");
+        std::fs::write(&partial, b"theirs").unwrap();
+        let moved = move_to_free_path(&partial, &target).await.unwrap();
+        assert_eq!(moved, dir.path().join("photo (2).jpg"));
+        assert_eq!(std::fs::read(&moved).unwrap(), b"theirs");
+        assert_eq!(std::fs::read(&target).unwrap(), b"mine");
+        assert!(!partial.exists());
+
+        std::fs::write(&partial, b"new").unwrap();
+        let free = dir.path().join("notes");
+        assert_eq!(move_to_free_path(&partial, &free).await.unwrap(), free);
+        assert_eq!(numbered_path(&free, 3), dir.path().join("notes (3)"));
+    }
+}
@@ -0,0 +1,257 @@
+use crate::config::Config;
+use crate::error::TsunaguError;
+use crate::models::DeviceInfo;
+use crate::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::error;
+
+/// A peer the user paired with
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TrustedDevice {
+    id: String,
+    name: String,
+    /// Base64 encoded identity public key
+    public_key: String,
+    /// Seconds since the Unix epoch
+    paired_at: u64,
+}
+
+impl TrustedDevice {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn public_key(&self) -> &str {
+        &self.public_key
+    }
+
+    pub fn paired_at(&self) -> u64 {
+        self.paired_at
+    }
+}
+
+/// How much a peer is trusted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrustLevel {
+    /// Paired before and presenting the same identity key
+    Trusted,
+    /// Never paired
+    Unknown,
+}
+
+/// Devices the user paired with, persisted in the config file
+#[derive(Debug)]
+pub struct TrustStore {
+    path: Option<PathBuf>,
+    devices: Vec<TrustedDevice>,
+}
+
+impl TrustStore {
+    /// A store that only lives in memory
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            devices: Vec::new(),
+        }
+    }
+
+    /// Load the store from the default config file
+    pub fn load_default() -> Result<Self> {
+        Self::load(&Config::default_path()?)
+    }
+
+    /// Load the trusted devices of the config file at `path`
+    pub fn load(path: &Path) -> Result<Self> {
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            devices: Config::load(path)?.trusted_devices,
+        })
+    }
+
+    /// Persist the store, a no-op for in-memory stores
+    ///
+    /// The rest of the config is re-read first so concurrent edits to other
+    /// settings are kept.
+    pub fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut config = Config::load(path)?;
+        config.trusted_devices = self.devices.clone();
+        config.save(path)
+    }
+
+    pub fn devices(&self) -> &[TrustedDevice] {
+        &self.devices
+    }
+
+    /// Mark `device` as trusted, replacing an earlier entry for the same name
+    pub fn trust(&mut self, device: &DeviceInfo) -> Result<()> {
+        let public_key = verified_key(device)?;
+        let paired_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        self.devices
+            .retain(|d| d.id != device.id() && !same_name(&d.name, device.name()));
+        self.devices.push(TrustedDevice {
+            id: device.id().to_string(),
+            name: device.name().to_string(),
+            public_key: STANDARD.encode(public_key),
+            paired_at,
+        });
+        Ok(())
+    }
+
+    /// Forget a device by id or name, returns whether anything was removed
+    pub fn remove(&mut self, id_or_name: &str) -> bool {
+        let before = self.devices.len();
+        self.devices
+            .retain(|d| d.id != id_or_name && !same_name(&d.name, id_or_name));
+        self.devices.len() != before
+    }
+
+    /// Check a peer against the store
+    ///
+    /// Fails with [`TsunaguError::IdentityChanged`] if a device with the same
+    /// name was paired with a different key, which may mean someone is
+    /// impersonating it.
+    pub fn check(&self, device: &DeviceInfo) -> Result<TrustLevel> {
+        if let Ok(public_key) = verified_key(device) {
+            let public_key = STANDARD.encode(public_key);
+            if self
+                .devices
+                .iter()
+                .any(|d| d.id == device.id() && d.public_key == public_key)
+            {
+                return Ok(TrustLevel::Trusted);
+            }
+        }
+
+        if let Some(known) = self
+            .devices
+            .iter()
+            .find(|d| same_name(&d.name, device.name()))
+        {
+            error!(
+                "IDENTITY OF {} HAS CHANGED: paired as {}, now presenting {}. \
+                 Someone may be impersonating this device.",
+                device.name(),
+                known.id,
+                device.id()
+            );
+            return Err(TsunaguError::IdentityChanged {
+                name: device.name().to_string(),
+                expected: known.id.clone(),
+                actual: device.id().to_string(),
+            });
+        }
+
+        Ok(TrustLevel::Unknown)
+    }
+}
+
+/// The identity key of `device`, only if its id really is the key's fingerprint
+fn verified_key(device: &DeviceInfo) -> Result<Vec<u8>> {
+    match device.public_key() {
+        Some(public_key) if device.has_verified_id() => Ok(public_key),
+        _ => Err(TsunaguError::Device(format!(
+            "{} has no verifiable identity",
+            device.name()
+        ))),
+    }
+}
+
+fn same_name(a: &str, b: &str) -> bool {
+    a.trim_end_matches('.')
+        .eq_ignore_ascii_case(b.trim_end_matches('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::DeviceIdentity;
+    use std::net::Ipv4Addr;
+
+    fn device(name: &str, identity: &DeviceIdentity) -> DeviceInfo {
+        DeviceInfo::new(
+            name.to_string(),
+            "Test Model".to_string(),
+            [Ipv4Addr::LOCALHOST.into()],
+            8000,
+            "TestOS".to_string(),
+            "1.0.0".to_string(),
+        )
+        .with_public_key(identity.public_key())
+    }
+
+    #[test]
+    fn test_trust_and_check() {
+        let identity = DeviceIdentity::generate().unwrap();
+        let laptop = device("Laptop", &identity);
+        let mut store = TrustStore::in_memory();
+
+        assert_eq!(store.check(&laptop).unwrap(), TrustLevel::Unknown);
+        store.trust(&laptop).unwrap();
+        assert_eq!(store.check(&laptop).unwrap(), TrustLevel::Trusted);
+
+        assert!(store.remove("laptop"));
+        assert_eq!(store.check(&laptop).unwrap(), TrustLevel::Unknown);
+    }
+
+    #[test]
+    fn test_changed_identity() {
+        let mut store = TrustStore::in_memory();
+        store
+            .trust(&device("Laptop", &DeviceIdentity::generate().unwrap()))
+            .unwrap();
+
+        let impostor = device("Laptop", &DeviceIdentity::generate().unwrap());
+        assert!(matches!(
+            store.check(&impostor),
+            Err(TsunaguError::IdentityChanged { .. })
+        ));
+    }
+
+    #[test]
+    fn test_device_without_identity_cannot_be_trusted() {
+        let plain = DeviceInfo::new(
+            "Plain".to_string(),
+            "Test Model".to_string(),
+            [Ipv4Addr::LOCALHOST.into()],
+            8000,
+            "TestOS".to_string(),
+            "1.0.0".to_string(),
+        );
+        let mut store = TrustStore::in_memory();
+        assert!(store.trust(&plain).is_err());
+        assert_eq!(store.check(&plain).unwrap(), TrustLevel::Unknown);
+    }
+
+    #[test]
+    fn test_persistence() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let laptop = device("Laptop", &DeviceIdentity::generate().unwrap());
+
+        let mut store = TrustStore::load(&path).unwrap();
+        store.trust(&laptop).unwrap();
+        store.save().unwrap();
+
+        let reloaded = TrustStore::load(&path).unwrap();
+        assert_eq!(reloaded.devices(), store.devices());
+        assert_eq!(reloaded.check(&laptop).unwrap(), TrustLevel::Trusted);
+        assert_eq!(
+            Config::load(&path).unwrap().trusted_devices,
+            store.devices()
+        );
+    }
+}
@@ -0,0 +1,64 @@
+// This file is automatically generated, so please do not edit it.
+// Generated by `flutter_rust_bridge`@ 2.3.0.
+
+// ignore_for_file: invalid_use_of_internal_member, unused_import, unnecessary_import
+
+import '../frb_generated.dart';
+import 'package:flutter_rust_bridge/flutter_rust_bridge_for_generated.dart';
+
+// These functions are ignored because they are not marked as `pub`: `device`
+// These function are ignored because they are on traits that is not defined in current crate (put an empty `#[frb]` on it to unignore): `clone`, `eq`, `fmt`
+
+/// Stream the discovered devices, a new list is sent whenever it changes
+///
+/// The stream ends when the service stops.
+Stream<List<Device>> watchDevices() =>
+    RustLib.instance.api.crateApiDevicesWatchDevices();
+
+/// A device on the network
+class Device {
+  final String id;
+  final String name;
+  final String model;
+  final String os;
+
+  /// Every address the device was found at
+  final List<String> addresses;
+  final int port;
+
+  /// Paired before and presenting the same identity
+  final bool trusted;
+
+  const Device({
+    required this.id,
+    required this.name,
+    required this.model,
+    required this.os,
+    required this.addresses,
+    required this.port,
+    required this.trusted,
+  });
+
+  @override
+  int get hashCode =>
+      id.hashCode ^
+      name.hashCode ^
+      model.hashCode ^
+      os.hashCode ^
+      addresses.hashCode ^
+      port.hashCode ^
+      trusted.hashCode;
+
+  @override
+  bool operator ==(Object other) =>
+      identical(this, other) ||
+      other is Device &&
+          runtimeType == other.runtimeType &&
+          id == other.id &&
+          name == other.name &&
+          model == other.model &&
+          os == other.os &&
+          addresses == other.addresses &&
+          port == other.port &&
+          trusted == other.trusted;
+}
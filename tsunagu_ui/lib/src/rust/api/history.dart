@@ -0,0 +1,157 @@
+// This file is automatically generated, so please do not edit it.
+// Generated by `flutter_rust_bridge`@ 2.3.0.
+
+// ignore_for_file: invalid_use_of_internal_member, unused_import, unnecessary_import
+
+import '../frb_generated.dart';
+import 'package:flutter_rust_bridge/flutter_rust_bridge_for_generated.dart';
+
+// These functions are ignored because they are not marked as `pub`: `history_item`, `status_name`
+
+/// Finished transfers matching `query`, newest first
+Future<List<HistoryItem>> loadHistory({required HistoryQuery query}) =>
+    RustLib.instance.api.crateApiHistoryLoadHistory(query: query);
+
+class HistoryFile {
+  /// Path relative to the transfer, `/` separated
+  final String path;
+  final BigInt size;
+
+  /// Hex encoded SHA-256, if the file was hashed
+  final String? hash;
+
+  /// Where the file was read from or saved to on this device
+  final String? localPath;
+
+  const HistoryFile({
+    required this.path,
+    required this.size,
+    required this.hash,
+    required this.localPath,
+  });
+
+  @override
+  int get hashCode =>
+      path.hashCode ^ size.hashCode ^ hash.hashCode ^ localPath.hashCode;
+
+  @override
+  bool operator ==(Object other) =>
+      identical(this, other) ||
+      other is HistoryFile &&
+          runtimeType == other.runtimeType &&
+          path == other.path &&
+          size == other.size &&
+          hash == other.hash &&
+          localPath == other.localPath;
+}
+
+/// A finished transfer for the history screen
+class HistoryItem {
+  final String transferId;
+  final bool sent;
+  final String peerId;
+  final String peerName;
+  final List<HistoryFile> files;
+  final BigInt totalSize;
+
+  /// Seconds since the Unix epoch
+  final BigInt startedAt;
+
+  /// Seconds since the Unix epoch
+  final BigInt finishedAt;
+  final BigInt durationMs;
+
+  /// `completed`, `failed` or `cancelled`
+  final String status;
+
+  /// Why the transfer failed
+  final String? error;
+
+  const HistoryItem({
+    required this.transferId,
+    required this.sent,
+    required this.peerId,
+    required this.peerName,
+    required this.files,
+    required this.totalSize,
+    required this.startedAt,
+    required this.finishedAt,
+    required this.durationMs,
+    required this.status,
+    required this.error,
+  });
+
+  @override
+  int get hashCode =>
+      transferId.hashCode ^
+      sent.hashCode ^
+      peerId.hashCode ^
+      peerName.hashCode ^
+      files.hashCode ^
+      totalSize.hashCode ^
+      startedAt.hashCode ^
+      finishedAt.hashCode ^
+      durationMs.hashCode ^
+      status.hashCode ^
+      error.hashCode;
+
+  @override
+  bool operator ==(Object other) =>
+      identical(this, other) ||
+      other is HistoryItem &&
+          runtimeType == other.runtimeType &&
+          transferId == other.transferId &&
+          sent == other.sent &&
+          peerId == other.peerId &&
+          peerName == other.peerName &&
+          files == other.files &&
+          totalSize == other.totalSize &&
+          startedAt == other.startedAt &&
+          finishedAt == other.finishedAt &&
+          durationMs == other.durationMs &&
+          status == other.status &&
+          error == other.error;
+}
+
+/// Which transfers to load, empty fields match everything
+class HistoryQuery {
+  /// Peer name, id or ip
+  final String? peer;
+
+  /// `completed`, `failed` or `cancelled`
+  final String? status;
+
+  /// Finished at or after, in seconds since the Unix epoch
+  final BigInt? since;
+
+  /// Finished before, in seconds since the Unix epoch
+  final BigInt? until;
+  final int? limit;
+
+  const HistoryQuery({
+    required this.peer,
+    required this.status,
+    required this.since,
+    required this.until,
+    required this.limit,
+  });
+
+  @override
+  int get hashCode =>
+      peer.hashCode ^
+      status.hashCode ^
+      since.hashCode ^
+      until.hashCode ^
+      limit.hashCode;
+
+  @override
+  bool operator ==(Object other) =>
+      identical(this, other) ||
+      other is HistoryQuery &&
+          runtimeType == other.runtimeType &&
+          peer == other.peer &&
+          status == other.status &&
+          since == other.since &&
+          until == other.until &&
+          limit == other.limit;
+}
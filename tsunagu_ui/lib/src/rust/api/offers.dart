@@ -0,0 +1,100 @@
+// This file is automatically generated, so please do not edit it.
+// Generated by `flutter_rust_bridge`@ 2.3.0.
+
+// ignore_for_file: invalid_use_of_internal_member, unused_import, unnecessary_import
+
+import '../frb_generated.dart';
+import 'devices.dart';
+import 'package:flutter_rust_bridge/flutter_rust_bridge_for_generated.dart';
+import 'package:freezed_annotation/freezed_annotation.dart' hide protected;
+part 'offers.freezed.dart';
+
+// These functions are ignored because they are not marked as `pub`: `new`, `transfer_files`
+// These function are ignored because they are on traits that is not defined in current crate (put an empty `#[frb]` on it to unignore): `clone`, `clone`, `fmt`, `fmt`
+
+/// Answer the offer of transfer `transfer_id`
+Future<void> answerOffer(
+        {required String transferId, required OfferAnswer answer}) =>
+    RustLib.instance.api
+        .crateApiOffersAnswerOffer(transferId: transferId, answer: answer);
+
+/// Offers still waiting for an answer, e.g. after the app was reopened
+Future<List<IncomingOffer>> pendingOffers() =>
+    RustLib.instance.api.crateApiOffersPendingOffers();
+
+/// Stream offers from devices that are not accepted automatically
+///
+/// Offers that are not answered within two minutes are rejected. The stream
+/// ends when the service stops.
+Stream<IncomingOffer> watchOffers() =>
+    RustLib.instance.api.crateApiOffersWatchOffers();
+
+/// Files another device wants to send, waiting for [`answer_offer`]
+class IncomingOffer {
+  final String transferId;
+  final Device sender;
+  final List<TransferFile> files;
+  final BigInt totalSize;
+
+  const IncomingOffer({
+    required this.transferId,
+    required this.sender,
+    required this.files,
+    required this.totalSize,
+  });
+
+  @override
+  int get hashCode =>
+      transferId.hashCode ^
+      sender.hashCode ^
+      files.hashCode ^
+      totalSize.hashCode;
+
+  @override
+  bool operator ==(Object other) =>
+      identical(this, other) ||
+      other is IncomingOffer &&
+          runtimeType == other.runtimeType &&
+          transferId == other.transferId &&
+          sender == other.sender &&
+          files == other.files &&
+          totalSize == other.totalSize;
+}
+
+/// The user's answer to an [`IncomingOffer`]
+@freezed
+sealed class OfferAnswer with _$OfferAnswer {
+  const OfferAnswer._();
+
+  const factory OfferAnswer.accept() = OfferAnswer_Accept;
+  /// Accept only the files at these indexes
+  const factory OfferAnswer.acceptFiles({
+    required Uint32List indexes,
+  }) = OfferAnswer_AcceptFiles;
+  const factory OfferAnswer.reject({
+    required String reason,
+  }) = OfferAnswer_Reject;
+}
+
+/// A file of an offer or transfer
+class TransferFile {
+  /// Path relative to the transfer, `/` separated
+  final String path;
+  final BigInt size;
+
+  const TransferFile({
+    required this.path,
+    required this.size,
+  });
+
+  @override
+  int get hashCode => path.hashCode ^ size.hashCode;
+
+  @override
+  bool operator ==(Object other) =>
+      identical(this, other) ||
+      other is TransferFile &&
+          runtimeType == other.runtimeType &&
+          path == other.path &&
+          size == other.size;
+}
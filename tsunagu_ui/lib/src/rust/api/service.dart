@@ -0,0 +1,25 @@
+// This file is automatically generated, so please do not edit it.
+// Generated by `flutter_rust_bridge`@ 2.3.0.
+
+// ignore_for_file: invalid_use_of_internal_member, unused_import, unnecessary_import
+
+import '../frb_generated.dart';
+import 'devices.dart';
+import 'package:flutter_rust_bridge/flutter_rust_bridge_for_generated.dart';
+
+// These types are ignored because they are not used by any `pub` functions: `Running`, `Service`
+// These functions are ignored because they are not marked as `pub`: `collect_offers`, `current`
+// These function are ignored because they are on traits that is not defined in current crate (put an empty `#[frb]` on it to unignore): `clone`
+
+Future<bool> isServiceRunning() =>
+    RustLib.instance.api.crateApiServiceIsServiceRunning();
+
+/// Start receiving, advertising and discovering devices
+///
+/// The device stays discoverable until the service stops. Uses the saved settings, changes to them apply on the next start.
+/// Returns this device as peers see it.
+Future<Device> startService() =>
+    RustLib.instance.api.crateApiServiceStartService();
+
+/// Stop the service, transfers in flight are left to finish
+Future<void> stopService() => RustLib.instance.api.crateApiServiceStopService();
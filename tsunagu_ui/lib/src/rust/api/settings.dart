@@ -0,0 +1,65 @@
+// This file is automatically generated, so please do not edit it.
+// Generated by `flutter_rust_bridge`@ 2.3.0.
+
+// ignore_for_file: invalid_use_of_internal_member, unused_import, unnecessary_import
+
+import '../frb_generated.dart';
+import 'package:flutter_rust_bridge/flutter_rust_bridge_for_generated.dart';
+
+Future<Settings> loadSettings() =>
+    RustLib.instance.api.crateApiSettingsLoadSettings();
+
+/// Save `settings`, keeping trusted devices and everything else in the config
+///
+/// A running service picks them up when it is started again.
+Future<void> saveSettings({required Settings settings}) =>
+    RustLib.instance.api.crateApiSettingsSaveSettings(settings: settings);
+
+/// The user editable part of the shared config
+class Settings {
+  final String deviceName;
+  final String saveDirectory;
+  final bool allowAutoReceive;
+
+  /// Any of `mdns`, `multicast` where mDNS is filtered, and `subnet_scan`
+  /// where all multicast is, the devices they find are merged
+  final List<String> discoveryBackends;
+
+  /// UDP port of the multicast discovery
+  final int discoveryPort;
+  final int transferPort;
+  final int maxConcurrentTransfers;
+
+  const Settings({
+    required this.deviceName,
+    required this.saveDirectory,
+    required this.allowAutoReceive,
+    required this.discoveryBackends,
+    required this.discoveryPort,
+    required this.transferPort,
+    required this.maxConcurrentTransfers,
+  });
+
+  @override
+  int get hashCode =>
+      deviceName.hashCode ^
+      saveDirectory.hashCode ^
+      allowAutoReceive.hashCode ^
+      discoveryBackends.hashCode ^
+      discoveryPort.hashCode ^
+      transferPort.hashCode ^
+      maxConcurrentTransfers.hashCode;
+
+  @override
+  bool operator ==(Object other) =>
+      identical(this, other) ||
+      other is Settings &&
+          runtimeType == other.runtimeType &&
+          deviceName == other.deviceName &&
+          saveDirectory == other.saveDirectory &&
+          allowAutoReceive == other.allowAutoReceive &&
+          discoveryBackends == other.discoveryBackends &&
+          discoveryPort == other.discoveryPort &&
+          transferPort == other.transferPort &&
+          maxConcurrentTransfers == other.maxConcurrentTransfers;
+}
@@ -0,0 +1,67 @@
+// This file is automatically generated, so please do not edit it.
+// Generated by `flutter_rust_bridge`@ 2.3.0.
+
+// ignore_for_file: invalid_use_of_internal_member, unused_import, unnecessary_import
+
+import '../frb_generated.dart';
+import 'devices.dart';
+import 'offers.dart';
+import 'package:flutter_rust_bridge/flutter_rust_bridge_for_generated.dart';
+import 'package:freezed_annotation/freezed_annotation.dart' hide protected;
+part 'transfers.freezed.dart';
+
+// These functions are ignored because they are not marked as `pub`: `update`
+
+/// Cancel a queued or running transfer, the peer is told as well
+Future<void> cancelTransfer({required String transferId}) =>
+    RustLib.instance.api
+        .crateApiTransfersCancelTransfer(transferId: transferId);
+
+/// Send files and directories to the discovered device `device_id`
+///
+/// Returns the transfer id, progress arrives through [`watch_transfers`].
+Future<String> sendFiles(
+        {required String deviceId, required List<String> paths}) =>
+    RustLib.instance.api
+        .crateApiTransfersSendFiles(deviceId: deviceId, paths: paths);
+
+/// Stream updates of all transfers, the stream ends when the service stops
+Stream<TransferUpdate> watchTransfers() =>
+    RustLib.instance.api.crateApiTransfersWatchTransfers();
+
+/// Something that happened to a transfer in either direction
+@freezed
+sealed class TransferUpdate with _$TransferUpdate {
+  const TransferUpdate._();
+
+  /// Data is about to flow
+  const factory TransferUpdate.started({
+    required String transferId,
+    required bool sent,
+    required Device peer,
+    required List<TransferFile> files,
+    required BigInt totalSize,
+  }) = TransferUpdate_Started;
+  const factory TransferUpdate.progress({
+    required String transferId,
+    required int fileIndex,
+    required BigInt bytesTransferred,
+    required BigInt totalBytes,
+    required double bytesPerSecond,
+
+    /// Estimated seconds left, unknown until data flows
+    required BigInt? etaSeconds,
+  }) = TransferUpdate_Progress;
+  const factory TransferUpdate.fileCompleted({
+    required String transferId,
+    required int index,
+  }) = TransferUpdate_FileCompleted;
+  /// The transfer reached its final status
+  const factory TransferUpdate.finished({
+    required String transferId,
+
+    /// `completed`, `failed` or `cancelled`
+    required String status,
+    required String? error,
+  }) = TransferUpdate_Finished;
+}
@@ -0,0 +1,38 @@
+// This file is automatically generated, so please do not edit it.
+// Generated by `flutter_rust_bridge`@ 2.3.0.
+
+// ignore_for_file: invalid_use_of_internal_member, unused_import, unnecessary_import
+
+import '../frb_generated.dart';
+import 'package:flutter_rust_bridge/flutter_rust_bridge_for_generated.dart';
+import 'package:freezed_annotation/freezed_annotation.dart' hide protected;
+part 'visibility.freezed.dart';
+
+/// Make this device discoverable for `seconds`, replacing the current window
+Future<void> makeDiscoverable({required BigInt seconds}) =>
+    RustLib.instance.api.crateApiVisibilityMakeDiscoverable(seconds: seconds);
+
+/// Keep this device discoverable until the service stops
+Future<void> makeDiscoverableWhileOpen() =>
+    RustLib.instance.api.crateApiVisibilityMakeDiscoverableWhileOpen();
+
+/// Hide this device right away, receiving from known peers still works
+Future<void> makeUndiscoverable() =>
+    RustLib.instance.api.crateApiVisibilityMakeUndiscoverable();
+
+Future<Visibility> visibility() =>
+    RustLib.instance.api.crateApiVisibilityVisibility();
+
+/// Whether other devices can find this one
+@freezed
+sealed class Visibility with _$Visibility {
+  const Visibility._();
+
+  const factory Visibility.hidden() = Visibility_Hidden;
+  /// Hidden again after `seconds`, for countdowns
+  const factory Visibility.for_({
+    required BigInt seconds,
+  }) = Visibility_For;
+  /// Visible for as long as the service runs
+  const factory Visibility.whileOpen() = Visibility_WhileOpen;
+}
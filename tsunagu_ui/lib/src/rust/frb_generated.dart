@@ -3,7 +3,14 @@
 
 // ignore_for_file: unused_import, unused_element, unnecessary_import, duplicate_ignore, invalid_use_of_internal_member, annotate_overrides, non_constant_identifier_names, curly_braces_in_flow_control_structures, prefer_const_literals_to_create_immutables, unused_field
 
+import 'api/devices.dart';
+import 'api/history.dart';
+import 'api/offers.dart';
+import 'api/service.dart';
+import 'api/settings.dart';
 import 'api/simple.dart';
+import 'api/transfers.dart';
+import 'api/visibility.dart';
 import 'dart:async';
 import 'dart:convert';
 import 'frb_generated.dart';
@@ -68,7 +75,7 @@ class RustLib extends BaseEntrypoint<RustLibApi, RustLibApiImpl, RustLibWire> {
   String get codegenVersion => '2.3.0';
 
   @override
-  int get rustContentHash => -1918914929;
+  int get rustContentHash => -1099878106;
 
   static const kDefaultExternalLibraryLoaderConfig =
       ExternalLibraryLoaderConfig(
@@ -79,101 +86,1285 @@ class RustLib extends BaseEntrypoint<RustLibApi, RustLibApiImpl, RustLibWire> {
 }
 
 abstract class RustLibApi extends BaseApi {
+  Stream<List<Device>> crateApiDevicesWatchDevices();
+
+  Future<List<HistoryItem>> crateApiHistoryLoadHistory(
+      {required HistoryQuery query});
+
+  Future<void> crateApiOffersAnswerOffer(
+      {required String transferId, required OfferAnswer answer});
+
+  Future<List<IncomingOffer>> crateApiOffersPendingOffers();
+
+  Stream<IncomingOffer> crateApiOffersWatchOffers();
+
+  Future<bool> crateApiServiceIsServiceRunning();
+
+  Future<Device> crateApiServiceStartService();
+
+  Future<void> crateApiServiceStopService();
+
+  Future<Settings> crateApiSettingsLoadSettings();
+
+  Future<void> crateApiSettingsSaveSettings({required Settings settings});
+
   String crateApiSimpleGreet({required String name});
 
-  Future<void> crateApiSimpleInitApp();
-}
+  Future<void> crateApiSimpleInitApp();
+
+  Future<void> crateApiTransfersCancelTransfer({required String transferId});
+
+  Future<String> crateApiTransfersSendFiles(
+      {required String deviceId, required List<String> paths});
+
+  Stream<TransferUpdate> crateApiTransfersWatchTransfers();
+
+  Future<void> crateApiVisibilityMakeDiscoverable({required BigInt seconds});
+
+  Future<void> crateApiVisibilityMakeDiscoverableWhileOpen();
+
+  Future<void> crateApiVisibilityMakeUndiscoverable();
+
+  Future<Visibility> crateApiVisibilityVisibility();
+}
+
+class RustLibApiImpl extends RustLibApiImplPlatform implements RustLibApi {
+  RustLibApiImpl({
+    required super.handler,
+    required super.wire,
+    required super.generalizedFrbRustBinding,
+    required super.portManager,
+  });
+
+  @override
+  Stream<List<Device>> crateApiDevicesWatchDevices() {
+    final sink = RustStreamSink<List<Device>>();
+    unawaited(handler.executeNormal(NormalTask(
+      callFfi: (port_) {
+        final serializer = SseSerializer(generalizedFrbRustBinding);
+        sse_encode_StreamSink_list_device_Sse(sink, serializer);
+        pdeCallFfi(generalizedFrbRustBinding, serializer,
+            funcId: 1, port: port_);
+      },
+      codec: SseCodec(
+        decodeSuccessData: sse_decode_unit,
+        decodeErrorData: sse_decode_AnyhowException,
+      ),
+      constMeta: kCrateApiDevicesWatchDevicesConstMeta,
+      argValues: [sink],
+      apiImpl: this,
+    )));
+    return sink.stream;
+  }
+
+  TaskConstMeta get kCrateApiDevicesWatchDevicesConstMeta =>
+      const TaskConstMeta(
+        debugName: "watch_devices",
+        argNames: ["sink"],
+      );
+
+  @override
+  Future<List<HistoryItem>> crateApiHistoryLoadHistory(
+      {required HistoryQuery query}) {
+    return handler.executeNormal(NormalTask(
+      callFfi: (port_) {
+        final serializer = SseSerializer(generalizedFrbRustBinding);
+        sse_encode_box_autoadd_history_query(query, serializer);
+        pdeCallFfi(generalizedFrbRustBinding, serializer,
+            funcId: 2, port: port_);
+      },
+      codec: SseCodec(
+        decodeSuccessData: sse_decode_list_history_item,
+        decodeErrorData: sse_decode_AnyhowException,
+      ),
+      constMeta: kCrateApiHistoryLoadHistoryConstMeta,
+      argValues: [query],
+      apiImpl: this,
+    ));
+  }
+
+  TaskConstMeta get kCrateApiHistoryLoadHistoryConstMeta => const TaskConstMeta(
+        debugName: "load_history",
+        argNames: ["query"],
+      );
+
+  @override
+  Future<void> crateApiOffersAnswerOffer(
+      {required String transferId, required OfferAnswer answer}) {
+    return handler.executeNormal(NormalTask(
+      callFfi: (port_) {
+        final serializer = SseSerializer(generalizedFrbRustBinding);
+        sse_encode_String(transferId, serializer);
+        sse_encode_box_autoadd_offer_answer(answer, serializer);
+        pdeCallFfi(generalizedFrbRustBinding, serializer,
+            funcId: 3, port: port_);
+      },
+      codec: SseCodec(
+        decodeSuccessData: sse_decode_unit,
+        decodeErrorData: sse_decode_AnyhowException,
+      ),
+      constMeta: kCrateApiOffersAnswerOfferConstMeta,
+      argValues: [transferId, answer],
+      apiImpl: this,
+    ));
+  }
+
+  TaskConstMeta get kCrateApiOffersAnswerOfferConstMeta => const TaskConstMeta(
+        debugName: "answer_offer",
+        argNames: ["transferId", "answer"],
+      );
+
+  @override
+  Future<List<IncomingOffer>> crateApiOffersPendingOffers() {
+    return handler.executeNormal(NormalTask(
+      callFfi: (port_) {
+        final serializer = SseSerializer(generalizedFrbRustBinding);
+        pdeCallFfi(generalizedFrbRustBinding, serializer,
+            funcId: 4, port: port_);
+      },
+      codec: SseCodec(
+        decodeSuccessData: sse_decode_list_incoming_offer,
+        decodeErrorData: sse_decode_AnyhowException,
+      ),
+      constMeta: kCrateApiOffersPendingOffersConstMeta,
+      argValues: [],
+      apiImpl: this,
+    ));
+  }
+
+  TaskConstMeta get kCrateApiOffersPendingOffersConstMeta =>
+      const TaskConstMeta(
+        debugName: "pending_offers",
+        argNames: [],
+      );
+
+  @override
+  Stream<IncomingOffer> crateApiOffersWatchOffers() {
+    final sink = RustStreamSink<IncomingOffer>();
+    unawaited(handler.executeNormal(NormalTask(
+      callFfi: (port_) {
+        final serializer = SseSerializer(generalizedFrbRustBinding);
+        sse_encode_StreamSink_incoming_offer_Sse(sink, serializer);
+        pdeCallFfi(generalizedFrbRustBinding, serializer,
+            funcId: 5, port: port_);
+      },
+      codec: SseCodec(
+        decodeSuccessData: sse_decode_unit,
+        decodeErrorData: sse_decode_AnyhowException,
+      ),
+      constMeta: kCrateApiOffersWatchOffersConstMeta,
+      argValues: [sink],
+      apiImpl: this,
+    )));
+    return sink.stream;
+  }
+
+  TaskConstMeta get kCrateApiOffersWatchOffersConstMeta => const TaskConstMeta(
+        debugName: "watch_offers",
+        argNames: ["sink"],
+      );
+
+  @override
+  Future<bool> crateApiServiceIsServiceRunning() {
+    return handler.executeNormal(NormalTask(
+      callFfi: (port_) {
+        final serializer = SseSerializer(generalizedFrbRustBinding);
+        pdeCallFfi(generalizedFrbRustBinding, serializer,
+            funcId: 6, port: port_);
+      },
+      codec: SseCodec(
+        decodeSuccessData: sse_decode_bool,
+        decodeErrorData: null,
+      ),
+      constMeta: kCrateApiServiceIsServiceRunningConstMeta,
+      argValues: [],
+      apiImpl: this,
+    ));
+  }
+
+  TaskConstMeta get kCrateApiServiceIsServiceRunningConstMeta =>
+      const TaskConstMeta(
+        debugName: "is_service_running",
+        argNames: [],
+      );
+
+  @override
+  Future<Device> crateApiServiceStartService() {
+    return handler.executeNormal(NormalTask(
+      callFfi: (port_) {
+        final serializer = SseSerializer(generalizedFrbRustBinding);
+        pdeCallFfi(generalizedFrbRustBinding, serializer,
+            funcId: 7, port: port_);
+      },
+      codec: SseCodec(
+        decodeSuccessData: sse_decode_device,
+        decodeErrorData: sse_decode_AnyhowException,
+      ),
+      constMeta: kCrateApiServiceStartServiceConstMeta,
+      argValues: [],
+      apiImpl: this,
+    ));
+  }
+
+  TaskConstMeta get kCrateApiServiceStartServiceConstMeta =>
+      const TaskConstMeta(
+        debugName: "start_service",
+        argNames: [],
+      );
+
+  @override
+  Future<void> crateApiServiceStopService() {
+    return handler.executeNormal(NormalTask(
+      callFfi: (port_) {
+        final serializer = SseSerializer(generalizedFrbRustBinding);
+        pdeCallFfi(generalizedFrbRustBinding, serializer,
+            funcId: 8, port: port_);
+      },
+      codec: SseCodec(
+        decodeSuccessData: sse_decode_unit,
+        decodeErrorData: sse_decode_AnyhowException,
+      ),
+      constMeta: kCrateApiServiceStopServiceConstMeta,
+      argValues: [],
+      apiImpl: this,
+    ));
+  }
+
+  TaskConstMeta get kCrateApiServiceStopServiceConstMeta => const TaskConstMeta(
+        debugName: "stop_service",
+        argNames: [],
+      );
+
+  @override
+  Future<Settings> crateApiSettingsLoadSettings() {
+    return handler.executeNormal(NormalTask(
+      callFfi: (port_) {
+        final serializer = SseSerializer(generalizedFrbRustBinding);
+        pdeCallFfi(generalizedFrbRustBinding, serializer,
+            funcId: 9, port: port_);
+      },
+      codec: SseCodec(
+        decodeSuccessData: sse_decode_settings,
+        decodeErrorData: sse_decode_AnyhowException,
+      ),
+      constMeta: kCrateApiSettingsLoadSettingsConstMeta,
+      argValues: [],
+      apiImpl: this,
+    ));
+  }
+
+  TaskConstMeta get kCrateApiSettingsLoadSettingsConstMeta =>
+      const TaskConstMeta(
+        debugName: "load_settings",
+        argNames: [],
+      );
+
+  @override
+  Future<void> crateApiSettingsSaveSettings({required Settings settings}) {
+    return handler.executeNormal(NormalTask(
+      callFfi: (port_) {
+        final serializer = SseSerializer(generalizedFrbRustBinding);
+        sse_encode_box_autoadd_settings(settings, serializer);
+        pdeCallFfi(generalizedFrbRustBinding, serializer,
+            funcId: 10, port: port_);
+      },
+      codec: SseCodec(
+        decodeSuccessData: sse_decode_unit,
+        decodeErrorData: sse_decode_AnyhowException,
+      ),
+      constMeta: kCrateApiSettingsSaveSettingsConstMeta,
+      argValues: [settings],
+      apiImpl: this,
+    ));
+  }
+
+  TaskConstMeta get kCrateApiSettingsSaveSettingsConstMeta =>
+      const TaskConstMeta(
+        debugName: "save_settings",
+        argNames: ["settings"],
+      );
+
+  @override
+  String crateApiSimpleGreet({required String name}) {
+    return handler.executeSync(SyncTask(
+      callFfi: () {
+        final serializer = SseSerializer(generalizedFrbRustBinding);
+        sse_encode_String(name, serializer);
+        return pdeCallFfi(generalizedFrbRustBinding, serializer, funcId: 11)!;
+      },
+      codec: SseCodec(
+        decodeSuccessData: sse_decode_String,
+        decodeErrorData: null,
+      ),
+      constMeta: kCrateApiSimpleGreetConstMeta,
+      argValues: [name],
+      apiImpl: this,
+    ));
+  }
+
+  TaskConstMeta get kCrateApiSimpleGreetConstMeta => const TaskConstMeta(
+        debugName: "greet",
+        argNames: ["name"],
+      );
+
+  @override
+  Future<void> crateApiSimpleInitApp() {
+    return handler.executeNormal(NormalTask(
+      callFfi: (port_) {
+        final serializer = SseSerializer(generalizedFrbRustBinding);
+        pdeCallFfi(generalizedFrbRustBinding, serializer,
+            funcId: 12, port: port_);
+      },
+      codec: SseCodec(
+        decodeSuccessData: sse_decode_unit,
+        decodeErrorData: null,
+      ),
+      constMeta: kCrateApiSimpleInitAppConstMeta,
+      argValues: [],
+      apiImpl: this,
+    ));
+  }
+
+  TaskConstMeta get kCrateApiSimpleInitAppConstMeta => const TaskConstMeta(
+        debugName: "init_app",
+        argNames: [],
+      );
+
+  @override
+  Future<void> crateApiTransfersCancelTransfer({required String transferId}) {
+    return handler.executeNormal(NormalTask(
+      callFfi: (port_) {
+        final serializer = SseSerializer(generalizedFrbRustBinding);
+        sse_encode_String(transferId, serializer);
+        pdeCallFfi(generalizedFrbRustBinding, serializer,
+            funcId: 13, port: port_);
+      },
+      codec: SseCodec(
+        decodeSuccessData: sse_decode_unit,
+        decodeErrorData: sse_decode_AnyhowException,
+      ),
+      constMeta: kCrateApiTransfersCancelTransferConstMeta,
+      argValues: [transferId],
+      apiImpl: this,
+    ));
+  }
+
+  TaskConstMeta get kCrateApiTransfersCancelTransferConstMeta =>
+      const TaskConstMeta(
+        debugName: "cancel_transfer",
+        argNames: ["transferId"],
+      );
+
+  @override
+  Future<String> crateApiTransfersSendFiles(
+      {required String deviceId, required List<String> paths}) {
+    return handler.executeNormal(NormalTask(
+      callFfi: (port_) {
+        final serializer = SseSerializer(generalizedFrbRustBinding);
+        sse_encode_String(deviceId, serializer);
+        sse_encode_list_String(paths, serializer);
+        pdeCallFfi(generalizedFrbRustBinding, serializer,
+            funcId: 14, port: port_);
+      },
+      codec: SseCodec(
+        decodeSuccessData: sse_decode_String,
+        decodeErrorData: sse_decode_AnyhowException,
+      ),
+      constMeta: kCrateApiTransfersSendFilesConstMeta,
+      argValues: [deviceId, paths],
+      apiImpl: this,
+    ));
+  }
+
+  TaskConstMeta get kCrateApiTransfersSendFilesConstMeta => const TaskConstMeta(
+        debugName: "send_files",
+        argNames: ["deviceId", "paths"],
+      );
+
+  @override
+  Stream<TransferUpdate> crateApiTransfersWatchTransfers() {
+    final sink = RustStreamSink<TransferUpdate>();
+    unawaited(handler.executeNormal(NormalTask(
+      callFfi: (port_) {
+        final serializer = SseSerializer(generalizedFrbRustBinding);
+        sse_encode_StreamSink_transfer_update_Sse(sink, serializer);
+        pdeCallFfi(generalizedFrbRustBinding, serializer,
+            funcId: 15, port: port_);
+      },
+      codec: SseCodec(
+        decodeSuccessData: sse_decode_unit,
+        decodeErrorData: sse_decode_AnyhowException,
+      ),
+      constMeta: kCrateApiTransfersWatchTransfersConstMeta,
+      argValues: [sink],
+      apiImpl: this,
+    )));
+    return sink.stream;
+  }
+
+  TaskConstMeta get kCrateApiTransfersWatchTransfersConstMeta =>
+      const TaskConstMeta(
+        debugName: "watch_transfers",
+        argNames: ["sink"],
+      );
+
+  @override
+  Future<void> crateApiVisibilityMakeDiscoverable({required BigInt seconds}) {
+    return handler.executeNormal(NormalTask(
+      callFfi: (port_) {
+        final serializer = SseSerializer(generalizedFrbRustBinding);
+        sse_encode_u_64(seconds, serializer);
+        pdeCallFfi(generalizedFrbRustBinding, serializer,
+            funcId: 16, port: port_);
+      },
+      codec: SseCodec(
+        decodeSuccessData: sse_decode_unit,
+        decodeErrorData: sse_decode_AnyhowException,
+      ),
+      constMeta: kCrateApiVisibilityMakeDiscoverableConstMeta,
+      argValues: [seconds],
+      apiImpl: this,
+    ));
+  }
+
+  TaskConstMeta get kCrateApiVisibilityMakeDiscoverableConstMeta =>
+      const TaskConstMeta(
+        debugName: "make_discoverable",
+        argNames: ["seconds"],
+      );
+
+  @override
+  Future<void> crateApiVisibilityMakeDiscoverableWhileOpen() {
+    return handler.executeNormal(NormalTask(
+      callFfi: (port_) {
+        final serializer = SseSerializer(generalizedFrbRustBinding);
+        pdeCallFfi(generalizedFrbRustBinding, serializer,
+            funcId: 17, port: port_);
+      },
+      codec: SseCodec(
+        decodeSuccessData: sse_decode_unit,
+        decodeErrorData: sse_decode_AnyhowException,
+      ),
+      constMeta: kCrateApiVisibilityMakeDiscoverableWhileOpenConstMeta,
+      argValues: [],
+      apiImpl: this,
+    ));
+  }
+
+  TaskConstMeta get kCrateApiVisibilityMakeDiscoverableWhileOpenConstMeta =>
+      const TaskConstMeta(
+        debugName: "make_discoverable_while_open",
+        argNames: [],
+      );
+
+  @override
+  Future<void> crateApiVisibilityMakeUndiscoverable() {
+    return handler.executeNormal(NormalTask(
+      callFfi: (port_) {
+        final serializer = SseSerializer(generalizedFrbRustBinding);
+        pdeCallFfi(generalizedFrbRustBinding, serializer,
+            funcId: 18, port: port_);
+      },
+      codec: SseCodec(
+        decodeSuccessData: sse_decode_unit,
+        decodeErrorData: sse_decode_AnyhowException,
+      ),
+      constMeta: kCrateApiVisibilityMakeUndiscoverableConstMeta,
+      argValues: [],
+      apiImpl: this,
+    ));
+  }
+
+  TaskConstMeta get kCrateApiVisibilityMakeUndiscoverableConstMeta =>
+      const TaskConstMeta(
+        debugName: "make_undiscoverable",
+        argNames: [],
+      );
+
+  @override
+  Future<Visibility> crateApiVisibilityVisibility() {
+    return handler.executeNormal(NormalTask(
+      callFfi: (port_) {
+        final serializer = SseSerializer(generalizedFrbRustBinding);
+        pdeCallFfi(generalizedFrbRustBinding, serializer,
+            funcId: 19, port: port_);
+      },
+      codec: SseCodec(
+        decodeSuccessData: sse_decode_visibility,
+        decodeErrorData: sse_decode_AnyhowException,
+      ),
+      constMeta: kCrateApiVisibilityVisibilityConstMeta,
+      argValues: [],
+      apiImpl: this,
+    ));
+  }
+
+  TaskConstMeta get kCrateApiVisibilityVisibilityConstMeta =>
+      const TaskConstMeta(
+        debugName: "visibility",
+        argNames: [],
+      );
+
+  @protected
+  AnyhowException dco_decode_AnyhowException(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return AnyhowException(raw as String);
+  }
+
+  @protected
+  RustStreamSink<IncomingOffer> dco_decode_StreamSink_incoming_offer_Sse(
+      dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    throw UnimplementedError();
+  }
+
+  @protected
+  RustStreamSink<List<Device>> dco_decode_StreamSink_list_device_Sse(
+      dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    throw UnimplementedError();
+  }
+
+  @protected
+  RustStreamSink<TransferUpdate> dco_decode_StreamSink_transfer_update_Sse(
+      dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    throw UnimplementedError();
+  }
+
+  @protected
+  String dco_decode_String(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return raw as String;
+  }
+
+  @protected
+  bool dco_decode_bool(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return raw as bool;
+  }
+
+  @protected
+  HistoryQuery dco_decode_box_autoadd_history_query(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return dco_decode_history_query(raw);
+  }
+
+  @protected
+  OfferAnswer dco_decode_box_autoadd_offer_answer(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return dco_decode_offer_answer(raw);
+  }
+
+  @protected
+  Settings dco_decode_box_autoadd_settings(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return dco_decode_settings(raw);
+  }
+
+  @protected
+  int dco_decode_box_autoadd_u_32(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return dco_decode_u_32(raw);
+  }
+
+  @protected
+  BigInt dco_decode_box_autoadd_u_64(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return dco_decode_u_64(raw);
+  }
+
+  @protected
+  Device dco_decode_device(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    final arr = raw as List<dynamic>;
+    if (arr.length != 7)
+      throw Exception('unexpected arr length: expect 7 but see ${arr.length}');
+    return Device(
+      id: dco_decode_String(arr[0]),
+      name: dco_decode_String(arr[1]),
+      model: dco_decode_String(arr[2]),
+      os: dco_decode_String(arr[3]),
+      addresses: dco_decode_list_String(arr[4]),
+      port: dco_decode_u_16(arr[5]),
+      trusted: dco_decode_bool(arr[6]),
+    );
+  }
+
+  @protected
+  double dco_decode_f_64(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return raw as double;
+  }
+
+  @protected
+  HistoryFile dco_decode_history_file(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    final arr = raw as List<dynamic>;
+    if (arr.length != 4)
+      throw Exception('unexpected arr length: expect 4 but see ${arr.length}');
+    return HistoryFile(
+      path: dco_decode_String(arr[0]),
+      size: dco_decode_u_64(arr[1]),
+      hash: dco_decode_opt_String(arr[2]),
+      localPath: dco_decode_opt_String(arr[3]),
+    );
+  }
+
+  @protected
+  HistoryItem dco_decode_history_item(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    final arr = raw as List<dynamic>;
+    if (arr.length != 11)
+      throw Exception('unexpected arr length: expect 11 but see ${arr.length}');
+    return HistoryItem(
+      transferId: dco_decode_String(arr[0]),
+      sent: dco_decode_bool(arr[1]),
+      peerId: dco_decode_String(arr[2]),
+      peerName: dco_decode_String(arr[3]),
+      files: dco_decode_list_history_file(arr[4]),
+      totalSize: dco_decode_u_64(arr[5]),
+      startedAt: dco_decode_u_64(arr[6]),
+      finishedAt: dco_decode_u_64(arr[7]),
+      durationMs: dco_decode_u_64(arr[8]),
+      status: dco_decode_String(arr[9]),
+      error: dco_decode_opt_String(arr[10]),
+    );
+  }
+
+  @protected
+  HistoryQuery dco_decode_history_query(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    final arr = raw as List<dynamic>;
+    if (arr.length != 5)
+      throw Exception('unexpected arr length: expect 5 but see ${arr.length}');
+    return HistoryQuery(
+      peer: dco_decode_opt_String(arr[0]),
+      status: dco_decode_opt_String(arr[1]),
+      since: dco_decode_opt_box_autoadd_u_64(arr[2]),
+      until: dco_decode_opt_box_autoadd_u_64(arr[3]),
+      limit: dco_decode_opt_box_autoadd_u_32(arr[4]),
+    );
+  }
+
+  @protected
+  IncomingOffer dco_decode_incoming_offer(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    final arr = raw as List<dynamic>;
+    if (arr.length != 4)
+      throw Exception('unexpected arr length: expect 4 but see ${arr.length}');
+    return IncomingOffer(
+      transferId: dco_decode_String(arr[0]),
+      sender: dco_decode_device(arr[1]),
+      files: dco_decode_list_transfer_file(arr[2]),
+      totalSize: dco_decode_u_64(arr[3]),
+    );
+  }
+
+  @protected
+  List<String> dco_decode_list_String(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return (raw as List<dynamic>).map(dco_decode_String).toList();
+  }
+
+  @protected
+  List<Device> dco_decode_list_device(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return (raw as List<dynamic>).map(dco_decode_device).toList();
+  }
+
+  @protected
+  List<HistoryFile> dco_decode_list_history_file(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return (raw as List<dynamic>).map(dco_decode_history_file).toList();
+  }
+
+  @protected
+  List<HistoryItem> dco_decode_list_history_item(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return (raw as List<dynamic>).map(dco_decode_history_item).toList();
+  }
+
+  @protected
+  List<IncomingOffer> dco_decode_list_incoming_offer(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return (raw as List<dynamic>).map(dco_decode_incoming_offer).toList();
+  }
+
+  @protected
+  Uint32List dco_decode_list_prim_u_32_strict(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return raw as Uint32List;
+  }
+
+  @protected
+  Uint8List dco_decode_list_prim_u_8_strict(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return raw as Uint8List;
+  }
+
+  @protected
+  List<TransferFile> dco_decode_list_transfer_file(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return (raw as List<dynamic>).map(dco_decode_transfer_file).toList();
+  }
+
+  @protected
+  OfferAnswer dco_decode_offer_answer(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    switch (raw[0]) {
+      case 0:
+        return OfferAnswer_Accept();
+      case 1:
+        return OfferAnswer_AcceptFiles(
+          indexes: dco_decode_list_prim_u_32_strict(raw[1]),
+        );
+      case 2:
+        return OfferAnswer_Reject(
+          reason: dco_decode_String(raw[1]),
+        );
+      default:
+        throw Exception("unreachable");
+    }
+  }
+
+  @protected
+  String? dco_decode_opt_String(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return raw == null ? null : dco_decode_String(raw);
+  }
+
+  @protected
+  int? dco_decode_opt_box_autoadd_u_32(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return raw == null ? null : dco_decode_box_autoadd_u_32(raw);
+  }
+
+  @protected
+  BigInt? dco_decode_opt_box_autoadd_u_64(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return raw == null ? null : dco_decode_box_autoadd_u_64(raw);
+  }
+
+  @protected
+  Settings dco_decode_settings(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    final arr = raw as List<dynamic>;
+    if (arr.length != 7)
+      throw Exception('unexpected arr length: expect 7 but see ${arr.length}');
+    return Settings(
+      deviceName: dco_decode_String(arr[0]),
+      saveDirectory: dco_decode_String(arr[1]),
+      allowAutoReceive: dco_decode_bool(arr[2]),
+      discoveryBackends: dco_decode_list_String(arr[3]),
+      discoveryPort: dco_decode_u_16(arr[4]),
+      transferPort: dco_decode_u_16(arr[5]),
+      maxConcurrentTransfers: dco_decode_u_32(arr[6]),
+    );
+  }
+
+  @protected
+  TransferFile dco_decode_transfer_file(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    final arr = raw as List<dynamic>;
+    if (arr.length != 2)
+      throw Exception('unexpected arr length: expect 2 but see ${arr.length}');
+    return TransferFile(
+      path: dco_decode_String(arr[0]),
+      size: dco_decode_u_64(arr[1]),
+    );
+  }
+
+  @protected
+  TransferUpdate dco_decode_transfer_update(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    switch (raw[0]) {
+      case 0:
+        return TransferUpdate_Started(
+          transferId: dco_decode_String(raw[1]),
+          sent: dco_decode_bool(raw[2]),
+          peer: dco_decode_device(raw[3]),
+          files: dco_decode_list_transfer_file(raw[4]),
+          totalSize: dco_decode_u_64(raw[5]),
+        );
+      case 1:
+        return TransferUpdate_Progress(
+          transferId: dco_decode_String(raw[1]),
+          fileIndex: dco_decode_u_32(raw[2]),
+          bytesTransferred: dco_decode_u_64(raw[3]),
+          totalBytes: dco_decode_u_64(raw[4]),
+          bytesPerSecond: dco_decode_f_64(raw[5]),
+          etaSeconds: dco_decode_opt_box_autoadd_u_64(raw[6]),
+        );
+      case 2:
+        return TransferUpdate_FileCompleted(
+          transferId: dco_decode_String(raw[1]),
+          index: dco_decode_u_32(raw[2]),
+        );
+      case 3:
+        return TransferUpdate_Finished(
+          transferId: dco_decode_String(raw[1]),
+          status: dco_decode_String(raw[2]),
+          error: dco_decode_opt_String(raw[3]),
+        );
+      default:
+        throw Exception("unreachable");
+    }
+  }
+
+  @protected
+  int dco_decode_u_16(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return raw as int;
+  }
+
+  @protected
+  int dco_decode_u_32(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return raw as int;
+  }
+
+  @protected
+  BigInt dco_decode_u_64(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return dcoDecodeU64(raw);
+  }
+
+  @protected
+  int dco_decode_u_8(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return raw as int;
+  }
+
+  @protected
+  void dco_decode_unit(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    return;
+  }
+
+  @protected
+  Visibility dco_decode_visibility(dynamic raw) {
+    // Codec=Dco (DartCObject based), see doc to use other codecs
+    switch (raw[0]) {
+      case 0:
+        return Visibility_Hidden();
+      case 1:
+        return Visibility_For(
+          seconds: dco_decode_u_64(raw[1]),
+        );
+      case 2:
+        return Visibility_WhileOpen();
+      default:
+        throw Exception("unreachable");
+    }
+  }
+
+  @protected
+  AnyhowException sse_decode_AnyhowException(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    var inner = sse_decode_String(deserializer);
+    return AnyhowException(inner);
+  }
+
+  @protected
+  RustStreamSink<IncomingOffer> sse_decode_StreamSink_incoming_offer_Sse(
+      SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    throw UnimplementedError('Unreachable ()');
+  }
+
+  @protected
+  RustStreamSink<List<Device>> sse_decode_StreamSink_list_device_Sse(
+      SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    throw UnimplementedError('Unreachable ()');
+  }
+
+  @protected
+  RustStreamSink<TransferUpdate> sse_decode_StreamSink_transfer_update_Sse(
+      SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    throw UnimplementedError('Unreachable ()');
+  }
+
+  @protected
+  String sse_decode_String(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    var inner = sse_decode_list_prim_u_8_strict(deserializer);
+    return utf8.decoder.convert(inner);
+  }
+
+  @protected
+  bool sse_decode_bool(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    return deserializer.buffer.getUint8() != 0;
+  }
+
+  @protected
+  HistoryQuery sse_decode_box_autoadd_history_query(
+      SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    return (sse_decode_history_query(deserializer));
+  }
+
+  @protected
+  OfferAnswer sse_decode_box_autoadd_offer_answer(
+      SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    return (sse_decode_offer_answer(deserializer));
+  }
+
+  @protected
+  Settings sse_decode_box_autoadd_settings(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    return (sse_decode_settings(deserializer));
+  }
+
+  @protected
+  int sse_decode_box_autoadd_u_32(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    return (sse_decode_u_32(deserializer));
+  }
+
+  @protected
+  BigInt sse_decode_box_autoadd_u_64(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    return (sse_decode_u_64(deserializer));
+  }
+
+  @protected
+  Device sse_decode_device(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    var var_id = sse_decode_String(deserializer);
+    var var_name = sse_decode_String(deserializer);
+    var var_model = sse_decode_String(deserializer);
+    var var_os = sse_decode_String(deserializer);
+    var var_addresses = sse_decode_list_String(deserializer);
+    var var_port = sse_decode_u_16(deserializer);
+    var var_trusted = sse_decode_bool(deserializer);
+    return Device(
+        id: var_id,
+        name: var_name,
+        model: var_model,
+        os: var_os,
+        addresses: var_addresses,
+        port: var_port,
+        trusted: var_trusted);
+  }
+
+  @protected
+  double sse_decode_f_64(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    return deserializer.buffer.getFloat64();
+  }
+
+  @protected
+  HistoryFile sse_decode_history_file(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    var var_path = sse_decode_String(deserializer);
+    var var_size = sse_decode_u_64(deserializer);
+    var var_hash = sse_decode_opt_String(deserializer);
+    var var_localPath = sse_decode_opt_String(deserializer);
+    return HistoryFile(
+        path: var_path,
+        size: var_size,
+        hash: var_hash,
+        localPath: var_localPath);
+  }
+
+  @protected
+  HistoryItem sse_decode_history_item(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    var var_transferId = sse_decode_String(deserializer);
+    var var_sent = sse_decode_bool(deserializer);
+    var var_peerId = sse_decode_String(deserializer);
+    var var_peerName = sse_decode_String(deserializer);
+    var var_files = sse_decode_list_history_file(deserializer);
+    var var_totalSize = sse_decode_u_64(deserializer);
+    var var_startedAt = sse_decode_u_64(deserializer);
+    var var_finishedAt = sse_decode_u_64(deserializer);
+    var var_durationMs = sse_decode_u_64(deserializer);
+    var var_status = sse_decode_String(deserializer);
+    var var_error = sse_decode_opt_String(deserializer);
+    return HistoryItem(
+        transferId: var_transferId,
+        sent: var_sent,
+        peerId: var_peerId,
+        peerName: var_peerName,
+        files: var_files,
+        totalSize: var_totalSize,
+        startedAt: var_startedAt,
+        finishedAt: var_finishedAt,
+        durationMs: var_durationMs,
+        status: var_status,
+        error: var_error);
+  }
+
+  @protected
+  HistoryQuery sse_decode_history_query(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    var var_peer = sse_decode_opt_String(deserializer);
+    var var_status = sse_decode_opt_String(deserializer);
+    var var_since = sse_decode_opt_box_autoadd_u_64(deserializer);
+    var var_until = sse_decode_opt_box_autoadd_u_64(deserializer);
+    var var_limit = sse_decode_opt_box_autoadd_u_32(deserializer);
+    return HistoryQuery(
+        peer: var_peer,
+        status: var_status,
+        since: var_since,
+        until: var_until,
+        limit: var_limit);
+  }
+
+  @protected
+  IncomingOffer sse_decode_incoming_offer(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    var var_transferId = sse_decode_String(deserializer);
+    var var_sender = sse_decode_device(deserializer);
+    var var_files = sse_decode_list_transfer_file(deserializer);
+    var var_totalSize = sse_decode_u_64(deserializer);
+    return IncomingOffer(
+        transferId: var_transferId,
+        sender: var_sender,
+        files: var_files,
+        totalSize: var_totalSize);
+  }
+
+  @protected
+  List<String> sse_decode_list_String(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+
+    var len_ = sse_decode_i_32(deserializer);
+    var ans_ = <String>[];
+    for (var idx_ = 0; idx_ < len_; ++idx_) {
+      ans_.add(sse_decode_String(deserializer));
+    }
+    return ans_;
+  }
+
+  @protected
+  List<Device> sse_decode_list_device(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+
+    var len_ = sse_decode_i_32(deserializer);
+    var ans_ = <Device>[];
+    for (var idx_ = 0; idx_ < len_; ++idx_) {
+      ans_.add(sse_decode_device(deserializer));
+    }
+    return ans_;
+  }
+
+  @protected
+  List<HistoryFile> sse_decode_list_history_file(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+
+    var len_ = sse_decode_i_32(deserializer);
+    var ans_ = <HistoryFile>[];
+    for (var idx_ = 0; idx_ < len_; ++idx_) {
+      ans_.add(sse_decode_history_file(deserializer));
+    }
+    return ans_;
+  }
+
+  @protected
+  List<HistoryItem> sse_decode_list_history_item(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+
+    var len_ = sse_decode_i_32(deserializer);
+    var ans_ = <HistoryItem>[];
+    for (var idx_ = 0; idx_ < len_; ++idx_) {
+      ans_.add(sse_decode_history_item(deserializer));
+    }
+    return ans_;
+  }
+
+  @protected
+  List<IncomingOffer> sse_decode_list_incoming_offer(
+      SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+
+    var len_ = sse_decode_i_32(deserializer);
+    var ans_ = <IncomingOffer>[];
+    for (var idx_ = 0; idx_ < len_; ++idx_) {
+      ans_.add(sse_decode_incoming_offer(deserializer));
+    }
+    return ans_;
+  }
+
+  @protected
+  Uint32List sse_decode_list_prim_u_32_strict(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    var len_ = sse_decode_i_32(deserializer);
+    return deserializer.buffer.getUint32List(len_);
+  }
 
-class RustLibApiImpl extends RustLibApiImplPlatform implements RustLibApi {
-  RustLibApiImpl({
-    required super.handler,
-    required super.wire,
-    required super.generalizedFrbRustBinding,
-    required super.portManager,
-  });
+  @protected
+  Uint8List sse_decode_list_prim_u_8_strict(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    var len_ = sse_decode_i_32(deserializer);
+    return deserializer.buffer.getUint8List(len_);
+  }
 
-  @override
-  String crateApiSimpleGreet({required String name}) {
-    return handler.executeSync(SyncTask(
-      callFfi: () {
-        final serializer = SseSerializer(generalizedFrbRustBinding);
-        sse_encode_String(name, serializer);
-        return pdeCallFfi(generalizedFrbRustBinding, serializer, funcId: 1)!;
-      },
-      codec: SseCodec(
-        decodeSuccessData: sse_decode_String,
-        decodeErrorData: null,
-      ),
-      constMeta: kCrateApiSimpleGreetConstMeta,
-      argValues: [name],
-      apiImpl: this,
-    ));
+  @protected
+  List<TransferFile> sse_decode_list_transfer_file(
+      SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+
+    var len_ = sse_decode_i_32(deserializer);
+    var ans_ = <TransferFile>[];
+    for (var idx_ = 0; idx_ < len_; ++idx_) {
+      ans_.add(sse_decode_transfer_file(deserializer));
+    }
+    return ans_;
   }
 
-  TaskConstMeta get kCrateApiSimpleGreetConstMeta => const TaskConstMeta(
-        debugName: "greet",
-        argNames: ["name"],
-      );
+  @protected
+  OfferAnswer sse_decode_offer_answer(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
 
-  @override
-  Future<void> crateApiSimpleInitApp() {
-    return handler.executeNormal(NormalTask(
-      callFfi: (port_) {
-        final serializer = SseSerializer(generalizedFrbRustBinding);
-        pdeCallFfi(generalizedFrbRustBinding, serializer,
-            funcId: 2, port: port_);
-      },
-      codec: SseCodec(
-        decodeSuccessData: sse_decode_unit,
-        decodeErrorData: null,
-      ),
-      constMeta: kCrateApiSimpleInitAppConstMeta,
-      argValues: [],
-      apiImpl: this,
-    ));
+    var tag_ = sse_decode_i_32(deserializer);
+    switch (tag_) {
+      case 0:
+        return OfferAnswer_Accept();
+      case 1:
+        var var_indexes = sse_decode_list_prim_u_32_strict(deserializer);
+        return OfferAnswer_AcceptFiles(indexes: var_indexes);
+      case 2:
+        var var_reason = sse_decode_String(deserializer);
+        return OfferAnswer_Reject(reason: var_reason);
+      default:
+        throw UnimplementedError('');
+    }
   }
 
-  TaskConstMeta get kCrateApiSimpleInitAppConstMeta => const TaskConstMeta(
-        debugName: "init_app",
-        argNames: [],
-      );
+  @protected
+  String? sse_decode_opt_String(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+
+    if (sse_decode_bool(deserializer)) {
+      return (sse_decode_String(deserializer));
+    } else {
+      return null;
+    }
+  }
 
   @protected
-  String dco_decode_String(dynamic raw) {
-    // Codec=Dco (DartCObject based), see doc to use other codecs
-    return raw as String;
+  int? sse_decode_opt_box_autoadd_u_32(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+
+    if (sse_decode_bool(deserializer)) {
+      return (sse_decode_box_autoadd_u_32(deserializer));
+    } else {
+      return null;
+    }
   }
 
   @protected
-  Uint8List dco_decode_list_prim_u_8_strict(dynamic raw) {
-    // Codec=Dco (DartCObject based), see doc to use other codecs
-    return raw as Uint8List;
+  BigInt? sse_decode_opt_box_autoadd_u_64(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+
+    if (sse_decode_bool(deserializer)) {
+      return (sse_decode_box_autoadd_u_64(deserializer));
+    } else {
+      return null;
+    }
   }
 
   @protected
-  int dco_decode_u_8(dynamic raw) {
-    // Codec=Dco (DartCObject based), see doc to use other codecs
-    return raw as int;
+  Settings sse_decode_settings(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    var var_deviceName = sse_decode_String(deserializer);
+    var var_saveDirectory = sse_decode_String(deserializer);
+    var var_allowAutoReceive = sse_decode_bool(deserializer);
+    var var_discoveryBackends = sse_decode_list_String(deserializer);
+    var var_discoveryPort = sse_decode_u_16(deserializer);
+    var var_transferPort = sse_decode_u_16(deserializer);
+    var var_maxConcurrentTransfers = sse_decode_u_32(deserializer);
+    return Settings(
+        deviceName: var_deviceName,
+        saveDirectory: var_saveDirectory,
+        allowAutoReceive: var_allowAutoReceive,
+        discoveryBackends: var_discoveryBackends,
+        discoveryPort: var_discoveryPort,
+        transferPort: var_transferPort,
+        maxConcurrentTransfers: var_maxConcurrentTransfers);
   }
 
   @protected
-  void dco_decode_unit(dynamic raw) {
-    // Codec=Dco (DartCObject based), see doc to use other codecs
-    return;
+  TransferFile sse_decode_transfer_file(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    var var_path = sse_decode_String(deserializer);
+    var var_size = sse_decode_u_64(deserializer);
+    return TransferFile(path: var_path, size: var_size);
   }
 
   @protected
-  String sse_decode_String(SseDeserializer deserializer) {
+  TransferUpdate sse_decode_transfer_update(SseDeserializer deserializer) {
     // Codec=Sse (Serialization based), see doc to use other codecs
-    var inner = sse_decode_list_prim_u_8_strict(deserializer);
-    return utf8.decoder.convert(inner);
+
+    var tag_ = sse_decode_i_32(deserializer);
+    switch (tag_) {
+      case 0:
+        var var_transferId = sse_decode_String(deserializer);
+        var var_sent = sse_decode_bool(deserializer);
+        var var_peer = sse_decode_device(deserializer);
+        var var_files = sse_decode_list_transfer_file(deserializer);
+        var var_totalSize = sse_decode_u_64(deserializer);
+        return TransferUpdate_Started(
+            transferId: var_transferId,
+            sent: var_sent,
+            peer: var_peer,
+            files: var_files,
+            totalSize: var_totalSize);
+      case 1:
+        var var_transferId = sse_decode_String(deserializer);
+        var var_fileIndex = sse_decode_u_32(deserializer);
+        var var_bytesTransferred = sse_decode_u_64(deserializer);
+        var var_totalBytes = sse_decode_u_64(deserializer);
+        var var_bytesPerSecond = sse_decode_f_64(deserializer);
+        var var_etaSeconds = sse_decode_opt_box_autoadd_u_64(deserializer);
+        return TransferUpdate_Progress(
+            transferId: var_transferId,
+            fileIndex: var_fileIndex,
+            bytesTransferred: var_bytesTransferred,
+            totalBytes: var_totalBytes,
+            bytesPerSecond: var_bytesPerSecond,
+            etaSeconds: var_etaSeconds);
+      case 2:
+        var var_transferId = sse_decode_String(deserializer);
+        var var_index = sse_decode_u_32(deserializer);
+        return TransferUpdate_FileCompleted(
+            transferId: var_transferId,
+            index: var_index);
+      case 3:
+        var var_transferId = sse_decode_String(deserializer);
+        var var_status = sse_decode_String(deserializer);
+        var var_error = sse_decode_opt_String(deserializer);
+        return TransferUpdate_Finished(
+            transferId: var_transferId,
+            status: var_status,
+            error: var_error);
+      default:
+        throw UnimplementedError('');
+    }
   }
 
   @protected
-  Uint8List sse_decode_list_prim_u_8_strict(SseDeserializer deserializer) {
+  int sse_decode_u_16(SseDeserializer deserializer) {
     // Codec=Sse (Serialization based), see doc to use other codecs
-    var len_ = sse_decode_i_32(deserializer);
-    return deserializer.buffer.getUint8List(len_);
+    return deserializer.buffer.getUint16();
+  }
+
+  @protected
+  int sse_decode_u_32(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    return deserializer.buffer.getUint32();
+  }
+
+  @protected
+  BigInt sse_decode_u_64(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    return deserializer.buffer.getBigUint64();
   }
 
   @protected
@@ -187,6 +1378,24 @@ class RustLibApiImpl extends RustLibApiImplPlatform implements RustLibApi {
     // Codec=Sse (Serialization based), see doc to use other codecs
   }
 
+  @protected
+  Visibility sse_decode_visibility(SseDeserializer deserializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+
+    var tag_ = sse_decode_i_32(deserializer);
+    switch (tag_) {
+      case 0:
+        return Visibility_Hidden();
+      case 1:
+        var var_seconds = sse_decode_u_64(deserializer);
+        return Visibility_For(seconds: var_seconds);
+      case 2:
+        return Visibility_WhileOpen();
+      default:
+        throw UnimplementedError('');
+    }
+  }
+
   @protected
   int sse_decode_i_32(SseDeserializer deserializer) {
     // Codec=Sse (Serialization based), see doc to use other codecs
@@ -194,9 +1403,49 @@ class RustLibApiImpl extends RustLibApiImplPlatform implements RustLibApi {
   }
 
   @protected
-  bool sse_decode_bool(SseDeserializer deserializer) {
+  void sse_encode_AnyhowException(
+      AnyhowException self, SseSerializer serializer) {
     // Codec=Sse (Serialization based), see doc to use other codecs
-    return deserializer.buffer.getUint8() != 0;
+    throw UnimplementedError('Unreachable ()');
+  }
+
+  @protected
+  void sse_encode_StreamSink_incoming_offer_Sse(
+      RustStreamSink<IncomingOffer> self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_String(
+        self.setupAndSerialize(
+            codec: SseCodec(
+          decodeSuccessData: sse_decode_incoming_offer,
+          decodeErrorData: sse_decode_AnyhowException,
+        )),
+        serializer);
+  }
+
+  @protected
+  void sse_encode_StreamSink_list_device_Sse(
+      RustStreamSink<List<Device>> self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_String(
+        self.setupAndSerialize(
+            codec: SseCodec(
+          decodeSuccessData: sse_decode_list_device,
+          decodeErrorData: sse_decode_AnyhowException,
+        )),
+        serializer);
+  }
+
+  @protected
+  void sse_encode_StreamSink_transfer_update_Sse(
+      RustStreamSink<TransferUpdate> self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_String(
+        self.setupAndSerialize(
+            codec: SseCodec(
+          decodeSuccessData: sse_decode_transfer_update,
+          decodeErrorData: sse_decode_AnyhowException,
+        )),
+        serializer);
   }
 
   @protected
@@ -205,6 +1454,163 @@ class RustLibApiImpl extends RustLibApiImplPlatform implements RustLibApi {
     sse_encode_list_prim_u_8_strict(utf8.encoder.convert(self), serializer);
   }
 
+  @protected
+  void sse_encode_bool(bool self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    serializer.buffer.putUint8(self ? 1 : 0);
+  }
+
+  @protected
+  void sse_encode_box_autoadd_history_query(
+      HistoryQuery self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_history_query(self, serializer);
+  }
+
+  @protected
+  void sse_encode_box_autoadd_offer_answer(
+      OfferAnswer self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_offer_answer(self, serializer);
+  }
+
+  @protected
+  void sse_encode_box_autoadd_settings(
+      Settings self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_settings(self, serializer);
+  }
+
+  @protected
+  void sse_encode_box_autoadd_u_32(int self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_u_32(self, serializer);
+  }
+
+  @protected
+  void sse_encode_box_autoadd_u_64(BigInt self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_u_64(self, serializer);
+  }
+
+  @protected
+  void sse_encode_device(Device self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_String(self.id, serializer);
+    sse_encode_String(self.name, serializer);
+    sse_encode_String(self.model, serializer);
+    sse_encode_String(self.os, serializer);
+    sse_encode_list_String(self.addresses, serializer);
+    sse_encode_u_16(self.port, serializer);
+    sse_encode_bool(self.trusted, serializer);
+  }
+
+  @protected
+  void sse_encode_f_64(double self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    serializer.buffer.putFloat64(self);
+  }
+
+  @protected
+  void sse_encode_history_file(HistoryFile self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_String(self.path, serializer);
+    sse_encode_u_64(self.size, serializer);
+    sse_encode_opt_String(self.hash, serializer);
+    sse_encode_opt_String(self.localPath, serializer);
+  }
+
+  @protected
+  void sse_encode_history_item(HistoryItem self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_String(self.transferId, serializer);
+    sse_encode_bool(self.sent, serializer);
+    sse_encode_String(self.peerId, serializer);
+    sse_encode_String(self.peerName, serializer);
+    sse_encode_list_history_file(self.files, serializer);
+    sse_encode_u_64(self.totalSize, serializer);
+    sse_encode_u_64(self.startedAt, serializer);
+    sse_encode_u_64(self.finishedAt, serializer);
+    sse_encode_u_64(self.durationMs, serializer);
+    sse_encode_String(self.status, serializer);
+    sse_encode_opt_String(self.error, serializer);
+  }
+
+  @protected
+  void sse_encode_history_query(HistoryQuery self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_opt_String(self.peer, serializer);
+    sse_encode_opt_String(self.status, serializer);
+    sse_encode_opt_box_autoadd_u_64(self.since, serializer);
+    sse_encode_opt_box_autoadd_u_64(self.until, serializer);
+    sse_encode_opt_box_autoadd_u_32(self.limit, serializer);
+  }
+
+  @protected
+  void sse_encode_incoming_offer(IncomingOffer self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_String(self.transferId, serializer);
+    sse_encode_device(self.sender, serializer);
+    sse_encode_list_transfer_file(self.files, serializer);
+    sse_encode_u_64(self.totalSize, serializer);
+  }
+
+  @protected
+  void sse_encode_list_String(List<String> self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_i_32(self.length, serializer);
+    for (final item in self) {
+      sse_encode_String(item, serializer);
+    }
+  }
+
+  @protected
+  void sse_encode_list_device(List<Device> self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_i_32(self.length, serializer);
+    for (final item in self) {
+      sse_encode_device(item, serializer);
+    }
+  }
+
+  @protected
+  void sse_encode_list_history_file(
+      List<HistoryFile> self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_i_32(self.length, serializer);
+    for (final item in self) {
+      sse_encode_history_file(item, serializer);
+    }
+  }
+
+  @protected
+  void sse_encode_list_history_item(
+      List<HistoryItem> self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_i_32(self.length, serializer);
+    for (final item in self) {
+      sse_encode_history_item(item, serializer);
+    }
+  }
+
+  @protected
+  void sse_encode_list_incoming_offer(
+      List<IncomingOffer> self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_i_32(self.length, serializer);
+    for (final item in self) {
+      sse_encode_incoming_offer(item, serializer);
+    }
+  }
+
+  @protected
+  void sse_encode_list_prim_u_32_strict(
+      Uint32List self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_i_32(self.length, serializer);
+    serializer.buffer.putUint32List(self);
+  }
+
   @protected
   void sse_encode_list_prim_u_8_strict(
       Uint8List self, SseSerializer serializer) {
@@ -213,6 +1619,150 @@ class RustLibApiImpl extends RustLibApiImplPlatform implements RustLibApi {
     serializer.buffer.putUint8List(self);
   }
 
+  @protected
+  void sse_encode_list_transfer_file(
+      List<TransferFile> self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_i_32(self.length, serializer);
+    for (final item in self) {
+      sse_encode_transfer_file(item, serializer);
+    }
+  }
+
+  @protected
+  void sse_encode_offer_answer(OfferAnswer self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    switch (self) {
+      case OfferAnswer_Accept():
+        sse_encode_i_32(0, serializer);
+      case OfferAnswer_AcceptFiles(indexes: final indexes):
+        sse_encode_i_32(1, serializer);
+        sse_encode_list_prim_u_32_strict(indexes, serializer);
+      case OfferAnswer_Reject(reason: final reason):
+        sse_encode_i_32(2, serializer);
+        sse_encode_String(reason, serializer);
+    }
+  }
+
+  @protected
+  void sse_encode_opt_String(String? self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+
+    sse_encode_bool(self != null, serializer);
+    if (self != null) {
+      sse_encode_String(self, serializer);
+    }
+  }
+
+  @protected
+  void sse_encode_opt_box_autoadd_u_32(int? self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+
+    sse_encode_bool(self != null, serializer);
+    if (self != null) {
+      sse_encode_box_autoadd_u_32(self, serializer);
+    }
+  }
+
+  @protected
+  void sse_encode_opt_box_autoadd_u_64(BigInt? self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+
+    sse_encode_bool(self != null, serializer);
+    if (self != null) {
+      sse_encode_box_autoadd_u_64(self, serializer);
+    }
+  }
+
+  @protected
+  void sse_encode_settings(Settings self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_String(self.deviceName, serializer);
+    sse_encode_String(self.saveDirectory, serializer);
+    sse_encode_bool(self.allowAutoReceive, serializer);
+    sse_encode_list_String(self.discoveryBackends, serializer);
+    sse_encode_u_16(self.discoveryPort, serializer);
+    sse_encode_u_16(self.transferPort, serializer);
+    sse_encode_u_32(self.maxConcurrentTransfers, serializer);
+  }
+
+  @protected
+  void sse_encode_transfer_file(TransferFile self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    sse_encode_String(self.path, serializer);
+    sse_encode_u_64(self.size, serializer);
+  }
+
+  @protected
+  void sse_encode_transfer_update(
+      TransferUpdate self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    switch (self) {
+      case TransferUpdate_Started(
+          transferId: final transferId,
+          sent: final sent,
+          peer: final peer,
+          files: final files,
+          totalSize: final totalSize
+        ):
+        sse_encode_i_32(0, serializer);
+        sse_encode_String(transferId, serializer);
+        sse_encode_bool(sent, serializer);
+        sse_encode_device(peer, serializer);
+        sse_encode_list_transfer_file(files, serializer);
+        sse_encode_u_64(totalSize, serializer);
+      case TransferUpdate_Progress(
+          transferId: final transferId,
+          fileIndex: final fileIndex,
+          bytesTransferred: final bytesTransferred,
+          totalBytes: final totalBytes,
+          bytesPerSecond: final bytesPerSecond,
+          etaSeconds: final etaSeconds
+        ):
+        sse_encode_i_32(1, serializer);
+        sse_encode_String(transferId, serializer);
+        sse_encode_u_32(fileIndex, serializer);
+        sse_encode_u_64(bytesTransferred, serializer);
+        sse_encode_u_64(totalBytes, serializer);
+        sse_encode_f_64(bytesPerSecond, serializer);
+        sse_encode_opt_box_autoadd_u_64(etaSeconds, serializer);
+      case TransferUpdate_FileCompleted(
+          transferId: final transferId,
+          index: final index
+        ):
+        sse_encode_i_32(2, serializer);
+        sse_encode_String(transferId, serializer);
+        sse_encode_u_32(index, serializer);
+      case TransferUpdate_Finished(
+          transferId: final transferId,
+          status: final status,
+          error: final error
+        ):
+        sse_encode_i_32(3, serializer);
+        sse_encode_String(transferId, serializer);
+        sse_encode_String(status, serializer);
+        sse_encode_opt_String(error, serializer);
+    }
+  }
+
+  @protected
+  void sse_encode_u_16(int self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    serializer.buffer.putUint16(self);
+  }
+
+  @protected
+  void sse_encode_u_32(int self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    serializer.buffer.putUint32(self);
+  }
+
+  @protected
+  void sse_encode_u_64(BigInt self, SseSerializer serializer) {
+    // Codec=Sse (Serialization based), see doc to use other codecs
+    serializer.buffer.putBigUint64(self);
+  }
+
   @protected
   void sse_encode_u_8(int self, SseSerializer serializer) {
     // Codec=Sse (Serialization based), see doc to use other codecs
@@ -225,14 +1775,22 @@ class RustLibApiImpl extends RustLibApiImplPlatform implements RustLibApi {
   }
 
   @protected
-  void sse_encode_i_32(int self, SseSerializer serializer) {
+  void sse_encode_visibility(Visibility self, SseSerializer serializer) {
     // Codec=Sse (Serialization based), see doc to use other codecs
-    serializer.buffer.putInt32(self);
+    switch (self) {
+      case Visibility_Hidden():
+        sse_encode_i_32(0, serializer);
+      case Visibility_For(seconds: final seconds):
+        sse_encode_i_32(1, serializer);
+        sse_encode_u_64(seconds, serializer);
+      case Visibility_WhileOpen():
+        sse_encode_i_32(2, serializer);
+    }
   }
 
   @protected
-  void sse_encode_bool(bool self, SseSerializer serializer) {
+  void sse_encode_i_32(int self, SseSerializer serializer) {
     // Codec=Sse (Serialization based), see doc to use other codecs
-    serializer.buffer.putUint8(self ? 1 : 0);
+    serializer.buffer.putInt32(self);
   }
 }
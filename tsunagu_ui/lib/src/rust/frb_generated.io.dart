@@ -3,7 +3,14 @@
 
 // ignore_for_file: unused_import, unused_element, unnecessary_import, duplicate_ignore, invalid_use_of_internal_member, annotate_overrides, non_constant_identifier_names, curly_braces_in_flow_control_structures, prefer_const_literals_to_create_immutables, unused_field
 
+import 'api/devices.dart';
+import 'api/history.dart';
+import 'api/offers.dart';
+import 'api/service.dart';
+import 'api/settings.dart';
 import 'api/simple.dart';
+import 'api/transfers.dart';
+import 'api/visibility.dart';
 import 'dart:async';
 import 'dart:convert';
 import 'dart:ffi' as ffi;
@@ -18,43 +25,364 @@ abstract class RustLibApiImplPlatform extends BaseApiImpl<RustLibWire> {
     required super.portManager,
   });
 
+  @protected
+  AnyhowException dco_decode_AnyhowException(dynamic raw);
+
+  @protected
+  RustStreamSink<IncomingOffer> dco_decode_StreamSink_incoming_offer_Sse(
+      dynamic raw);
+
+  @protected
+  RustStreamSink<List<Device>> dco_decode_StreamSink_list_device_Sse(
+      dynamic raw);
+
+  @protected
+  RustStreamSink<TransferUpdate> dco_decode_StreamSink_transfer_update_Sse(
+      dynamic raw);
+
   @protected
   String dco_decode_String(dynamic raw);
 
+  @protected
+  bool dco_decode_bool(dynamic raw);
+
+  @protected
+  HistoryQuery dco_decode_box_autoadd_history_query(dynamic raw);
+
+  @protected
+  OfferAnswer dco_decode_box_autoadd_offer_answer(dynamic raw);
+
+  @protected
+  Settings dco_decode_box_autoadd_settings(dynamic raw);
+
+  @protected
+  int dco_decode_box_autoadd_u_32(dynamic raw);
+
+  @protected
+  BigInt dco_decode_box_autoadd_u_64(dynamic raw);
+
+  @protected
+  Device dco_decode_device(dynamic raw);
+
+  @protected
+  double dco_decode_f_64(dynamic raw);
+
+  @protected
+  HistoryFile dco_decode_history_file(dynamic raw);
+
+  @protected
+  HistoryItem dco_decode_history_item(dynamic raw);
+
+  @protected
+  HistoryQuery dco_decode_history_query(dynamic raw);
+
+  @protected
+  IncomingOffer dco_decode_incoming_offer(dynamic raw);
+
+  @protected
+  List<String> dco_decode_list_String(dynamic raw);
+
+  @protected
+  List<Device> dco_decode_list_device(dynamic raw);
+
+  @protected
+  List<HistoryFile> dco_decode_list_history_file(dynamic raw);
+
+  @protected
+  List<HistoryItem> dco_decode_list_history_item(dynamic raw);
+
+  @protected
+  List<IncomingOffer> dco_decode_list_incoming_offer(dynamic raw);
+
+  @protected
+  Uint32List dco_decode_list_prim_u_32_strict(dynamic raw);
+
   @protected
   Uint8List dco_decode_list_prim_u_8_strict(dynamic raw);
 
+  @protected
+  List<TransferFile> dco_decode_list_transfer_file(dynamic raw);
+
+  @protected
+  OfferAnswer dco_decode_offer_answer(dynamic raw);
+
+  @protected
+  String? dco_decode_opt_String(dynamic raw);
+
+  @protected
+  int? dco_decode_opt_box_autoadd_u_32(dynamic raw);
+
+  @protected
+  BigInt? dco_decode_opt_box_autoadd_u_64(dynamic raw);
+
+  @protected
+  Settings dco_decode_settings(dynamic raw);
+
+  @protected
+  TransferFile dco_decode_transfer_file(dynamic raw);
+
+  @protected
+  TransferUpdate dco_decode_transfer_update(dynamic raw);
+
+  @protected
+  int dco_decode_u_16(dynamic raw);
+
+  @protected
+  int dco_decode_u_32(dynamic raw);
+
+  @protected
+  BigInt dco_decode_u_64(dynamic raw);
+
   @protected
   int dco_decode_u_8(dynamic raw);
 
   @protected
   void dco_decode_unit(dynamic raw);
 
+  @protected
+  Visibility dco_decode_visibility(dynamic raw);
+
+  @protected
+  AnyhowException sse_decode_AnyhowException(SseDeserializer deserializer);
+
+  @protected
+  RustStreamSink<IncomingOffer> sse_decode_StreamSink_incoming_offer_Sse(
+      SseDeserializer deserializer);
+
+  @protected
+  RustStreamSink<List<Device>> sse_decode_StreamSink_list_device_Sse(
+      SseDeserializer deserializer);
+
+  @protected
+  RustStreamSink<TransferUpdate> sse_decode_StreamSink_transfer_update_Sse(
+      SseDeserializer deserializer);
+
   @protected
   String sse_decode_String(SseDeserializer deserializer);
 
+  @protected
+  bool sse_decode_bool(SseDeserializer deserializer);
+
+  @protected
+  HistoryQuery sse_decode_box_autoadd_history_query(
+      SseDeserializer deserializer);
+
+  @protected
+  OfferAnswer sse_decode_box_autoadd_offer_answer(SseDeserializer deserializer);
+
+  @protected
+  Settings sse_decode_box_autoadd_settings(SseDeserializer deserializer);
+
+  @protected
+  int sse_decode_box_autoadd_u_32(SseDeserializer deserializer);
+
+  @protected
+  BigInt sse_decode_box_autoadd_u_64(SseDeserializer deserializer);
+
+  @protected
+  Device sse_decode_device(SseDeserializer deserializer);
+
+  @protected
+  double sse_decode_f_64(SseDeserializer deserializer);
+
+  @protected
+  HistoryFile sse_decode_history_file(SseDeserializer deserializer);
+
+  @protected
+  HistoryItem sse_decode_history_item(SseDeserializer deserializer);
+
+  @protected
+  HistoryQuery sse_decode_history_query(SseDeserializer deserializer);
+
+  @protected
+  IncomingOffer sse_decode_incoming_offer(SseDeserializer deserializer);
+
+  @protected
+  List<String> sse_decode_list_String(SseDeserializer deserializer);
+
+  @protected
+  List<Device> sse_decode_list_device(SseDeserializer deserializer);
+
+  @protected
+  List<HistoryFile> sse_decode_list_history_file(SseDeserializer deserializer);
+
+  @protected
+  List<HistoryItem> sse_decode_list_history_item(SseDeserializer deserializer);
+
+  @protected
+  List<IncomingOffer> sse_decode_list_incoming_offer(
+      SseDeserializer deserializer);
+
+  @protected
+  Uint32List sse_decode_list_prim_u_32_strict(SseDeserializer deserializer);
+
   @protected
   Uint8List sse_decode_list_prim_u_8_strict(SseDeserializer deserializer);
 
+  @protected
+  List<TransferFile> sse_decode_list_transfer_file(
+      SseDeserializer deserializer);
+
+  @protected
+  OfferAnswer sse_decode_offer_answer(SseDeserializer deserializer);
+
+  @protected
+  String? sse_decode_opt_String(SseDeserializer deserializer);
+
+  @protected
+  int? sse_decode_opt_box_autoadd_u_32(SseDeserializer deserializer);
+
+  @protected
+  BigInt? sse_decode_opt_box_autoadd_u_64(SseDeserializer deserializer);
+
+  @protected
+  Settings sse_decode_settings(SseDeserializer deserializer);
+
+  @protected
+  TransferFile sse_decode_transfer_file(SseDeserializer deserializer);
+
+  @protected
+  TransferUpdate sse_decode_transfer_update(SseDeserializer deserializer);
+
+  @protected
+  int sse_decode_u_16(SseDeserializer deserializer);
+
+  @protected
+  int sse_decode_u_32(SseDeserializer deserializer);
+
+  @protected
+  BigInt sse_decode_u_64(SseDeserializer deserializer);
+
   @protected
   int sse_decode_u_8(SseDeserializer deserializer);
 
   @protected
   void sse_decode_unit(SseDeserializer deserializer);
 
+  @protected
+  Visibility sse_decode_visibility(SseDeserializer deserializer);
+
   @protected
   int sse_decode_i_32(SseDeserializer deserializer);
 
   @protected
-  bool sse_decode_bool(SseDeserializer deserializer);
+  void sse_encode_AnyhowException(
+      AnyhowException self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_StreamSink_incoming_offer_Sse(
+      RustStreamSink<IncomingOffer> self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_StreamSink_list_device_Sse(
+      RustStreamSink<List<Device>> self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_StreamSink_transfer_update_Sse(
+      RustStreamSink<TransferUpdate> self, SseSerializer serializer);
 
   @protected
   void sse_encode_String(String self, SseSerializer serializer);
 
+  @protected
+  void sse_encode_bool(bool self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_box_autoadd_history_query(
+      HistoryQuery self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_box_autoadd_offer_answer(
+      OfferAnswer self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_box_autoadd_settings(Settings self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_box_autoadd_u_32(int self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_box_autoadd_u_64(BigInt self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_device(Device self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_f_64(double self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_history_file(HistoryFile self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_history_item(HistoryItem self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_history_query(HistoryQuery self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_incoming_offer(IncomingOffer self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_list_String(List<String> self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_list_device(List<Device> self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_list_history_file(
+      List<HistoryFile> self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_list_history_item(
+      List<HistoryItem> self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_list_incoming_offer(
+      List<IncomingOffer> self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_list_prim_u_32_strict(
+      Uint32List self, SseSerializer serializer);
+
   @protected
   void sse_encode_list_prim_u_8_strict(
       Uint8List self, SseSerializer serializer);
 
+  @protected
+  void sse_encode_list_transfer_file(
+      List<TransferFile> self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_offer_answer(OfferAnswer self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_opt_String(String? self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_opt_box_autoadd_u_32(int? self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_opt_box_autoadd_u_64(BigInt? self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_settings(Settings self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_transfer_file(TransferFile self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_transfer_update(
+      TransferUpdate self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_u_16(int self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_u_32(int self, SseSerializer serializer);
+
+  @protected
+  void sse_encode_u_64(BigInt self, SseSerializer serializer);
+
   @protected
   void sse_encode_u_8(int self, SseSerializer serializer);
 
@@ -62,10 +390,10 @@ abstract class RustLibApiImplPlatform extends BaseApiImpl<RustLibWire> {
   void sse_encode_unit(void self, SseSerializer serializer);
 
   @protected
-  void sse_encode_i_32(int self, SseSerializer serializer);
+  void sse_encode_visibility(Visibility self, SseSerializer serializer);
 
   @protected
-  void sse_encode_bool(bool self, SseSerializer serializer);
+  void sse_encode_i_32(int self, SseSerializer serializer);
 }
 
 // Section: wire_class